@@ -0,0 +1,13 @@
+#![no_main]
+
+use libfuzzer_sys::fuzz_target;
+use scidtopgn::scid::index::IndexFile;
+
+fuzz_target!(|data: &[u8]| {
+    if let Ok(index) = IndexFile::from_bytes("fuzz.si4", data) {
+        for game in index.game_indices() {
+            let _ = game.date_string();
+            let _ = game.result_string();
+        }
+    }
+});
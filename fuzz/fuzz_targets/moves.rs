@@ -0,0 +1,19 @@
+#![no_main]
+
+use libfuzzer_sys::fuzz_target;
+use scidtopgn::chess::{GameResult, Position};
+use scidtopgn::pgn::writer::{movetext, MovetextOptions};
+use scidtopgn::scid::moves::decode_game;
+
+// Decoding alone isn't enough: the exporter then generates SAN on the
+// decoded board, so render the movetext too
+fuzz_target!(|data: &[u8]| {
+    if let Ok(record) = decode_game(data) {
+        let start = match &record.start_fen {
+            Some(fen) => Position::from_fen(fen).expect("decoder accepted a bad FEN"),
+            None => Position::starting_position(),
+        };
+        let options = MovetextOptions { include_variations: true, include_comments: true };
+        movetext(&start, &record.moves, GameResult::Unknown, &options);
+    }
+});
@@ -0,0 +1,10 @@
+#![no_main]
+
+use std::path::Path;
+
+use libfuzzer_sys::fuzz_target;
+use scidtopgn::scid::names::NameDatabase;
+
+fuzz_target!(|data: &[u8]| {
+    let _ = NameDatabase::from_bytes(Path::new("fuzz.sn4"), data);
+});
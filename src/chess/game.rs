@@ -0,0 +1,166 @@
+//! Game model shared by the SCID decoder and the PGN reader
+//!
+//! A game is a set of tags plus a tree of moves. The main line is a `Line`;
+//! every move in it can carry NAGs, a comment and any number of variations,
+//! each of which is an alternative to that move and is itself a `Line`.
+
+use super::position::{FenError, Move, Position};
+
+/// Deepest variation nesting accepted from PGN or SCID input. Move trees
+/// are walked recursively, so this bounds stack use on hostile input.
+pub const MAX_VARIATION_DEPTH: usize = 100;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Default)]
+pub enum GameResult {
+    #[default]
+    Unknown,
+    WhiteWins,
+    BlackWins,
+    Draw,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, Default)]
+pub struct Line {
+    /// Comment before the first move of the line
+    pub comment: Option<String>,
+    pub moves: Vec<MoveNode>,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct MoveNode {
+    pub mv: Move,
+    /// Numeric annotation glyphs following the move ($1 = "!", ...)
+    pub nags: Vec<u8>,
+    /// Comment following the move
+    pub comment: Option<String>,
+    /// Alternatives to this move, each starting from the position before it
+    pub variations: Vec<Line>,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, Default)]
+pub struct Game {
+    /// Tag pairs in the order they were read
+    pub tags: Vec<(String, String)>,
+    pub moves: Line,
+    pub result: GameResult,
+}
+
+impl GameResult {
+    /// Parse a PGN result token; anything unrecognised is an unknown result
+    pub fn from_pgn(text: &str) -> GameResult {
+        match text.trim() {
+            "1-0" => GameResult::WhiteWins,
+            "0-1" => GameResult::BlackWins,
+            "1/2-1/2" | "1/2" | "=" | "½-½" => GameResult::Draw,
+            _ => GameResult::Unknown,
+        }
+    }
+
+    pub fn as_pgn(self) -> &'static str {
+        match self {
+            GameResult::Unknown => "*",
+            GameResult::WhiteWins => "1-0",
+            GameResult::BlackWins => "0-1",
+            GameResult::Draw => "1/2-1/2",
+        }
+    }
+
+    /// Result code as stored in the index VarCounts field
+    pub fn from_scid(code: u8) -> GameResult {
+        match code {
+            1 => GameResult::WhiteWins,
+            2 => GameResult::BlackWins,
+            3 => GameResult::Draw,
+            _ => GameResult::Unknown,
+        }
+    }
+
+    pub fn to_scid(self) -> u8 {
+        match self {
+            GameResult::Unknown => 0,
+            GameResult::WhiteWins => 1,
+            GameResult::BlackWins => 2,
+            GameResult::Draw => 3,
+        }
+    }
+}
+
+impl MoveNode {
+    pub fn new(mv: Move) -> Self {
+        MoveNode {
+            mv,
+            nags: Vec::new(),
+            comment: None,
+            variations: Vec::new(),
+        }
+    }
+}
+
+impl Line {
+    /// Visit every move node in this line and all nested variations
+    pub fn for_each_node<F: FnMut(&MoveNode)>(&self, f: &mut F) {
+        for node in &self.moves {
+            f(node);
+            for variation in &node.variations {
+                variation.for_each_node(f);
+            }
+        }
+    }
+
+    /// Number of variations, comments and NAGs in this line and all nested lines
+    pub fn annotation_counts(&self) -> (usize, usize, usize) {
+        let mut variations = 0;
+        let mut comments = usize::from(self.comment.is_some());
+        let mut nags = 0;
+        for node in &self.moves {
+            variations += node.variations.len();
+            comments += usize::from(node.comment.is_some());
+            nags += node.nags.len();
+            for variation in &node.variations {
+                let (v, c, n) = variation.annotation_counts();
+                variations += v;
+                comments += c;
+                nags += n;
+            }
+        }
+        (variations, comments, nags)
+    }
+}
+
+impl Game {
+    /// Look up a tag value by name
+    pub fn tag(&self, name: &str) -> Option<&str> {
+        self.tags.iter().find(|(n, _)| n == name).map(|(_, v)| v.as_str())
+    }
+
+    /// Set a tag, replacing an existing value or appending a new pair
+    pub fn set_tag(&mut self, name: &str, value: &str) {
+        match self.tags.iter_mut().find(|(n, _)| n == name) {
+            Some(tag) => tag.1 = value.to_string(),
+            None => self.tags.push((name.to_string(), value.to_string())),
+        }
+    }
+
+    pub fn remove_tag(&mut self, name: &str) {
+        self.tags.retain(|(n, _)| n != name);
+    }
+
+    /// The position the game starts from: the FEN tag if present, otherwise
+    /// the standard starting position
+    pub fn start_position(&self) -> Result<Position, FenError> {
+        match self.tag("FEN") {
+            Some(fen) => Position::from_fen(fen),
+            None => Ok(Position::starting_position()),
+        }
+    }
+
+    /// Number of half-moves in the main line
+    pub fn ply_count(&self) -> usize {
+        self.moves.moves.len()
+    }
+
+    /// Main line moves in order, without annotations
+    pub fn main_line(&self) -> impl Iterator<Item = &Move> {
+        self.moves.moves.iter().map(|node| &node.mv)
+    }
+}
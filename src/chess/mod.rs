@@ -0,0 +1,7 @@
+pub mod position;
+pub mod game;
+pub mod polyglot;
+
+pub use position::{Color, Piece, Move, Position, Square, FenError};
+pub use game::{Game, GameResult, Line, MoveNode, MAX_VARIATION_DEPTH};
+pub use polyglot::polyglot_move;
@@ -0,0 +1,784 @@
+//! Chess position handling shared by the SCID and PGN sides
+//!
+//! Squares are numbered 0-63 with a1 = 0, b1 = 1, ... h8 = 63, which is the
+//! same numbering SCID uses in its .sg4 move encoding. The position keeps a
+//! simple square-indexed board; that is fast enough for replaying games and
+//! keeps FEN, SAN and move generation easy to follow.
+
+use std::fmt;
+
+pub type Square = u8;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum Color {
+    White,
+    Black,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum Piece {
+    Pawn,
+    Knight,
+    Bishop,
+    Rook,
+    Queen,
+    King,
+}
+
+/// Castling right bits
+pub const CASTLE_WHITE_KINGSIDE: u8 = 1;
+pub const CASTLE_WHITE_QUEENSIDE: u8 = 2;
+pub const CASTLE_BLACK_KINGSIDE: u8 = 4;
+pub const CASTLE_BLACK_QUEENSIDE: u8 = 8;
+
+pub const STARTING_FEN: &str = "rnbqkbnr/pppppppp/8/8/8/8/PPPPPPPP/RNBQKBNR w KQkq - 0 1";
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Move {
+    pub from_square: u8,
+    pub to_square: u8,
+    pub piece: Piece,
+    pub captured_piece: Option<Piece>,
+    pub promotion: Option<Piece>,
+    pub is_castling: bool,
+    pub is_en_passant: bool,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Position {
+    board: [Option<(Color, Piece)>; 64],
+    pub to_move: Color,
+    pub castling: u8,
+    pub en_passant: Option<Square>,
+    pub half_move_clock: u16,
+    pub full_move_number: u16,
+}
+
+/// Error returned for a FEN string that does not describe a usable position
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct FenError(pub String);
+
+impl fmt::Display for FenError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "Invalid FEN: {}", self.0)
+    }
+}
+
+impl std::error::Error for FenError {}
+
+const KNIGHT_OFFSETS: [(i8, i8); 8] = [(1, 2), (2, 1), (2, -1), (1, -2), (-1, -2), (-2, -1), (-2, 1), (-1, 2)];
+const KING_OFFSETS: [(i8, i8); 8] = [(1, 0), (1, 1), (0, 1), (-1, 1), (-1, 0), (-1, -1), (0, -1), (1, -1)];
+const ROOK_DIRECTIONS: [(i8, i8); 4] = [(1, 0), (0, 1), (-1, 0), (0, -1)];
+const BISHOP_DIRECTIONS: [(i8, i8); 4] = [(1, 1), (-1, 1), (-1, -1), (1, -1)];
+const PROMOTION_PIECES: [Piece; 4] = [Piece::Queen, Piece::Rook, Piece::Bishop, Piece::Knight];
+
+pub fn square_file(square: Square) -> u8 {
+    square % 8
+}
+
+pub fn square_rank(square: Square) -> u8 {
+    square / 8
+}
+
+pub fn make_square(file: u8, rank: u8) -> Square {
+    rank * 8 + file
+}
+
+/// Square reached from `square` by a (file, rank) step, if still on the board
+fn offset_square(square: Square, (df, dr): (i8, i8)) -> Option<Square> {
+    let file = square_file(square) as i8 + df;
+    let rank = square_rank(square) as i8 + dr;
+    if (0..8).contains(&file) && (0..8).contains(&rank) {
+        Some(make_square(file as u8, rank as u8))
+    } else {
+        None
+    }
+}
+
+/// Convert square index to algebraic notation (e.g., 0 -> "a1")
+pub fn square_to_algebraic(square: u8) -> String {
+    let file = (square % 8) as char;
+    let rank = (square / 8) + 1;
+    format!("{}{}", (b'a' + file as u8) as char, rank)
+}
+
+/// Parse algebraic notation (e.g., "e4") into a square index
+pub fn algebraic_to_square(name: &str) -> Option<Square> {
+    let bytes = name.as_bytes();
+    if bytes.len() != 2 || !(b'a'..=b'h').contains(&bytes[0]) || !(b'1'..=b'8').contains(&bytes[1]) {
+        return None;
+    }
+    Some(make_square(bytes[0] - b'a', bytes[1] - b'1'))
+}
+
+impl Color {
+    pub fn opposite(self) -> Color {
+        match self {
+            Color::White => Color::Black,
+            Color::Black => Color::White,
+        }
+    }
+
+    /// Index used for per-colour arrays (white = 0, black = 1)
+    pub fn index(self) -> usize {
+        match self {
+            Color::White => 0,
+            Color::Black => 1,
+        }
+    }
+}
+
+impl Piece {
+    pub fn to_char(self) -> char {
+        match self {
+            Piece::Pawn => 'P',
+            Piece::Knight => 'N',
+            Piece::Bishop => 'B',
+            Piece::Rook => 'R',
+            Piece::Queen => 'Q',
+            Piece::King => 'K',
+        }
+    }
+
+    /// Parse an upper- or lower-case piece letter
+    pub fn from_char(c: char) -> Option<Piece> {
+        match c.to_ascii_uppercase() {
+            'P' => Some(Piece::Pawn),
+            'N' => Some(Piece::Knight),
+            'B' => Some(Piece::Bishop),
+            'R' => Some(Piece::Rook),
+            'Q' => Some(Piece::Queen),
+            'K' => Some(Piece::King),
+            _ => None,
+        }
+    }
+
+    /// Index used for per-piece arrays (pawn = 0 ... king = 5)
+    pub fn index(self) -> usize {
+        match self {
+            Piece::Pawn => 0,
+            Piece::Knight => 1,
+            Piece::Bishop => 2,
+            Piece::Rook => 3,
+            Piece::Queen => 4,
+            Piece::King => 5,
+        }
+    }
+}
+
+impl Move {
+    /// A null move ("--" in PGN) is stored as a king move to its own square
+    pub fn null(king_square: Square) -> Move {
+        Move {
+            from_square: king_square,
+            to_square: king_square,
+            piece: Piece::King,
+            captured_piece: None,
+            promotion: None,
+            is_castling: false,
+            is_en_passant: false,
+        }
+    }
+
+    pub fn is_null(&self) -> bool {
+        self.from_square == self.to_square
+    }
+
+    /// Convert move to UCI long algebraic notation (e.g. "e2e4", "e7e8q")
+    pub fn to_uci(&self) -> String {
+        if self.is_null() {
+            return "0000".to_string();
+        }
+        let mut uci = format!("{}{}",
+                              square_to_algebraic(self.from_square),
+                              square_to_algebraic(self.to_square));
+        if let Some(piece) = self.promotion {
+            uci.push(piece.to_char().to_ascii_lowercase());
+        }
+        uci
+    }
+}
+
+impl Position {
+    pub fn starting_position() -> Self {
+        Position::from_fen(STARTING_FEN).expect("starting FEN is valid")
+    }
+
+    /// A board with no pieces; used to build positions piece by piece
+    pub fn empty() -> Self {
+        Position {
+            board: [None; 64],
+            to_move: Color::White,
+            castling: 0,
+            en_passant: None,
+            half_move_clock: 0,
+            full_move_number: 1,
+        }
+    }
+
+    pub fn piece_at(&self, square: Square) -> Option<(Color, Piece)> {
+        self.board[square as usize]
+    }
+
+    pub fn set_piece(&mut self, square: Square, piece: Option<(Color, Piece)>) {
+        self.board[square as usize] = piece;
+    }
+
+    pub fn king_square(&self, color: Color) -> Option<Square> {
+        (0..64).find(|&sq| self.board[sq as usize] == Some((color, Piece::King)))
+    }
+
+    /// Whether both positions have the same pieces on the same squares,
+    /// whatever the side to move, castling rights and counters
+    pub fn same_pieces(&self, other: &Position) -> bool {
+        self.board == other.board
+    }
+
+    /// Parse a FEN string. The move counters may be omitted, as they often
+    /// are in EPD and in hand-written FEN tags.
+    pub fn from_fen(fen: &str) -> Result<Self, FenError> {
+        let mut fields = fen.split_whitespace();
+        let placement = fields.next().ok_or_else(|| FenError("empty string".to_string()))?;
+        let mut pos = Position::empty();
+
+        let ranks: Vec<&str> = placement.split('/').collect();
+        if ranks.len() != 8 {
+            return Err(FenError(format!("expected 8 ranks, found {}", ranks.len())));
+        }
+        for (i, rank_text) in ranks.iter().enumerate() {
+            let rank = 7 - i as u8;
+            let mut file = 0u8;
+            for c in rank_text.chars() {
+                if let Some(skip) = c.to_digit(10) {
+                    file += skip as u8;
+                } else {
+                    let piece = Piece::from_char(c)
+                        .ok_or_else(|| FenError(format!("unknown piece '{}'", c)))?;
+                    let color = if c.is_ascii_uppercase() { Color::White } else { Color::Black };
+                    if file >= 8 {
+                        return Err(FenError(format!("rank {} has more than 8 files", rank + 1)));
+                    }
+                    pos.set_piece(make_square(file, rank), Some((color, piece)));
+                    file += 1;
+                }
+                if file > 8 {
+                    return Err(FenError(format!("rank {} has more than 8 files", rank + 1)));
+                }
+            }
+        }
+
+        pos.to_move = match fields.next().unwrap_or("w") {
+            "w" | "W" => Color::White,
+            "b" | "B" => Color::Black,
+            other => return Err(FenError(format!("bad side to move '{}'", other))),
+        };
+
+        let castling = fields.next().unwrap_or("-");
+        for c in castling.chars() {
+            pos.castling |= match c {
+                'K' => CASTLE_WHITE_KINGSIDE,
+                'Q' => CASTLE_WHITE_QUEENSIDE,
+                'k' => CASTLE_BLACK_KINGSIDE,
+                'q' => CASTLE_BLACK_QUEENSIDE,
+                '-' => 0,
+                other => return Err(FenError(format!("bad castling flag '{}'", other))),
+            };
+        }
+
+        let en_passant = fields.next().unwrap_or("-");
+        if en_passant != "-" {
+            pos.en_passant = Some(algebraic_to_square(en_passant)
+                .ok_or_else(|| FenError(format!("bad en passant square '{}'", en_passant)))?);
+        }
+
+        pos.half_move_clock = fields.next().and_then(|f| f.parse().ok()).unwrap_or(0);
+        pos.full_move_number = fields.next().and_then(|f| f.parse().ok()).unwrap_or(1).max(1);
+
+        for color in [Color::White, Color::Black] {
+            let kings = pos.board.iter().filter(|&&p| p == Some((color, Piece::King))).count();
+            if kings != 1 {
+                return Err(FenError(format!("{:?} has {} kings", color, kings)));
+            }
+        }
+        pos.clean_castling_rights();
+
+        Ok(pos)
+    }
+
+    /// Piece placement, side, castling and en passant fields (the EPD prefix)
+    pub fn to_epd(&self) -> String {
+        let mut epd = String::new();
+        for rank in (0..8).rev() {
+            let mut empty = 0;
+            for file in 0..8 {
+                match self.piece_at(make_square(file, rank)) {
+                    None => empty += 1,
+                    Some((color, piece)) => {
+                        if empty > 0 {
+                            epd.push_str(&empty.to_string());
+                            empty = 0;
+                        }
+                        let c = piece.to_char();
+                        epd.push(if color == Color::White { c } else { c.to_ascii_lowercase() });
+                    }
+                }
+            }
+            if empty > 0 {
+                epd.push_str(&empty.to_string());
+            }
+            if rank > 0 {
+                epd.push('/');
+            }
+        }
+
+        epd.push_str(if self.to_move == Color::White { " w " } else { " b " });
+
+        if self.castling == 0 {
+            epd.push('-');
+        } else {
+            for (bit, c) in [(CASTLE_WHITE_KINGSIDE, 'K'), (CASTLE_WHITE_QUEENSIDE, 'Q'),
+                             (CASTLE_BLACK_KINGSIDE, 'k'), (CASTLE_BLACK_QUEENSIDE, 'q')] {
+                if self.castling & bit != 0 {
+                    epd.push(c);
+                }
+            }
+        }
+
+        epd.push(' ');
+        match self.en_passant {
+            Some(square) => epd.push_str(&square_to_algebraic(square)),
+            None => epd.push('-'),
+        }
+        epd
+    }
+
+    pub fn to_fen(&self) -> String {
+        format!("{} {} {}", self.to_epd(), self.half_move_clock, self.full_move_number)
+    }
+
+    /// Drop castling rights whose king or rook is no longer on its home square
+    fn clean_castling_rights(&mut self) {
+        let checks = [
+            (CASTLE_WHITE_KINGSIDE, Color::White, 4, 7),
+            (CASTLE_WHITE_QUEENSIDE, Color::White, 4, 0),
+            (CASTLE_BLACK_KINGSIDE, Color::Black, 60, 63),
+            (CASTLE_BLACK_QUEENSIDE, Color::Black, 60, 56),
+        ];
+        for (bit, color, king, rook) in checks {
+            if self.piece_at(king) != Some((color, Piece::King))
+                || self.piece_at(rook) != Some((color, Piece::Rook)) {
+                self.castling &= !bit;
+            }
+        }
+    }
+
+    /// Is `square` attacked by any piece of colour `by`?
+    pub fn is_square_attacked(&self, square: Square, by: Color) -> bool {
+        // Pawns attack diagonally forward, so look diagonally backward from the target
+        let pawn_rank_step = if by == Color::White { -1 } else { 1 };
+        for df in [-1, 1] {
+            if let Some(from) = offset_square(square, (df, pawn_rank_step)) {
+                if self.piece_at(from) == Some((by, Piece::Pawn)) {
+                    return true;
+                }
+            }
+        }
+        for offset in KNIGHT_OFFSETS {
+            if let Some(from) = offset_square(square, offset) {
+                if self.piece_at(from) == Some((by, Piece::Knight)) {
+                    return true;
+                }
+            }
+        }
+        for offset in KING_OFFSETS {
+            if let Some(from) = offset_square(square, offset) {
+                if self.piece_at(from) == Some((by, Piece::King)) {
+                    return true;
+                }
+            }
+        }
+        for (directions, slider) in [(ROOK_DIRECTIONS, Piece::Rook), (BISHOP_DIRECTIONS, Piece::Bishop)] {
+            for direction in directions {
+                let mut current = square;
+                while let Some(next) = offset_square(current, direction) {
+                    if let Some((color, piece)) = self.piece_at(next) {
+                        if color == by && (piece == slider || piece == Piece::Queen) {
+                            return true;
+                        }
+                        break;
+                    }
+                    current = next;
+                }
+            }
+        }
+        false
+    }
+
+    /// Is the side to move in check?
+    pub fn in_check(&self) -> bool {
+        match self.king_square(self.to_move) {
+            Some(king) => self.is_square_attacked(king, self.to_move.opposite()),
+            None => false,
+        }
+    }
+
+    /// Build a move from its squares, filling in piece, capture and special
+    /// move details from the board. Returns None if `from` is empty.
+    pub fn build_move(&self, from: Square, to: Square, promotion: Option<Piece>) -> Option<Move> {
+        let (_, piece) = self.piece_at(from)?;
+        let is_en_passant = piece == Piece::Pawn
+            && square_file(from) != square_file(to)
+            && self.piece_at(to).is_none()
+            && self.en_passant == Some(to);
+        let captured_piece = if is_en_passant {
+            Some(Piece::Pawn)
+        } else {
+            self.piece_at(to).map(|(_, p)| p)
+        };
+        let is_castling = piece == Piece::King && (square_file(from) as i8 - square_file(to) as i8).abs() == 2;
+        Some(Move {
+            from_square: from,
+            to_square: to,
+            piece,
+            captured_piece,
+            promotion,
+            is_castling,
+            is_en_passant,
+        })
+    }
+
+    fn push_pawn_moves(&self, from: Square, to: Square, moves: &mut Vec<Move>) {
+        let last_rank = if self.to_move == Color::White { 7 } else { 0 };
+        if square_rank(to) == last_rank {
+            for piece in PROMOTION_PIECES {
+                moves.extend(self.build_move(from, to, Some(piece)));
+            }
+        } else {
+            moves.extend(self.build_move(from, to, None));
+        }
+    }
+
+    /// All moves for the side to move, ignoring whether they leave the king in check
+    pub fn pseudo_legal_moves(&self) -> Vec<Move> {
+        let us = self.to_move;
+        let mut moves = Vec::with_capacity(48);
+        for from in 0..64u8 {
+            let piece = match self.piece_at(from) {
+                Some((color, piece)) if color == us => piece,
+                _ => continue,
+            };
+            match piece {
+                Piece::Pawn => {
+                    let (step, start_rank) = if us == Color::White { (1, 1) } else { (-1, 6) };
+                    if let Some(one) = offset_square(from, (0, step)) {
+                        if self.piece_at(one).is_none() {
+                            self.push_pawn_moves(from, one, &mut moves);
+                            if square_rank(from) == start_rank {
+                                if let Some(two) = offset_square(one, (0, step)) {
+                                    if self.piece_at(two).is_none() {
+                                        moves.extend(self.build_move(from, two, None));
+                                    }
+                                }
+                            }
+                        }
+                    }
+                    for df in [-1, 1] {
+                        if let Some(to) = offset_square(from, (df, step)) {
+                            let enemy = matches!(self.piece_at(to), Some((color, _)) if color != us);
+                            if enemy || self.en_passant == Some(to) {
+                                self.push_pawn_moves(from, to, &mut moves);
+                            }
+                        }
+                    }
+                }
+                Piece::Knight | Piece::King => {
+                    let offsets = if piece == Piece::Knight { KNIGHT_OFFSETS } else { KING_OFFSETS };
+                    for offset in offsets {
+                        if let Some(to) = offset_square(from, offset) {
+                            if !matches!(self.piece_at(to), Some((color, _)) if color == us) {
+                                moves.extend(self.build_move(from, to, None));
+                            }
+                        }
+                    }
+                }
+                Piece::Bishop | Piece::Rook | Piece::Queen => {
+                    let directions: &[(i8, i8)] = match piece {
+                        Piece::Bishop => &BISHOP_DIRECTIONS,
+                        Piece::Rook => &ROOK_DIRECTIONS,
+                        _ => &[(1, 0), (0, 1), (-1, 0), (0, -1), (1, 1), (-1, 1), (-1, -1), (1, -1)],
+                    };
+                    for &direction in directions {
+                        let mut current = from;
+                        while let Some(to) = offset_square(current, direction) {
+                            match self.piece_at(to) {
+                                None => moves.extend(self.build_move(from, to, None)),
+                                Some((color, _)) => {
+                                    if color != us {
+                                        moves.extend(self.build_move(from, to, None));
+                                    }
+                                    break;
+                                }
+                            }
+                            current = to;
+                        }
+                    }
+                }
+            }
+        }
+        self.push_castling_moves(&mut moves);
+        moves
+    }
+
+    fn push_castling_moves(&self, moves: &mut Vec<Move>) {
+        let us = self.to_move;
+        let them = us.opposite();
+        let (king, kingside, queenside) = match us {
+            Color::White => (4, CASTLE_WHITE_KINGSIDE, CASTLE_WHITE_QUEENSIDE),
+            Color::Black => (60, CASTLE_BLACK_KINGSIDE, CASTLE_BLACK_QUEENSIDE),
+        };
+        if self.piece_at(king) != Some((us, Piece::King)) || self.is_square_attacked(king, them) {
+            return;
+        }
+        if self.castling & kingside != 0
+            && self.piece_at(king + 1).is_none()
+            && self.piece_at(king + 2).is_none()
+            && !self.is_square_attacked(king + 1, them)
+            && !self.is_square_attacked(king + 2, them) {
+            moves.extend(self.build_move(king, king + 2, None));
+        }
+        if self.castling & queenside != 0
+            && self.piece_at(king - 1).is_none()
+            && self.piece_at(king - 2).is_none()
+            && self.piece_at(king - 3).is_none()
+            && !self.is_square_attacked(king - 1, them)
+            && !self.is_square_attacked(king - 2, them) {
+            moves.extend(self.build_move(king, king - 2, None));
+        }
+    }
+
+    /// All legal moves for the side to move
+    pub fn legal_moves(&self) -> Vec<Move> {
+        self.pseudo_legal_moves()
+            .into_iter()
+            .filter(|mv| self.leaves_king_safe(mv))
+            .collect()
+    }
+
+    fn leaves_king_safe(&self, mv: &Move) -> bool {
+        let mut after = self.clone();
+        after.make_move(mv);
+        match after.king_square(self.to_move) {
+            Some(king) => !after.is_square_attacked(king, after.to_move),
+            None => true,
+        }
+    }
+
+    /// Is `mv` one of the legal moves in this position?
+    pub fn is_legal(&self, mv: &Move) -> bool {
+        if mv.is_null() {
+            return !self.in_check();
+        }
+        self.pseudo_legal_moves().iter().any(|m| m == mv) && self.leaves_king_safe(mv)
+    }
+
+    /// Play a move on the board. The move is assumed to be legal (or at
+    /// least pseudo-legal); null moves just pass the turn.
+    pub fn make_move(&mut self, mv: &Move) {
+        let us = self.to_move;
+        self.en_passant = None;
+
+        if !mv.is_null() {
+            if mv.piece == Piece::Pawn || mv.captured_piece.is_some() {
+                self.half_move_clock = 0;
+            } else {
+                self.half_move_clock = self.half_move_clock.saturating_add(1);
+            }
+
+            if mv.is_en_passant {
+                let captured = make_square(square_file(mv.to_square), square_rank(mv.from_square));
+                self.set_piece(captured, None);
+            }
+
+            if mv.is_castling {
+                let rank = square_rank(mv.from_square);
+                let (rook_from, rook_to) = if mv.to_square > mv.from_square {
+                    (make_square(7, rank), make_square(5, rank))
+                } else {
+                    (make_square(0, rank), make_square(3, rank))
+                };
+                let rook = self.piece_at(rook_from);
+                self.set_piece(rook_from, None);
+                self.set_piece(rook_to, rook);
+            }
+
+            let placed = mv.promotion.unwrap_or(mv.piece);
+            self.set_piece(mv.from_square, None);
+            self.set_piece(mv.to_square, Some((us, placed)));
+
+            if mv.piece == Piece::Pawn && (mv.to_square as i8 - mv.from_square as i8).abs() == 16 {
+                self.en_passant = Some((mv.from_square + mv.to_square) / 2);
+            }
+
+            self.clean_castling_rights();
+        } else {
+            self.half_move_clock = self.half_move_clock.saturating_add(1);
+        }
+
+        if us == Color::Black {
+            self.full_move_number = self.full_move_number.saturating_add(1);
+        }
+        self.to_move = us.opposite();
+    }
+
+    /// Standard algebraic notation for a legal move in this position
+    pub fn san(&self, mv: &Move) -> String {
+        if mv.is_null() {
+            return "--".to_string();
+        }
+
+        let mut san = if mv.is_castling {
+            if mv.to_square > mv.from_square { "O-O".to_string() } else { "O-O-O".to_string() }
+        } else if mv.piece == Piece::Pawn {
+            let mut san = String::new();
+            if mv.captured_piece.is_some() {
+                san.push((b'a' + square_file(mv.from_square)) as char);
+                san.push('x');
+            }
+            san.push_str(&square_to_algebraic(mv.to_square));
+            if let Some(promotion) = mv.promotion {
+                san.push('=');
+                san.push(promotion.to_char());
+            }
+            san
+        } else {
+            let mut san = String::new();
+            san.push(mv.piece.to_char());
+
+            // Disambiguate against other pieces of the same type reaching the same square
+            let rivals: Vec<Move> = self.legal_moves()
+                .into_iter()
+                .filter(|m| m.piece == mv.piece && m.to_square == mv.to_square && m.from_square != mv.from_square)
+                .collect();
+            if !rivals.is_empty() {
+                let same_file = rivals.iter().any(|m| square_file(m.from_square) == square_file(mv.from_square));
+                let same_rank = rivals.iter().any(|m| square_rank(m.from_square) == square_rank(mv.from_square));
+                if !same_file {
+                    san.push((b'a' + square_file(mv.from_square)) as char);
+                } else if !same_rank {
+                    san.push((b'1' + square_rank(mv.from_square)) as char);
+                } else {
+                    san.push_str(&square_to_algebraic(mv.from_square));
+                }
+            }
+
+            if mv.captured_piece.is_some() {
+                san.push('x');
+            }
+            san.push_str(&square_to_algebraic(mv.to_square));
+            san
+        };
+
+        let mut after = self.clone();
+        after.make_move(mv);
+        if after.in_check() {
+            san.push(if after.legal_moves().is_empty() { '#' } else { '+' });
+        }
+        san
+    }
+
+    /// Find the legal move described by a SAN string. Also accepts the
+    /// common variants seen in real PGN files: "0-0" or "o-o", missing "=" before a
+    /// promotion piece, check/annotation suffixes and long algebraic
+    /// ("e2e4", "Ng1-f3").
+    pub fn parse_san(&self, text: &str) -> Option<Move> {
+        let text = text.trim_end_matches(['+', '#', '!', '?']);
+        if text.is_empty() {
+            return None;
+        }
+
+        match text {
+            "--" | "Z0" | "0000" | "@@@@" => {
+                return self.king_square(self.to_move).map(Move::null);
+            }
+            "O-O" | "0-0" | "OO" | "o-o" => return self.find_castling(true),
+            "O-O-O" | "0-0-0" | "OOO" | "o-o-o" => return self.find_castling(false),
+            _ => {}
+        }
+
+        let chars: Vec<char> = text.chars().collect();
+        let mut end = chars.len();
+
+        // Promotion suffix: "=Q", "Q" or "q" after the destination rank
+        let mut promotion = None;
+        if end >= 3 {
+            let last = chars[end - 1];
+            let prev = chars[end - 2];
+            if (prev == '=' || prev.is_ascii_digit()) && matches!(last.to_ascii_uppercase(), 'Q' | 'R' | 'B' | 'N') {
+                promotion = Piece::from_char(last);
+                end -= if prev == '=' { 2 } else { 1 };
+            }
+        }
+        if end < 2 {
+            return None;
+        }
+
+        let to: String = chars[end - 2..end].iter().collect();
+        let to = algebraic_to_square(&to)?;
+        let mut start = 0;
+        let piece = match chars[0] {
+            c @ ('N' | 'B' | 'R' | 'Q' | 'K') => {
+                start = 1;
+                Piece::from_char(c)
+            }
+            'P' => {
+                start = 1;
+                Some(Piece::Pawn)
+            }
+            _ => None,
+        };
+
+        // Whatever sits between the piece letter and the destination narrows
+        // down the origin square: a file, a rank or a full square
+        let mut from_file = None;
+        let mut from_rank = None;
+        for &c in &chars[start..end - 2] {
+            match c {
+                'a'..='h' => from_file = Some(c as u8 - b'a'),
+                '1'..='8' => from_rank = Some(c as u8 - b'1'),
+                'x' | ':' | '-' => {}
+                _ => return None,
+            }
+        }
+
+        let candidates: Vec<Move> = self.legal_moves()
+            .into_iter()
+            .filter(|m| m.to_square == to && m.promotion == promotion)
+            .filter(|m| from_file.is_none_or(|f| square_file(m.from_square) == f))
+            .filter(|m| from_rank.is_none_or(|r| square_rank(m.from_square) == r))
+            .filter(|m| match piece {
+                Some(p) => m.piece == p,
+                // Without a piece letter SAN means a pawn; coordinate
+                // notation ("g1f3") gives both squares for any piece
+                None => m.piece == Piece::Pawn || (from_file.is_some() && from_rank.is_some()),
+            })
+            .collect();
+
+        if candidates.len() == 1 {
+            candidates.into_iter().next()
+        } else {
+            None
+        }
+    }
+
+    fn find_castling(&self, kingside: bool) -> Option<Move> {
+        self.legal_moves()
+            .into_iter()
+            .find(|m| m.is_castling && (m.to_square > m.from_square) == kingside)
+    }
+}
+
+impl Default for Position {
+    fn default() -> Self {
+        Position::starting_position()
+    }
+}
@@ -0,0 +1,186 @@
+//! EPD position dump
+//!
+//! Replays the main line of each game and writes positions as EPD lines,
+//! for building engine test suites and training sets:
+//!
+//! ```text
+//! rnbqkbnr/pppppppp/8/8/4P3/8/PPPP1PPP/RNBQKBNR b KQkq e3 id "game 1, ply 1"; fmvn 1; hmvc 0; sm e5; c9 "1/2-1/2";
+//! ```
+//!
+//! ## Opcodes
+//! - `id`: game number in the database and the ply of the position
+//!   (0 is the standard start position)
+//! - `fmvn` / `hmvc`: full move number and halfmove clock, the FEN fields
+//!   EPD leaves out
+//! - `sm`: the move played from the position, in SAN; absent for the last
+//!   position of a game
+//! - `c9`: the game result, the convention used by labelled training sets
+//!
+//! Games whose moves can't be decoded are skipped with a warning.
+
+use std::fmt;
+use std::fs::File;
+use std::io::{self, BufWriter, Write};
+use std::path::Path;
+use std::str::FromStr;
+
+use crate::chess::{Game, Position};
+use crate::scid::ScidDatabase;
+use super::{ply, selected_games, GameFilter};
+
+/// Which positions of each game to write
+#[derive(Debug, Clone, PartialEq, Eq, Default)]
+pub enum EpdPositions {
+    /// The start position and the position after every move
+    #[default]
+    All,
+    /// Only the position after the last move
+    Final,
+    /// The positions after these numbers of half-moves from the standard
+    /// start; plies a game doesn't reach are left out
+    Plies(Vec<u32>),
+}
+
+impl fmt::Display for EpdPositions {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            EpdPositions::All => f.write_str("all"),
+            EpdPositions::Final => f.write_str("final"),
+            EpdPositions::Plies(plies) => {
+                let plies: Vec<_> = plies.iter().map(|ply| ply.to_string()).collect();
+                f.write_str(&plies.join(","))
+            }
+        }
+    }
+}
+
+impl FromStr for EpdPositions {
+    type Err = String;
+
+    /// "all", "final" or a comma-separated list of plies such as "10,20,30"
+    fn from_str(text: &str) -> Result<Self, Self::Err> {
+        match text.trim() {
+            "all" => Ok(EpdPositions::All),
+            "final" => Ok(EpdPositions::Final),
+            plies => plies.split(',')
+                .map(|ply| ply.trim().parse::<u32>()
+                    .map_err(|_| format!("invalid ply '{}' (expected all, final or a list of plies)", ply.trim())))
+                .collect::<Result<Vec<_>, _>>()
+                .map(EpdPositions::Plies),
+        }
+    }
+}
+
+/// EPD exporter for SCID databases
+pub struct EpdExporter {
+    positions: EpdPositions,
+    filter: GameFilter,
+    max_games: Option<usize>,
+}
+
+impl EpdExporter {
+    pub fn new() -> Self {
+        EpdExporter {
+            positions: EpdPositions::All,
+            filter: GameFilter::new(),
+            max_games: None,
+        }
+    }
+
+    pub fn with_positions(mut self, positions: EpdPositions) -> Self {
+        self.positions = positions;
+        self
+    }
+
+    /// Only export games the filter matches
+    pub fn with_filter(mut self, filter: GameFilter) -> Self {
+        self.filter = filter;
+        self
+    }
+
+    pub fn with_max_games(mut self, max: usize) -> Self {
+        self.max_games = Some(max);
+        self
+    }
+
+    /// Export SCID database positions to an EPD file
+    pub fn export(&mut self, database: &mut ScidDatabase, output_path: &Path) -> io::Result<usize> {
+        let file = File::create(output_path)?;
+        let mut writer = BufWriter::new(file);
+        let exported = self.write(database, &mut writer)?;
+        writer.flush()?;
+        Ok(exported)
+    }
+
+    /// Write the positions of the selected games to `writer`, returning how
+    /// many games were written
+    pub fn write<W: Write>(&mut self, database: &mut ScidDatabase, writer: &mut W) -> io::Result<usize> {
+        let mut exported = 0;
+        for (game_num, game_index) in selected_games(database, &self.filter, self.max_games) {
+            let game = match database.load_game(&game_index) {
+                Ok(game) => game,
+                Err(e) => {
+                    eprintln!("Warning: skipping game {}: {}", game_num + 1, e);
+                    continue;
+                }
+            };
+            if let Err(e) = self.write_game(writer, game_num + 1, &game) {
+                // A bad FEN is a problem with this game, not the output
+                if e.kind() != io::ErrorKind::InvalidData {
+                    return Err(e);
+                }
+                eprintln!("Warning: skipping game {}: {}", game_num + 1, e);
+                continue;
+            }
+
+            exported += 1;
+
+            // Progress indicator for large exports
+            if exported % 1000 == 0 {
+                eprintln!("Exported {} games...", exported);
+            }
+        }
+        Ok(exported)
+    }
+
+    fn write_game<W: Write>(&self, writer: &mut W, number: usize, game: &Game) -> io::Result<()> {
+        let mut pos = game.start_position()
+            .map_err(|e| io::Error::new(io::ErrorKind::InvalidData, format!("invalid start position: {}", e)))?;
+        let result = game.result.as_pgn();
+        let moves = &game.moves.moves;
+
+        for i in 0..=moves.len() {
+            let ply = ply(&pos) - 1;
+            let wanted = match &self.positions {
+                EpdPositions::All => true,
+                EpdPositions::Final => i == moves.len(),
+                EpdPositions::Plies(plies) => plies.contains(&ply),
+            };
+            let next = moves.get(i).map(|node| &node.mv);
+            if wanted {
+                let played = next.map(|mv| pos.san(mv));
+                write_epd(writer, &pos, number, ply, played.as_deref(), result)?;
+            }
+            if let Some(mv) = next {
+                pos.make_move(mv);
+            }
+        }
+        Ok(())
+    }
+}
+
+impl Default for EpdExporter {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+fn write_epd<W: Write>(writer: &mut W, pos: &Position, number: usize, ply: u32,
+                       played: Option<&str>, result: &str) -> io::Result<()> {
+    write!(writer, "{} id \"game {}, ply {}\"; fmvn {}; hmvc {};",
+           pos.to_epd(), number, ply, pos.full_move_number, pos.half_move_clock)?;
+    if let Some(san) = played {
+        write!(writer, " sm {};", san)?;
+    }
+    writeln!(writer, " c9 \"{}\";", result)
+}
@@ -0,0 +1,497 @@
+//! Filter expressions for game selection
+//!
+//! A small query language over the index fields of a game, compiled once
+//! into a predicate and evaluated like a `GameFilter`, before any .sg4 data
+//! is read:
+//!
+//! ```text
+//! white~"Carlsen" and date>=2020 and eco in B90..B99 and not result="1/2-1/2"
+//! ```
+//!
+//! ## Syntax
+//! - Conditions are `field op value` or `field in low..high`
+//! - Operators: `=`, `!=`, `<`, `<=`, `>`, `>=`, and `~` (contains)
+//! - Conditions combine with `and`, `or`, `not` and parentheses; `and`
+//!   binds tighter than `or`
+//! - Values are bare words (`B90`, `2020.05`, `1-0`) or double-quoted
+//!   strings with `\"` and `\\` escapes
+//!
+//! ## Fields
+//! - `white`, `black`, `event`, `site`, `round`: names, compared without
+//!   regard to case with `=`, `!=` and `~`
+//! - `player`: either player's name
+//! - `date` (`YYYY`, `YYYY.MM` or `YYYY.MM.DD`), `year`, `eco`, `ply`,
+//!   `white_elo`, `black_elo`: ordered values
+//! - `elo`: both players' ratings, so `elo>=2500` means both are 2500+;
+//!   unrated players count as 0
+//! - `result`: `1-0`, `0-1`, `1/2-1/2` or `*`, with `=` and `!=`
+//! - `flags`: SCID flag letters; `flags~WT` means both are set, `flags=WT`
+//!   means exactly those are set
+//!
+//! A value that covers a span covers all of it: `date=2020` is any day of
+//! 2020, `eco<=B99` includes `B99a4`, and `date in 2019..2020` runs to the
+//! end of 2020. Games with an unknown date, ECO code or name don't satisfy
+//! any condition on it except `!=`.
+
+use std::fmt;
+use std::ops;
+use std::str::FromStr;
+
+use crate::scid::eco::ECO_NONE;
+use crate::scid::names::NameDatabase;
+use crate::scid::GameIndex;
+use super::filter::{eco_bounds, parse_date, parse_result};
+
+/// Flag bits a user can set, everything above the deleted flag
+const USER_FLAGS: u16 = 0xFFF0;
+
+/// A compiled filter expression
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct FilterExpr {
+    node: Node,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+enum Node {
+    And(Box<Node>, Box<Node>),
+    Or(Box<Node>, Box<Node>),
+    Not(Box<Node>),
+    Test(Field, Op, Value),
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum Field {
+    White,
+    Black,
+    Player,
+    Event,
+    Site,
+    Round,
+    Date,
+    Year,
+    Result,
+    WhiteElo,
+    BlackElo,
+    Elo,
+    Eco,
+    Ply,
+    Flags,
+}
+
+/// Comparison of a field with a value; `!=` compiles to `not` and `=`, and
+/// `in` to `=` with a range covering both ends
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum Op {
+    Eq,
+    Lt,
+    Le,
+    Gt,
+    Ge,
+    Contains,
+}
+
+/// A value to compare with: lowercase text for names, or the inclusive
+/// span of keys a value covers for everything else
+#[derive(Debug, Clone, PartialEq, Eq)]
+enum Value {
+    Text(String),
+    Span(u32, u32),
+}
+
+impl Field {
+    fn from_name(name: &str) -> Option<Field> {
+        Some(match name.to_ascii_lowercase().as_str() {
+            "white" => Field::White,
+            "black" => Field::Black,
+            "player" => Field::Player,
+            "event" => Field::Event,
+            "site" => Field::Site,
+            "round" => Field::Round,
+            "date" => Field::Date,
+            "year" => Field::Year,
+            "result" => Field::Result,
+            "white_elo" | "whiteelo" => Field::WhiteElo,
+            "black_elo" | "blackelo" => Field::BlackElo,
+            "elo" => Field::Elo,
+            "eco" => Field::Eco,
+            "ply" | "plies" | "plycount" => Field::Ply,
+            "flags" => Field::Flags,
+            _ => return None,
+        })
+    }
+
+    fn is_name(self) -> bool {
+        matches!(self, Field::White | Field::Black | Field::Player | Field::Event | Field::Site | Field::Round)
+    }
+
+    /// Whether `op` means anything for this field
+    fn allows(self, op: Op) -> bool {
+        match self {
+            _ if self.is_name() => matches!(op, Op::Eq | Op::Contains),
+            Field::Result => op == Op::Eq,
+            Field::Flags => matches!(op, Op::Eq | Op::Contains),
+            _ => op != Op::Contains,
+        }
+    }
+
+    /// The span of keys `text` covers for this field
+    fn value(self, text: &str) -> Result<Value, String> {
+        let (low, high) = match self {
+            _ if self.is_name() => return Ok(Value::Text(text.to_lowercase())),
+            Field::Date => {
+                let (year, month, day) = parse_date(text)?;
+                let high_month = if month == 0 { 12 } else { month };
+                let high_day = if day == 0 { 31 } else { day };
+                (date_key(year, month, day), date_key(year, high_month, high_day))
+            }
+            Field::Eco => {
+                let (low, high) = eco_bounds(text)?;
+                (low as u32, high as u32)
+            }
+            Field::Result => {
+                let result = parse_result(text)?.to_scid() as u32;
+                (result, result)
+            }
+            Field::Flags => {
+                let mask = GameIndex::flag_mask(text)
+                    .ok_or_else(|| format!("invalid flags '{}' (expected letters from DWBMENPTKQ!?U)", text))?;
+                (mask as u32, mask as u32)
+            }
+            _ => {
+                let number = text.parse::<u16>()
+                    .map_err(|_| format!("invalid number '{}'", text))?;
+                (number as u32, number as u32)
+            }
+        };
+        Ok(Value::Span(low, high))
+    }
+
+    /// The keys of this field for `game`, with None for unknown values;
+    /// `player` and `elo` have one per player
+    fn keys(self, game: &GameIndex) -> [Option<u32>; 2] {
+        let known = |unknown: bool, key: u32| (!unknown).then_some(key);
+        let key = match self {
+            Field::Date => known(game.year == 0, date_key(game.year, game.month, game.day)),
+            Field::Year => known(game.year == 0, game.year as u32),
+            Field::Result => Some(game.result as u32),
+            Field::WhiteElo => Some(game.white_elo as u32),
+            Field::BlackElo => Some(game.black_elo as u32),
+            Field::Elo => return [Some(game.white_elo as u32), Some(game.black_elo as u32)],
+            Field::Eco => known(game.eco == ECO_NONE, game.eco as u32),
+            Field::Ply => Some(game.num_half_moves as u32),
+            _ => None,
+        };
+        [key, key]
+    }
+
+    /// The names of this field for `game`; `player` has both players
+    fn names<'a>(self, game: &GameIndex, names: &'a NameDatabase) -> [Option<&'a str>; 2] {
+        let white = names.player_name(game.white_id);
+        let black = names.player_name(game.black_id);
+        let name = match self {
+            Field::White => white,
+            Field::Black => black,
+            Field::Player => return [white, black],
+            Field::Event => names.event_name(game.event_id),
+            Field::Site => names.site_name(game.site_id),
+            _ => names.round_name(game.round_id),
+        };
+        [name, name]
+    }
+}
+
+fn date_key(year: u16, month: u8, day: u8) -> u32 {
+    (year as u32) << 9 | (month as u32) << 5 | day as u32
+}
+
+impl FilterExpr {
+    /// Compile an expression, with the column of the problem in the error
+    pub fn parse(text: &str) -> Result<FilterExpr, String> {
+        let tokens = tokenize(text)?;
+        let mut parser = Parser { tokens: &tokens, pos: 0, end: text.chars().count() + 1 };
+        let node = parser.or()?;
+        match parser.peek() {
+            None => Ok(FilterExpr { node }),
+            Some(token) => Err(format!("unexpected '{}' at column {}", token.text, token.column)),
+        }
+    }
+
+    /// Both expressions must match
+    pub fn and(self, other: FilterExpr) -> FilterExpr {
+        FilterExpr { node: Node::And(Box::new(self.node), Box::new(other.node)) }
+    }
+
+    /// Either expression must match
+    pub fn or(self, other: FilterExpr) -> FilterExpr {
+        FilterExpr { node: Node::Or(Box::new(self.node), Box::new(other.node)) }
+    }
+
+    /// Whether the game satisfies the expression
+    pub fn matches(&self, game: &GameIndex, names: &NameDatabase) -> bool {
+        self.node.matches(game, names)
+    }
+}
+
+impl ops::Not for FilterExpr {
+    type Output = FilterExpr;
+
+    fn not(self) -> FilterExpr {
+        FilterExpr { node: Node::Not(Box::new(self.node)) }
+    }
+}
+
+impl FromStr for FilterExpr {
+    type Err = String;
+
+    fn from_str(text: &str) -> Result<Self, Self::Err> {
+        FilterExpr::parse(text)
+    }
+}
+
+impl Node {
+    fn matches(&self, game: &GameIndex, names: &NameDatabase) -> bool {
+        match self {
+            Node::And(left, right) => left.matches(game, names) && right.matches(game, names),
+            Node::Or(left, right) => left.matches(game, names) || right.matches(game, names),
+            Node::Not(node) => !node.matches(game, names),
+            Node::Test(field, op, Value::Text(pattern)) => {
+                let test = |name: Option<&str>| {
+                    // "?" is how SCID stores a name nobody entered
+                    let Some(name) = name.filter(|name| *name != "?") else {
+                        return false;
+                    };
+                    let name = name.to_lowercase();
+                    match op {
+                        Op::Contains => name.contains(pattern.as_str()),
+                        _ => name == *pattern,
+                    }
+                };
+                field.names(game, names).into_iter().any(test)
+            }
+            Node::Test(Field::Flags, op, Value::Span(mask, _)) => {
+                let flags = (game.flags & USER_FLAGS) as u32;
+                match op {
+                    Op::Contains => flags & mask == *mask,
+                    _ => flags == *mask,
+                }
+            }
+            Node::Test(field, op, Value::Span(low, high)) => {
+                let test = |key: Option<u32>| key.is_some_and(|key| match op {
+                    Op::Eq | Op::Contains => *low <= key && key <= *high,
+                    Op::Lt => key < *low,
+                    Op::Le => key <= *high,
+                    Op::Gt => key > *high,
+                    Op::Ge => key >= *low,
+                });
+                // Both ratings for "elo"; the two keys are the same otherwise
+                field.keys(game).into_iter().all(test)
+            }
+        }
+    }
+}
+
+impl fmt::Display for Op {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.write_str(match self {
+            Op::Eq => "=",
+            Op::Lt => "<",
+            Op::Le => "<=",
+            Op::Gt => ">",
+            Op::Ge => ">=",
+            Op::Contains => "~",
+        })
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+enum TokenKind {
+    Word,
+    Quoted,
+    Symbol,
+}
+
+#[derive(Debug)]
+struct Token {
+    kind: TokenKind,
+    text: String,
+    /// One-based character column where the token starts
+    column: usize,
+}
+
+const SYMBOLS: [&str; 10] = ["..", "!=", "<=", ">=", "=", "<", ">", "~", "(", ")"];
+
+fn tokenize(text: &str) -> Result<Vec<Token>, String> {
+    let chars: Vec<char> = text.chars().collect();
+    let starts_with = |i: usize, symbol: &str| symbol.chars().enumerate().all(|(j, c)| chars.get(i + j) == Some(&c));
+    let mut tokens = Vec::new();
+    let mut i = 0;
+
+    while i < chars.len() {
+        let column = i + 1;
+        if chars[i].is_whitespace() {
+            i += 1;
+        } else if chars[i] == '"' {
+            let mut value = String::new();
+            i += 1;
+            loop {
+                match chars.get(i) {
+                    None => return Err(format!("unterminated string at column {}", column)),
+                    Some('"') => break,
+                    Some('\\') if matches!(chars.get(i + 1), Some('"' | '\\')) => {
+                        value.push(chars[i + 1]);
+                        i += 2;
+                    }
+                    Some(&c) => {
+                        value.push(c);
+                        i += 1;
+                    }
+                }
+            }
+            i += 1;
+            tokens.push(Token { kind: TokenKind::Quoted, text: value, column });
+        } else if let Some(symbol) = SYMBOLS.iter().find(|symbol| starts_with(i, symbol)) {
+            i += symbol.len();
+            tokens.push(Token { kind: TokenKind::Symbol, text: symbol.to_string(), column });
+        } else {
+            // A word runs to whitespace or a symbol, so "B90..B99" is three
+            // tokens but "1/2-1/2" and "2020.05" are one
+            let start = i;
+            while i < chars.len()
+                && !chars[i].is_whitespace()
+                && chars[i] != '"'
+                && !SYMBOLS.iter().any(|symbol| starts_with(i, symbol)) {
+                i += 1;
+            }
+            tokens.push(Token { kind: TokenKind::Word, text: chars[start..i].iter().collect(), column });
+        }
+    }
+    Ok(tokens)
+}
+
+/// Recursive descent over the tokens: `or` of `and`s of `not`s of
+/// conditions and parenthesised expressions
+struct Parser<'a> {
+    tokens: &'a [Token],
+    pos: usize,
+    /// Column just past the end, for errors about missing tokens
+    end: usize,
+}
+
+impl Parser<'_> {
+    fn peek(&self) -> Option<&Token> {
+        self.tokens.get(self.pos)
+    }
+
+    fn next(&mut self) -> Option<&Token> {
+        let token = self.tokens.get(self.pos);
+        self.pos += 1;
+        token
+    }
+
+    fn keyword(&mut self, keyword: &str) -> bool {
+        let found = self.peek()
+            .is_some_and(|token| token.kind == TokenKind::Word && token.text.eq_ignore_ascii_case(keyword));
+        if found {
+            self.pos += 1;
+        }
+        found
+    }
+
+    fn symbol(&mut self, symbol: &str) -> bool {
+        let found = self.peek().is_some_and(|token| token.kind == TokenKind::Symbol && token.text == symbol);
+        if found {
+            self.pos += 1;
+        }
+        found
+    }
+
+    fn expected(&self, what: &str) -> String {
+        match self.peek() {
+            Some(token) => format!("expected {} at column {}, found '{}'", what, token.column, token.text),
+            None => format!("expected {} at column {}", what, self.end),
+        }
+    }
+
+    fn or(&mut self) -> Result<Node, String> {
+        let mut node = self.and()?;
+        while self.keyword("or") {
+            node = Node::Or(Box::new(node), Box::new(self.and()?));
+        }
+        Ok(node)
+    }
+
+    fn and(&mut self) -> Result<Node, String> {
+        let mut node = self.not()?;
+        while self.keyword("and") {
+            node = Node::And(Box::new(node), Box::new(self.not()?));
+        }
+        Ok(node)
+    }
+
+    fn not(&mut self) -> Result<Node, String> {
+        if self.keyword("not") {
+            return Ok(Node::Not(Box::new(self.not()?)));
+        }
+        if self.symbol("(") {
+            let node = self.or()?;
+            if !self.symbol(")") {
+                return Err(self.expected("')'"));
+            }
+            return Ok(node);
+        }
+        self.condition()
+    }
+
+    fn condition(&mut self) -> Result<Node, String> {
+        let field = match self.peek() {
+            Some(token) if token.kind == TokenKind::Word => Field::from_name(&token.text)
+                .ok_or_else(|| format!("unknown field '{}' at column {}", token.text, token.column))?,
+            _ => return Err(self.expected("a field name")),
+        };
+        self.pos += 1;
+
+        if self.keyword("in") {
+            if field.is_name() || matches!(field, Field::Result | Field::Flags) {
+                return Err(format!("'in' needs an ordered field at column {}", self.tokens[self.pos - 1].column));
+            }
+            let Value::Span(low, _) = self.value(field)? else { unreachable!() };
+            if !self.symbol("..") {
+                return Err(self.expected("'..'"));
+            }
+            let Value::Span(_, high) = self.value(field)? else { unreachable!() };
+            return Ok(Node::Test(field, Op::Eq, Value::Span(low, high)));
+        }
+
+        let column = self.peek().map_or(self.end, |token| token.column);
+        let (op, negate) = match self.next().filter(|token| token.kind == TokenKind::Symbol).map(|token| token.text.as_str()) {
+            Some("=") => (Op::Eq, false),
+            Some("!=") => (Op::Eq, true),
+            Some("<") => (Op::Lt, false),
+            Some("<=") => (Op::Le, false),
+            Some(">") => (Op::Gt, false),
+            Some(">=") => (Op::Ge, false),
+            Some("~") => (Op::Contains, false),
+            _ => {
+                self.pos -= 1;
+                return Err(self.expected("an operator"));
+            }
+        };
+        if !field.allows(op) {
+            return Err(format!("operator '{}' can't be used with this field at column {}", op, column));
+        }
+        let test = Node::Test(field, op, self.value(field)?);
+        Ok(if negate { Node::Not(Box::new(test)) } else { test })
+    }
+
+    fn value(&mut self, field: Field) -> Result<Value, String> {
+        match self.peek() {
+            Some(token) if token.kind != TokenKind::Symbol => {
+                let value = field.value(&token.text)
+                    .map_err(|e| format!("{} at column {}", e, token.column))?;
+                self.pos += 1;
+                Ok(value)
+            }
+            _ => Err(self.expected("a value")),
+        }
+    }
+}
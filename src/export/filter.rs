@@ -0,0 +1,271 @@
+//! Game selection by index fields
+//!
+//! A `GameFilter` is checked against each game's `GameIndex` and the names
+//! it refers to, so games are chosen before any .sg4 data is read. Every
+//! condition that is set must hold; an empty filter matches every game.
+//!
+//! ## Matching
+//! - Names: case-insensitive substring, so "carlsen" matches
+//!   "Carlsen, Magnus"
+//! - Dates: inclusive; a bound without a month or day covers the whole
+//!   year or month, and games with no year never match a date bound
+//! - Elo: both players' index ratings must be in range; unrated players
+//!   only pass when there is no minimum
+//! - ECO: inclusive, with "B99" as an upper bound covering "B99a4"; games
+//!   without an ECO code never match
+//! - Flags: every given index flag must be set
+//!
+//! Conditions the fixed fields can't express, such as alternatives or
+//! negations, can be added as a `FilterExpr`. Games can also be left out
+//! by number, which `selects` checks and `matches` doesn't.
+
+use std::collections::BTreeSet;
+
+use crate::chess::GameResult;
+use crate::scid::eco::{eco_from_str, ECO_NONE};
+use crate::scid::names::NameDatabase;
+use crate::scid::GameIndex;
+use super::expression::FilterExpr;
+
+/// Conditions on the index fields of a game
+#[derive(Debug, Clone, Default)]
+pub struct GameFilter {
+    player: Option<String>,
+    white: Option<String>,
+    black: Option<String>,
+    event: Option<String>,
+    site: Option<String>,
+    pairing: Option<(u32, u32)>,
+    date_from: Option<(u16, u8, u8)>,
+    date_to: Option<(u16, u8, u8)>,
+    result: Option<GameResult>,
+    min_elo: Option<u16>,
+    max_elo: Option<u16>,
+    eco: Option<(u16, u16)>,
+    min_ply: Option<u16>,
+    flags: u16,
+    expression: Option<FilterExpr>,
+    /// Zero-based game numbers to leave out
+    excluded: BTreeSet<usize>,
+}
+
+impl GameFilter {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Games where either player's name contains `name`
+    pub fn with_player(mut self, name: &str) -> Self {
+        self.player = Some(name.to_lowercase());
+        self
+    }
+
+    pub fn with_white(mut self, name: &str) -> Self {
+        self.white = Some(name.to_lowercase());
+        self
+    }
+
+    pub fn with_black(mut self, name: &str) -> Self {
+        self.black = Some(name.to_lowercase());
+        self
+    }
+
+    pub fn with_event(mut self, name: &str) -> Self {
+        self.event = Some(name.to_lowercase());
+        self
+    }
+
+    pub fn with_site(mut self, name: &str) -> Self {
+        self.site = Some(name.to_lowercase());
+        self
+    }
+
+    /// Games between these two player IDs, with either one as white
+    pub fn with_pairing(mut self, first: u32, second: u32) -> Self {
+        self.pairing = Some((first, second));
+        self
+    }
+
+    /// Games on or after this (year, month, day); 0 for an unknown month or day
+    pub fn with_date_from(mut self, date: (u16, u8, u8)) -> Self {
+        self.date_from = Some(date);
+        self
+    }
+
+    /// Games on or before this (year, month, day); a month or day of 0
+    /// covers the whole year or month
+    pub fn with_date_to(mut self, (year, month, day): (u16, u8, u8)) -> Self {
+        let month = if month == 0 { 12 } else { month };
+        let day = if day == 0 { 31 } else { day };
+        self.date_to = Some((year, month, day));
+        self
+    }
+
+    pub fn with_result(mut self, result: GameResult) -> Self {
+        self.result = Some(result);
+        self
+    }
+
+    /// Games where both players are rated at least this much
+    pub fn with_min_elo(mut self, elo: u16) -> Self {
+        self.min_elo = Some(elo);
+        self
+    }
+
+    /// Games where neither player is rated above this
+    pub fn with_max_elo(mut self, elo: u16) -> Self {
+        self.max_elo = Some(elo);
+        self
+    }
+
+    /// Games with an index ECO code between `from` and `to` inclusive, as
+    /// encoded by `eco_from_str`; see `parse_eco_range`
+    pub fn with_eco_range(mut self, from: u16, to: u16) -> Self {
+        self.eco = Some((from, to));
+        self
+    }
+
+    pub fn with_min_ply(mut self, ply: u16) -> Self {
+        self.min_ply = Some(ply);
+        self
+    }
+
+    /// Games with all of these index flag bits set; see `GameIndex::flag_mask`
+    pub fn with_flags(mut self, flags: u16) -> Self {
+        self.flags = flags;
+        self
+    }
+
+    /// Games the expression matches, as well as the other conditions;
+    /// a second expression is combined with `and`
+    pub fn with_expression(mut self, expression: FilterExpr) -> Self {
+        self.expression = Some(match self.expression.take() {
+            Some(existing) => existing.and(expression),
+            None => expression,
+        });
+        self
+    }
+
+    /// Leave out these games, by zero-based number, e.g. the duplicates
+    /// found by `DuplicateFinder`
+    pub fn with_excluded_games(mut self, games: impl IntoIterator<Item = usize>) -> Self {
+        self.excluded.extend(games);
+        self
+    }
+
+    /// Whether game number `game_num` (zero-based) passes the filter,
+    /// including the games it leaves out by number
+    pub fn selects(&self, game_num: usize, game: &GameIndex, names: &NameDatabase) -> bool {
+        !self.excluded.contains(&game_num) && self.matches(game, names)
+    }
+
+    /// Whether the game passes every condition of the filter on its index
+    /// fields
+    pub fn matches(&self, game: &GameIndex, names: &NameDatabase) -> bool {
+        if self.pairing.is_some_and(|(first, second)| {
+            (game.white_id, game.black_id) != (first, second) && (game.white_id, game.black_id) != (second, first)
+        }) {
+            return false;
+        }
+
+        let name_ok = |pattern: &Option<String>, name: Option<&str>| match pattern {
+            Some(pattern) => name.is_some_and(|name| name.to_lowercase().contains(pattern.as_str())),
+            None => true,
+        };
+        let white = names.player_name(game.white_id);
+        let black = names.player_name(game.black_id);
+        if self.player.is_some() && !name_ok(&self.player, white) && !name_ok(&self.player, black) {
+            return false;
+        }
+        if !name_ok(&self.white, white)
+            || !name_ok(&self.black, black)
+            || !name_ok(&self.event, names.event_name(game.event_id))
+            || !name_ok(&self.site, names.site_name(game.site_id)) {
+            return false;
+        }
+
+        if self.date_from.is_some() || self.date_to.is_some() {
+            let date = (game.year, game.month, game.day);
+            if game.year == 0
+                || self.date_from.is_some_and(|from| date < from)
+                || self.date_to.is_some_and(|to| date > to) {
+                return false;
+            }
+        }
+
+        if self.result.is_some_and(|result| result != GameResult::from_scid(game.result)) {
+            return false;
+        }
+
+        for elo in [game.white_elo, game.black_elo] {
+            if self.min_elo.is_some_and(|min| elo < min) || self.max_elo.is_some_and(|max| elo > max) {
+                return false;
+            }
+        }
+
+        if self.eco.is_some_and(|(from, to)| game.eco == ECO_NONE || game.eco < from || game.eco > to) {
+            return false;
+        }
+
+        self.min_ply.is_none_or(|min| game.num_half_moves >= min)
+            && game.flags & self.flags == self.flags
+            && self.expression.as_ref().is_none_or(|expression| expression.matches(game, names))
+    }
+}
+
+/// Parse a date bound such as "2020", "2020.05" or "2020-05-17" into
+/// (year, month, day), with 0 for the parts left out
+pub fn parse_date(text: &str) -> Result<(u16, u8, u8), String> {
+    let invalid = || format!("invalid date '{}' (expected YYYY, YYYY.MM or YYYY.MM.DD)", text);
+    let mut parts = text.trim().split(['.', '-', '/']);
+    let year: u16 = parts.next().and_then(|year| year.parse().ok()).ok_or_else(invalid)?;
+    let month: u8 = match parts.next() {
+        Some(month) => month.parse().ok().filter(|month| (1..=12).contains(month)).ok_or_else(invalid)?,
+        None => 0,
+    };
+    let day: u8 = match parts.next() {
+        Some(day) => day.parse().ok().filter(|day| (1..=31).contains(day)).ok_or_else(invalid)?,
+        None => 0,
+    };
+    if parts.next().is_some() || year == 0 {
+        return Err(invalid());
+    }
+    Ok((year, month, day))
+}
+
+/// Parse an ECO code or range such as "B90", "B90-B99" or "B90..B99" into
+/// the inclusive index codes for `GameFilter::with_eco_range`
+pub fn parse_eco_range(text: &str) -> Result<(u16, u16), String> {
+    let (from, to) = text.split_once("..")
+        .or_else(|| text.split_once('-'))
+        .unwrap_or((text, text));
+    let (from, _) = eco_bounds(from)?;
+    let (_, to) = eco_bounds(to)?;
+    if from > to {
+        return Err(format!("empty ECO range '{}'", text.trim()));
+    }
+    Ok((from, to))
+}
+
+/// The first and last index codes an ECO code covers: "B90" covers its
+/// extensions up to "B90z4", "B90a" up to "B90a4"
+pub(crate) fn eco_bounds(eco: &str) -> Result<(u16, u16), String> {
+    let code = match eco_from_str(eco) {
+        ECO_NONE => return Err(format!("invalid ECO code '{}'", eco.trim())),
+        code => code,
+    };
+    let extensions = match eco.trim().len() {
+        3 => 130,
+        4 => 4,
+        _ => 0,
+    };
+    Ok((code, code + extensions))
+}
+
+/// Parse a result such as "1-0", "0-1", "1/2-1/2" or "*"
+pub fn parse_result(text: &str) -> Result<GameResult, String> {
+    match GameResult::from_pgn(text) {
+        GameResult::Unknown if text.trim() != "*" => Err(format!("invalid result '{}' (expected 1-0, 0-1, 1/2-1/2 or *)", text.trim())),
+        result => Ok(result),
+    }
+}
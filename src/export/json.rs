@@ -0,0 +1,329 @@
+//! JSON and NDJSON export
+//!
+//! Each game becomes one object with the index headers as typed fields and
+//! the moves as a tree of SAN/UCI pairs:
+//!
+//! ```text
+//! {"number":1,"event":"Paris m","site":"Paris FRA","date":"1858-12-20",
+//!  "round":"1","white":"Morphy, Paul","black":"Anderssen, Adolf",
+//!  "result":"1/2-1/2","white_elo":2690,"black_elo":null,"eco":"C78",
+//!  "ply_count":9,"flags":{"non_standard_start":false,...,"custom":[]},
+//!  "tags":{"Annotator":"Tester"},"start_fen":null,
+//!  "moves":[{"ply":1,"san":"e4","uci":"e2e4"},...]}
+//! ```
+//!
+//! Unknown names, dates, ratings and ECO codes are `null` rather than the
+//! "?" placeholders of PGN. Dates are ISO 8601, shortened to "YYYY-MM" or
+//! "YYYY" when only part of the date is known. NAGs are always written;
+//! comments and variations only when enabled, as for PGN. A game whose
+//! moves can't be decoded keeps its index fields, has no moves and says why
+//! in `error`.
+//!
+//! NDJSON output writes one object per line; otherwise the games are
+//! written as a single JSON array.
+
+use std::fs::File;
+use std::io::{self, BufWriter, Write};
+use std::path::Path;
+
+use serde::Serialize;
+use serde_json::{Map, Value};
+
+use crate::chess::{Line, Position};
+use crate::scid::eco::eco_to_string;
+use crate::scid::index::{IDX_FLAG_DELETE, IDX_FLAG_PROMO, IDX_FLAG_START, IDX_FLAG_UPROMO};
+use crate::scid::{GameIndex, ScidDatabase};
+use super::{ply, selected_games, GameFilter, TYPED_TAGS};
+
+/// One exported game
+#[derive(Debug, Serialize)]
+pub struct JsonGame {
+    /// Game number in the database, counting from 1
+    pub number: usize,
+    pub event: Option<String>,
+    pub site: Option<String>,
+    pub date: Option<String>,
+    pub round: Option<String>,
+    pub white: Option<String>,
+    pub black: Option<String>,
+    pub result: &'static str,
+    pub white_elo: Option<u16>,
+    pub black_elo: Option<u16>,
+    pub eco: Option<String>,
+    pub ply_count: u16,
+    pub flags: JsonFlags,
+    /// Tags stored in the game record, in their original order
+    pub tags: Map<String, Value>,
+    pub start_fen: Option<String>,
+    /// Comment before the first move
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub comment: Option<String>,
+    pub moves: Vec<JsonMove>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub error: Option<String>,
+}
+
+/// Index flags describing the game: those set from its moves, then the
+/// ones a user marks it with, as `GameIndex::flag_string` lists them
+#[derive(Debug, Serialize)]
+pub struct JsonFlags {
+    pub non_standard_start: bool,
+    pub promotions: bool,
+    pub under_promotions: bool,
+    pub deleted: bool,
+    pub white_opening: bool,
+    pub black_opening: bool,
+    pub middlegame: bool,
+    pub endgame: bool,
+    pub novelty: bool,
+    pub pawn_structure: bool,
+    pub tactics: bool,
+    pub kingside: bool,
+    pub queenside: bool,
+    pub brilliancy: bool,
+    pub blunder: bool,
+    pub user: bool,
+    /// Numbers (1 to 6) of the custom flags set
+    pub custom: Vec<u8>,
+}
+
+impl JsonFlags {
+    fn new(game_index: &GameIndex) -> Self {
+        let flag = |bit: u16| game_index.flags & (1 << bit) != 0;
+        JsonFlags {
+            non_standard_start: game_index.flags & IDX_FLAG_START != 0,
+            promotions: game_index.flags & IDX_FLAG_PROMO != 0,
+            under_promotions: game_index.flags & IDX_FLAG_UPROMO != 0,
+            deleted: game_index.flags & IDX_FLAG_DELETE != 0,
+            white_opening: flag(4),
+            black_opening: flag(5),
+            middlegame: flag(6),
+            endgame: flag(7),
+            novelty: flag(8),
+            pawn_structure: flag(9),
+            tactics: flag(10),
+            kingside: flag(11),
+            queenside: flag(12),
+            brilliancy: flag(13),
+            blunder: flag(14),
+            user: flag(15),
+            custom: (1..=6).filter(|number| game_index.custom_flags & (1 << (number - 1)) != 0).collect(),
+        }
+    }
+}
+
+#[derive(Debug, Serialize)]
+pub struct JsonMove {
+    /// Half-move number of the move, counting from 1 at the standard start
+    pub ply: u32,
+    pub san: String,
+    pub uci: String,
+    #[serde(skip_serializing_if = "Vec::is_empty")]
+    pub nags: Vec<u8>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub comment: Option<String>,
+    /// Alternatives to this move, each starting from the position before it
+    #[serde(skip_serializing_if = "Vec::is_empty")]
+    pub variations: Vec<JsonLine>,
+}
+
+#[derive(Debug, Serialize)]
+pub struct JsonLine {
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub comment: Option<String>,
+    pub moves: Vec<JsonMove>,
+}
+
+/// JSON exporter for SCID databases
+pub struct JsonExporter {
+    ndjson: bool,
+    pretty: bool,
+    include_variations: bool,
+    include_comments: bool,
+    filter: GameFilter,
+    max_games: Option<usize>,
+}
+
+impl JsonExporter {
+    pub fn new() -> Self {
+        JsonExporter {
+            ndjson: false,
+            pretty: false,
+            include_variations: false,
+            include_comments: false,
+            filter: GameFilter::new(),
+            max_games: None,
+        }
+    }
+
+    /// Write one object per line instead of a single array
+    pub fn with_ndjson(mut self, ndjson: bool) -> Self {
+        self.ndjson = ndjson;
+        self
+    }
+
+    /// Indent the output; ignored for NDJSON, which needs one game per line
+    pub fn with_pretty(mut self, pretty: bool) -> Self {
+        self.pretty = pretty;
+        self
+    }
+
+    pub fn with_variations(mut self, include: bool) -> Self {
+        self.include_variations = include;
+        self
+    }
+
+    pub fn with_comments(mut self, include: bool) -> Self {
+        self.include_comments = include;
+        self
+    }
+
+    /// Only export games the filter matches
+    pub fn with_filter(mut self, filter: GameFilter) -> Self {
+        self.filter = filter;
+        self
+    }
+
+    pub fn with_max_games(mut self, max: usize) -> Self {
+        self.max_games = Some(max);
+        self
+    }
+
+    /// Export SCID database to a JSON or NDJSON file
+    pub fn export(&mut self, database: &mut ScidDatabase, output_path: &Path) -> io::Result<usize> {
+        let file = File::create(output_path)?;
+        let mut writer = BufWriter::new(file);
+        let exported = self.write(database, &mut writer)?;
+        writer.flush()?;
+        Ok(exported)
+    }
+
+    /// Write the selected games to `writer`, returning how many were written
+    pub fn write<W: Write>(&mut self, database: &mut ScidDatabase, writer: &mut W) -> io::Result<usize> {
+        let games = selected_games(database, &self.filter, self.max_games);
+        let mut exported = 0;
+
+        if !self.ndjson {
+            writer.write_all(b"[")?;
+        }
+
+        for (game_num, game_index) in &games {
+            let game = self.json_game(database, *game_num, game_index);
+
+            if self.ndjson {
+                serde_json::to_writer(&mut *writer, &game)?;
+            } else {
+                writer.write_all(if exported == 0 { b"\n" } else { b",\n" })?;
+                if self.pretty {
+                    serde_json::to_writer_pretty(&mut *writer, &game)?;
+                } else {
+                    serde_json::to_writer(&mut *writer, &game)?;
+                }
+            }
+
+            exported += 1;
+
+            if self.ndjson {
+                writer.write_all(b"\n")?;
+            }
+
+            // Progress indicator for large exports
+            if exported % 1000 == 0 {
+                eprintln!("Exported {} games...", exported);
+            }
+        }
+
+        if !self.ndjson {
+            writer.write_all(b"\n]\n")?;
+        }
+        Ok(exported)
+    }
+
+    /// Build the JSON form of one game; `game_num` is its zero-based
+    /// position in the index
+    pub fn json_game(&self, database: &mut ScidDatabase, game_num: usize, game_index: &GameIndex) -> JsonGame {
+        let known = |name: Option<&str>| name.filter(|name| *name != "?").map(str::to_string);
+
+        let mut json = JsonGame {
+            number: game_num + 1,
+            event: known(database.event_name(game_index.event_id)),
+            site: known(database.site_name(game_index.site_id)),
+            date: game_index.iso_date(),
+            round: known(database.round_name(game_index.round_id)),
+            white: known(database.player_name(game_index.white_id)),
+            black: known(database.player_name(game_index.black_id)),
+            result: game_index.result_string(),
+            white_elo: Some(game_index.white_elo).filter(|elo| *elo > 0),
+            black_elo: Some(game_index.black_elo).filter(|elo| *elo > 0),
+            eco: eco_to_string(game_index.eco),
+            ply_count: game_index.num_half_moves,
+            flags: JsonFlags::new(game_index),
+            tags: Map::new(),
+            start_fen: None,
+            comment: None,
+            moves: Vec::new(),
+            error: None,
+        };
+
+        let game = match database.load_game(game_index) {
+            Ok(game) => game,
+            Err(e) => {
+                json.error = Some(format!("Unable to decode SCID moves: {}", e));
+                return json;
+            }
+        };
+        let start = match game.start_position() {
+            Ok(start) => start,
+            Err(e) => {
+                json.error = Some(format!("Invalid start position: {}", e));
+                return json;
+            }
+        };
+
+        for (name, value) in &game.tags {
+            if !TYPED_TAGS.contains(&name.as_str()) {
+                json.tags.insert(name.clone(), Value::String(value.clone()));
+            }
+        }
+        json.start_fen = game.tag("FEN").map(str::to_string);
+        let line = self.json_line(&start, &game.moves);
+        json.comment = line.comment;
+        json.moves = line.moves;
+        json
+    }
+
+    fn json_line(&self, start: &Position, line: &Line) -> JsonLine {
+        let mut pos = start.clone();
+        let mut moves = Vec::with_capacity(line.moves.len());
+
+        for node in &line.moves {
+            let ply = ply(&pos);
+            let variations = if self.include_variations {
+                node.variations.iter().map(|variation| self.json_line(&pos, variation)).collect()
+            } else {
+                Vec::new()
+            };
+
+            moves.push(JsonMove {
+                ply,
+                san: pos.san(&node.mv),
+                uci: node.mv.to_uci(),
+                nags: node.nags.clone(),
+                comment: node.comment.clone().filter(|_| self.include_comments),
+                variations,
+            });
+            pos.make_move(&node.mv);
+        }
+
+        JsonLine {
+            comment: line.comment.clone().filter(|_| self.include_comments),
+            moves,
+        }
+    }
+}
+
+impl Default for JsonExporter {
+    fn default() -> Self {
+        Self::new()
+    }
+}
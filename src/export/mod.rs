@@ -0,0 +1,56 @@
+//! Export formats other than PGN
+//!
+//! Every exporter walks the same selection of games as `PgnExporter`:
+//! deleted games are skipped, a `GameFilter` chooses games from the index
+//! and `max_games` limits the number written.
+
+pub mod csv;
+pub mod epd;
+pub mod expression;
+pub mod filter;
+pub mod json;
+#[cfg(feature = "parquet")]
+pub mod parquet;
+pub mod polyglot;
+#[cfg(feature = "sqlite")]
+pub mod sqlite;
+
+pub use csv::{CsvColumn, CsvExporter};
+pub use epd::{EpdExporter, EpdPositions};
+pub use expression::FilterExpr;
+pub use filter::GameFilter;
+pub use json::JsonExporter;
+#[cfg(feature = "parquet")]
+pub use parquet::ParquetExporter;
+pub use polyglot::{BookEntry, PolyglotExporter};
+#[cfg(feature = "sqlite")]
+pub use sqlite::SqliteExporter;
+
+use crate::chess::{Color, Position};
+use crate::scid::{GameIndex, ScidDatabase};
+
+/// Tags the structured formats write as typed fields; the rest are kept as
+/// name/value pairs
+const TYPED_TAGS: [&str; 13] = [
+    "Event", "Site", "Date", "Round", "White", "Black", "Result",
+    "WhiteElo", "BlackElo", "ECO", "PlyCount", "SetUp", "FEN",
+];
+
+/// The index entries an export writes, in database order, with their
+/// zero-based game numbers: every game that isn't deleted and matches
+/// `filter`, up to `max_games` of them
+pub fn selected_games(database: &ScidDatabase, filter: &GameFilter, max_games: Option<usize>) -> Vec<(usize, GameIndex)> {
+    database.game_indices()
+        .iter()
+        .enumerate()
+        .filter(|(game_num, game_index)| !game_index.is_deleted() && filter.selects(*game_num, game_index, database.names()))
+        .take(max_games.unwrap_or(usize::MAX))
+        .map(|(game_num, game_index)| (game_num, game_index.clone()))
+        .collect()
+}
+
+/// Half-move number of the next move from `pos`, counting from 1 at the
+/// standard start
+fn ply(pos: &Position) -> u32 {
+    (u32::from(pos.full_move_number).max(1) - 1) * 2 + u32::from(pos.to_move == Color::Black) + 1
+}
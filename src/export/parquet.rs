@@ -0,0 +1,400 @@
+//! Apache Parquet export through Arrow record batches
+//!
+//! The games table has one row per game, built from the index fields of
+//! `ScidDatabase::game_indices()` plus a summary of the decoded game:
+//!
+//! ```text
+//! number, event, site, date, year, month, day, round, white, black,
+//! result, white_elo, black_elo, eco, ply_count, flags,
+//! non_standard_start, promotions, under_promotions,
+//! variations, comments, nags,        -- annotation counts from the index
+//! start_fen, final_fen, error        -- from the decoded moves
+//! ```
+//!
+//! The optional plies table has one row per main line move:
+//!
+//! ```text
+//! game, ply, san, uci, fen, clock, eval_cp, eval_mate
+//! ```
+//!
+//! `game` is the `number` of the game row and `fen` is the position after
+//! the move. `clock` (seconds left) and the engine evaluation come from
+//! `[%clk 1:23:45]` and `[%eval 0.35]` / `[%eval #-3]` commands in the move
+//! comment, as written by Lichess, ChessBase and most broadcast tools.
+//!
+//! Rows are written in batches of `BATCH_SIZE`, so memory use doesn't grow
+//! with the database. Both files are Snappy-compressed.
+
+use std::fs::File;
+use std::io;
+use std::path::{Path, PathBuf};
+use std::sync::Arc;
+
+use arrow_array::builder::{
+    BooleanBuilder, Float64Builder, Int32Builder, StringBuilder, UInt16Builder, UInt32Builder, UInt8Builder,
+};
+use arrow_array::{ArrayRef, RecordBatch};
+use arrow_schema::{DataType, Field, Schema, SchemaRef};
+use parquet::arrow::ArrowWriter;
+use parquet::basic::Compression;
+use parquet::file::properties::WriterProperties;
+
+use crate::chess::Game;
+use crate::scid::eco::eco_to_string;
+use crate::scid::index::{IDX_FLAG_PROMO, IDX_FLAG_START, IDX_FLAG_UPROMO};
+use crate::scid::{GameIndex, ScidDatabase};
+use super::{ply, selected_games, GameFilter};
+
+/// Rows buffered before a record batch is written
+const BATCH_SIZE: usize = 8192;
+
+/// Parquet exporter for SCID databases
+pub struct ParquetExporter {
+    include_moves: bool,
+    plies_path: Option<PathBuf>,
+    filter: GameFilter,
+    max_games: Option<usize>,
+}
+
+impl ParquetExporter {
+    pub fn new() -> Self {
+        ParquetExporter {
+            include_moves: true,
+            plies_path: None,
+            filter: GameFilter::new(),
+            max_games: None,
+        }
+    }
+
+    /// Decode games for the start/final position and error columns;
+    /// without this only the index is read and the plies table is empty
+    pub fn with_moves(mut self, include: bool) -> Self {
+        self.include_moves = include;
+        self
+    }
+
+    /// Also write the plies table to this file
+    pub fn with_plies<P: AsRef<Path>>(mut self, path: P) -> Self {
+        self.plies_path = Some(path.as_ref().to_path_buf());
+        self
+    }
+
+    /// Only export games the filter matches
+    pub fn with_filter(mut self, filter: GameFilter) -> Self {
+        self.filter = filter;
+        self
+    }
+
+    pub fn with_max_games(mut self, max: usize) -> Self {
+        self.max_games = Some(max);
+        self
+    }
+
+    /// Export SCID database to Parquet, returning the number of games written
+    pub fn export(&mut self, database: &mut ScidDatabase, output_path: &Path) -> io::Result<usize> {
+        let mut games_writer = parquet_writer(output_path, game_schema())?;
+        let mut plies_writer = match &self.plies_path {
+            Some(path) => Some(parquet_writer(path, ply_schema())?),
+            None => None,
+        };
+
+        let mut games = GameColumns::default();
+        let mut plies = PlyColumns::default();
+        let mut exported = 0;
+
+        for (game_num, game_index) in selected_games(database, &self.filter, self.max_games) {
+            let number = game_num as u32 + 1;
+            let game = if self.include_moves {
+                Some(database.load_game(&game_index))
+            } else {
+                None
+            };
+            games.append(database, number, &game_index, game.as_ref());
+
+            if let (Some(_), Some(Ok(game))) = (&plies_writer, &game) {
+                plies.append(number, game);
+            }
+
+            exported += 1;
+            if games.len >= BATCH_SIZE {
+                write_batch(&mut games_writer, games.finish())?;
+            }
+            if let Some(writer) = &mut plies_writer {
+                if plies.len >= BATCH_SIZE {
+                    write_batch(writer, plies.finish())?;
+                }
+            }
+
+            // Progress indicator for large exports
+            if exported % 1000 == 0 {
+                eprintln!("Exported {} games...", exported);
+            }
+        }
+
+        write_batch(&mut games_writer, games.finish())?;
+        games_writer.close().map_err(io::Error::other)?;
+        if let Some(mut writer) = plies_writer {
+            write_batch(&mut writer, plies.finish())?;
+            writer.close().map_err(io::Error::other)?;
+        }
+        Ok(exported)
+    }
+}
+
+impl Default for ParquetExporter {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+fn parquet_writer(path: &Path, schema: SchemaRef) -> io::Result<ArrowWriter<File>> {
+    let properties = WriterProperties::builder()
+        .set_compression(Compression::SNAPPY)
+        .build();
+    ArrowWriter::try_new(File::create(path)?, schema, Some(properties)).map_err(io::Error::other)
+}
+
+fn write_batch(writer: &mut ArrowWriter<File>, batch: io::Result<RecordBatch>) -> io::Result<()> {
+    let batch = batch?;
+    if batch.num_rows() > 0 {
+        writer.write(&batch).map_err(io::Error::other)?;
+    }
+    Ok(())
+}
+
+fn game_schema() -> SchemaRef {
+    let text = |name| Field::new(name, DataType::Utf8, true);
+    Arc::new(Schema::new(vec![
+        Field::new("number", DataType::UInt32, false),
+        text("event"),
+        text("site"),
+        text("date"),
+        Field::new("year", DataType::UInt16, true),
+        Field::new("month", DataType::UInt8, true),
+        Field::new("day", DataType::UInt8, true),
+        text("round"),
+        text("white"),
+        text("black"),
+        Field::new("result", DataType::Utf8, false),
+        Field::new("white_elo", DataType::UInt16, true),
+        Field::new("black_elo", DataType::UInt16, true),
+        text("eco"),
+        Field::new("ply_count", DataType::UInt16, false),
+        Field::new("flags", DataType::Utf8, false),
+        Field::new("non_standard_start", DataType::Boolean, false),
+        Field::new("promotions", DataType::Boolean, false),
+        Field::new("under_promotions", DataType::Boolean, false),
+        Field::new("variations", DataType::UInt8, false),
+        Field::new("comments", DataType::UInt8, false),
+        Field::new("nags", DataType::UInt8, false),
+        text("start_fen"),
+        text("final_fen"),
+        text("error"),
+    ]))
+}
+
+fn ply_schema() -> SchemaRef {
+    Arc::new(Schema::new(vec![
+        Field::new("game", DataType::UInt32, false),
+        Field::new("ply", DataType::UInt32, false),
+        Field::new("san", DataType::Utf8, false),
+        Field::new("uci", DataType::Utf8, false),
+        Field::new("fen", DataType::Utf8, false),
+        Field::new("clock", DataType::Float64, true),
+        Field::new("eval_cp", DataType::Int32, true),
+        Field::new("eval_mate", DataType::Int32, true),
+    ]))
+}
+
+/// Column builders for the games table, in schema order
+#[derive(Default)]
+struct GameColumns {
+    len: usize,
+    number: UInt32Builder,
+    event: StringBuilder,
+    site: StringBuilder,
+    date: StringBuilder,
+    year: UInt16Builder,
+    month: UInt8Builder,
+    day: UInt8Builder,
+    round: StringBuilder,
+    white: StringBuilder,
+    black: StringBuilder,
+    result: StringBuilder,
+    white_elo: UInt16Builder,
+    black_elo: UInt16Builder,
+    eco: StringBuilder,
+    ply_count: UInt16Builder,
+    flags: StringBuilder,
+    non_standard_start: BooleanBuilder,
+    promotions: BooleanBuilder,
+    under_promotions: BooleanBuilder,
+    variations: UInt8Builder,
+    comments: UInt8Builder,
+    nags: UInt8Builder,
+    start_fen: StringBuilder,
+    final_fen: StringBuilder,
+    error: StringBuilder,
+}
+
+impl GameColumns {
+    fn append(&mut self, database: &ScidDatabase, number: u32, game_index: &GameIndex, game: Option<&io::Result<Game>>) {
+        let known = |name: Option<&str>| name.filter(|name| *name != "?").map(str::to_string);
+        let date = game_index.iso_date();
+        let year = date.as_ref().map(|_| game_index.year);
+        let month = Some(game_index.month).filter(|month| year.is_some() && (1..=12).contains(month));
+        let day = Some(game_index.day).filter(|day| month.is_some() && (1..=31).contains(day));
+
+        self.number.append_value(number);
+        self.event.append_option(known(database.event_name(game_index.event_id)));
+        self.site.append_option(known(database.site_name(game_index.site_id)));
+        self.date.append_option(date);
+        self.year.append_option(year);
+        self.month.append_option(month);
+        self.day.append_option(day);
+        self.round.append_option(known(database.round_name(game_index.round_id)));
+        self.white.append_option(known(database.player_name(game_index.white_id)));
+        self.black.append_option(known(database.player_name(game_index.black_id)));
+        self.result.append_value(game_index.result_string());
+        self.white_elo.append_option(Some(game_index.white_elo).filter(|elo| *elo > 0));
+        self.black_elo.append_option(Some(game_index.black_elo).filter(|elo| *elo > 0));
+        self.eco.append_option(eco_to_string(game_index.eco));
+        self.ply_count.append_value(game_index.num_half_moves);
+        self.flags.append_value(game_index.flag_string());
+        self.non_standard_start.append_value(game_index.flags & IDX_FLAG_START != 0);
+        self.promotions.append_value(game_index.flags & IDX_FLAG_PROMO != 0);
+        self.under_promotions.append_value(game_index.flags & IDX_FLAG_UPROMO != 0);
+        self.variations.append_value(game_index.var_count);
+        self.comments.append_value(game_index.comment_count);
+        self.nags.append_value(game_index.nag_count);
+
+        let (start_fen, final_fen, error) = match game {
+            None => (None, None, None),
+            Some(Err(e)) => (None, None, Some(format!("Unable to decode SCID moves: {}", e))),
+            Some(Ok(game)) => match game.start_position() {
+                Ok(mut pos) => {
+                    for node in &game.moves.moves {
+                        pos.make_move(&node.mv);
+                    }
+                    (game.tag("FEN").map(str::to_string), Some(pos.to_fen()), None)
+                }
+                Err(e) => (None, None, Some(format!("Invalid start position: {}", e))),
+            },
+        };
+        self.start_fen.append_option(start_fen);
+        self.final_fen.append_option(final_fen);
+        self.error.append_option(error);
+        self.len += 1;
+    }
+
+    fn finish(&mut self) -> io::Result<RecordBatch> {
+        self.len = 0;
+        let columns: Vec<ArrayRef> = vec![
+            Arc::new(self.number.finish()),
+            Arc::new(self.event.finish()),
+            Arc::new(self.site.finish()),
+            Arc::new(self.date.finish()),
+            Arc::new(self.year.finish()),
+            Arc::new(self.month.finish()),
+            Arc::new(self.day.finish()),
+            Arc::new(self.round.finish()),
+            Arc::new(self.white.finish()),
+            Arc::new(self.black.finish()),
+            Arc::new(self.result.finish()),
+            Arc::new(self.white_elo.finish()),
+            Arc::new(self.black_elo.finish()),
+            Arc::new(self.eco.finish()),
+            Arc::new(self.ply_count.finish()),
+            Arc::new(self.flags.finish()),
+            Arc::new(self.non_standard_start.finish()),
+            Arc::new(self.promotions.finish()),
+            Arc::new(self.under_promotions.finish()),
+            Arc::new(self.variations.finish()),
+            Arc::new(self.comments.finish()),
+            Arc::new(self.nags.finish()),
+            Arc::new(self.start_fen.finish()),
+            Arc::new(self.final_fen.finish()),
+            Arc::new(self.error.finish()),
+        ];
+        RecordBatch::try_new(game_schema(), columns).map_err(io::Error::other)
+    }
+}
+
+/// Column builders for the plies table, in schema order
+#[derive(Default)]
+struct PlyColumns {
+    len: usize,
+    game: UInt32Builder,
+    ply: UInt32Builder,
+    san: StringBuilder,
+    uci: StringBuilder,
+    fen: StringBuilder,
+    clock: Float64Builder,
+    eval_cp: Int32Builder,
+    eval_mate: Int32Builder,
+}
+
+impl PlyColumns {
+    fn append(&mut self, number: u32, game: &Game) {
+        let Ok(mut pos) = game.start_position() else {
+            return;
+        };
+        for node in &game.moves.moves {
+            let comment = node.comment.as_deref().unwrap_or("");
+            let (eval_cp, eval_mate) = match comment_command(comment, "%eval") {
+                Some(eval) => parse_eval(eval),
+                None => (None, None),
+            };
+
+            self.game.append_value(number);
+            self.ply.append_value(ply(&pos));
+            self.san.append_value(pos.san(&node.mv));
+            self.uci.append_value(node.mv.to_uci());
+            pos.make_move(&node.mv);
+            self.fen.append_value(pos.to_fen());
+            self.clock.append_option(comment_command(comment, "%clk").and_then(parse_clock));
+            self.eval_cp.append_option(eval_cp);
+            self.eval_mate.append_option(eval_mate);
+            self.len += 1;
+        }
+    }
+
+    fn finish(&mut self) -> io::Result<RecordBatch> {
+        self.len = 0;
+        let columns: Vec<ArrayRef> = vec![
+            Arc::new(self.game.finish()),
+            Arc::new(self.ply.finish()),
+            Arc::new(self.san.finish()),
+            Arc::new(self.uci.finish()),
+            Arc::new(self.fen.finish()),
+            Arc::new(self.clock.finish()),
+            Arc::new(self.eval_cp.finish()),
+            Arc::new(self.eval_mate.finish()),
+        ];
+        RecordBatch::try_new(ply_schema(), columns).map_err(io::Error::other)
+    }
+}
+
+/// The argument of an embedded command such as `[%clk 0:03:00]`
+fn comment_command<'a>(comment: &'a str, name: &str) -> Option<&'a str> {
+    let start = comment.find(&format!("[{} ", name))? + name.len() + 2;
+    let end = start + comment[start..].find(']')?;
+    Some(comment[start..end].trim())
+}
+
+/// "H:MM:SS" or "H:MM:SS.s" as seconds
+fn parse_clock(text: &str) -> Option<f64> {
+    text.split(':').try_fold(0.0, |total, part| Some(total * 60.0 + part.parse::<f64>().ok()?))
+        .filter(|seconds| seconds.is_finite() && *seconds >= 0.0)
+}
+
+/// "0.35" as 35 centipawns, "#-3" as mate in 3 for Black
+fn parse_eval(text: &str) -> (Option<i32>, Option<i32>) {
+    match text.strip_prefix('#') {
+        Some(mate) => (None, mate.parse().ok()),
+        None => {
+            let pawns = text.split(',').next().and_then(|value| value.parse::<f64>().ok());
+            (pawns.filter(|pawns| pawns.is_finite() && pawns.abs() < 1e6).map(|pawns| (pawns * 100.0).round() as i32), None)
+        }
+    }
+}
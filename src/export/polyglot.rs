@@ -0,0 +1,229 @@
+//! Polyglot opening book generation
+//!
+//! Replays the main line of each selected game and counts every move
+//! played from every position, then writes the counts as a Polyglot `.bin`
+//! book: 16-byte big-endian entries of key, move, weight and learn value,
+//! sorted by key. Keys and moves use `chess::polyglot`.
+//!
+//! ## Weights
+//! A move scores 2 for each game the side playing it won and 1 for each
+//! draw or unknown result, as in Polyglot's own `make-book`. Moves that
+//! only lost score 0 and are left out, as are moves played in fewer than
+//! `min_games` games. When the largest score doesn't fit in 16 bits all
+//! weights for that position are scaled down proportionally.
+//!
+//! ## Filters
+//! - `min_elo`: only count moves by players rated at least this much in
+//!   the index; unrated players are left out
+//! - `max_ply`: only count the first this many half-moves of each game
+//! - `side`: only count moves by one colour, for a repertoire book
+//!
+//! Games with unreadable moves are skipped.
+
+use std::collections::HashMap;
+use std::fs::File;
+use std::io::{self, BufWriter, Write};
+use std::path::Path;
+
+use crate::chess::{polyglot_move, Color, GameResult};
+use crate::scid::ScidDatabase;
+use super::{selected_games, GameFilter};
+
+/// One Polyglot book entry
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct BookEntry {
+    pub key: u64,
+    pub mv: u16,
+    pub weight: u16,
+    pub learn: u32,
+}
+
+impl BookEntry {
+    /// Size of an entry in a .bin file
+    pub const SIZE: usize = 16;
+
+    pub fn to_bytes(&self) -> [u8; Self::SIZE] {
+        let mut bytes = [0u8; Self::SIZE];
+        bytes[0..8].copy_from_slice(&self.key.to_be_bytes());
+        bytes[8..10].copy_from_slice(&self.mv.to_be_bytes());
+        bytes[10..12].copy_from_slice(&self.weight.to_be_bytes());
+        bytes[12..16].copy_from_slice(&self.learn.to_be_bytes());
+        bytes
+    }
+
+    /// Parse a whole .bin file; a trailing partial entry is ignored
+    pub fn read_book(data: &[u8]) -> Vec<BookEntry> {
+        data.chunks_exact(Self::SIZE)
+            .map(|bytes| BookEntry {
+                key: u64::from_be_bytes(bytes[0..8].try_into().unwrap()),
+                mv: u16::from_be_bytes(bytes[8..10].try_into().unwrap()),
+                weight: u16::from_be_bytes(bytes[10..12].try_into().unwrap()),
+                learn: u32::from_be_bytes(bytes[12..16].try_into().unwrap()),
+            })
+            .collect()
+    }
+}
+
+/// Games and score for one move from one position
+#[derive(Debug, Default, Clone, Copy)]
+struct MoveStats {
+    games: u32,
+    score: u64,
+}
+
+/// Polyglot book builder for SCID databases
+pub struct PolyglotExporter {
+    min_elo: u16,
+    max_ply: Option<u32>,
+    side: Option<Color>,
+    min_games: u32,
+    filter: GameFilter,
+    max_games: Option<usize>,
+}
+
+impl PolyglotExporter {
+    pub fn new() -> Self {
+        PolyglotExporter {
+            min_elo: 0,
+            max_ply: None,
+            side: None,
+            min_games: 1,
+            filter: GameFilter::new(),
+            max_games: None,
+        }
+    }
+
+    /// Only count moves by players with at least this rating
+    pub fn with_min_elo(mut self, min_elo: u16) -> Self {
+        self.min_elo = min_elo;
+        self
+    }
+
+    /// Only count this many half-moves from the start of each game
+    pub fn with_max_ply(mut self, max_ply: u32) -> Self {
+        self.max_ply = Some(max_ply);
+        self
+    }
+
+    /// Only count moves by this side
+    pub fn with_side(mut self, side: Color) -> Self {
+        self.side = Some(side);
+        self
+    }
+
+    /// Leave out moves played in fewer games than this
+    pub fn with_min_games(mut self, min_games: u32) -> Self {
+        self.min_games = min_games;
+        self
+    }
+
+    /// Only count games the filter matches
+    pub fn with_filter(mut self, filter: GameFilter) -> Self {
+        self.filter = filter;
+        self
+    }
+
+    pub fn with_max_games(mut self, max: usize) -> Self {
+        self.max_games = Some(max);
+        self
+    }
+
+    /// Build the book and write it to a .bin file, returning the number of
+    /// games that contributed moves
+    pub fn export(&mut self, database: &mut ScidDatabase, output_path: &Path) -> io::Result<usize> {
+        let (entries, games) = self.build(database);
+        let file = File::create(output_path)?;
+        let mut writer = BufWriter::new(file);
+        for entry in &entries {
+            writer.write_all(&entry.to_bytes())?;
+        }
+        writer.flush()?;
+        Ok(games)
+    }
+
+    /// Book entries for the selected games, sorted as Polyglot requires
+    pub fn entries(&mut self, database: &mut ScidDatabase) -> Vec<BookEntry> {
+        self.build(database).0
+    }
+
+    fn build(&self, database: &mut ScidDatabase) -> (Vec<BookEntry>, usize) {
+        let mut stats: HashMap<(u64, u16), MoveStats> = HashMap::new();
+        let mut used = 0;
+
+        for (_, game_index) in selected_games(database, &self.filter, self.max_games) {
+            let elo_ok = |color: Color| {
+                let elo = match color {
+                    Color::White => game_index.white_elo,
+                    Color::Black => game_index.black_elo,
+                };
+                self.min_elo == 0 || elo >= self.min_elo
+            };
+            let counted = |color: Color| self.side.is_none_or(|side| side == color) && elo_ok(color);
+            // Nothing to count: decide from the index without reading the game
+            if !counted(Color::White) && !counted(Color::Black) {
+                continue;
+            }
+
+            let Ok(game) = database.load_game(&game_index) else {
+                continue;
+            };
+            let Ok(mut pos) = game.start_position() else {
+                continue;
+            };
+
+            let mut contributed = false;
+            for (ply, node) in game.moves.moves.iter().enumerate() {
+                if self.max_ply.is_some_and(|max| ply as u32 >= max) {
+                    break;
+                }
+                let mover = pos.to_move;
+                if counted(mover) && !node.mv.is_null() {
+                    let entry = stats.entry((pos.polyglot_key(), polyglot_move(&node.mv))).or_default();
+                    entry.games += 1;
+                    entry.score += match (game.result, mover) {
+                        (GameResult::WhiteWins, Color::White) | (GameResult::BlackWins, Color::Black) => 2,
+                        (GameResult::WhiteWins, Color::Black) | (GameResult::BlackWins, Color::White) => 0,
+                        (GameResult::Draw, _) | (GameResult::Unknown, _) => 1,
+                    };
+                    contributed = true;
+                }
+                pos.make_move(&node.mv);
+            }
+
+            if contributed {
+                used += 1;
+                // Progress indicator for large books
+                if used % 1000 == 0 {
+                    eprintln!("Added {} games...", used);
+                }
+            }
+        }
+
+        let mut entries: Vec<(u64, u16, MoveStats)> = stats.into_iter()
+            .filter(|(_, stats)| stats.games >= self.min_games && stats.score > 0)
+            .map(|((key, mv), stats)| (key, mv, stats))
+            .collect();
+        // By key, best move first within a position
+        entries.sort_by(|a, b| a.0.cmp(&b.0).then(b.2.score.cmp(&a.2.score)).then(a.1.cmp(&b.1)));
+
+        let mut book = Vec::with_capacity(entries.len());
+        for position in entries.chunk_by(|a, b| a.0 == b.0) {
+            let max_score = position[0].2.score;
+            for &(key, mv, stats) in position {
+                let weight = if max_score > u16::MAX as u64 {
+                    (stats.score * u16::MAX as u64 / max_score).max(1)
+                } else {
+                    stats.score
+                };
+                book.push(BookEntry { key, mv, weight: weight as u16, learn: 0 });
+            }
+        }
+        (book, used)
+    }
+}
+
+impl Default for PolyglotExporter {
+    fn default() -> Self {
+        Self::new()
+    }
+}
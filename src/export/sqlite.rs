@@ -0,0 +1,274 @@
+//! SQLite export
+//!
+//! Writes a converted database as normalized tables that can be queried
+//! with plain SQL and no server:
+//!
+//! ```text
+//! players   (id, name)              -- from the .sn4 namebase, by SCID ID
+//! events    (id, name)
+//! sites     (id, name)
+//! games     (id, white_id, black_id, event_id, site_id, round, date, year,
+//!            result, white_elo, black_elo, eco, ply_count, flags,
+//!            start_fen, error)     -- from the .si4 index; id = game number
+//! tags      (game_id, name, value)  -- other tags from the game record
+//! moves     (game_id, ply, san, uci, nags, comment)
+//! positions (game_id, ply, epd)     -- ply 0 is the start position
+//! ```
+//!
+//! `moves` and `positions` cover the main line only. Ply numbers count from
+//! 1 at the standard start, as in the JSON export, so a game from a set-up
+//! position may start above 1. `nags` is a space-separated list such as
+//! "1 14". Positions are stored as EPD (FEN without the move counters) so
+//! the same position matches across games, and `positions.epd` is indexed
+//! for that purpose, along with the player, event, site, date, ECO and
+//! result columns of `games`.
+//!
+//! Unknown names, dates, ratings and ECO codes are NULL. A game whose moves
+//! can't be decoded keeps its `games` row with the reason in `error`.
+
+use std::fs;
+use std::io;
+use std::path::Path;
+
+use rusqlite::{params, Connection, Transaction};
+
+use crate::scid::eco::eco_to_string;
+use crate::scid::{GameIndex, ScidDatabase};
+use super::{ply, selected_games, GameFilter, TYPED_TAGS};
+
+const SCHEMA: &str = "
+CREATE TABLE players (id INTEGER PRIMARY KEY, name TEXT NOT NULL);
+CREATE TABLE events (id INTEGER PRIMARY KEY, name TEXT NOT NULL);
+CREATE TABLE sites (id INTEGER PRIMARY KEY, name TEXT NOT NULL);
+CREATE TABLE games (
+    id INTEGER PRIMARY KEY,
+    white_id INTEGER REFERENCES players (id),
+    black_id INTEGER REFERENCES players (id),
+    event_id INTEGER REFERENCES events (id),
+    site_id INTEGER REFERENCES sites (id),
+    round TEXT,
+    date TEXT,
+    year INTEGER,
+    result TEXT NOT NULL,
+    white_elo INTEGER,
+    black_elo INTEGER,
+    eco TEXT,
+    ply_count INTEGER NOT NULL,
+    flags TEXT NOT NULL,
+    start_fen TEXT,
+    error TEXT
+);
+CREATE TABLE tags (
+    game_id INTEGER NOT NULL REFERENCES games (id),
+    name TEXT NOT NULL,
+    value TEXT NOT NULL
+);
+CREATE TABLE moves (
+    game_id INTEGER NOT NULL REFERENCES games (id),
+    ply INTEGER NOT NULL,
+    san TEXT NOT NULL,
+    uci TEXT NOT NULL,
+    nags TEXT,
+    comment TEXT,
+    PRIMARY KEY (game_id, ply)
+) WITHOUT ROWID;
+CREATE TABLE positions (
+    game_id INTEGER NOT NULL REFERENCES games (id),
+    ply INTEGER NOT NULL,
+    epd TEXT NOT NULL,
+    PRIMARY KEY (game_id, ply)
+) WITHOUT ROWID;
+";
+
+/// Created after the data is loaded, which is faster than maintaining them
+/// row by row
+const INDEXES: &str = "
+CREATE INDEX players_name ON players (name);
+CREATE INDEX events_name ON events (name);
+CREATE INDEX sites_name ON sites (name);
+CREATE INDEX games_white ON games (white_id);
+CREATE INDEX games_black ON games (black_id);
+CREATE INDEX games_event ON games (event_id);
+CREATE INDEX games_site ON games (site_id);
+CREATE INDEX games_date ON games (date);
+CREATE INDEX games_eco ON games (eco);
+CREATE INDEX games_result ON games (result);
+CREATE INDEX tags_name ON tags (name, value);
+CREATE INDEX positions_epd ON positions (epd);
+";
+
+/// SQLite exporter for SCID databases
+pub struct SqliteExporter {
+    include_moves: bool,
+    include_positions: bool,
+    filter: GameFilter,
+    max_games: Option<usize>,
+}
+
+impl SqliteExporter {
+    pub fn new() -> Self {
+        SqliteExporter {
+            include_moves: true,
+            include_positions: true,
+            filter: GameFilter::new(),
+            max_games: None,
+        }
+    }
+
+    /// Decode games to fill the `tags` and `moves` tables; without this
+    /// only the index is read and the .sg4 file is not touched
+    pub fn with_moves(mut self, include: bool) -> Self {
+        self.include_moves = include;
+        self
+    }
+
+    /// Fill the `positions` table; ignored without moves
+    pub fn with_positions(mut self, include: bool) -> Self {
+        self.include_positions = include;
+        self
+    }
+
+    /// Only export games the filter matches
+    pub fn with_filter(mut self, filter: GameFilter) -> Self {
+        self.filter = filter;
+        self
+    }
+
+    pub fn with_max_games(mut self, max: usize) -> Self {
+        self.max_games = Some(max);
+        self
+    }
+
+    /// Export SCID database to a new SQLite file, replacing any existing one
+    pub fn export(&mut self, database: &mut ScidDatabase, output_path: &Path) -> io::Result<usize> {
+        if output_path.exists() {
+            fs::remove_file(output_path)?;
+        }
+        let mut connection = Connection::open(output_path).map_err(io::Error::other)?;
+        self.write(database, &mut connection)
+    }
+
+    /// Create the tables in `connection` and fill them, returning how many
+    /// games were written
+    pub fn write(&mut self, database: &mut ScidDatabase, connection: &mut Connection) -> io::Result<usize> {
+        let transaction = connection.transaction().map_err(io::Error::other)?;
+        let exported = self.write_tables(database, &transaction)?;
+        transaction.execute_batch(INDEXES).map_err(io::Error::other)?;
+        transaction.commit().map_err(io::Error::other)?;
+        Ok(exported)
+    }
+
+    fn write_tables(&self, database: &mut ScidDatabase, transaction: &Transaction) -> io::Result<usize> {
+        transaction.execute_batch(SCHEMA).map_err(io::Error::other)?;
+
+        let names = database.names();
+        for (table, map) in [("players", &names.players), ("events", &names.events), ("sites", &names.sites)] {
+            let mut insert = transaction.prepare(&format!("INSERT INTO {} (id, name) VALUES (?1, ?2)", table))
+                .map_err(io::Error::other)?;
+            let mut entries: Vec<_> = map.iter().collect();
+            entries.sort();
+            for (id, name) in entries {
+                insert.execute(params![id, name]).map_err(io::Error::other)?;
+            }
+        }
+
+        let games = selected_games(database, &self.filter, self.max_games);
+        let mut exported = 0;
+        for (game_num, game_index) in &games {
+            self.write_game(database, transaction, *game_num, game_index)
+                .map_err(io::Error::other)?;
+            exported += 1;
+
+            // Progress indicator for large exports
+            if exported % 1000 == 0 {
+                eprintln!("Exported {} games...", exported);
+            }
+        }
+        Ok(exported)
+    }
+
+    fn write_game(&self, database: &mut ScidDatabase, transaction: &Transaction,
+                  game_num: usize, game_index: &GameIndex) -> rusqlite::Result<()> {
+        let id = game_num as i64 + 1;
+        // Only IDs the namebase knows are stored, so the foreign keys hold;
+        // like the other formats, "?" names count as unknown
+        let names = database.names();
+        let known_id = |id: u32, name: Option<&str>| name.filter(|name| *name != "?").map(|_| id);
+        let white_id = known_id(game_index.white_id, names.player_name(game_index.white_id));
+        let black_id = known_id(game_index.black_id, names.player_name(game_index.black_id));
+        let event_id = known_id(game_index.event_id, names.event_name(game_index.event_id));
+        let site_id = known_id(game_index.site_id, names.site_name(game_index.site_id));
+        let round = names.round_name(game_index.round_id).filter(|round| *round != "?").map(str::to_string);
+
+        let date = game_index.iso_date();
+        let year = date.as_ref().map(|_| game_index.year);
+        let rating = |elo: u16| Some(elo).filter(|elo| *elo > 0);
+
+        let (game, error) = if self.include_moves {
+            match database.load_game(game_index) {
+                Ok(game) => (Some(game), None),
+                Err(e) => (None, Some(format!("Unable to decode SCID moves: {}", e))),
+            }
+        } else {
+            (None, None)
+        };
+        let start_fen = game.as_ref().and_then(|game| game.tag("FEN")).map(str::to_string);
+
+        transaction.prepare_cached(
+            "INSERT INTO games (id, white_id, black_id, event_id, site_id, round, date, year, result,
+                                white_elo, black_elo, eco, ply_count, flags, start_fen, error)
+             VALUES (?1, ?2, ?3, ?4, ?5, ?6, ?7, ?8, ?9, ?10, ?11, ?12, ?13, ?14, ?15, ?16)")?
+            .execute(params![
+                id, white_id, black_id, event_id, site_id, round, date, year,
+                game_index.result_string(), rating(game_index.white_elo), rating(game_index.black_elo),
+                eco_to_string(game_index.eco), game_index.num_half_moves,
+                game_index.flag_string(), start_fen, error,
+            ])?;
+
+        let Some(game) = game else {
+            return Ok(());
+        };
+
+        let mut insert_tag = transaction.prepare_cached("INSERT INTO tags (game_id, name, value) VALUES (?1, ?2, ?3)")?;
+        for (name, value) in &game.tags {
+            if !TYPED_TAGS.contains(&name.as_str()) {
+                insert_tag.execute(params![id, name, value])?;
+            }
+        }
+
+        let mut pos = match game.start_position() {
+            Ok(pos) => pos,
+            Err(e) => {
+                transaction.execute("UPDATE games SET error = ?2 WHERE id = ?1",
+                                    params![id, format!("Invalid start position: {}", e)])?;
+                return Ok(());
+            }
+        };
+        let mut insert_move = transaction.prepare_cached(
+            "INSERT INTO moves (game_id, ply, san, uci, nags, comment) VALUES (?1, ?2, ?3, ?4, ?5, ?6)")?;
+        let mut insert_position = transaction.prepare_cached(
+            "INSERT INTO positions (game_id, ply, epd) VALUES (?1, ?2, ?3)")?;
+
+        if self.include_positions {
+            insert_position.execute(params![id, ply(&pos) - 1, pos.to_epd()])?;
+        }
+        for node in &game.moves.moves {
+            let ply = ply(&pos);
+            let nags = (!node.nags.is_empty())
+                .then(|| node.nags.iter().map(|nag| nag.to_string()).collect::<Vec<_>>().join(" "));
+            insert_move.execute(params![id, ply, pos.san(&node.mv), node.mv.to_uci(), nags, node.comment])?;
+
+            pos.make_move(&node.mv);
+            if self.include_positions {
+                insert_position.execute(params![id, ply, pos.to_epd()])?;
+            }
+        }
+        Ok(())
+    }
+}
+
+impl Default for SqliteExporter {
+    fn default() -> Self {
+        Self::new()
+    }
+}
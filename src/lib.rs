@@ -0,0 +1,11 @@
+//! SCID database reading and PGN export
+//!
+//! The `scidtopgn` binary is a thin CLI over these modules; they are also
+//! usable directly as a library.
+
+pub mod chess;
+pub mod scid;
+pub mod pgn;
+pub mod export;
+pub mod search;
+pub mod report;
@@ -1,91 +1,700 @@
-use clap::Parser;
+use clap::{Parser, Subcommand, ValueEnum};
+use std::fs;
+use std::io::{BufReader, Write};
 use std::path::PathBuf;
 use std::process;
 
-mod scid;
-mod pgn;
+use scidtopgn::chess::{Color, GameResult, Position};
+use scidtopgn::export::{CsvColumn, CsvExporter, EpdExporter, EpdPositions, FilterExpr, GameFilter, JsonExporter, PolyglotExporter};
+use scidtopgn::export::filter::{parse_date, parse_eco_range, parse_result};
+#[cfg(feature = "parquet")]
+use scidtopgn::export::ParquetExporter;
+#[cfg(feature = "sqlite")]
+use scidtopgn::export::SqliteExporter;
+use scidtopgn::report::{Crosstables, HeadToHead, PlayerProfile};
+use scidtopgn::report::crosstable::write_html_page;
+use scidtopgn::scid::{Compactor, DatabaseInfo, GameIndex, ScidDatabase, ScidWriter, SpellingFile, Verifier};
+use scidtopgn::pgn::{PgnExporter, PgnReader};
+use scidtopgn::search::{BishopColors, DuplicateFinder, DuplicateStrictness, MaterialPattern, MaterialScope, MaterialSearch, OpeningTree, PositionMatch, PositionSearch};
 
-use scid::ScidDatabase;
-use pgn::PgnExporter;
-
-/// SCID to PGN Converter - MAJOR FIXES IMPLEMENTED (July 2025)
-/// 
-/// ## Critical Issues Resolved:
-/// 
-/// ### 1. Date Parsing Bug
-/// **Problem**: Dates showed as "52298.152.207" instead of readable dates
-/// **Fix**: Correct SCID bit-field extraction in index.rs
-/// **Result**: Now shows proper dates like "1791.12.24"
-/// 
-/// ### 2. Name Extraction Bug  
-/// **Problem**: Names extracted partially - "Michael" became "ichael"
-/// **Fix**: Proper SCID .sn4 front-coded string parsing in names.rs
-/// **Result**: Complete names extracted correctly
+/// SCID to PGN Converter
 /// 
-/// ### 3. Development Speed Optimization
-/// **Addition**: --max-games=10 default for faster testing during development
-/// **Purpose**: Avoid processing 1.8M games during debugging
+/// Exports SCID 4 databases (.si4, .sn4, .sg4) to PGN, JSON, NDJSON,
+/// CSV/TSV, SQLite, Parquet or EPD, with moves, variations, comments and
+/// NAGs, and filters on the index fields before any game is read. The
+/// subcommands import PGN into a new database, build Polyglot books, search
+/// positions and material, report on players and tournaments, and verify,
+/// compact or describe a database.
 /// 
-/// ## Current Status:
-/// ✅ Date parsing working
-/// ✅ Name extraction working  
-/// ✅ Basic PGN structure output
-/// ❌ Game moves parsing (next major task)
+/// Without `--max-games` the export stops after the first 10 games;
+/// `--max-games 0` exports all of them.
 /// 
 /// ## Usage Examples:
 /// ```bash
-/// # Convert first 10 games (development mode)
+/// # Convert the first 10 games
 /// ./scidtopgn database_name
 /// 
 /// # Convert all games
-/// ./scidtopgn --max-games=0 database_name
+/// ./scidtopgn --max-games 0 database_name
 /// 
 /// # Specify output file
 /// ./scidtopgn -o output.pgn database_name
+/// 
+/// # One JSON object per game
+/// ./scidtopgn --format ndjson database_name
+/// 
+/// # Index metadata only, without reading any moves
+/// ./scidtopgn --format csv --columns white,black,date,result database_name
+/// 
+/// # Normalized tables for querying with SQL
+/// ./scidtopgn --format sqlite database_name
+/// 
+/// # Positions after 20 and 40 half-moves as EPD
+/// ./scidtopgn --format epd --positions 20,40 database_name
+/// 
+/// # Build a SCID database from PGN files
+/// ./scidtopgn import -o database_name games.pgn more.pgn
+/// 
+/// # Build a Polyglot opening book from games between 2400+ players
+/// ./scidtopgn book --min-elo 2400 --max-ply 30 -o book.bin database_name
+/// 
+/// # Games reaching the Najdorf, with the same pieces whoever is to move
+/// ./scidtopgn search --pieces database_name "rnbqkb1r/1p2pppp/p2p1n2/8/3NP3/2N5/PPP2PPP/R1BQKB1R w KQkq - 0 6"
+/// 
+/// # Games passing through rook and pawn against rook, for either colour
+/// ./scidtopgn material --any --flip database_name "KRP vs KR"
+/// 
+/// # Moves played after 1. e4 c5 by 2600+ players, as JSON
+/// ./scidtopgn tree --moves "1. e4 c5" --min-elo 2600 --json database_name
+/// 
+/// # Profile of a player's results, openings and opponents
+/// ./scidtopgn player database_name "Carlsen, Magnus"
+/// 
+/// # Two players' games against each other, also written as PGN
+/// ./scidtopgn head-to-head --pgn carlsen-nakamura.pgn database_name carlsen nakamura
+/// 
+/// # Crosstables of the Tata Steel tournaments as an HTML page
+/// ./scidtopgn crosstable --event "Tata Steel" --format html -o tata.html database_name
+/// 
+/// # Check a database for damage before exporting it
+/// ./scidtopgn verify database_name
+/// 
+/// # Drop deleted games and unused names, repairing index fields
+/// ./scidtopgn compact -o compacted database_name
+/// 
+/// # Header fields, totals and fragmentation of a database
+/// ./scidtopgn info database_name
 /// ```
 #[derive(Parser)]
 #[command(name = "scidtopgn")]
 #[command(about = "Convert SCID databases to PGN format")]
 #[command(version = "0.1.0")]
+#[command(args_conflicts_with_subcommands = true, subcommand_negates_reqs = true)]
 struct Args {
+    #[command(subcommand)]
+    command: Option<Command>,
+    
+    #[command(flatten)]
+    export: ExportArgs,
+}
+
+#[derive(Subcommand)]
+enum Command {
+    /// Convert PGN files into a new SCID database
+    Import(ImportArgs),
+    /// Build a Polyglot opening book (.bin) from a SCID database
+    Book(BookArgs),
+    /// List the games that reach a position
+    Search(SearchArgs),
+    /// List the games with a material balance
+    Material(MaterialArgs),
+    /// Show the moves played from a position, with results and ratings
+    Tree(TreeArgs),
+    /// Report a player's scores, ratings, openings and opponents
+    Player(PlayerArgs),
+    /// Report two players' record against each other
+    HeadToHead(HeadToHeadArgs),
+    /// Build tournament crosstables with tiebreaks and performances
+    Crosstable(CrosstableArgs),
+    /// Check a database for damage; exits 1 if any is found, 2 if it can't be read
+    Verify(VerifyArgs),
+    /// Drop deleted games, gaps and unused names, and repair index fields
+    Compact(CompactArgs),
+    /// Show the database header, name and game totals, and fragmentation
+    Info(InfoArgs),
+}
+
+/// Arguments for the default command: export a SCID database to PGN
+#[derive(clap::Args)]
+struct ExportArgs {
     /// Path to the SCID database (without extension - will look for .si4, .sg4, .sn4)
-    #[arg(value_name = "DATABASE")]
-    database: PathBuf,
+    #[arg(value_name = "DATABASE", required = true)]
+    database: Option<PathBuf>,
     
-    /// Output PGN file (if not specified, uses database name with .pgn extension)
+    /// Output file (if not specified, uses database name with the format's extension)
     #[arg(short, long, value_name = "FILE")]
     output: Option<PathBuf>,
     
+    /// Output format
+    #[arg(long, value_enum, default_value = "pgn")]
+    format: ExportFormat,
+    
+    /// Indent JSON output (ignored for PGN and NDJSON)
+    #[arg(long)]
+    pretty: bool,
+    
+    /// Comma-separated CSV/TSV columns (default: all)
+    #[arg(long, value_name = "COLUMNS", value_delimiter = ',')]
+    columns: Vec<CsvColumn>,
+    
+    /// Leave out the SQLite positions table
+    #[arg(long)]
+    no_positions: bool,
+    
+    /// Also write one Parquet row per ply to OUTPUT with a .plies.parquet extension
+    #[arg(long)]
+    plies: bool,
+    
+    /// EPD positions to write: all, final, or a comma-separated list of plies
+    #[arg(long, value_name = "SELECTION", default_value_t = EpdPositions::All)]
+    positions: EpdPositions,
+    
     /// Force overwrite existing output file
     #[arg(short, long)]
     force: bool,
     
-    /// Include variations in PGN output
+    /// Include variations in the output
     #[arg(short, long)]
     variations: bool,
     
-    /// Include comments in PGN output  
+    /// Include comments in the output
     #[arg(short, long)]
     comments: bool,
     
     /// Maximum number of games to export (0 = all games)
     #[arg(long, default_value = "10")]
     max_games: usize,
+    
+    /// Correct player, event and site names with a SCID spelling file (.ssp)
+    /// and add title and FIDE ID tags
+    #[arg(long, value_name = "FILE")]
+    spelling: Option<PathBuf>,
+    
+    /// Leave out duplicate games, keeping the most complete copy of each
+    #[arg(long, value_enum, value_name = "STRICTNESS", num_args = 0..=1, default_missing_value = "normal")]
+    dedup: Option<DedupArg>,
+    
+    /// Write the duplicates found by --dedup to FILE
+    #[arg(long, value_name = "FILE", requires = "dedup")]
+    duplicate_report: Option<PathBuf>,
+    
+    #[command(flatten)]
+    filter: FilterArgs,
+}
+
+/// Game selection from the index, applied before any game is read
+#[derive(clap::Args)]
+#[command(next_help_heading = "Filters")]
+struct FilterArgs {
+    /// Games where either player's name contains NAME (case-insensitive)
+    #[arg(long, value_name = "NAME")]
+    player: Option<String>,
+    
+    /// Games where White's name contains NAME
+    #[arg(long, value_name = "NAME")]
+    white: Option<String>,
+    
+    /// Games where Black's name contains NAME
+    #[arg(long, value_name = "NAME")]
+    black: Option<String>,
+    
+    /// Games whose event contains NAME
+    #[arg(long, value_name = "NAME")]
+    event: Option<String>,
+    
+    /// Games whose site contains NAME
+    #[arg(long, value_name = "NAME")]
+    site: Option<String>,
+    
+    /// Games on or after this date (YYYY, YYYY.MM or YYYY.MM.DD)
+    #[arg(long, value_name = "DATE", value_parser = parse_date)]
+    date_from: Option<(u16, u8, u8)>,
+    
+    /// Games on or before this date (YYYY, YYYY.MM or YYYY.MM.DD)
+    #[arg(long, value_name = "DATE", value_parser = parse_date)]
+    date_to: Option<(u16, u8, u8)>,
+    
+    /// Games with this result (1-0, 0-1, 1/2-1/2 or *)
+    #[arg(long, value_name = "RESULT", value_parser = parse_result)]
+    result: Option<GameResult>,
+    
+    /// Games where both players are rated at least this much
+    #[arg(long, value_name = "ELO")]
+    min_elo: Option<u16>,
+    
+    /// Games where neither player is rated above this
+    #[arg(long, value_name = "ELO")]
+    max_elo: Option<u16>,
+    
+    /// Games with an ECO code in this range, e.g. B90-B99
+    #[arg(long, value_name = "RANGE", value_parser = parse_eco_range)]
+    eco: Option<(u16, u16)>,
+    
+    /// Games with at least this many half-moves
+    #[arg(long, value_name = "PLIES")]
+    min_ply: Option<u16>,
+    
+    /// Games with all of these index flags set, e.g. WT
+    #[arg(long, value_name = "LETTERS", value_parser = parse_flags)]
+    flags: Option<u16>,
+    
+    /// Games matching a filter expression, e.g. 'white~Carlsen and eco in B90..B99';
+    /// may be repeated
+    #[arg(long, value_name = "EXPR")]
+    filter: Vec<FilterExpr>,
+}
+
+impl FilterArgs {
+    fn filter(&self) -> GameFilter {
+        let mut filter = GameFilter::new();
+        if let Some(name) = &self.player {
+            filter = filter.with_player(name);
+        }
+        if let Some(name) = &self.white {
+            filter = filter.with_white(name);
+        }
+        if let Some(name) = &self.black {
+            filter = filter.with_black(name);
+        }
+        if let Some(name) = &self.event {
+            filter = filter.with_event(name);
+        }
+        if let Some(name) = &self.site {
+            filter = filter.with_site(name);
+        }
+        if let Some(date) = self.date_from {
+            filter = filter.with_date_from(date);
+        }
+        if let Some(date) = self.date_to {
+            filter = filter.with_date_to(date);
+        }
+        if let Some(result) = self.result {
+            filter = filter.with_result(result);
+        }
+        if let Some(elo) = self.min_elo {
+            filter = filter.with_min_elo(elo);
+        }
+        if let Some(elo) = self.max_elo {
+            filter = filter.with_max_elo(elo);
+        }
+        if let Some((from, to)) = self.eco {
+            filter = filter.with_eco_range(from, to);
+        }
+        if let Some(ply) = self.min_ply {
+            filter = filter.with_min_ply(ply);
+        }
+        if let Some(flags) = self.flags {
+            filter = filter.with_flags(flags);
+        }
+        for expression in &self.filter {
+            filter = filter.with_expression(expression.clone());
+        }
+        filter
+    }
+}
+
+fn parse_flags(letters: &str) -> Result<u16, String> {
+    GameIndex::flag_mask(letters)
+        .ok_or_else(|| format!("invalid flags '{}' (expected letters from DWBMENPTKQ!?U)", letters))
+}
+
+#[derive(Clone, Copy, ValueEnum)]
+enum ExportFormat {
+    /// PGN text
+    Pgn,
+    /// A JSON array of games
+    Json,
+    /// One JSON object per line
+    Ndjson,
+    /// Comma-separated index metadata, without moves
+    Csv,
+    /// Tab-separated index metadata, without moves
+    Tsv,
+    /// EPD lines for the positions of each game
+    Epd,
+    /// SQLite database with players, events, sites, games, moves and positions tables
+    #[cfg(feature = "sqlite")]
+    Sqlite,
+    /// Apache Parquet table of games (needs the "parquet" feature)
+    #[cfg(feature = "parquet")]
+    Parquet,
+}
+
+impl ExportFormat {
+    fn extension(self) -> &'static str {
+        match self {
+            ExportFormat::Pgn => "pgn",
+            ExportFormat::Json => "json",
+            ExportFormat::Ndjson => "ndjson",
+            ExportFormat::Csv => "csv",
+            ExportFormat::Tsv => "tsv",
+            ExportFormat::Epd => "epd",
+            #[cfg(feature = "sqlite")]
+            ExportFormat::Sqlite => "sqlite",
+            #[cfg(feature = "parquet")]
+            ExportFormat::Parquet => "parquet",
+        }
+    }
+    
+    fn name(self) -> &'static str {
+        match self {
+            ExportFormat::Pgn => "PGN",
+            ExportFormat::Json => "JSON",
+            ExportFormat::Ndjson => "NDJSON",
+            ExportFormat::Csv => "CSV",
+            ExportFormat::Tsv => "TSV",
+            ExportFormat::Epd => "EPD",
+            #[cfg(feature = "sqlite")]
+            ExportFormat::Sqlite => "SQLite",
+            #[cfg(feature = "parquet")]
+            ExportFormat::Parquet => "Parquet",
+        }
+    }
+}
+
+#[derive(clap::Args)]
+struct ImportArgs {
+    /// PGN files to import, in order
+    #[arg(value_name = "PGN", required = true)]
+    inputs: Vec<PathBuf>,
+    
+    /// SCID database to create, without extension (if not specified, uses the first PGN file's name)
+    #[arg(short, long, value_name = "DATABASE")]
+    output: Option<PathBuf>,
+    
+    /// Force overwrite existing database files
+    #[arg(short, long)]
+    force: bool,
+    
+    /// Description stored in the database header
+    #[arg(short, long, default_value = "")]
+    description: String,
+}
+
+#[derive(clap::Args)]
+struct BookArgs {
+    /// Path to the SCID database (without extension)
+    #[arg(value_name = "DATABASE")]
+    database: PathBuf,
+    
+    /// Book file to write (if not specified, uses database name with .bin extension)
+    #[arg(short, long, value_name = "FILE")]
+    output: Option<PathBuf>,
+    
+    /// Force overwrite existing book file
+    #[arg(short, long)]
+    force: bool,
+    
+    /// Only count moves by players rated at least this much
+    #[arg(long, default_value = "0")]
+    min_elo: u16,
+    
+    /// Only count this many half-moves from the start of each game (0 = all)
+    #[arg(long, default_value = "0")]
+    max_ply: u32,
+    
+    /// Only count moves by this side
+    #[arg(long, value_enum)]
+    side: Option<Side>,
+    
+    /// Leave out moves played in fewer games than this
+    #[arg(long, default_value = "1")]
+    min_games: u32,
+}
+
+#[derive(clap::Args)]
+struct SearchArgs {
+    /// Path to the SCID database (without extension)
+    #[arg(value_name = "DATABASE")]
+    database: PathBuf,
+    
+    /// Position to find, as FEN (move counters may be left out)
+    #[arg(value_name = "FEN")]
+    fen: String,
+    
+    /// Match the pieces only, ignoring side to move, castling and en passant
+    #[arg(long)]
+    pieces: bool,
+    
+    /// Maximum number of games to list (0 = all games)
+    #[arg(long, default_value = "0")]
+    max_games: usize,
+    
+    #[command(flatten)]
+    filter: FilterArgs,
+}
+
+#[derive(clap::Args)]
+struct MaterialArgs {
+    /// Path to the SCID database (without extension)
+    #[arg(value_name = "DATABASE")]
+    database: PathBuf,
+    
+    /// Material of each side, white first, e.g. "KRP vs KR" or "KBP* vs KBP*"
+    #[arg(value_name = "MATERIAL")]
+    pattern: MaterialPattern,
+    
+    /// Match any position of the game, not just the final one
+    #[arg(long)]
+    any: bool,
+    
+    /// Only match positions where the pattern's first side is to move
+    #[arg(long, value_enum)]
+    to_move: Option<Side>,
+    
+    /// Also match the pattern with the colours reversed
+    #[arg(long)]
+    flip: bool,
+    
+    /// Require one bishop each, on squares of the same or opposite colours
+    #[arg(long, value_enum)]
+    bishops: Option<BishopArg>,
+    
+    /// Maximum number of games to list (0 = all games)
+    #[arg(long, default_value = "0")]
+    max_games: usize,
+    
+    #[command(flatten)]
+    filter: FilterArgs,
+}
+
+#[derive(clap::Args)]
+struct TreeArgs {
+    /// Path to the SCID database (without extension)
+    #[arg(value_name = "DATABASE")]
+    database: PathBuf,
+    
+    /// Moves from the standard start position, in SAN (default: no moves)
+    #[arg(long, value_name = "MOVES", conflicts_with = "fen")]
+    moves: Option<String>,
+    
+    /// Position as FEN, instead of moves
+    #[arg(long, value_name = "FEN")]
+    fen: Option<String>,
+    
+    /// Write JSON instead of a table
+    #[arg(long)]
+    json: bool,
+    
+    #[command(flatten)]
+    filter: FilterArgs,
+}
+
+#[derive(clap::Args)]
+struct PlayerArgs {
+    /// Path to the SCID database (without extension)
+    #[arg(value_name = "DATABASE")]
+    database: PathBuf,
+    
+    /// Player name, or a part of it that only one player's name contains
+    #[arg(value_name = "NAME")]
+    name: String,
+    
+    /// Most openings and opponents to list (0 = all)
+    #[arg(long, default_value = "10")]
+    top: usize,
+    
+    /// Write JSON instead of text
+    #[arg(long)]
+    json: bool,
+    
+    #[command(flatten)]
+    filter: FilterArgs,
+}
+
+#[derive(clap::Args)]
+struct HeadToHeadArgs {
+    /// Path to the SCID database (without extension)
+    #[arg(value_name = "DATABASE")]
+    database: PathBuf,
+    
+    /// First player, whose point of view the scores take
+    #[arg(value_name = "FIRST")]
+    first: String,
+    
+    /// Second player
+    #[arg(value_name = "SECOND")]
+    second: String,
+    
+    /// Write JSON instead of text
+    #[arg(long)]
+    json: bool,
+    
+    /// Also write the games to this PGN file
+    #[arg(long, value_name = "FILE")]
+    pgn: Option<PathBuf>,
+    
+    /// Force overwrite an existing PGN file
+    #[arg(short, long)]
+    force: bool,
+    
+    #[command(flatten)]
+    filter: FilterArgs,
+}
+
+#[derive(clap::Args)]
+struct CrosstableArgs {
+    /// Path to the SCID database (without extension)
+    #[arg(value_name = "DATABASE")]
+    database: PathBuf,
+    
+    /// Output format
+    #[arg(long, value_enum, default_value = "text")]
+    format: CrosstableFormat,
+    
+    /// Output file (default: standard output)
+    #[arg(short, long, value_name = "FILE")]
+    output: Option<PathBuf>,
+    
+    /// Force overwrite an existing output file
+    #[arg(short, long)]
+    force: bool,
+    
+    /// Leave out tournaments with fewer players
+    #[arg(long, default_value = "2")]
+    min_players: usize,
+    
+    #[command(flatten)]
+    filter: FilterArgs,
+}
+
+#[derive(clap::Args)]
+struct VerifyArgs {
+    /// Path to the SCID database (without extension)
+    #[arg(value_name = "DATABASE")]
+    database: PathBuf,
+    
+    /// Only check the index and namebase, without decoding games
+    #[arg(long)]
+    index_only: bool,
+    
+    /// Write JSON instead of text
+    #[arg(long)]
+    json: bool,
+}
+
+#[derive(clap::Args)]
+struct CompactArgs {
+    /// Path to the SCID database (without extension)
+    #[arg(value_name = "DATABASE")]
+    database: PathBuf,
+    
+    /// Database to write, without extension (default: replace DATABASE)
+    #[arg(short, long, value_name = "OUTPUT")]
+    output: Option<PathBuf>,
+    
+    /// Force overwrite existing output database files
+    #[arg(short, long)]
+    force: bool,
+    
+    /// Copy index entries as they are instead of repairing them from the games
+    #[arg(long)]
+    no_repair: bool,
+}
+
+#[derive(clap::Args)]
+struct InfoArgs {
+    /// Path to the SCID database (without extension)
+    #[arg(value_name = "DATABASE")]
+    database: PathBuf,
+    
+    /// Write JSON instead of text
+    #[arg(long)]
+    json: bool,
+}
+
+#[derive(Clone, Copy, ValueEnum)]
+enum CrosstableFormat {
+    /// Aligned text tables
+    Text,
+    /// An HTML page with one table per tournament
+    Html,
+    /// JSON array of crosstables
+    Json,
+}
+
+#[derive(Clone, Copy, ValueEnum)]
+enum Side {
+    White,
+    Black,
+}
+
+impl Side {
+    fn color(self) -> Color {
+        match self {
+            Side::White => Color::White,
+            Side::Black => Color::Black,
+        }
+    }
+}
+
+#[derive(Clone, Copy, ValueEnum)]
+enum BishopArg {
+    Same,
+    Opposite,
+}
+
+#[derive(Clone, Copy, ValueEnum)]
+enum DedupArg {
+    /// Same player names, full date and result
+    Strict,
+    /// Player names ignoring case and punctuation, same year and result
+    Normal,
+    /// Same surnames, any date and result
+    Loose,
+}
+
+impl DedupArg {
+    fn strictness(self) -> DuplicateStrictness {
+        match self {
+            DedupArg::Strict => DuplicateStrictness::Strict,
+            DedupArg::Normal => DuplicateStrictness::Normal,
+            DedupArg::Loose => DuplicateStrictness::Loose,
+        }
+    }
 }
 
 fn main() {
     let args = Args::parse();
     
+    match args.command {
+        Some(Command::Import(import)) => run_import(import),
+        Some(Command::Book(book)) => run_book(book),
+        Some(Command::Search(search)) => run_search(search),
+        Some(Command::Material(material)) => run_material(material),
+        Some(Command::Tree(tree)) => run_tree(tree),
+        Some(Command::Player(player)) => run_player(player),
+        Some(Command::HeadToHead(head_to_head)) => run_head_to_head(head_to_head),
+        Some(Command::Crosstable(crosstable)) => run_crosstable(crosstable),
+        Some(Command::Verify(verify)) => run_verify(verify),
+        Some(Command::Compact(compact)) => run_compact(compact),
+        Some(Command::Info(info)) => run_info(info),
+        None => run_export(args.export),
+    }
+}
+
+fn run_export(args: ExportArgs) {
+    let database_path = args.database.expect("clap requires DATABASE");
+    
     // Determine output file path
     let output_path = match args.output {
         Some(path) => path,
         None => {
-            let mut path = args.database.clone();
-            path.set_extension("pgn");
+            let mut path = database_path.clone();
+            path.set_extension(args.format.extension());
             path
         }
     };
-    
     // Check if output file exists and we're not forcing overwrite
     if output_path.exists() && !args.force {
         eprintln!("Error: Output file '{}' already exists. Use --force to overwrite.", 
@@ -93,10 +702,15 @@ fn main() {
         process::exit(1);
     }
     
-    println!("Converting SCID database '{}' to PGN format...", args.database.display());
+    println!("Converting SCID database '{}' to {} format...", database_path.display(), args.format.name());
     
-    // Load SCID database
-    let mut database = match ScidDatabase::load(&args.database) {
+    // Load SCID database; CSV and TSV only need the index and namebase,
+    // unless games are decoded to find duplicates
+    let loaded = match args.format {
+        ExportFormat::Csv | ExportFormat::Tsv if args.dedup.is_none() => ScidDatabase::load_index_only(&database_path),
+        _ => ScidDatabase::load(&database_path),
+    };
+    let mut database = match loaded {
         Ok(db) => db,
         Err(e) => {
             eprintln!("Error loading SCID database: {}", e);
@@ -106,24 +720,554 @@ fn main() {
     
     println!("Loaded database with {} games", database.num_games());
     
-    // Create PGN exporter
-    let mut exporter = PgnExporter::new()
-        .with_variations(args.variations)
-        .with_comments(args.comments);
+    if let Some(path) = &args.spelling {
+        match SpellingFile::load(path) {
+            Ok(spelling) => {
+                let changed = database.apply_spelling(&spelling);
+                println!("Corrected {} names using '{}'", changed, path.display());
+            }
+            Err(e) => {
+                eprintln!("Error reading spelling file '{}': {}", path.display(), e);
+                process::exit(1);
+            }
+        }
+    }
     
-    if args.max_games > 0 {
-        exporter = exporter.with_max_games(args.max_games);
+    let max_games = (args.max_games > 0).then_some(args.max_games);
+    let mut filter = args.filter.filter();
+    if let Some(dedup) = args.dedup {
+        let duplicates = DuplicateFinder::new()
+            .with_strictness(dedup.strictness())
+            .with_filter(filter.clone())
+            .find(&mut database);
+        println!("Found {} duplicate games", duplicates.dropped().count());
+        if let Some(path) = &args.duplicate_report {
+            let written = fs::File::create(path).and_then(|file| {
+                let mut writer = std::io::BufWriter::new(file);
+                duplicates.write_report(&database, &mut writer)?;
+                writer.flush()
+            });
+            if let Err(e) = written {
+                eprintln!("Error writing duplicate report '{}': {}", path.display(), e);
+                process::exit(1);
+            }
+        }
+        filter = filter.with_excluded_games(duplicates.dropped());
     }
+    let result = match args.format {
+        ExportFormat::Pgn => {
+            let mut exporter = PgnExporter::new()
+                .with_filter(filter)
+                .with_variations(args.variations)
+                .with_comments(args.comments);
+            if let Some(max) = max_games {
+                exporter = exporter.with_max_games(max);
+            }
+            exporter.export(&mut database, &output_path)
+        }
+        ExportFormat::Json | ExportFormat::Ndjson => {
+            let mut exporter = JsonExporter::new()
+                .with_filter(filter)
+                .with_ndjson(matches!(args.format, ExportFormat::Ndjson))
+                .with_pretty(args.pretty)
+                .with_variations(args.variations)
+                .with_comments(args.comments);
+            if let Some(max) = max_games {
+                exporter = exporter.with_max_games(max);
+            }
+            exporter.export(&mut database, &output_path)
+        }
+        ExportFormat::Csv | ExportFormat::Tsv => {
+            let delimiter = if matches!(args.format, ExportFormat::Tsv) { b'\t' } else { b',' };
+            let mut exporter = CsvExporter::new().with_filter(filter).with_delimiter(delimiter);
+            if !args.columns.is_empty() {
+                exporter = exporter.with_columns(args.columns);
+            }
+            if let Some(max) = max_games {
+                exporter = exporter.with_max_games(max);
+            }
+            exporter.export(&database, &output_path)
+        }
+        ExportFormat::Epd => {
+            let mut exporter = EpdExporter::new().with_filter(filter).with_positions(args.positions);
+            if let Some(max) = max_games {
+                exporter = exporter.with_max_games(max);
+            }
+            exporter.export(&mut database, &output_path)
+        }
+        #[cfg(feature = "sqlite")]
+        ExportFormat::Sqlite => {
+            let mut exporter = SqliteExporter::new().with_filter(filter).with_positions(!args.no_positions);
+            if let Some(max) = max_games {
+                exporter = exporter.with_max_games(max);
+            }
+            exporter.export(&mut database, &output_path)
+        }
+        #[cfg(feature = "parquet")]
+        ExportFormat::Parquet => {
+            let mut exporter = ParquetExporter::new().with_filter(filter);
+            if args.plies {
+                exporter = exporter.with_plies(output_path.with_extension("plies.parquet"));
+            }
+            if let Some(max) = max_games {
+                exporter = exporter.with_max_games(max);
+            }
+            exporter.export(&mut database, &output_path)
+        }
+    };
     
-    // Export to PGN
-    match exporter.export(&mut database, &output_path) {
+    match result {
         Ok(exported_count) => {
             println!("Successfully exported {} games to '{}'", 
                     exported_count, output_path.display());
         }
         Err(e) => {
-            eprintln!("Error exporting to PGN: {}", e);
+            eprintln!("Error exporting to {}: {}", args.format.name(), e);
+            process::exit(1);
+        }
+    }
+}
+
+fn run_import(args: ImportArgs) {
+    let base_path = args.output.unwrap_or_else(|| args.inputs[0].with_extension(""));
+    
+    // Check none of the database files exist unless we're forcing overwrite
+    for extension in ["si4", "sn4", "sg4"] {
+        let path = base_path.with_extension(extension);
+        if path.exists() && !args.force {
+            eprintln!("Error: Database file '{}' already exists. Use --force to overwrite.", 
+                     path.display());
+            process::exit(1);
+        }
+    }
+    
+    println!("Importing PGN into SCID database '{}'...", base_path.display());
+    
+    let mut writer = match ScidWriter::create(&base_path) {
+        Ok(writer) => writer.with_description(&args.description),
+        Err(e) => {
+            eprintln!("Error creating SCID database: {}", e);
+            process::exit(1);
+        }
+    };
+    
+    let mut skipped = 0;
+    for input in &args.inputs {
+        let reader = match fs::File::open(input) {
+            Ok(file) => PgnReader::new(BufReader::new(file)),
+            Err(e) => {
+                eprintln!("Error reading '{}': {}", input.display(), e);
+                process::exit(1);
+            }
+        };
+        
+        for game in reader {
+            match game {
+                Ok(game) => {
+                    if let Err(e) = writer.add_game(&game) {
+                        eprintln!("Error writing game {}: {}", writer.num_games() + 1, e);
+                        process::exit(1);
+                    }
+                }
+                Err(e) => {
+                    // A broken game shouldn't stop the rest of the file importing
+                    eprintln!("Warning: skipping {} in '{}'", e, input.display());
+                    skipped += 1;
+                }
+            }
+        }
+    }
+    
+    match writer.finish() {
+        Ok(count) => {
+            println!("Successfully imported {} games into '{}' ({} skipped)", 
+                    count, base_path.display(), skipped);
+        }
+        Err(e) => {
+            eprintln!("Error writing SCID database: {}", e);
+            process::exit(1);
+        }
+    }
+}
+
+fn run_book(args: BookArgs) {
+    let output_path = args.output.unwrap_or_else(|| args.database.with_extension("bin"));
+    if output_path.exists() && !args.force {
+        eprintln!("Error: Output file '{}' already exists. Use --force to overwrite.", 
+                 output_path.display());
+        process::exit(1);
+    }
+    
+    println!("Building Polyglot book from SCID database '{}'...", args.database.display());
+    
+    let mut database = match ScidDatabase::load(&args.database) {
+        Ok(db) => db,
+        Err(e) => {
+            eprintln!("Error loading SCID database: {}", e);
+            process::exit(1);
+        }
+    };
+    
+    let mut exporter = PolyglotExporter::new()
+        .with_min_elo(args.min_elo)
+        .with_min_games(args.min_games);
+    if args.max_ply > 0 {
+        exporter = exporter.with_max_ply(args.max_ply);
+    }
+    if let Some(side) = args.side {
+        exporter = exporter.with_side(side.color());
+    }
+    
+    match exporter.export(&mut database, &output_path) {
+        Ok(games) => {
+            println!("Successfully built '{}' from {} games", output_path.display(), games);
+        }
+        Err(e) => {
+            eprintln!("Error writing book: {}", e);
+            process::exit(1);
+        }
+    }
+}
+
+fn run_search(args: SearchArgs) {
+    let target = match Position::from_fen(&args.fen) {
+        Ok(pos) => pos,
+        Err(e) => {
+            eprintln!("Error: {}", e);
+            process::exit(1);
+        }
+    };
+    
+    let mut database = match ScidDatabase::load(&args.database) {
+        Ok(db) => db,
+        Err(e) => {
+            eprintln!("Error loading SCID database: {}", e);
+            process::exit(1);
+        }
+    };
+    
+    let matching = if args.pieces { PositionMatch::Pieces } else { PositionMatch::Exact };
+    let mut search = PositionSearch::new(target)
+        .with_match(matching)
+        .with_filter(args.filter.filter());
+    if args.max_games > 0 {
+        search = search.with_max_games(args.max_games);
+    }
+    
+    let candidates = search.candidates(&database).len();
+    let hits = search.search(&mut database);
+    for hit in &hits {
+        println!("Game {}, ply {}: {}", hit.game + 1, hit.ply, game_summary(&database, hit.game));
+    }
+    eprintln!("Found {} games ({} of {} games searched after index pruning)",
+              hits.len(), candidates, database.num_games());
+}
+
+fn run_material(args: MaterialArgs) {
+    let mut database = match ScidDatabase::load(&args.database) {
+        Ok(db) => db,
+        Err(e) => {
+            eprintln!("Error loading SCID database: {}", e);
+            process::exit(1);
+        }
+    };
+    
+    let scope = if args.any { MaterialScope::Any } else { MaterialScope::Final };
+    let bishops = match args.bishops {
+        Some(BishopArg::Same) => BishopColors::Same,
+        Some(BishopArg::Opposite) => BishopColors::Opposite,
+        None => BishopColors::Any,
+    };
+    let mut search = MaterialSearch::new(args.pattern)
+        .with_scope(scope)
+        .with_flip(args.flip)
+        .with_bishops(bishops)
+        .with_filter(args.filter.filter());
+    if let Some(side) = args.to_move {
+        search = search.with_side_to_move(side.color());
+    }
+    if args.max_games > 0 {
+        search = search.with_max_games(args.max_games);
+    }
+    
+    let candidates = search.candidates(&database).len();
+    let hits = search.search(&mut database);
+    for hit in &hits {
+        let flipped = if hit.flipped { " (colours reversed)" } else { "" };
+        println!("Game {}, ply {}{}: {}", hit.game + 1, hit.ply, flipped, game_summary(&database, hit.game));
+    }
+    eprintln!("Found {} games ({} of {} games searched after index pruning)",
+              hits.len(), candidates, database.num_games());
+}
+
+fn run_tree(args: TreeArgs) {
+    let tree = match (&args.fen, &args.moves) {
+        (Some(fen), _) => Position::from_fen(fen).map(OpeningTree::new).map_err(|e| e.to_string()),
+        (None, moves) => OpeningTree::from_moves(moves.as_deref().unwrap_or("")),
+    };
+    let tree = match tree {
+        Ok(tree) => tree.with_filter(args.filter.filter()),
+        Err(e) => {
+            eprintln!("Error: {}", e);
+            process::exit(1);
+        }
+    };
+    
+    let mut database = match ScidDatabase::load(&args.database) {
+        Ok(db) => db,
+        Err(e) => {
+            eprintln!("Error loading SCID database: {}", e);
+            process::exit(1);
+        }
+    };
+    
+    let report = tree.build(&mut database);
+    let mut stdout = std::io::stdout().lock();
+    let written = if args.json {
+        serde_json::to_writer_pretty(&mut stdout, &report).map_err(std::io::Error::from)
+            .and_then(|()| writeln!(stdout))
+    } else {
+        report.write_table(&mut stdout)
+    };
+    if let Err(e) = written {
+        eprintln!("Error writing tree: {}", e);
+        process::exit(1);
+    }
+}
+
+fn run_player(args: PlayerArgs) {
+    // The report only needs the index and namebase
+    let database = match ScidDatabase::load_index_only(&args.database) {
+        Ok(db) => db,
+        Err(e) => {
+            eprintln!("Error loading SCID database: {}", e);
             process::exit(1);
         }
+    };
+    
+    let mut profile = PlayerProfile::new(&args.name).with_filter(args.filter.filter());
+    if args.top > 0 {
+        profile = profile.with_limit(args.top);
     }
+    let report = match profile.build(&database) {
+        Ok(report) => report,
+        Err(e) => {
+            eprintln!("Error: {}", e);
+            process::exit(1);
+        }
+    };
+    
+    let mut stdout = std::io::stdout().lock();
+    let written = if args.json {
+        serde_json::to_writer_pretty(&mut stdout, &report).map_err(std::io::Error::from)
+            .and_then(|()| writeln!(stdout))
+    } else {
+        report.write_text(&mut stdout)
+    };
+    if let Err(e) = written {
+        eprintln!("Error writing report: {}", e);
+        process::exit(1);
+    }
+}
+
+fn run_head_to_head(args: HeadToHeadArgs) {
+    if let Some(path) = &args.pgn {
+        if path.exists() && !args.force {
+            eprintln!("Error: Output file '{}' already exists. Use --force to overwrite.", path.display());
+            process::exit(1);
+        }
+    }
+    
+    let mut database = match ScidDatabase::load(&args.database) {
+        Ok(db) => db,
+        Err(e) => {
+            eprintln!("Error loading SCID database: {}", e);
+            process::exit(1);
+        }
+    };
+    
+    let head_to_head = HeadToHead::new(&args.first, &args.second).with_filter(args.filter.filter());
+    let report = match head_to_head.build(&database) {
+        Ok(report) => report,
+        Err(e) => {
+            eprintln!("Error: {}", e);
+            process::exit(1);
+        }
+    };
+    
+    let mut stdout = std::io::stdout().lock();
+    let written = if args.json {
+        serde_json::to_writer_pretty(&mut stdout, &report).map_err(std::io::Error::from)
+            .and_then(|()| writeln!(stdout))
+    } else {
+        report.write_text(&mut stdout)
+    };
+    if let Err(e) = written {
+        eprintln!("Error writing report: {}", e);
+        process::exit(1);
+    }
+    
+    if let Some(path) = &args.pgn {
+        // The names resolved for the report, so they resolve again here
+        let filter = head_to_head.game_filter(&database).expect("players already resolved");
+        match PgnExporter::new().with_filter(filter).export(&mut database, path) {
+            Ok(games) => eprintln!("Wrote {} games to '{}'", games, path.display()),
+            Err(e) => {
+                eprintln!("Error writing PGN: {}", e);
+                process::exit(1);
+            }
+        }
+    }
+}
+
+fn run_crosstable(args: CrosstableArgs) {
+    if let Some(path) = &args.output {
+        if path.exists() && !args.force {
+            eprintln!("Error: Output file '{}' already exists. Use --force to overwrite.", path.display());
+            process::exit(1);
+        }
+    }
+    
+    // Crosstables only need the index and namebase
+    let database = match ScidDatabase::load_index_only(&args.database) {
+        Ok(db) => db,
+        Err(e) => {
+            eprintln!("Error loading SCID database: {}", e);
+            process::exit(1);
+        }
+    };
+    
+    let crosstables = Crosstables::new()
+        .with_filter(args.filter.filter())
+        .with_min_players(args.min_players)
+        .build(&database);
+    
+    let mut writer: Box<dyn Write> = match &args.output {
+        Some(path) => match fs::File::create(path) {
+            Ok(file) => Box::new(std::io::BufWriter::new(file)),
+            Err(e) => {
+                eprintln!("Error creating '{}': {}", path.display(), e);
+                process::exit(1);
+            }
+        },
+        None => Box::new(std::io::stdout().lock()),
+    };
+    let written = match args.format {
+        CrosstableFormat::Text => crosstables.iter().enumerate().try_for_each(|(i, crosstable)| {
+            if i > 0 {
+                writeln!(writer)?;
+            }
+            crosstable.write_text(&mut writer)
+        }),
+        CrosstableFormat::Html => write_html_page(&crosstables, &mut writer),
+        CrosstableFormat::Json => serde_json::to_writer_pretty(&mut writer, &crosstables).map_err(std::io::Error::from)
+            .and_then(|()| writeln!(writer)),
+    };
+    if let Err(e) = written.and_then(|()| writer.flush()) {
+        eprintln!("Error writing crosstables: {}", e);
+        process::exit(1);
+    }
+}
+
+fn run_verify(args: VerifyArgs) {
+    let loaded = if args.index_only {
+        ScidDatabase::load_index_only(&args.database)
+    } else {
+        ScidDatabase::load(&args.database)
+    };
+    let mut database = match loaded {
+        Ok(db) => db,
+        Err(e) => {
+            eprintln!("Error loading SCID database: {}", e);
+            process::exit(2);
+        }
+    };
+    
+    let report = Verifier::new().verify(&mut database);
+    let mut stdout = std::io::stdout().lock();
+    let written = if args.json {
+        serde_json::to_writer_pretty(&mut stdout, &report).map_err(std::io::Error::from)
+            .and_then(|()| writeln!(stdout))
+    } else {
+        report.write_text(&mut stdout)
+    };
+    if let Err(e) = written {
+        eprintln!("Error writing report: {}", e);
+        process::exit(2);
+    }
+    if !report.is_clean() {
+        process::exit(1);
+    }
+}
+
+fn run_compact(args: CompactArgs) {
+    let compactor = Compactor::new().with_repair(!args.no_repair);
+    // Writing over the database being read goes through a temporary copy
+    let compacted = match args.output.as_ref().filter(|&output| output != &args.database) {
+        Some(base_path) => {
+            for extension in ["si4", "sn4", "sg4"] {
+                let path = base_path.with_extension(extension);
+                if path.exists() && !args.force {
+                    eprintln!("Error: Database file '{}' already exists. Use --force to overwrite.",
+                             path.display());
+                    process::exit(1);
+                }
+            }
+            let mut database = match ScidDatabase::load(&args.database) {
+                Ok(db) => db,
+                Err(e) => {
+                    eprintln!("Error loading SCID database: {}", e);
+                    process::exit(1);
+                }
+            };
+            compactor.compact(&mut database, base_path)
+        }
+        None => compactor.compact_in_place(&args.database),
+    };
+    
+    let report = match compacted {
+        Ok(report) => report,
+        Err(e) => {
+            eprintln!("Error compacting SCID database: {}", e);
+            process::exit(1);
+        }
+    };
+    if let Err(e) = report.write_text(&mut std::io::stdout().lock()) {
+        eprintln!("Error writing report: {}", e);
+        process::exit(1);
+    }
+}
+
+fn run_info(args: InfoArgs) {
+    let database = match ScidDatabase::load(&args.database) {
+        Ok(db) => db,
+        Err(e) => {
+            eprintln!("Error loading SCID database: {}", e);
+            process::exit(1);
+        }
+    };
+    
+    let info = DatabaseInfo::new(&database);
+    let mut stdout = std::io::stdout().lock();
+    let written = if args.json {
+        serde_json::to_writer_pretty(&mut stdout, &info).map_err(std::io::Error::from)
+            .and_then(|()| writeln!(stdout))
+    } else {
+        info.write_text(&mut stdout)
+    };
+    if let Err(e) = written {
+        eprintln!("Error writing info: {}", e);
+        process::exit(1);
+    }
+}
+
+/// Players, event, date and result of a game, for search listings
+fn game_summary(database: &ScidDatabase, game: usize) -> String {
+    let game_index = &database.game_indices()[game];
+    format!("{} - {}, {} {}, {}",
+            database.player_name(game_index.white_id).unwrap_or("?"),
+            database.player_name(game_index.black_id).unwrap_or("?"),
+            database.event_name(game_index.event_id).unwrap_or("?"),
+            game_index.date_string(),
+            game_index.result_string())
 }
@@ -1,3 +1,7 @@
 pub mod exporter;
+pub mod reader;
+pub mod writer;
 
 pub use exporter::PgnExporter;
+pub use writer::write_game;
+pub use reader::{parse_games, PgnError, PgnReader};
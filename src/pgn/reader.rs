@@ -0,0 +1,566 @@
+//! Streaming PGN reader producing the same `Game` model the SCID decoder yields
+//!
+//! Tag pairs, SAN movetext with move numbers, `{}` and `;` comments, NAGs
+//! (both `$n` and `!`/`?` suffixes) and nested `( )` variations are
+//! supported. Each move is checked against the position, so a game with
+//! an illegal move is reported as an error rather than imported wrongly.
+//!
+//! `PgnReader` reads one game's worth of lines at a time, so files of any
+//! size can be processed. Lines starting with `%` are escape lines and are
+//! skipped, as the PGN standard requires.
+//!
+//! ## Real-World Malformations Tolerated
+//! - Latin-1 (or otherwise non-UTF-8) lines and a leading byte order mark
+//! - Missing result tokens and missing blank lines between games
+//! - Unescaped quotes inside tag values, and tag values missing the closing quote
+//! - A `{` comment left open at the end of a game (closed at the next `[Event`)
+//! - Stray `)`, unclosed `(`, NAGs before the first move and `e.p.` suffixes
+//! - Castling written with zeros or lowercase letters, `1/2` and `½-½` results
+
+use std::collections::VecDeque;
+use std::fmt;
+use std::io::{self, BufRead};
+
+use crate::chess::{Game, GameResult, Line, MoveNode, Position, MAX_VARIATION_DEPTH};
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct PgnError {
+    /// 1-based number of the game in the input
+    pub game_number: usize,
+    /// 1-based line on which the game starts
+    pub line: usize,
+    pub message: String,
+}
+
+impl fmt::Display for PgnError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "game {} (line {}): {}", self.game_number, self.line, self.message)
+    }
+}
+
+impl std::error::Error for PgnError {}
+
+#[derive(Debug, Clone, PartialEq)]
+enum Token {
+    Tag(String, String),
+    Comment(String),
+    Nag(u8),
+    Open,
+    Close,
+    MoveNumber,
+    San(String),
+    Result(GameResult),
+}
+
+/// Iterator over the games in a PGN stream
+///
+/// Games that fail to parse are yielded as errors in place, so the caller
+/// can skip them and carry on. A read error ends the iteration.
+pub struct PgnReader<R> {
+    reader: R,
+    pending: VecDeque<Result<Game, PgnError>>,
+    /// Lines of the game being collected
+    chunk: String,
+    chunk_line: usize,
+    chunk_has_movetext: bool,
+    in_comment: bool,
+    line_number: usize,
+    games_read: usize,
+    done: bool,
+}
+
+impl<R: BufRead> PgnReader<R> {
+    pub fn new(reader: R) -> Self {
+        PgnReader {
+            reader,
+            pending: VecDeque::new(),
+            chunk: String::new(),
+            chunk_line: 1,
+            chunk_has_movetext: false,
+            in_comment: false,
+            line_number: 0,
+            games_read: 0,
+            done: false,
+        }
+    }
+
+    /// Read the next line, decoding it as UTF-8 or, failing that, Latin-1
+    fn read_line(&mut self) -> io::Result<Option<String>> {
+        let mut bytes = Vec::new();
+        if self.reader.read_until(b'\n', &mut bytes)? == 0 {
+            return Ok(None);
+        }
+        self.line_number += 1;
+        let mut line = match String::from_utf8(bytes) {
+            Ok(line) => line,
+            Err(e) => e.into_bytes().iter().map(|&b| b as char).collect(),
+        };
+        if self.line_number == 1 && line.starts_with('\u{feff}') {
+            line.remove(0);
+        }
+        Ok(Some(line))
+    }
+
+    /// Collect lines until the next game starts (or the input ends) and
+    /// parse them into the pending queue
+    fn fill(&mut self) {
+        loop {
+            let line = match self.read_line() {
+                Ok(Some(line)) => line,
+                Ok(None) => {
+                    self.done = true;
+                    self.flush_chunk();
+                    return;
+                }
+                Err(e) => {
+                    self.done = true;
+                    self.flush_chunk();
+                    self.pending.push_back(Err(PgnError {
+                        game_number: self.games_read + 1,
+                        line: self.line_number,
+                        message: format!("read error: {}", e),
+                    }));
+                    return;
+                }
+            };
+
+            if line.starts_with('%') {
+                continue; // Escape line
+            }
+            if self.chunk.is_empty() && line.trim().is_empty() {
+                continue;
+            }
+
+            let trimmed = line.trim_start();
+            if trimmed.starts_with('[') {
+                if self.in_comment && trimmed.starts_with("[Event ") {
+                    // A comment left open swallowed the end of the previous game
+                    self.chunk.push('}');
+                    self.in_comment = false;
+                }
+                if !self.in_comment && self.chunk_has_movetext {
+                    self.flush_chunk();
+                    self.chunk_line = self.line_number;
+                    self.chunk.push_str(&line);
+                    if !self.pending.is_empty() {
+                        return;
+                    }
+                    continue;
+                }
+            }
+
+            self.scan_line(&line);
+            if self.chunk.is_empty() {
+                self.chunk_line = self.line_number;
+            }
+            self.chunk.push_str(&line);
+            if !line.ends_with('\n') {
+                self.chunk.push('\n');
+            }
+        }
+    }
+
+    /// Track whether a line holds movetext and whether it leaves a brace comment open
+    fn scan_line(&mut self, line: &str) {
+        let is_tag_line = !self.in_comment && line.trim_start().starts_with('[');
+        for c in line.chars() {
+            if self.in_comment {
+                if c == '}' {
+                    self.in_comment = false;
+                }
+            } else if c == '{' {
+                self.in_comment = true;
+                self.chunk_has_movetext |= !is_tag_line;
+            } else if c == ';' {
+                break;
+            } else if !c.is_whitespace() && !is_tag_line {
+                self.chunk_has_movetext = true;
+            }
+        }
+    }
+
+    fn flush_chunk(&mut self) {
+        let chunk = std::mem::take(&mut self.chunk);
+        self.chunk_has_movetext = false;
+        self.in_comment = false;
+        for game in parse_chunk(&chunk, self.games_read + 1, self.chunk_line) {
+            self.games_read += 1;
+            self.pending.push_back(game);
+        }
+    }
+}
+
+impl<R: BufRead> Iterator for PgnReader<R> {
+    type Item = Result<Game, PgnError>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        loop {
+            if let Some(game) = self.pending.pop_front() {
+                return Some(game);
+            }
+            if self.done {
+                return None;
+            }
+            self.fill();
+        }
+    }
+}
+
+/// Parse every game in a PGN text held in memory
+pub fn parse_games(text: &str) -> Vec<Result<Game, PgnError>> {
+    PgnReader::new(text.as_bytes()).collect()
+}
+
+/// Parse the games in one chunk of text. Usually a chunk is a single game,
+/// but games not separated by tags (only by result tokens) are split here.
+fn parse_chunk(text: &str, first_game: usize, first_line: usize) -> Vec<Result<Game, PgnError>> {
+    let mut games = Vec::new();
+    let mut builder = GameBuilder::new(first_game, first_line);
+
+    for (token, line) in tokenize(text) {
+        let line = first_line + line;
+        let is_tag = matches!(token, Token::Tag(..));
+        if is_tag && builder.in_movetext {
+            // Tags after movetext without a result token start a new game
+            games.push(builder.finish());
+            builder = GameBuilder::new(first_game + games.len(), line);
+        }
+        // A result ends the game even inside an unclosed variation
+        let ends_game = matches!(token, Token::Result(_));
+        builder.push(token);
+        if ends_game {
+            games.push(builder.finish());
+            builder = GameBuilder::new(first_game + games.len(), line);
+        }
+    }
+    if builder.has_content() {
+        games.push(builder.finish());
+    }
+
+    games
+}
+
+/// One level of the move tree being built: the line so far, the position
+/// after its last move and the position before it (where variations start)
+struct Frame {
+    line: Line,
+    pos: Position,
+    before: Option<Position>,
+}
+
+struct GameBuilder {
+    game_number: usize,
+    line: usize,
+    tags: Vec<(String, String)>,
+    frames: Vec<Frame>,
+    result: Option<GameResult>,
+    in_movetext: bool,
+    error: Option<String>,
+}
+
+impl GameBuilder {
+    fn new(game_number: usize, line: usize) -> Self {
+        GameBuilder {
+            game_number,
+            line,
+            tags: Vec::new(),
+            frames: Vec::new(),
+            result: None,
+            in_movetext: false,
+            error: None,
+        }
+    }
+
+    fn has_content(&self) -> bool {
+        !self.tags.is_empty() || self.in_movetext
+    }
+
+    fn fail(&mut self, message: String) {
+        if self.error.is_none() {
+            self.error = Some(message);
+        }
+    }
+
+    /// Set up the main line frame from the FEN tag on the first movetext token
+    fn start_movetext(&mut self) {
+        if self.in_movetext {
+            return;
+        }
+        self.in_movetext = true;
+        let fen = self.tags.iter().find(|(n, _)| n == "FEN").map(|(_, v)| v.clone());
+        let pos = match fen {
+            Some(fen) => match Position::from_fen(&fen) {
+                Ok(pos) => pos,
+                Err(e) => {
+                    self.fail(e.to_string());
+                    Position::starting_position()
+                }
+            },
+            None => Position::starting_position(),
+        };
+        self.frames.push(Frame { line: Line::default(), pos, before: None });
+    }
+
+    fn push(&mut self, token: Token) {
+        if let Token::Tag(name, value) = token {
+            self.tags.push((name, value));
+            return;
+        }
+        self.start_movetext();
+        if self.error.is_some() {
+            return;
+        }
+
+        match token {
+            Token::Tag(..) | Token::MoveNumber => {}
+            Token::Comment(text) => {
+                let frame = self.frames.last_mut().unwrap();
+                let target = match frame.line.moves.last_mut() {
+                    Some(node) => &mut node.comment,
+                    None => &mut frame.line.comment,
+                };
+                append_comment(target, &text);
+            }
+            Token::Nag(nag) => {
+                // A NAG before the first move has nothing to annotate; drop it
+                if let Some(node) = self.frames.last_mut().unwrap().line.moves.last_mut() {
+                    node.nags.push(nag);
+                }
+            }
+            Token::Open => {
+                if self.frames.len() > MAX_VARIATION_DEPTH {
+                    self.fail(format!("variations nested more than {} deep", MAX_VARIATION_DEPTH));
+                    return;
+                }
+                let parent = self.frames.last().unwrap();
+                match &parent.before {
+                    Some(before) => {
+                        let pos = before.clone();
+                        self.frames.push(Frame { line: Line::default(), pos, before: None });
+                    }
+                    None => self.fail("variation before the first move".to_string()),
+                }
+            }
+            Token::Close => {
+                if self.frames.len() < 2 {
+                    return; // Stray ')'
+                }
+                let variation = self.frames.pop().unwrap().line;
+                let parent = self.frames.last_mut().unwrap();
+                if let Some(node) = parent.line.moves.last_mut() {
+                    if !variation.moves.is_empty() || variation.comment.is_some() {
+                        node.variations.push(variation);
+                    }
+                }
+            }
+            Token::San(san) => {
+                let frame = self.frames.last_mut().unwrap();
+                match frame.pos.parse_san(&san) {
+                    Some(mv) => {
+                        frame.before = Some(frame.pos.clone());
+                        frame.pos.make_move(&mv);
+                        frame.line.moves.push(MoveNode::new(mv));
+                    }
+                    None => {
+                        let message = format!("illegal or ambiguous move '{}' in position {}",
+                                              san, frame.pos.to_fen());
+                        self.fail(message);
+                    }
+                }
+            }
+            Token::Result(result) => self.result = Some(result),
+        }
+    }
+
+    fn finish(mut self) -> Result<Game, PgnError> {
+        if let Some(message) = self.error.take() {
+            return Err(PgnError { game_number: self.game_number, line: self.line, message });
+        }
+
+        // Close variations left open at the end of the game
+        while self.frames.len() > 1 {
+            self.push(Token::Close);
+        }
+        let moves = self.frames.pop().map(|frame| frame.line).unwrap_or_default();
+
+        let tag_result = self.tags.iter()
+            .find(|(n, _)| n == "Result")
+            .map(|(_, v)| GameResult::from_pgn(v));
+        let result = self.result.or(tag_result).unwrap_or_default();
+
+        Ok(Game { tags: self.tags, moves, result })
+    }
+}
+
+fn append_comment(target: &mut Option<String>, text: &str) {
+    let text = text.trim();
+    match target {
+        Some(existing) if !existing.is_empty() => {
+            if !text.is_empty() {
+                existing.push(' ');
+                existing.push_str(text);
+            }
+        }
+        _ => *target = Some(text.to_string()),
+    }
+}
+
+fn is_symbol_char(c: char) -> bool {
+    c.is_ascii_alphanumeric() || matches!(c, '_' | '+' | '#' | '=' | ':' | '-' | '/')
+}
+
+/// Split PGN text into tokens, each paired with its 0-based line offset
+fn tokenize(text: &str) -> Vec<(Token, usize)> {
+    let mut tokens = Vec::new();
+    let chars: Vec<char> = text.chars().collect();
+    let mut i = 0;
+    let mut line = 0;
+    let mut scanned = 0;
+
+    while i < chars.len() {
+        while scanned < i {
+            if chars[scanned] == '\n' {
+                line += 1;
+            }
+            scanned += 1;
+        }
+
+        let c = chars[i];
+        match c {
+            '[' => {
+                let (token, next) = read_tag(&chars, i + 1);
+                tokens.extend(token.map(|t| (t, line)));
+                i = next;
+            }
+            '{' => {
+                let end = chars[i + 1..].iter().position(|&c| c == '}').map(|p| i + 1 + p);
+                let stop = end.unwrap_or(chars.len());
+                tokens.push((Token::Comment(chars[i + 1..stop].iter().collect()), line));
+                i = stop + 1;
+            }
+            ';' => {
+                let end = chars[i..].iter().position(|&c| c == '\n').map(|p| i + p).unwrap_or(chars.len());
+                tokens.push((Token::Comment(chars[i + 1..end].iter().collect()), line));
+                i = end;
+            }
+            '(' => {
+                tokens.push((Token::Open, line));
+                i += 1;
+            }
+            ')' => {
+                tokens.push((Token::Close, line));
+                i += 1;
+            }
+            '$' => {
+                let start = i + 1;
+                let mut end = start;
+                while end < chars.len() && chars[end].is_ascii_digit() {
+                    end += 1;
+                }
+                let number: String = chars[start..end].iter().collect();
+                if let Ok(nag) = number.parse() {
+                    tokens.push((Token::Nag(nag), line));
+                }
+                i = end.max(start);
+            }
+            '!' | '?' => {
+                let start = i;
+                while i < chars.len() && matches!(chars[i], '!' | '?') {
+                    i += 1;
+                }
+                let suffix: String = chars[start..i].iter().collect();
+                if let Some(nag) = suffix_nag(&suffix) {
+                    tokens.push((Token::Nag(nag), line));
+                }
+            }
+            '*' => {
+                tokens.push((Token::Result(GameResult::Unknown), line));
+                i += 1;
+            }
+            '½' if chars[i..].starts_with(&['½', '-', '½']) => {
+                tokens.push((Token::Result(GameResult::Draw), line));
+                i += 3;
+            }
+            'e' if chars[i..].starts_with(&['e', '.', 'p', '.']) => {
+                i += 4; // En passant suffix
+            }
+            c if is_symbol_char(c) => {
+                let start = i;
+                while i < chars.len() && is_symbol_char(chars[i]) {
+                    i += 1;
+                }
+                let symbol: String = chars[start..i].iter().collect();
+                if symbol.chars().all(|c| c.is_ascii_digit()) {
+                    // Move number; the dots that follow are part of it
+                    while i < chars.len() && chars[i] == '.' {
+                        i += 1;
+                    }
+                    tokens.push((Token::MoveNumber, line));
+                } else {
+                    match symbol.as_str() {
+                        "1-0" | "0-1" | "1/2-1/2" => {
+                            tokens.push((Token::Result(GameResult::from_pgn(&symbol)), line));
+                        }
+                        "1/2" => tokens.push((Token::Result(GameResult::Draw), line)),
+                        "ep" => {}
+                        _ => tokens.push((Token::San(symbol), line)),
+                    }
+                }
+            }
+            _ => i += 1,
+        }
+    }
+
+    tokens
+}
+
+/// Read a tag pair after its opening '[': returns the token (if well formed)
+/// and the index just past the closing ']'
+///
+/// Tags are read up to the end of their line, so a value missing its
+/// closing quote can't swallow the tags that follow. The value runs from
+/// the first to the last quote before the closing ']', which keeps
+/// unescaped quotes inside it.
+fn read_tag(chars: &[char], mut i: usize) -> (Option<Token>, usize) {
+    let line_end = chars[i..].iter().position(|&c| c == '\n').map(|p| i + p).unwrap_or(chars.len());
+    let close = chars[i..line_end].iter().rposition(|&c| c == ']').map(|p| i + p);
+    let end = close.unwrap_or(line_end);
+
+    while i < end && chars[i].is_whitespace() {
+        i += 1;
+    }
+    let name_start = i;
+    while i < end && (chars[i].is_alphanumeric() || chars[i] == '_') {
+        i += 1;
+    }
+    let name: String = chars[name_start..i].iter().collect();
+
+    let mut value = String::new();
+    if let Some(open) = chars[i..end].iter().position(|&c| c == '"').map(|p| i + 1 + p) {
+        let last = chars[open..end].iter().rposition(|&c| c == '"').map(|p| open + p).unwrap_or(end);
+        let mut j = open;
+        while j < last {
+            if chars[j] == '\\' && j + 1 < last {
+                j += 1;
+            }
+            value.push(chars[j]);
+            j += 1;
+        }
+    }
+
+    let token = if name.is_empty() { None } else { Some(Token::Tag(name, value)) };
+    (token, close.map(|c| c + 1).unwrap_or(line_end))
+}
+
+/// NAG equivalent of a move suffix annotation
+fn suffix_nag(suffix: &str) -> Option<u8> {
+    match suffix {
+        "!" => Some(1),
+        "?" => Some(2),
+        "!!" => Some(3),
+        "??" => Some(4),
+        "!?" => Some(5),
+        "?!" => Some(6),
+        _ => None,
+    }
+}
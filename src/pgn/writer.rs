@@ -0,0 +1,125 @@
+use std::io::{self, Write};
+
+use crate::chess::{Color, Game, GameResult, Line, Position};
+
+/// PGN movetext generation
+///
+/// Turns a move tree into SAN movetext with move numbers, NAGs and
+/// (optionally) comments and variations, wrapped to stay under 80 columns
+/// as the PGN standard recommends.
+pub struct MovetextOptions {
+    pub include_variations: bool,
+    pub include_comments: bool,
+}
+
+const MAX_LINE_LENGTH: usize = 79;
+
+/// The Seven Tag Roster, written first and in this order
+const ROSTER: [&str; 7] = ["Event", "Site", "Date", "Round", "White", "Black", "Result"];
+
+/// Write a complete game: the Seven Tag Roster (with "?" for missing
+/// tags), the remaining tags in their stored order, a blank line and the
+/// movetext. The Result tag always agrees with the game result.
+pub fn write_game<W: Write>(writer: &mut W, game: &Game, options: &MovetextOptions) -> io::Result<()> {
+    for name in ROSTER {
+        let value = match name {
+            "Result" => game.result.as_pgn(),
+            _ => game.tag(name).unwrap_or("?"),
+        };
+        writeln!(writer, "[{} \"{}\"]", name, escape_tag_value(value))?;
+    }
+    for (name, value) in &game.tags {
+        if !ROSTER.contains(&name.as_str()) {
+            writeln!(writer, "[{} \"{}\"]", name, escape_tag_value(value))?;
+        }
+    }
+    writeln!(writer)?;
+
+    let start = game.start_position()
+        .map_err(|e| io::Error::new(io::ErrorKind::InvalidData, e))?;
+    writeln!(writer, "{}", movetext(&start, &game.moves, game.result, options))
+}
+
+/// Escape backslashes and quotes inside a PGN tag value
+pub fn escape_tag_value(value: &str) -> String {
+    value.replace('\\', "\\\\").replace('"', "\\\"")
+}
+
+/// Movetext for a game starting at `start`, terminated by the result
+pub fn movetext(start: &Position, line: &Line, result: GameResult, options: &MovetextOptions) -> String {
+    let mut tokens = Vec::new();
+    line_tokens(start, line, options, &mut tokens);
+    tokens.push(result.as_pgn().to_string());
+    wrap_tokens(&tokens)
+}
+
+fn line_tokens(start: &Position, line: &Line, options: &MovetextOptions, tokens: &mut Vec<String>) {
+    if options.include_comments {
+        if let Some(comment) = &line.comment {
+            tokens.push(comment_token(comment));
+        }
+    }
+
+    let mut pos = start.clone();
+    let mut need_number = true;
+    for node in &line.moves {
+        if pos.to_move == Color::White {
+            tokens.push(format!("{}.", pos.full_move_number));
+        } else if need_number {
+            tokens.push(format!("{}...", pos.full_move_number));
+        }
+        need_number = false;
+
+        tokens.push(pos.san(&node.mv));
+        for nag in &node.nags {
+            tokens.push(format!("${}", nag));
+        }
+
+        if options.include_comments {
+            if let Some(comment) = &node.comment {
+                tokens.push(comment_token(comment));
+                need_number = true;
+            }
+        }
+
+        if options.include_variations {
+            for variation in &node.variations {
+                let mut inner = Vec::new();
+                line_tokens(&pos, variation, options, &mut inner);
+                if !inner.is_empty() {
+                    let last = inner.len() - 1;
+                    inner[0].insert(0, '(');
+                    inner[last].push(')');
+                    tokens.extend(inner);
+                    need_number = true;
+                }
+            }
+        }
+
+        pos.make_move(&node.mv);
+    }
+}
+
+/// Braces can't be escaped inside a PGN comment, so a closing brace in the
+/// text is replaced
+fn comment_token(comment: &str) -> String {
+    let text = comment.replace('}', ")");
+    format!("{{{}}}", text.split_whitespace().collect::<Vec<_>>().join(" "))
+}
+
+fn wrap_tokens(tokens: &[String]) -> String {
+    let mut text = String::new();
+    let mut line_length = 0;
+    for token in tokens {
+        if line_length > 0 && line_length + 1 + token.len() > MAX_LINE_LENGTH {
+            text.push('\n');
+            line_length = 0;
+        } else if line_length > 0 {
+            text.push(' ');
+            line_length += 1;
+        }
+        text.push_str(token);
+        line_length += token.len();
+    }
+    text
+}
@@ -0,0 +1,412 @@
+//! Tournament crosstables
+//!
+//! Games are grouped into tournaments by event, site and year, using the
+//! namebase IDs in the index; games without a result are left out. Each
+//! tournament is a round robin when every pair of players met the same
+//! number of times, and a Swiss otherwise.
+//!
+//! ## Tiebreaks
+//! - Sonneborn-Berger: the scores of the opponents beaten, plus half the
+//!   scores of those drawn with
+//! - Buchholz: the sum of the opponents' scores, once per game
+//! - Performance: the average opponent rating plus 400 times (wins -
+//!   losses) divided by games, over games against rated opponents
+//!
+//! Players are ranked by score, then Sonneborn-Berger and Buchholz in a
+//! round robin, or Buchholz and Sonneborn-Berger in a Swiss.
+
+use std::collections::HashMap;
+use std::io::{self, Write};
+
+use serde::Serialize;
+
+use crate::chess::Color;
+use crate::export::{selected_games, GameFilter};
+use crate::scid::ScidDatabase;
+use super::{outcome, Performance};
+
+/// Event ID, site ID and year, which group games into tournaments
+type TournamentKey = (u32, u32, u16);
+
+/// How the pairings of a tournament were made
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+#[serde(tag = "type", rename_all = "kebab-case")]
+pub enum TournamentFormat {
+    /// Every pair of players met `cycles` times
+    RoundRobin { cycles: u32 },
+    Swiss,
+}
+
+/// One game from a player's point of view
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub struct CrosstableGame {
+    pub round: String,
+    /// Rank of the opponent
+    pub opponent: usize,
+    /// "white" or "black"
+    pub color: &'static str,
+    /// 1, 0.5 or 0
+    pub points: f64,
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub struct CrosstableRow {
+    pub rank: usize,
+    pub name: String,
+    /// From the player's first rated game in the tournament
+    pub elo: Option<u16>,
+    pub score: f64,
+    pub sonneborn_berger: f64,
+    pub buchholz: f64,
+    pub performance: Option<u16>,
+    /// In round order
+    pub games: Vec<CrosstableGame>,
+}
+
+/// One tournament, players in rank order
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub struct Crosstable {
+    pub event: String,
+    pub site: String,
+    pub year: Option<u16>,
+    pub format: TournamentFormat,
+    /// Highest round number, or the most games played by any player if
+    /// that's more (e.g. when rounds aren't numbered)
+    pub rounds: usize,
+    pub players: Vec<CrosstableRow>,
+}
+
+impl Crosstable {
+    fn title(&self) -> String {
+        let year = self.year.map_or(String::new(), |year| format!(" {}", year));
+        let format = match self.format {
+            TournamentFormat::RoundRobin { cycles: 1 } => "round robin".to_string(),
+            TournamentFormat::RoundRobin { cycles } => format!("{}-cycle round robin", cycles),
+            TournamentFormat::Swiss => "Swiss".to_string(),
+        };
+        format!("{}, {}{} ({}, {} players, {} rounds)", self.event, self.site, year, format,
+                self.players.len(), self.rounds)
+    }
+
+    /// Column headings and a row of cells for each player: one column per
+    /// opponent in a round robin, with a result per game separated by
+    /// spaces, and one per round in a Swiss, left empty for a bye
+    fn result_columns(&self) -> (Vec<String>, Vec<Vec<String>>) {
+        match self.format {
+            TournamentFormat::RoundRobin { .. } => {
+                let headings = (1..=self.players.len()).map(|rank| rank.to_string()).collect();
+                let cells = self.players.iter().map(|row| {
+                    (1..=self.players.len()).map(|opponent| {
+                        if opponent == row.rank {
+                            return "X".to_string();
+                        }
+                        row.games.iter()
+                            .filter(|game| game.opponent == opponent)
+                            .map(|game| format_points(game.points))
+                            .collect::<Vec<_>>()
+                            .join(" ")
+                    }).collect()
+                }).collect();
+                (headings, cells)
+            }
+            TournamentFormat::Swiss => {
+                let headings = (1..=self.rounds).map(|round| format!("R{}", round)).collect();
+                let cells = self.players.iter().map(|row| {
+                    let mut cells = vec![String::new(); self.rounds];
+                    let cell = |game: &CrosstableGame| {
+                        format!("{}{}{}", game.opponent, &game.color[..1], format_points(game.points))
+                    };
+                    // Numbered rounds go in their own column; the others
+                    // take the free columns in game order
+                    let (numbered, unnumbered): (Vec<_>, Vec<_>) = row.games.iter()
+                        .partition(|game| round_number(&game.round).is_some_and(|round| round as usize <= self.rounds));
+                    for game in numbered {
+                        let column = &mut cells[round_number(&game.round).unwrap() as usize - 1];
+                        if !column.is_empty() {
+                            column.push(' ');
+                        }
+                        column.push_str(&cell(game));
+                    }
+                    let mut free = 0;
+                    for game in unnumbered {
+                        while free < cells.len() && !cells[free].is_empty() {
+                            free += 1;
+                        }
+                        if free == cells.len() {
+                            cells.push(String::new());
+                        }
+                        cells[free] = cell(game);
+                    }
+                    cells
+                }).collect();
+                (headings, cells)
+            }
+        }
+    }
+
+    /// Write the crosstable as aligned text
+    pub fn write_text<W: Write>(&self, writer: &mut W) -> io::Result<()> {
+        writeln!(writer, "{}", self.title())?;
+        let (headings, cells) = self.result_columns();
+        let name_width = self.players.iter().map(|row| row.name.chars().count()).max().unwrap_or(0).max(6);
+        let width = cells.iter().flatten().chain(&headings).map(|cell| cell.chars().count()).max().unwrap_or(1);
+
+        write!(writer, "{:>3}  {:<name_width$} {:>5} ", "#", "Player", "Elo", name_width = name_width)?;
+        for heading in &headings {
+            write!(writer, " {:>width$}", heading, width = width)?;
+        }
+        writeln!(writer, " {:>6} {:>7} {:>7} {:>5}", "Score", "SB", "Buch", "Perf")?;
+
+        let optional = |value: Option<u16>| value.map_or("-".to_string(), |value| value.to_string());
+        for (row, cells) in self.players.iter().zip(&cells) {
+            write!(writer, "{:>3}  {:<name_width$} {:>5} ", row.rank, row.name, optional(row.elo),
+                   name_width = name_width)?;
+            for cell in cells {
+                write!(writer, " {:>width$}", cell, width = width)?;
+            }
+            writeln!(writer, " {:>6} {:>7.2} {:>7.1} {:>5}", format_points(row.score), row.sonneborn_berger,
+                     row.buchholz, optional(row.performance))?;
+        }
+        Ok(())
+    }
+
+    /// Write the crosstable as an HTML section with a table
+    pub fn write_html<W: Write>(&self, writer: &mut W) -> io::Result<()> {
+        let (headings, cells) = self.result_columns();
+        writeln!(writer, "<section class=\"crosstable\">")?;
+        writeln!(writer, "<h2>{}</h2>", escape_html(&self.title()))?;
+        writeln!(writer, "<table>")?;
+        write!(writer, "<tr><th>#</th><th>Player</th><th>Elo</th>")?;
+        for heading in &headings {
+            write!(writer, "<th>{}</th>", heading)?;
+        }
+        writeln!(writer, "<th>Score</th><th>SB</th><th>Buch</th><th>Perf</th></tr>")?;
+
+        let optional = |value: Option<u16>| value.map_or("-".to_string(), |value| value.to_string());
+        for (row, cells) in self.players.iter().zip(&cells) {
+            write!(writer, "<tr><td>{}</td><td>{}</td><td>{}</td>", row.rank, escape_html(&row.name),
+                   optional(row.elo))?;
+            for cell in cells {
+                write!(writer, "<td>{}</td>", cell)?;
+            }
+            writeln!(writer, "<td>{}</td><td>{:.2}</td><td>{:.1}</td><td>{}</td></tr>", format_points(row.score),
+                     row.sonneborn_berger, row.buchholz, optional(row.performance))?;
+        }
+        writeln!(writer, "</table>")?;
+        writeln!(writer, "</section>")
+    }
+}
+
+/// Write crosstables as a complete HTML page
+pub fn write_html_page<W: Write>(crosstables: &[Crosstable], writer: &mut W) -> io::Result<()> {
+    writeln!(writer, "<!DOCTYPE html>")?;
+    writeln!(writer, "<html>")?;
+    writeln!(writer, "<head>")?;
+    writeln!(writer, "<meta charset=\"utf-8\">")?;
+    writeln!(writer, "<title>Crosstables</title>")?;
+    writeln!(writer, "<style>table {{ border-collapse: collapse; }} \
+                      th, td {{ border: 1px solid #999; padding: 2px 6px; text-align: center; }}</style>")?;
+    writeln!(writer, "</head>")?;
+    writeln!(writer, "<body>")?;
+    for crosstable in crosstables {
+        crosstable.write_html(writer)?;
+    }
+    writeln!(writer, "</body>")?;
+    writeln!(writer, "</html>")
+}
+
+/// Points as in a crosstable: "1", "½", "2½"
+fn format_points(points: f64) -> String {
+    let whole = points.trunc() as u32;
+    match (whole, points.fract() > 0.0) {
+        (0, true) => "½".to_string(),
+        (whole, true) => format!("{}½", whole),
+        (whole, false) => whole.to_string(),
+    }
+}
+
+/// The number a round name starts with, e.g. 3 for "3" or "3.1"; None
+/// for "?" and other names without one
+fn round_number(round: &str) -> Option<u32> {
+    round.split(|c: char| !c.is_ascii_digit()).next()
+        .and_then(|number| number.parse().ok())
+        .filter(|&number| number > 0)
+}
+
+fn escape_html(text: &str) -> String {
+    text.replace('&', "&amp;").replace('<', "&lt;").replace('>', "&gt;").replace('"', "&quot;")
+}
+
+/// A game as one player saw it, before ranks are known
+struct Played {
+    /// Round number, date and game number, for ordering
+    order: (u32, (u16, u8, u8), usize),
+    round: String,
+    opponent: u32,
+    color: Color,
+    points: f64,
+}
+
+#[derive(Default)]
+struct Entry {
+    elo: Option<u16>,
+    games: Vec<Played>,
+    performance: Performance,
+}
+
+/// Crosstable builder
+pub struct Crosstables {
+    filter: GameFilter,
+    min_players: usize,
+}
+
+impl Crosstables {
+    pub fn new() -> Self {
+        Crosstables { filter: GameFilter::new(), min_players: 2 }
+    }
+
+    /// Only count games the filter matches
+    pub fn with_filter(mut self, filter: GameFilter) -> Self {
+        self.filter = filter;
+        self
+    }
+
+    /// Leave out tournaments with fewer players (default 2)
+    pub fn with_min_players(mut self, min_players: usize) -> Self {
+        self.min_players = min_players;
+        self
+    }
+
+    /// A crosstable for every tournament, in order of first game
+    pub fn build(&self, database: &ScidDatabase) -> Vec<Crosstable> {
+        let mut tournaments: Vec<(TournamentKey, HashMap<u32, Entry>)> = Vec::new();
+        let mut positions: HashMap<TournamentKey, usize> = HashMap::new();
+        for (game_num, game_index) in selected_games(database, &self.filter, None) {
+            if outcome(&game_index, Color::White).is_none() {
+                continue;
+            }
+            let key = (game_index.event_id, game_index.site_id, game_index.year);
+            let position = *positions.entry(key).or_insert_with(|| {
+                tournaments.push((key, HashMap::new()));
+                tournaments.len() - 1
+            });
+            let players = &mut tournaments[position].1;
+
+            let round = database.round_name(game_index.round_id).unwrap_or("?").to_string();
+            let order = (round_number(&round).unwrap_or(u32::MAX), (game_index.year, game_index.month, game_index.day), game_num);
+            for (color, player, elo, opponent) in [
+                (Color::White, game_index.white_id, game_index.white_elo, game_index.black_id),
+                (Color::Black, game_index.black_id, game_index.black_elo, game_index.white_id),
+            ] {
+                let entry = players.entry(player).or_default();
+                if entry.elo.is_none() && elo > 0 {
+                    entry.elo = Some(elo);
+                }
+                entry.performance.add(&game_index, color);
+                let points = match outcome(&game_index, color) {
+                    Some(1) => 1.0,
+                    Some(0) => 0.5,
+                    _ => 0.0,
+                };
+                entry.games.push(Played { order, round: round.clone(), opponent, color, points });
+            }
+        }
+
+        tournaments.into_iter()
+            .filter(|(_, players)| players.len() >= self.min_players)
+            .map(|(key, players)| crosstable(database, key, players))
+            .collect()
+    }
+}
+
+impl Default for Crosstables {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+/// Rank the players of the tournament with an event, site and year
+fn crosstable(database: &ScidDatabase, (event_id, site_id, year): TournamentKey,
+              mut players: HashMap<u32, Entry>) -> Crosstable {
+    let scores: HashMap<u32, f64> = players.iter()
+        .map(|(&id, entry)| (id, entry.games.iter().map(|game| game.points).sum()))
+        .collect();
+
+    // Games per pair of players
+    let mut pairings: HashMap<(u32, u32), u32> = HashMap::new();
+    for (&id, entry) in &players {
+        for game in entry.games.iter().filter(|game| id < game.opponent) {
+            *pairings.entry((id, game.opponent)).or_default() += 1;
+        }
+    }
+    let pairs = players.len() * (players.len() - 1) / 2;
+    let cycles = pairings.values().next().copied().unwrap_or(0);
+    let format = if pairings.len() == pairs && pairings.values().all(|&games| games == cycles) {
+        TournamentFormat::RoundRobin { cycles }
+    } else {
+        TournamentFormat::Swiss
+    };
+
+    let mut rows: Vec<(u32, CrosstableRow)> = players.iter_mut()
+        .map(|(&id, entry)| {
+            entry.games.sort_by_key(|game| game.order);
+            let mut sonneborn_berger = 0.0;
+            let mut buchholz = 0.0;
+            for game in &entry.games {
+                sonneborn_berger += game.points * scores[&game.opponent];
+                buchholz += scores[&game.opponent];
+            }
+            (id, CrosstableRow {
+                rank: 0,
+                name: database.player_name(id).unwrap_or("?").to_string(),
+                elo: entry.elo,
+                score: scores[&id],
+                sonneborn_berger,
+                buchholz,
+                performance: entry.performance.rating(),
+                games: Vec::new(),
+            })
+        })
+        .collect();
+    let tiebreaks = |row: &CrosstableRow| match format {
+        TournamentFormat::RoundRobin { .. } => (row.sonneborn_berger, row.buchholz),
+        TournamentFormat::Swiss => (row.buchholz, row.sonneborn_berger),
+    };
+    rows.sort_by(|(_, a), (_, b)| {
+        b.score.total_cmp(&a.score)
+            .then_with(|| {
+                let ((a_first, a_second), (b_first, b_second)) = (tiebreaks(a), tiebreaks(b));
+                b_first.total_cmp(&a_first).then(b_second.total_cmp(&a_second))
+            })
+            .then_with(|| a.name.cmp(&b.name))
+    });
+
+    let ranks: HashMap<u32, usize> = rows.iter().enumerate().map(|(i, (id, _))| (*id, i + 1)).collect();
+    let rounds = players.values()
+        .flat_map(|entry| entry.games.iter().filter_map(|game| round_number(&game.round)).map(|round| round as usize)
+            .chain([entry.games.len()]))
+        .max()
+        .unwrap_or(0);
+    let rows = rows.into_iter().enumerate().map(|(i, (id, mut row))| {
+        row.rank = i + 1;
+        row.games = players[&id].games.iter().map(|game| CrosstableGame {
+            round: game.round.clone(),
+            opponent: ranks[&game.opponent],
+            color: match game.color {
+                Color::White => "white",
+                Color::Black => "black",
+            },
+            points: game.points,
+        }).collect();
+        row
+    }).collect();
+
+    Crosstable {
+        event: database.event_name(event_id).unwrap_or("?").to_string(),
+        site: database.site_name(site_id).unwrap_or("?").to_string(),
+        year: (year > 0).then_some(year),
+        format,
+        rounds,
+        players: rows,
+    }
+}
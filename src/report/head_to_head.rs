@@ -0,0 +1,158 @@
+//! Head-to-head record between two players
+//!
+//! Both names are looked up as for the player report. Scores and openings
+//! are from the first player's point of view; the second player's score is
+//! the complement. The games themselves can be exported with any exporter
+//! through `HeadToHead::game_filter`.
+
+use std::collections::HashMap;
+use std::io::{self, Write};
+
+use serde::Serialize;
+
+use crate::chess::Color;
+use crate::export::{selected_games, GameFilter};
+use crate::scid::eco::eco_to_string;
+use crate::scid::ScidDatabase;
+use super::{resolve_player, score_columns, OpeningStat, Score};
+
+/// One game between the two players
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub struct HeadToHeadGame {
+    /// Game number in the database, counting from 1
+    pub number: usize,
+    pub date: String,
+    pub event: String,
+    pub site: String,
+    pub round: String,
+    pub white: String,
+    pub black: String,
+    pub result: &'static str,
+    pub eco: Option<String>,
+    pub ply_count: u16,
+}
+
+/// The two players' games against each other, in database order
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub struct HeadToHeadReport {
+    pub first: String,
+    pub second: String,
+    /// The first player's score overall, with white and with black
+    pub total: Score,
+    pub first_white: Score,
+    pub first_black: Score,
+    /// By three-character ECO code, most played first
+    pub openings: Vec<OpeningStat>,
+    pub games: Vec<HeadToHeadGame>,
+}
+
+impl HeadToHeadReport {
+    /// Write the report as plain text sections
+    pub fn write_text<W: Write>(&self, writer: &mut W) -> io::Result<()> {
+        writeln!(writer, "{} vs {} ({} games)", self.first, self.second, self.total.games)?;
+        writeln!(writer)?;
+        writeln!(writer, "{:<16} {:>6} {:>5} {:>5} {:>5} {:>7}", "Colour", "Games", "+", "=", "-", "Score")?;
+        for (label, score) in [("As White", &self.first_white), ("As Black", &self.first_black), ("Total", &self.total)] {
+            writeln!(writer, "{:<16} {}", label, score_columns(score))?;
+        }
+
+        if !self.openings.is_empty() {
+            writeln!(writer)?;
+            writeln!(writer, "Openings:")?;
+            for opening in &self.openings {
+                writeln!(writer, "{:<16} {}", opening.eco, score_columns(&opening.score))?;
+            }
+        }
+
+        if !self.games.is_empty() {
+            writeln!(writer)?;
+            writeln!(writer, "Games:")?;
+            for game in &self.games {
+                writeln!(writer, "{:>6}  {}  {} - {}  {}  {}  {}", game.number, game.date, game.white, game.black,
+                         game.result, game.eco.as_deref().unwrap_or("-"), game.event)?;
+            }
+        }
+        Ok(())
+    }
+}
+
+/// Head-to-head report builder
+pub struct HeadToHead {
+    first: String,
+    second: String,
+    filter: GameFilter,
+}
+
+impl HeadToHead {
+    pub fn new(first: &str, second: &str) -> Self {
+        HeadToHead { first: first.to_string(), second: second.to_string(), filter: GameFilter::new() }
+    }
+
+    /// Only count games the filter matches
+    pub fn with_filter(mut self, filter: GameFilter) -> Self {
+        self.filter = filter;
+        self
+    }
+
+    /// The filter for the games between the two players, for exporting
+    /// them; fails unless each name picks out one player, and they differ
+    pub fn game_filter(&self, database: &ScidDatabase) -> Result<GameFilter, String> {
+        let (first, second) = self.players(database)?;
+        Ok(self.filter.clone().with_pairing(first, second))
+    }
+
+    /// Build the report from the index
+    pub fn build(&self, database: &ScidDatabase) -> Result<HeadToHeadReport, String> {
+        let (first_id, second_id) = self.players(database)?;
+        let filter = self.filter.clone().with_pairing(first_id, second_id);
+        let name = |id: u32| database.player_name(id).unwrap_or("?").to_string();
+
+        let mut report = HeadToHeadReport {
+            first: name(first_id),
+            second: name(second_id),
+            total: Score::default(),
+            first_white: Score::default(),
+            first_black: Score::default(),
+            openings: Vec::new(),
+            games: Vec::new(),
+        };
+        let mut openings: HashMap<String, Score> = HashMap::new();
+        for (game_num, game_index) in selected_games(database, &filter, None) {
+            let color = if game_index.white_id == first_id { Color::White } else { Color::Black };
+            report.total.add(&game_index, color);
+            match color {
+                Color::White => report.first_white.add(&game_index, color),
+                Color::Black => report.first_black.add(&game_index, color),
+            }
+            let eco = eco_to_string(game_index.eco);
+            if let Some(eco) = &eco {
+                openings.entry(eco.chars().take(3).collect()).or_default().add(&game_index, color);
+            }
+            report.games.push(HeadToHeadGame {
+                number: game_num + 1,
+                date: game_index.date_string(),
+                event: database.event_name(game_index.event_id).unwrap_or("?").to_string(),
+                site: database.site_name(game_index.site_id).unwrap_or("?").to_string(),
+                round: database.round_name(game_index.round_id).unwrap_or("?").to_string(),
+                white: name(game_index.white_id),
+                black: name(game_index.black_id),
+                result: game_index.result_string(),
+                eco,
+                ply_count: game_index.num_half_moves,
+            });
+        }
+
+        report.openings = openings.into_iter().map(|(eco, score)| OpeningStat { eco, score }).collect();
+        report.openings.sort_by(|a, b| b.score.games.cmp(&a.score.games).then_with(|| a.eco.cmp(&b.eco)));
+        Ok(report)
+    }
+
+    fn players(&self, database: &ScidDatabase) -> Result<(u32, u32), String> {
+        let first = resolve_player(database.names(), &self.first)?;
+        let second = resolve_player(database.names(), &self.second)?;
+        if first == second {
+            return Err(format!("'{}' and '{}' are the same player", self.first, self.second));
+        }
+        Ok((first, second))
+    }
+}
@@ -0,0 +1,128 @@
+//! Reports aggregated from the index
+//!
+//! Reports read only the `.si4` index and `.sn4` namebase, so they are fast
+//! on large databases and work without the game file. Games are chosen
+//! with the same `GameFilter` the exporters use.
+
+pub mod crosstable;
+pub mod head_to_head;
+pub mod player;
+
+pub use crosstable::{Crosstable, CrosstableGame, CrosstableRow, Crosstables, TournamentFormat};
+pub use head_to_head::{HeadToHead, HeadToHeadGame, HeadToHeadReport};
+pub use player::{OpeningStat, OpponentStat, PlayerProfile, PlayerReport, RatingPoint, YearStat};
+
+use serde::Serialize;
+
+use crate::chess::{Color, GameResult};
+use crate::scid::names::NameDatabase;
+use crate::scid::GameIndex;
+
+/// Most candidate names listed when a name matches several players
+const MAX_LISTED_PLAYERS: usize = 10;
+
+/// The one player ID `query` picks out; see `NameDatabase::find_players`
+pub(crate) fn resolve_player(names: &NameDatabase, query: &str) -> Result<u32, String> {
+    match names.find_players(query).as_slice() {
+        [] => Err(format!("no player matches '{}'", query)),
+        &[id] => Ok(id),
+        found => {
+            let mut listed: Vec<&str> = found.iter().take(MAX_LISTED_PLAYERS)
+                .filter_map(|&id| names.player_name(id))
+                .collect();
+            if found.len() > MAX_LISTED_PLAYERS {
+                listed.push("...");
+            }
+            Err(format!("'{}' matches {} players: {}", query, found.len(), listed.join("; ")))
+        }
+    }
+}
+
+/// Wins, draws and losses from one player's point of view
+#[derive(Debug, Clone, Default, PartialEq, Serialize)]
+pub struct Score {
+    /// Every game, including those without a result
+    pub games: u32,
+    pub wins: u32,
+    pub draws: u32,
+    pub losses: u32,
+    /// Points as a percentage of the games with a result, to one decimal
+    pub percent: Option<f64>,
+}
+
+impl Score {
+    /// Count a game played with `color`
+    pub(crate) fn add(&mut self, game_index: &GameIndex, color: Color) {
+        self.games += 1;
+        match outcome(game_index, color) {
+            Some(1) => self.wins += 1,
+            Some(0) => self.draws += 1,
+            Some(_) => self.losses += 1,
+            None => return,
+        }
+        let decided = self.wins + self.draws + self.losses;
+        let points = self.wins as f64 + self.draws as f64 / 2.0;
+        self.percent = Some((points * 1000.0 / decided as f64).round() / 10.0);
+    }
+
+    /// Points scored: one per win, a half per draw
+    pub fn points(&self) -> f64 {
+        self.wins as f64 + self.draws as f64 / 2.0
+    }
+}
+
+/// Games, wins, draws, losses and percentage as text columns, under the
+/// headings `Games`, `+`, `=`, `-` and `Score`
+pub(crate) fn score_columns(score: &Score) -> String {
+    let percent = score.percent.map_or("-".to_string(), |percent| format!("{:.1}%", percent));
+    format!("{:>6} {:>5} {:>5} {:>5} {:>7}", score.games, score.wins, score.draws, score.losses, percent)
+}
+
+/// The result for the side playing `color`: 1 for a win, 0 for a draw, -1
+/// for a loss, None if the game has no result
+pub(crate) fn outcome(game_index: &GameIndex, color: Color) -> Option<i64> {
+    match (GameResult::from_scid(game_index.result), color) {
+        (GameResult::Unknown, _) => None,
+        (GameResult::Draw, _) => Some(0),
+        (GameResult::WhiteWins, Color::White) | (GameResult::BlackWins, Color::Black) => Some(1),
+        _ => Some(-1),
+    }
+}
+
+/// Running totals for a performance rating over games against rated
+/// opponents with a result
+#[derive(Debug, Clone, Copy, Default)]
+pub(crate) struct Performance {
+    opponent_elo_total: u64,
+    /// Wins minus losses
+    balance: i64,
+    games: u32,
+}
+
+impl Performance {
+    /// Count a game played with `color`, if it has a result and the
+    /// opponent is rated
+    pub(crate) fn add(&mut self, game_index: &GameIndex, color: Color) {
+        let opponent_elo = match color {
+            Color::White => game_index.black_elo,
+            Color::Black => game_index.white_elo,
+        };
+        if let (Some(balance), true) = (outcome(game_index, color), opponent_elo > 0) {
+            self.opponent_elo_total += opponent_elo as u64;
+            self.balance += balance;
+            self.games += 1;
+        }
+    }
+
+    pub(crate) fn average_opponent(&self) -> Option<u16> {
+        (self.games > 0).then(|| (self.opponent_elo_total as f64 / self.games as f64).round() as u16)
+    }
+
+    /// Average opponent rating plus 400 times (wins - losses) over games
+    pub(crate) fn rating(&self) -> Option<u16> {
+        (self.games > 0).then(|| {
+            let total = self.opponent_elo_total as i64 + 400 * self.balance;
+            (total as f64 / self.games as f64).round().max(0.0) as u16
+        })
+    }
+}
@@ -0,0 +1,237 @@
+//! Player report: a profile of one player's games
+//!
+//! The player is looked up in the namebase by name, ignoring case; a part
+//! of a name is enough when only one player has it. The report has:
+//! - the score with each colour and overall
+//! - rating history: the player's rating in dated games, each time it
+//!   changes
+//! - openings by three-character ECO code, with each colour
+//! - opponents faced, with the score against each
+//! - score and performance rating per year, performance being the average
+//!   opponent rating plus 400 times (wins - losses) divided by games
+
+use std::collections::HashMap;
+use std::io::{self, Write};
+
+use serde::Serialize;
+
+use crate::chess::Color;
+use crate::export::{selected_games, GameFilter};
+use crate::scid::eco::eco_to_string;
+use crate::scid::ScidDatabase;
+use super::{resolve_player, score_columns, Performance, Score};
+
+/// The player's rating from a game on `date`
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub struct RatingPoint {
+    /// YYYY.MM.DD, with "??" for unknown month or day
+    pub date: String,
+    pub elo: u16,
+}
+
+/// Games with an opening, by three-character ECO code such as "B90"
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub struct OpeningStat {
+    pub eco: String,
+    pub score: Score,
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub struct OpponentStat {
+    pub name: String,
+    pub score: Score,
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub struct YearStat {
+    pub year: u16,
+    pub score: Score,
+    /// Over the games against rated opponents with a result
+    pub average_opponent_elo: Option<u16>,
+    pub performance: Option<u16>,
+}
+
+/// Everything known about a player's games; lists are most played first,
+/// except rating history and years, which are in date order
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub struct PlayerReport {
+    pub name: String,
+    pub total: Score,
+    pub white: Score,
+    pub black: Score,
+    pub ratings: Vec<RatingPoint>,
+    pub white_openings: Vec<OpeningStat>,
+    pub black_openings: Vec<OpeningStat>,
+    pub opponents: Vec<OpponentStat>,
+    pub years: Vec<YearStat>,
+}
+
+impl PlayerReport {
+    /// Write the report as plain text sections
+    pub fn write_text<W: Write>(&self, writer: &mut W) -> io::Result<()> {
+        writeln!(writer, "Player: {} ({} games)", self.name, self.total.games)?;
+        writeln!(writer)?;
+        writeln!(writer, "{:<8} {:>6} {:>5} {:>5} {:>5} {:>7}", "Colour", "Games", "+", "=", "-", "Score")?;
+        for (label, score) in [("White", &self.white), ("Black", &self.black), ("Total", &self.total)] {
+            writeln!(writer, "{:<8} {}", label, score_columns(score))?;
+        }
+
+        if !self.ratings.is_empty() {
+            writeln!(writer)?;
+            writeln!(writer, "Rating history:")?;
+            for point in &self.ratings {
+                writeln!(writer, "{}  {}", point.date, point.elo)?;
+            }
+        }
+
+        for (label, openings) in [("Openings as White:", &self.white_openings), ("Openings as Black:", &self.black_openings)] {
+            if !openings.is_empty() {
+                writeln!(writer)?;
+                writeln!(writer, "{}", label)?;
+                for opening in openings {
+                    writeln!(writer, "{:<8} {}", opening.eco, score_columns(&opening.score))?;
+                }
+            }
+        }
+
+        if !self.opponents.is_empty() {
+            writeln!(writer)?;
+            writeln!(writer, "Opponents:")?;
+            let width = self.opponents.iter().map(|opponent| opponent.name.chars().count()).max().unwrap_or(0);
+            for opponent in &self.opponents {
+                writeln!(writer, "{:<width$} {}", opponent.name, score_columns(&opponent.score), width = width)?;
+            }
+        }
+
+        if !self.years.is_empty() {
+            writeln!(writer)?;
+            writeln!(writer, "{:<8} {:>6} {:>5} {:>5} {:>5} {:>7} {:>6} {:>6}",
+                     "Year", "Games", "+", "=", "-", "Score", "AvOpp", "Perf")?;
+            let optional = |value: Option<u16>| value.map_or("-".to_string(), |value| value.to_string());
+            for year in &self.years {
+                writeln!(writer, "{:<8} {} {:>6} {:>6}", year.year, score_columns(&year.score),
+                         optional(year.average_opponent_elo), optional(year.performance))?;
+            }
+        }
+        Ok(())
+    }
+}
+
+/// Player report builder
+pub struct PlayerProfile {
+    name: String,
+    filter: GameFilter,
+    limit: Option<usize>,
+}
+
+impl PlayerProfile {
+    pub fn new(name: &str) -> Self {
+        PlayerProfile { name: name.to_string(), filter: GameFilter::new(), limit: None }
+    }
+
+    /// Only count games the filter matches
+    pub fn with_filter(mut self, filter: GameFilter) -> Self {
+        self.filter = filter;
+        self
+    }
+
+    /// Keep only this many of the most played openings and opponents
+    pub fn with_limit(mut self, limit: usize) -> Self {
+        self.limit = Some(limit);
+        self
+    }
+
+    /// Build the report from the index; fails unless the name picks out
+    /// exactly one player
+    pub fn build(&self, database: &ScidDatabase) -> Result<PlayerReport, String> {
+        let names = database.names();
+        let player_id = resolve_player(names, &self.name)?;
+
+        let mut report = PlayerReport {
+            name: names.player_name(player_id).unwrap_or("?").to_string(),
+            total: Score::default(),
+            white: Score::default(),
+            black: Score::default(),
+            ratings: Vec::new(),
+            white_openings: Vec::new(),
+            black_openings: Vec::new(),
+            opponents: Vec::new(),
+            years: Vec::new(),
+        };
+        let mut ratings = Vec::new();
+        let mut openings: [HashMap<String, Score>; 2] = [HashMap::new(), HashMap::new()];
+        let mut opponents: HashMap<u32, Score> = HashMap::new();
+        let mut years: HashMap<u16, (Score, Performance)> = HashMap::new();
+
+        for (_, game_index) in selected_games(database, &self.filter, None) {
+            let (color, elo, opponent_id) = if game_index.white_id == player_id {
+                (Color::White, game_index.white_elo, game_index.black_id)
+            } else if game_index.black_id == player_id {
+                (Color::Black, game_index.black_elo, game_index.white_id)
+            } else {
+                continue;
+            };
+
+            report.total.add(&game_index, color);
+            match color {
+                Color::White => report.white.add(&game_index, color),
+                Color::Black => report.black.add(&game_index, color),
+            }
+            if elo > 0 && game_index.year > 0 {
+                ratings.push(((game_index.year, game_index.month, game_index.day), game_index.date_string(), elo));
+            }
+            if let Some(eco) = eco_to_string(game_index.eco) {
+                let basic: String = eco.chars().take(3).collect();
+                openings[color.index()].entry(basic).or_default().add(&game_index, color);
+            }
+            opponents.entry(opponent_id).or_default().add(&game_index, color);
+            if game_index.year > 0 {
+                let (score, performance) = years.entry(game_index.year).or_default();
+                score.add(&game_index, color);
+                performance.add(&game_index, color);
+            }
+        }
+
+        ratings.sort_by_key(|(date, _, _)| *date);
+        for (_, date, elo) in ratings {
+            if report.ratings.last().is_none_or(|last| last.elo != elo) {
+                report.ratings.push(RatingPoint { date, elo });
+            }
+        }
+
+        let [white_openings, black_openings] = openings.map(|openings| {
+            let mut openings: Vec<OpeningStat> = openings.into_iter()
+                .map(|(eco, score)| OpeningStat { eco, score })
+                .collect();
+            openings.sort_by(|a, b| b.score.games.cmp(&a.score.games).then_with(|| a.eco.cmp(&b.eco)));
+            self.truncate(openings)
+        });
+        report.white_openings = white_openings;
+        report.black_openings = black_openings;
+
+        let mut opponents: Vec<OpponentStat> = opponents.into_iter()
+            .map(|(id, score)| OpponentStat { name: names.player_name(id).unwrap_or("?").to_string(), score })
+            .collect();
+        opponents.sort_by(|a, b| b.score.games.cmp(&a.score.games).then_with(|| a.name.cmp(&b.name)));
+        report.opponents = self.truncate(opponents);
+
+        let mut years: Vec<YearStat> = years.into_iter()
+            .map(|(year, (score, performance))| YearStat {
+                year,
+                score,
+                average_opponent_elo: performance.average_opponent(),
+                performance: performance.rating(),
+            })
+            .collect();
+        years.sort_by_key(|year| year.year);
+        report.years = years;
+        Ok(report)
+    }
+
+    fn truncate<T>(&self, mut list: Vec<T>) -> Vec<T> {
+        if let Some(limit) = self.limit {
+            list.truncate(limit);
+        }
+        list
+    }
+}
@@ -0,0 +1,226 @@
+//! Database compaction and repair
+//!
+//! Compacting copies a database's games into new files, leaving out:
+//! - games marked deleted
+//! - the gaps that deleting or replacing games leaves in the .sg4 file
+//! - names no remaining game refers to
+//!
+//! Game records are copied byte for byte, so nothing in a game changes.
+//! Each copied record is also decoded, and when that succeeds the index
+//! fields describing the moves are recomputed from the game: the ply count,
+//! the start and promotion flags, the annotation counts and the material
+//! signatures. A result that contradicts a final checkmate or stalemate is
+//! corrected too. Records that can't be decoded are copied with their index
+//! entries as they are; records that can't be read at all are lost.
+
+use std::collections::HashSet;
+use std::fs;
+use std::io::{self, Write};
+use std::path::Path;
+
+use crate::chess::{Color, Game, GameResult};
+use super::writer::set_move_fields;
+use super::{GameIndex, ScidDatabase, ScidWriter};
+
+/// Index fields corrected in one game
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Repair {
+    /// Game number in the original database, counting from 1
+    pub game: usize,
+    /// E.g. "ply count" or "result"
+    pub fields: Vec<&'static str>,
+}
+
+/// What compaction did
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct CompactReport {
+    pub games_before: usize,
+    pub games_after: usize,
+    /// Games dropped because they were marked deleted
+    pub deleted: usize,
+    /// Original game numbers of records that couldn't be read, so were lost
+    pub unreadable: Vec<usize>,
+    /// Original game numbers of records copied without being decoded
+    pub undecodable: Vec<usize>,
+    pub repairs: Vec<Repair>,
+    /// Player, event, site and round names, in total
+    pub names_before: usize,
+    pub names_after: usize,
+    /// Sizes of the .sg4 file
+    pub bytes_before: u64,
+    pub bytes_after: u64,
+}
+
+impl CompactReport {
+    /// Write a summary, then each repair and lost game
+    pub fn write_text<W: Write>(&self, writer: &mut W) -> io::Result<()> {
+        writeln!(writer, "Games: {} -> {} ({} deleted, {} unreadable)", self.games_before, self.games_after,
+                 self.deleted, self.unreadable.len())?;
+        writeln!(writer, "Names: {} -> {}", self.names_before, self.names_after)?;
+        writeln!(writer, "Game file: {} -> {} bytes", self.bytes_before, self.bytes_after)?;
+        writeln!(writer, "Repaired {} games; {} could not be decoded", self.repairs.len(), self.undecodable.len())?;
+        for repair in &self.repairs {
+            writeln!(writer, "game {}: repaired {}", repair.game, repair.fields.join(", "))?;
+        }
+        for game in &self.undecodable {
+            writeln!(writer, "game {}: copied without decoding", game)?;
+        }
+        for game in &self.unreadable {
+            writeln!(writer, "game {}: unreadable, dropped", game)?;
+        }
+        Ok(())
+    }
+}
+
+/// Compaction of a SCID database into new files
+pub struct Compactor {
+    repair: bool,
+}
+
+impl Compactor {
+    pub fn new() -> Self {
+        Compactor { repair: true }
+    }
+
+    /// Whether to recompute index fields from decoded games (default true)
+    pub fn with_repair(mut self, repair: bool) -> Self {
+        self.repair = repair;
+        self
+    }
+
+    /// Write the compacted database to `output` (a base path without
+    /// extension), which must differ from the database's own
+    pub fn compact<P: AsRef<Path>>(&self, database: &mut ScidDatabase, output: P) -> io::Result<CompactReport> {
+        let names = database.names();
+        let mut report = CompactReport {
+            games_before: database.num_games(),
+            names_before: names.players.len() + names.events.len() + names.sites.len() + names.rounds.len(),
+            bytes_before: database.game_file_len().unwrap_or(0),
+            ..CompactReport::default()
+        };
+
+        let description = database.header().description();
+        let mut writer = ScidWriter::create(output.as_ref())?.with_description(&description);
+        let mut kept_names: [HashSet<String>; 4] = Default::default();
+        for (game_num, game_index) in database.game_indices().to_vec().into_iter().enumerate() {
+            if game_index.is_deleted() {
+                report.deleted += 1;
+                continue;
+            }
+            let Ok(record) = database.game_data(&game_index) else {
+                report.unreadable.push(game_num + 1);
+                continue;
+            };
+
+            let mut fields = Vec::new();
+            let found = [
+                database.player_name(game_index.white_id),
+                database.player_name(game_index.black_id),
+                database.event_name(game_index.event_id),
+                database.site_name(game_index.site_id),
+                database.round_name(game_index.round_id),
+            ];
+            if found.contains(&None) {
+                fields.push("names");
+            }
+            let names = found.map(|name| name.unwrap_or("?").to_string());
+
+            let mut repaired = game_index.clone();
+            if self.repair {
+                match database.load_game(&game_index) {
+                    Ok(game) => fields.extend(repair_index(&mut repaired, &game)),
+                    Err(_) => report.undecodable.push(game_num + 1),
+                }
+            }
+            if !fields.is_empty() {
+                report.repairs.push(Repair { game: game_num + 1, fields });
+            }
+
+            let [white, black, event, site, round] = &names;
+            writer.add_record(&repaired, [white, black, event, site, round], &record)?;
+            for (i, section) in [0, 0, 1, 2, 3].into_iter().enumerate() {
+                kept_names[section].insert(names[i].clone());
+            }
+        }
+
+        report.games_after = writer.finish()?;
+        report.names_after = kept_names.iter().map(|names| names.len()).sum();
+        report.bytes_after = fs::metadata(output.as_ref().with_extension("sg4"))?.len();
+        Ok(report)
+    }
+
+    /// Compact the database at `base_path` in place: the compacted files
+    /// are written beside it and then renamed over the originals
+    pub fn compact_in_place<P: AsRef<Path>>(&self, base_path: P) -> io::Result<CompactReport> {
+        let base_path = base_path.as_ref();
+        let file_name = base_path.file_name()
+            .ok_or_else(|| io::Error::new(io::ErrorKind::InvalidInput, "database path has no file name"))?;
+        let temp = base_path.with_file_name(format!("{}-compacting", file_name.to_string_lossy()));
+
+        let mut database = ScidDatabase::load(base_path)?;
+        let report = self.compact(&mut database, &temp);
+        drop(database);
+        let report = match report {
+            Ok(report) => report,
+            Err(e) => {
+                for extension in ["si4", "sn4", "sg4"] {
+                    let _ = fs::remove_file(temp.with_extension(extension));
+                }
+                return Err(e);
+            }
+        };
+        for extension in ["sg4", "sn4", "si4"] {
+            fs::rename(temp.with_extension(extension), base_path.with_extension(extension))?;
+        }
+        Ok(report)
+    }
+}
+
+impl Default for Compactor {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+/// Recompute the index fields a decoded game determines, returning the
+/// names of those that changed
+fn repair_index(index: &mut GameIndex, game: &Game) -> Vec<&'static str> {
+    let before = index.clone();
+    set_move_fields(index, game);
+    if let Some(result) = forced_result(game) {
+        index.result = result.to_scid();
+    }
+
+    let mut changed = Vec::new();
+    for (field, differs) in [
+        ("ply count", before.num_half_moves != index.num_half_moves),
+        ("flags", before.flags != index.flags),
+        ("annotation counts", (before.var_count, before.comment_count, before.nag_count)
+            != (index.var_count, index.comment_count, index.nag_count)),
+        ("final material", before.final_material != index.final_material),
+        ("home pawns", before.home_pawns != index.home_pawns),
+        ("result", before.result != index.result),
+    ] {
+        if differs {
+            changed.push(field);
+        }
+    }
+    changed
+}
+
+/// The result a final checkmate or stalemate decides, if the game ends in
+/// one
+fn forced_result(game: &Game) -> Option<GameResult> {
+    let mut position = game.start_position().ok()?;
+    for mv in game.main_line() {
+        position.make_move(mv);
+    }
+    if !position.legal_moves().is_empty() {
+        return None;
+    }
+    Some(match (position.in_check(), position.to_move) {
+        (false, _) => GameResult::Draw,
+        (true, Color::White) => GameResult::BlackWins,
+        (true, Color::Black) => GameResult::WhiteWins,
+    })
+}
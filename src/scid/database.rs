@@ -1,8 +1,15 @@
+use std::collections::HashSet;
 use std::path::{Path, PathBuf};
+use std::fs;
 use std::io;
 
 use super::{index::IndexFile, names::NameDatabase, games::GameFile};
+use crate::chess::{Game, GameResult};
+use super::eco::eco_to_string;
+use super::moves::{decode_game, GameRecord};
 use super::{ScidHeader, GameIndex};
+use super::spelling::SpellingFile;
+use super::version::check_versions_match;
 
 /// Main SCID database structure - INTEGRATION OF ALL MAJOR FIXES
 /// 
@@ -17,15 +24,22 @@ use super::{ScidHeader, GameIndex};
 /// - **base_name.sn4**: Name database with player, event, site, round names
 /// - **base_name.sg4**: Game file with actual chess moves and annotations
 /// 
-/// ## Error Type Integration Challenge
-/// NameDatabase::parse_names() returns `Result<NameDatabase, Box<dyn std::error::Error>>`
-/// but load() needs to return `io::Result<Self>`. Fixed with .map_err() conversion.
+/// ## Version Validation
+/// Each loader rejects header versions outside `version::SUPPORTED_VERSIONS`,
+/// and load() additionally rejects a .si4/.sn4 pair whose versions differ.
+/// These surface as `io::Error`s wrapping a `ScidError`.
+/// 
+/// ## Malformed Files
+/// Truncated files, bad magic bytes and index entries pointing outside the
+/// .sg4 file are reported as `ScidError`s (wrapped in `io::Error`) rather
+/// than panicking, so untrusted databases can be loaded safely.
 /// 
 /// Contains all three SCID files integrated into a single interface
 pub struct ScidDatabase {
     index: IndexFile,
     names: NameDatabase,
-    games: GameFile,
+    /// None when loaded with `load_index_only`
+    games: Option<GameFile>,
     base_path: PathBuf,
 }
 
@@ -33,7 +47,19 @@ impl ScidDatabase {
     /// Load a SCID database from the base path (without extension)
     /// Will look for .si4, .sn4, and .sg4 files
     pub fn load<P: AsRef<Path>>(base_path: P) -> io::Result<Self> {
-        let base_path = base_path.as_ref().to_path_buf();
+        Self::open(base_path.as_ref(), true)
+    }
+    
+    /// Load only the .si4 index and .sn4 namebase. The .sg4 file is never
+    /// opened (it need not exist), so reading the index of a large database
+    /// costs no game file I/O; `game_data` and everything built on it fail
+    /// with `ErrorKind::Unsupported`.
+    pub fn load_index_only<P: AsRef<Path>>(base_path: P) -> io::Result<Self> {
+        Self::open(base_path.as_ref(), false)
+    }
+    
+    fn open(base_path: &Path, with_games: bool) -> io::Result<Self> {
+        let base_path = base_path.to_path_buf();
         
         // Construct file paths
         let mut si4_path = base_path.clone();
@@ -60,7 +86,7 @@ impl ScidDatabase {
             ));
         }
         
-        if !sg4_path.exists() {
+        if with_games && !sg4_path.exists() {
             return Err(io::Error::new(
                 io::ErrorKind::NotFound,
                 format!("Game file not found: {}", sg4_path.display())
@@ -68,10 +94,14 @@ impl ScidDatabase {
         }
         
         // Load the files
-        let index = IndexFile::load(si4_path)?;
-        let names = NameDatabase::parse_names(sn4_path.to_str().unwrap())
-            .map_err(|e| io::Error::new(io::ErrorKind::InvalidData, e.to_string()))?;
-        let games = GameFile::load(sg4_path)?;
+        let index = IndexFile::load(&si4_path)?;
+        let names = NameDatabase::from_bytes(&sn4_path, &fs::read(&sn4_path)?)?;
+        
+        // Both headers must agree, otherwise the name IDs in the index
+        // cannot be trusted to refer to this namebase
+        check_versions_match(&si4_path, index.header().version, &sn4_path, names.version)?;
+        
+        let games = if with_games { Some(GameFile::load(sg4_path)?) } else { None };
         
         Ok(ScidDatabase {
             index,
@@ -101,6 +131,11 @@ impl ScidDatabase {
         self.index.game_indices()
     }
     
+    /// Get the namebase
+    pub fn names(&self) -> &NameDatabase {
+        &self.names
+    }
+    
     /// Get a player name by ID
     pub fn player_name(&self, player_id: u32) -> Option<&str> {
         self.names.player_name(player_id)
@@ -121,9 +156,95 @@ impl ScidDatabase {
         self.names.round_name(round_id)
     }
     
+    /// Correct player, event and site names with a spelling file, and add
+    /// title and FIDE ID tags for the players it knows; returns the number
+    /// of names changed
+    pub fn apply_spelling(&mut self, spelling: &SpellingFile) -> usize {
+        self.names.apply_spelling(spelling)
+    }
+    
+    /// Size of the .sg4 file in bytes; None when loaded with
+    /// `load_index_only`
+    pub fn game_file_len(&self) -> Option<u64> {
+        self.games.as_ref().map(|games| games.file_len())
+    }
+    
     /// Get the raw game data for a game
     pub fn game_data(&mut self, game_index: &GameIndex) -> io::Result<Vec<u8>> {
-        self.games.game_data(game_index.offset, game_index.length)
+        match &mut self.games {
+            Some(games) => games.game_data(game_index.offset, game_index.length),
+            None => Err(io::Error::new(
+                io::ErrorKind::Unsupported,
+                "Game file not loaded: database was opened with load_index_only"
+            )),
+        }
+    }
+    
+    /// Decode the tags, start position and moves stored for a game
+    pub fn game_record(&mut self, game_index: &GameIndex) -> io::Result<GameRecord> {
+        let data = self.game_data(game_index)?;
+        decode_game(&data)
+    }
+    
+    /// Build a game holding only the tags stored in the index and namebase.
+    /// Names missing from the namebase become "?", as in PGN.
+    pub fn index_game(&self, game_index: &GameIndex) -> Game {
+        let date = game_index.date_string();
+        let mut tags = vec![
+            ("Event", self.event_name(game_index.event_id)),
+            ("Site", self.site_name(game_index.site_id)),
+            ("Date", Some(date.as_str())),
+            ("Round", self.round_name(game_index.round_id)),
+            ("White", self.player_name(game_index.white_id)),
+            ("Black", self.player_name(game_index.black_id)),
+            ("Result", Some(game_index.result_string())),
+        ]
+        .into_iter()
+        .map(|(name, value)| (name.to_string(), value.unwrap_or("?").to_string()))
+        .collect::<Vec<_>>();
+        
+        if game_index.white_elo > 0 {
+            tags.push(("WhiteElo".to_string(), game_index.white_elo.to_string()));
+        }
+        if game_index.black_elo > 0 {
+            tags.push(("BlackElo".to_string(), game_index.black_elo.to_string()));
+        }
+        for (side, player_id) in [("White", game_index.white_id), ("Black", game_index.black_id)] {
+            if let Some(title) = self.names.player_info(player_id).and_then(|info| info.title.as_ref()) {
+                tags.push((format!("{}Title", side), title.clone()));
+            }
+        }
+        for (side, player_id) in [("White", game_index.white_id), ("Black", game_index.black_id)] {
+            if let Some(fide_id) = self.names.player_info(player_id).and_then(|info| info.fide_id) {
+                tags.push((format!("{}FideId", side), fide_id.to_string()));
+            }
+        }
+        if let Some(eco) = eco_to_string(game_index.eco) {
+            tags.push(("ECO".to_string(), eco));
+        }
+        tags.push(("PlyCount".to_string(), game_index.num_half_moves.to_string()));
+        
+        Game {
+            tags,
+            moves: Default::default(),
+            result: GameResult::from_scid(game_index.result),
+        }
+    }
+    
+    /// Load a complete game: the index tags followed by SetUp/FEN and the
+    /// tags kept in the game record, plus the move tree
+    pub fn load_game(&mut self, game_index: &GameIndex) -> io::Result<Game> {
+        let record = self.game_record(game_index)?;
+        let mut game = self.index_game(game_index);
+        if let Some(fen) = record.start_fen {
+            game.tags.push(("SetUp".to_string(), "1".to_string()));
+            game.tags.push(("FEN".to_string(), fen));
+        }
+        // Tags from a spelling file replace any the game stored itself
+        let index_tags: HashSet<String> = game.tags.iter().map(|(name, _)| name.clone()).collect();
+        game.tags.extend(record.tags.into_iter().filter(|(name, _)| !index_tags.contains(name)));
+        game.moves = record.moves;
+        Ok(game)
     }
     
     /// Get the base path of the database
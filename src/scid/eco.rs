@@ -0,0 +1,64 @@
+/// SCID ECO code encoding
+///
+/// The index stores the ECO code as a 16-bit number covering the basic
+/// codes A00-E99 plus SCID's optional extensions ("B90a", "B90a1"):
+/// ```text
+/// code = letter * 13100 + number * 131 + extension + 1
+/// extension = 0, or 1 + (ext_letter * 5) + ext_digit   (a-z, 1-4)
+/// ```
+/// Zero means no ECO code.
+pub const ECO_NONE: u16 = 0;
+
+/// Encode an ECO string such as "B90" or "B90a1"; invalid strings give ECO_NONE
+pub fn eco_from_str(text: &str) -> u16 {
+    let bytes = text.trim().as_bytes();
+    if bytes.len() < 3
+        || !(b'A'..=b'E').contains(&bytes[0])
+        || !bytes[1].is_ascii_digit()
+        || !bytes[2].is_ascii_digit() {
+        return ECO_NONE;
+    }
+
+    let mut code = (bytes[0] - b'A') as u16 * 13100
+        + (bytes[1] - b'0') as u16 * 1310
+        + (bytes[2] - b'0') as u16 * 131;
+
+    if let Some(&ext) = bytes.get(3) {
+        if ext.is_ascii_lowercase() {
+            code += 1 + (ext - b'a') as u16 * 5;
+            if let Some(&digit) = bytes.get(4) {
+                if (b'1'..=b'4').contains(&digit) {
+                    code += (digit - b'0') as u16;
+                }
+            }
+        }
+    }
+
+    code + 1
+}
+
+/// Decode an index ECO code into its string form, or None for ECO_NONE
+/// and out-of-range values
+pub fn eco_to_string(code: u16) -> Option<String> {
+    if code == ECO_NONE {
+        return None;
+    }
+    let value = code - 1;
+    let letter = value / 13100;
+    if letter > 4 {
+        return None;
+    }
+    let number = (value % 13100) / 131;
+    let extension = value % 131;
+
+    let mut eco = format!("{}{:02}", (b'A' + letter as u8) as char, number);
+    if extension > 0 {
+        let ext_letter = (extension - 1) / 5;
+        let ext_digit = (extension - 1) % 5;
+        eco.push((b'a' + ext_letter as u8) as char);
+        if ext_digit > 0 {
+            eco.push_str(&ext_digit.to_string());
+        }
+    }
+    Some(eco)
+}
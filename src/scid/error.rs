@@ -0,0 +1,124 @@
+use std::error::Error;
+use std::fmt;
+use std::io;
+use std::path::PathBuf;
+
+use super::version::version_name;
+
+/// Errors describing SCID files we refuse to parse
+///
+/// The loaders return `io::Result`, so these are wrapped in an
+/// `io::Error` of kind `InvalidData`. Callers that need the details can
+/// recover them with `io::Error::get_ref()` and `downcast_ref::<ScidError>()`.
+///
+/// Every parser reports malformed or truncated input as one of these rather
+/// than panicking, whatever bytes it is given.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum ScidError {
+    /// Header version is not one of `version::SUPPORTED_VERSIONS`
+    UnsupportedVersion {
+        path: PathBuf,
+        found: u16,
+        supported: &'static [u16],
+    },
+    /// The .si4 and .sn4 headers carry different versions
+    VersionMismatch {
+        index_path: PathBuf,
+        index_version: u16,
+        name_path: PathBuf,
+        name_version: u16,
+    },
+    /// The file doesn't start with the expected magic bytes
+    InvalidMagic {
+        path: PathBuf,
+        expected: &'static [u8],
+    },
+    /// The file ends inside a structure its header says is there
+    Truncated {
+        path: PathBuf,
+        /// What was being read, e.g. "game index 12 of 40"
+        what: String,
+        /// Byte offset where that structure starts
+        offset: u64,
+    },
+    /// A front-coded name shares more bytes with the name before it than
+    /// either has
+    InvalidName {
+        path: PathBuf,
+        /// Which name, e.g. "player name 3 of 40"
+        what: String,
+        /// Byte offset where the name starts
+        offset: u64,
+    },
+    /// An index entry points outside the .sg4 file
+    GameOutOfRange {
+        path: PathBuf,
+        offset: u64,
+        length: u32,
+        file_len: u64,
+    },
+    /// An .sg4 game record that can't be decoded
+    InvalidGameRecord {
+        /// Byte offset within the record where decoding failed
+        position: usize,
+        reason: String,
+    },
+}
+
+impl fmt::Display for ScidError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            ScidError::UnsupportedVersion { path, found, supported } => {
+                write!(f, "Unsupported SCID format version {}", found)?;
+                if let Some(name) = version_name(*found) {
+                    write!(f, " ({})", name)?;
+                }
+                let supported: Vec<String> = supported
+                    .iter()
+                    .map(|v| match version_name(*v) {
+                        Some(name) => format!("{} ({})", v, name),
+                        None => v.to_string(),
+                    })
+                    .collect();
+                write!(f, " in '{}'; supported versions: {}", path.display(), supported.join(", "))
+            }
+            ScidError::VersionMismatch { index_path, index_version, name_path, name_version } => {
+                write!(
+                    f,
+                    "SCID version mismatch: '{}' is version {} but '{}' is version {}",
+                    index_path.display(),
+                    index_version,
+                    name_path.display(),
+                    name_version
+                )
+            }
+            ScidError::InvalidMagic { path, expected } => {
+                write!(f, "'{}' is not a SCID file: expected magic {:?}",
+                       path.display(), String::from_utf8_lossy(expected))
+            }
+            ScidError::Truncated { path, what, offset } => {
+                write!(f, "'{}' is truncated: {} at byte {} runs past the end of the file",
+                       path.display(), what, offset)
+            }
+            ScidError::InvalidName { path, what, offset } => {
+                write!(f, "'{}' has an invalid {} at byte {}: its shared prefix is longer than the name",
+                       path.display(), what, offset)
+            }
+            ScidError::GameOutOfRange { path, offset, length, file_len } => {
+                write!(f, "Game record of {} bytes at offset {} is outside '{}' ({} bytes)",
+                       length, offset, path.display(), file_len)
+            }
+            ScidError::InvalidGameRecord { position, reason } => {
+                write!(f, "Invalid game record at byte {}: {}", position, reason)
+            }
+        }
+    }
+}
+
+impl Error for ScidError {}
+
+impl From<ScidError> for io::Error {
+    fn from(error: ScidError) -> Self {
+        io::Error::new(io::ErrorKind::InvalidData, error)
+    }
+}
@@ -1,23 +1,43 @@
 use std::fs::File;
 use std::io::{self, Read, Seek, SeekFrom};
-use std::path::Path;
+use std::path::{Path, PathBuf};
+
+use super::error::ScidError;
 
 /// SCID sg4 game file parser
 /// Contains the actual moves, variations and comments of each game
-
 pub struct GameFile {
     file: File,
+    path: PathBuf,
+    file_len: u64,
 }
 
 impl GameFile {
     /// Load a SCID .sg4 game file
     pub fn load<P: AsRef<Path>>(path: P) -> io::Result<Self> {
-        let file = File::open(path)?;
-        Ok(GameFile { file })
+        let path = path.as_ref().to_path_buf();
+        let file = File::open(&path)?;
+        let file_len = file.metadata()?.len();
+        Ok(GameFile { file, path, file_len })
+    }
+    
+    /// Size of the .sg4 file in bytes
+    pub fn file_len(&self) -> u64 {
+        self.file_len
     }
     
     /// Get the raw game data for a specific offset and length
-    pub fn game_data(&mut self, offset: u32, length: u16) -> io::Result<Vec<u8>> {
+    pub fn game_data(&mut self, offset: u32, length: u32) -> io::Result<Vec<u8>> {
+        // Check the range first: the index may come from a different or damaged file
+        if offset as u64 + length as u64 > self.file_len {
+            return Err(ScidError::GameOutOfRange {
+                path: self.path.clone(),
+                offset: offset as u64,
+                length,
+                file_len: self.file_len,
+            }.into());
+        }
+        
         self.file.seek(SeekFrom::Start(offset as u64))?;
         
         let mut buffer = vec![0u8; length as usize];
@@ -1,7 +1,11 @@
-use std::fs::File;
-use std::io::{self, Read, BufReader};
+use std::fs;
+use std::io::{self, Read, Write};
 use std::path::Path;
 
+use super::error::ScidError;
+use super::signature::{FinalMaterial, HomePawnMoves, HOME_PAWN_DATA_SIZE};
+use super::version::{check_version, SCID_VERSION_4};
+
 /// SCID .si4 index file parser - CRITICAL DATE PARSING IMPLEMENTATION
 /// 
 /// ## Major Issue Solved (July 2025) 
@@ -10,14 +14,14 @@ use std::path::Path;
 /// **Solution**: Proper bit manipulation following SCID's date encoding specification
 /// 
 /// ## SCID Date Encoding Format (20-bit packed field)
-/// ```
+/// ```text
 /// Bits 0-4:   Day (1-31)     - 5 bits
 /// Bits 5-8:   Month (1-12)   - 4 bits  
 /// Bits 9-19:  Year - 1900    - 11 bits (supports years 1900-2947)
 /// ```
 /// 
 /// ## Working Date Extraction Code
-/// ```rust
+/// ```text
 /// let day = (encoded_date & 0x1F) as u8;           // Extract bits 0-4
 /// let month = ((encoded_date >> 5) & 0x0F) as u8;  // Extract bits 5-8
 /// let year = ((encoded_date >> 9) & 0x7FF) as u16 + 1900; // Extract bits 9-19, add 1900
@@ -42,7 +46,7 @@ pub struct ScidHeader {
 #[derive(Debug, Clone)]
 pub struct GameIndex {
     pub offset: u32,        // Offset in .sg4 file (3 bytes)
-    pub length: u16,        // Length of game data in .sg4 (2 bytes)
+    pub length: u32,        // Length of game data in .sg4 (17 bits)
     pub white_id: u32,      // Player ID in .sn4 (3 bytes)
     pub black_id: u32,      // Player ID in .sn4 (3 bytes)
     pub event_id: u32,      // Event ID in .sn4 (3 bytes)
@@ -56,10 +60,11 @@ pub struct GameIndex {
     pub white_elo: u16,     // White player rating (2 bytes)
     pub black_elo: u16,     // Black player rating (2 bytes)
     pub flags: u16,         // Various flags (2 bytes)
+    pub custom_flags: u8,   // Custom flags 1-6 (low 6 bits of Length_High)
     pub num_half_moves: u16, // Number of half-moves (2 bytes)
     pub stored_line_code: u8, // Stored line code (1 byte)
-    pub final_material: [u8; 2], // Final position material (2 bytes)
-    pub pawn_advancement: [u8; 2], // Pawn advancement info (2 bytes)
+    pub final_material: FinalMaterial, // Final position material (3 bytes), see signature.rs
+    pub home_pawns: HomePawnMoves, // Home pawn moves (9 bytes), see signature.rs
     pub var_count: u8,      // Variation count (1 byte)
     pub comment_count: u8,  // Comment count (1 byte)
     pub nag_count: u8,      // NAG count (1 byte)
@@ -67,6 +72,33 @@ pub struct GameIndex {
     pub reserved: [u8; 5],  // Reserved bytes (5 bytes)
 }
 
+/// Largest game record the index can describe (17-bit length)
+pub const MAX_GAME_LENGTH: u32 = 0x1FFFF;
+
+/// Magic bytes at the start of every .si4 file
+pub const INDEX_MAGIC: [u8; 8] = *b"Scid.si\0";
+
+// GameIndex::flags bits; the first three mirror the .sg4 record flags
+pub const IDX_FLAG_START: u16 = 1 << 0;
+pub const IDX_FLAG_PROMO: u16 = 1 << 1;
+pub const IDX_FLAG_UPROMO: u16 = 1 << 2;
+pub const IDX_FLAG_DELETE: u16 = 1 << 3;
+
+/// Bits of `GameIndex::custom_flags` in use: bit N is custom flag N + 1,
+/// named in the header's `custom_flags`
+pub const CUSTOM_FLAGS_MASK: u8 = 0x3F;
+
+/// Letters SCID shows for the user-settable flags, bits 3 to 15: deleted,
+/// white/black opening, middlegame, endgame, novelty, pawn structure,
+/// tactics, kingside, queenside, brilliancy, blunder and user
+const IDX_FLAG_LETTERS: &[u8; 13] = b"DWBMENPTKQ!?U";
+
+/// Size of the .si4 header in bytes
+pub const INDEX_HEADER_SIZE: usize = 182;
+
+/// Size of one game index record in bytes
+pub const INDEX_ENTRY_SIZE: usize = 47;
+
 pub struct IndexFile {
     header: ScidHeader,
     games: Vec<GameIndex>,
@@ -75,28 +107,51 @@ pub struct IndexFile {
 impl IndexFile {
     /// Load a SCID .si4 index file
     pub fn load<P: AsRef<Path>>(path: P) -> io::Result<Self> {
-        let file = File::open(path)?;
-        let mut reader = BufReader::new(file);
+        let path = path.as_ref();
+        let data = fs::read(path)?;
+        Self::from_bytes(path, &data)
+    }
+    
+    /// Parse the contents of a .si4 file; `path` is only used in errors.
+    ///
+    /// The header's game count is checked against the data length before
+    /// anything is allocated, so a corrupt count can't exhaust memory.
+    pub fn from_bytes<P: AsRef<Path>>(path: P, data: &[u8]) -> io::Result<Self> {
+        let path = path.as_ref();
+        if !data.starts_with(&INDEX_MAGIC) {
+            return Err(ScidError::InvalidMagic { path: path.to_path_buf(), expected: &INDEX_MAGIC }.into());
+        }
+        if data.len() < INDEX_HEADER_SIZE {
+            return Err(ScidError::Truncated {
+                path: path.to_path_buf(),
+                what: "header".to_string(),
+                offset: 0,
+            }.into());
+        }
         
         // Parse header (182 bytes total)
+        let mut reader = data;
         let header = Self::parse_header(&mut reader)?;
         
-        println!("DEBUG: Header parsed, num_games: {}", header.num_games);
+        // Refuse versions whose record layout we don't know rather than
+        // producing garbage game indices
+        check_version(path, header.version)?;
         
-        // Parse game indices
-        let mut games = Vec::with_capacity(header.num_games as usize);
-        for i in 0..header.num_games {
-            let game_index = Self::parse_game_index(&mut reader)?;
-            if i < 5 {
-                println!("DEBUG: Game {}: year={}, month={}, day={}, white_elo={}, black_elo={}, event_id={}, site_id={}, white_id={}, black_id={}", 
-                    i, game_index.year, game_index.month, game_index.day, 
-                    game_index.white_elo, game_index.black_elo, game_index.event_id, 
-                    game_index.site_id, game_index.white_id, game_index.black_id);
-            }
-            games.push(game_index);
+        let num_games = header.num_games as usize;
+        let available = (data.len() - INDEX_HEADER_SIZE) / INDEX_ENTRY_SIZE;
+        if num_games > available {
+            return Err(ScidError::Truncated {
+                path: path.to_path_buf(),
+                what: format!("game index {} of {}", available + 1, num_games),
+                offset: (INDEX_HEADER_SIZE + available * INDEX_ENTRY_SIZE) as u64,
+            }.into());
         }
         
-        println!("DEBUG: Parsed {} games", games.len());
+        // Parse game indices
+        let mut games = Vec::with_capacity(num_games);
+        for _ in 0..num_games {
+            games.push(Self::parse_game_index(&mut reader)?);
+        }
         
         Ok(IndexFile { header, games })
     }
@@ -117,28 +172,17 @@ impl IndexFile {
         &self.games
     }
     
+    /// Callers check the magic first, so a mismatch is a typed `ScidError`
     fn parse_header<R: Read>(reader: &mut R) -> io::Result<ScidHeader> {
         let mut magic = [0u8; 8];
         reader.read_exact(&mut magic)?;
         
-        // Check magic header: "Scid.si\0"
-        let expected_magic = [0x53, 0x63, 0x69, 0x64, 0x2E, 0x73, 0x69, 0x00];
-        if magic != expected_magic {
-            return Err(io::Error::new(
-                io::ErrorKind::InvalidData,
-                "Invalid SCID magic header"
-            ));
-        }
-        
         // Based on SCID source code WriteHeader() function:
         let version = Self::read_u16_le(reader)?; // Header.version (2 bytes)
         let db_type = Self::read_u32_le(reader)?; // Header.baseType (4 bytes)
         let num_games = Self::read_u24_le(reader)?; // Header.numGames (3 bytes)
         let auto_load_game = Self::read_u24_le(reader)?; // Header.autoLoad (3 bytes)
         
-        println!("DEBUG: Parsed header - version: {}, db_type: {}, num_games: {}, auto_load: {}", 
-            version, db_type, num_games, auto_load_game);
-        
         // Read description (SCID_DESC_LENGTH + 1 = 108 bytes)
         let mut database_info = [0u8; 108];
         reader.read_exact(&mut database_info)?;
@@ -149,15 +193,11 @@ impl IndexFile {
             reader.read_exact(flag)?;
         }
         
-        // Use a smaller, more reasonable number for testing
-        let safe_num_games = std::cmp::min(num_games, 1000);
-        println!("DEBUG: Using limited num_games: {} (original: {})", safe_num_games, num_games);
-        
         Ok(ScidHeader {
             magic,
             version,
             db_type,
-            num_games: safe_num_games,
+            num_games,
             auto_load_game,
             database_info,
             custom_flags,
@@ -194,11 +234,11 @@ impl IndexFile {
         let white_elo = Self::read_u16_le(reader)?; // WhiteElo is 2 bytes
         let black_elo = Self::read_u16_le(reader)?; // BlackElo is 2 bytes
 
-        let _final_mat_sig = Self::read_u32_le(reader)?; // FinalMatSig is 4 bytes
+        let final_mat_sig = Self::read_u32_le(reader)?; // FinalMatSig is 3 bytes, then StoredLineCode
         let num_half_moves_low = Self::read_u8(reader)?; // NumHalfMoves low byte
 
         // Read the 9-byte HomePawnData array
-        let mut home_pawn_data = [0u8; 9];
+        let mut home_pawn_data = [0u8; HOME_PAWN_DATA_SIZE];
         reader.read_exact(&mut home_pawn_data)?;
 
         // CRITICAL DATE PARSING - FIXES "52298.152.207" INVALID DATE BUG
@@ -235,6 +275,7 @@ impl IndexFile {
 
         // Calculate actual length from Length_Low and Length_High
         let length = length_low as u32 + ((length_high as u32 & 0x80) << 9);
+        let custom_flags = length_high & CUSTOM_FLAGS_MASK;
 
         // Extract result from VarCounts (top 4 bits)
         let result = (var_counts >> 12) as u8;
@@ -249,12 +290,13 @@ impl IndexFile {
         let black_elo_rating = black_elo & 0x0FFF;
 
         // Check if deleted (bit in flags)
-        let deleted = if flags & 0x08 != 0 { 1 } else { 0 }; // IDX_FLAG_DELETE = bit 3
+        let deleted = if flags & IDX_FLAG_DELETE != 0 { 1 } else { 0 };
 
+        let stored_line_code = (final_mat_sig >> 24) as u8;
+        let final_material = FinalMaterial::from_signature(final_mat_sig & 0xFFFFFF);
+        let home_pawns = HomePawnMoves::from_bytes(&home_pawn_data);
+        
         // For now, use placeholders for some fields
-        let stored_line_code = 0;
-        let final_material = [0u8; 2];
-        let pawn_advancement = [0u8; 2];
         let reserved = [0u8; 5];
 
         // Calculate num_half_moves from the low byte and home_pawn_data[0] high bits
@@ -262,7 +304,7 @@ impl IndexFile {
 
         Ok(GameIndex {
             offset,
-            length: length as u16, // Cast to u16 for now
+            length,
             white_id,
             black_id,
             event_id,
@@ -276,10 +318,11 @@ impl IndexFile {
             white_elo: white_elo_rating,
             black_elo: black_elo_rating,
             flags,
+            custom_flags,
             num_half_moves,
             stored_line_code,
             final_material,
-            pawn_advancement,
+            home_pawns,
             var_count: var_count as u8,
             comment_count: comment_count as u8,
             nag_count: nag_count as u8,
@@ -288,6 +331,99 @@ impl IndexFile {
         })
     }
     
+    /// Write a .si4 header; the inverse of parse_header()
+    pub fn write_header<W: Write>(writer: &mut W, header: &ScidHeader) -> io::Result<()> {
+        writer.write_all(&header.magic)?;
+        writer.write_all(&header.version.to_le_bytes())?;
+        writer.write_all(&header.db_type.to_le_bytes())?;
+        Self::write_u24_le(writer, header.num_games)?;
+        Self::write_u24_le(writer, header.auto_load_game)?;
+        writer.write_all(&header.database_info)?;
+        for flag in &header.custom_flags {
+            writer.write_all(flag)?;
+        }
+        Ok(())
+    }
+    
+    /// Write one 47-byte game index record; the inverse of parse_game_index()
+    pub fn write_game_index<W: Write>(writer: &mut W, game: &GameIndex) -> io::Result<()> {
+        if game.length > MAX_GAME_LENGTH {
+            return Err(io::Error::new(
+                io::ErrorKind::InvalidInput,
+                format!("Game record of {} bytes exceeds the .sg4 limit of {}", game.length, MAX_GAME_LENGTH)
+            ));
+        }
+        let round_id = game.round_id as u32;
+        let id_limits = [
+            ("white player", game.white_id, 0xFFFFF),
+            ("black player", game.black_id, 0xFFFFF),
+            ("event", game.event_id, 0x7FFFF),
+            ("site", game.site_id, 0x7FFFF),
+            ("round", round_id, 0x3FFFF),
+        ];
+        for (what, id, limit) in id_limits {
+            if id > limit {
+                return Err(io::Error::new(
+                    io::ErrorKind::InvalidInput,
+                    format!("{} ID {} does not fit in an index record", what, id)
+                ));
+            }
+        }
+        
+        writer.write_all(&game.offset.to_le_bytes())?;
+        writer.write_all(&(game.length as u16).to_le_bytes())?;
+        writer.write_all(&[((game.length >> 16) as u8) << 7 | (game.custom_flags & CUSTOM_FLAGS_MASK)])?;
+        writer.write_all(&game.flags.to_le_bytes())?;
+        
+        writer.write_all(&[(((game.white_id >> 16) as u8) << 4) | (game.black_id >> 16) as u8])?;
+        writer.write_all(&(game.white_id as u16).to_le_bytes())?;
+        writer.write_all(&(game.black_id as u16).to_le_bytes())?;
+        
+        let event_site_rnd_high = (((game.event_id >> 16) as u8) << 5)
+            | (((game.site_id >> 16) as u8) << 2)
+            | (round_id >> 16) as u8;
+        writer.write_all(&[event_site_rnd_high])?;
+        writer.write_all(&(game.event_id as u16).to_le_bytes())?;
+        writer.write_all(&(game.site_id as u16).to_le_bytes())?;
+        writer.write_all(&(round_id as u16).to_le_bytes())?;
+        
+        let var_counts = ((game.result as u16 & 15) << 12)
+            | ((game.nag_count.min(15) as u16) << 8)
+            | ((game.comment_count.min(15) as u16) << 4)
+            | game.var_count.min(15) as u16;
+        writer.write_all(&var_counts.to_le_bytes())?;
+        writer.write_all(&game.eco.to_le_bytes())?;
+        
+        // Same packed layout parse_game_index() reads: day, month, year
+        let dates = ((game.year as u32 & 0x7FF) << 9) | ((game.month as u32 & 15) << 5) | (game.day as u32 & 31);
+        writer.write_all(&dates.to_le_bytes())?;
+        
+        writer.write_all(&(game.white_elo & 0x0FFF).to_le_bytes())?;
+        writer.write_all(&(game.black_elo & 0x0FFF).to_le_bytes())?;
+        
+        let final_mat_sig = game.final_material.signature() | (game.stored_line_code as u32) << 24;
+        writer.write_all(&final_mat_sig.to_le_bytes())?;
+        
+        // The top two bits of the home pawn count byte hold the ply count's
+        let num_half_moves = game.num_half_moves.min(0x3FF);
+        writer.write_all(&[num_half_moves as u8])?;
+        let mut home_pawn_data = game.home_pawns.to_bytes();
+        home_pawn_data[0] = (home_pawn_data[0] & 0x3F) | ((num_half_moves >> 8) as u8) << 6;
+        writer.write_all(&home_pawn_data)?;
+        
+        Ok(())
+    }
+    
+    fn write_u24_le<W: Write>(writer: &mut W, value: u32) -> io::Result<()> {
+        if value > 0xFFFFFF {
+            return Err(io::Error::new(
+                io::ErrorKind::InvalidInput,
+                format!("Value {} does not fit in 3 bytes", value)
+            ));
+        }
+        writer.write_all(&value.to_le_bytes()[..3])
+    }
+    
     // Helper functions for reading different data types
     fn read_u8<R: Read>(reader: &mut R) -> io::Result<u8> {
         let mut buf = [0u8; 1];
@@ -314,6 +450,45 @@ impl IndexFile {
     }
 }
 
+impl ScidHeader {
+    /// Header for a new SCID 4 database with the given description
+    pub fn new(num_games: u32, description: &str) -> Self {
+        // Description is null-terminated within its 108 bytes
+        let mut database_info = [0u8; 108];
+        let mut len = description.len().min(database_info.len() - 1);
+        while !description.is_char_boundary(len) {
+            len -= 1;
+        }
+        database_info[..len].copy_from_slice(&description.as_bytes()[..len]);
+        
+        ScidHeader {
+            magic: INDEX_MAGIC,
+            version: SCID_VERSION_4,
+            db_type: 0,
+            num_games,
+            auto_load_game: 0,
+            database_info,
+            custom_flags: [[0u8; 9]; 6],
+        }
+    }
+    
+    /// The database description, up to its null terminator
+    pub fn description(&self) -> String {
+        header_string(&self.database_info)
+    }
+    
+    /// Names given to the six custom flags, "" where a flag has none
+    pub fn custom_flag_names(&self) -> [String; 6] {
+        self.custom_flags.each_ref().map(|name| header_string(name))
+    }
+}
+
+/// A null-terminated string from a fixed-size header field
+fn header_string(bytes: &[u8]) -> String {
+    let len = bytes.iter().position(|&b| b == 0).unwrap_or(bytes.len());
+    String::from_utf8_lossy(&bytes[..len]).into_owned()
+}
+
 impl GameIndex {
     /// Get game result as a human-readable string
     pub fn result_string(&self) -> &'static str {
@@ -331,16 +506,49 @@ impl GameIndex {
         self.deleted != 0
     }
     
+    /// The user-settable flags as SCID displays them, e.g. "WT" for a game
+    /// marked as a white opening with tactics
+    pub fn flag_string(&self) -> String {
+        IDX_FLAG_LETTERS.iter()
+            .enumerate()
+            .filter(|(bit, _)| self.flags & (IDX_FLAG_DELETE << bit) != 0)
+            .map(|(_, &letter)| letter as char)
+            .collect()
+    }
+    
+    /// The flag bits for a string of flag letters as `flag_string` writes
+    /// them, e.g. "WT"; None if any character isn't a flag letter
+    pub fn flag_mask(letters: &str) -> Option<u16> {
+        letters.chars().try_fold(0, |mask, letter| {
+            let bit = IDX_FLAG_LETTERS.iter().position(|&flag| flag as char == letter.to_ascii_uppercase())?;
+            Some(mask | IDX_FLAG_DELETE << bit)
+        })
+    }
+    
     /// Format the game date as YYYY.MM.DD
     pub fn date_string(&self) -> String {
-        // Handle invalid dates more gracefully
+        // Handle invalid dates more gracefully; unknown parts are "??" as in PGN
         if self.year == 0 || self.year > 2100 {
             "????.??.??".to_string()
         } else {
-            let safe_month = if self.month == 0 || self.month > 12 { 1 } else { self.month };
-            let safe_day = if self.day == 0 || self.day > 31 { 1 } else { self.day };
+            let month = if self.month == 0 || self.month > 12 { "??".to_string() } else { format!("{:02}", self.month) };
+            let day = if self.day == 0 || self.day > 31 { "??".to_string() } else { format!("{:02}", self.day) };
             
-            format!("{:04}.{:02}.{:02}", self.year, safe_month, safe_day)
+            format!("{:04}.{}.{}", self.year, month, day)
+        }
+    }
+    
+    /// Format the game date as an ISO 8601 date, shortened to "YYYY-MM" or
+    /// "YYYY" when the day or month is unknown; None if the year is unknown
+    pub fn iso_date(&self) -> Option<String> {
+        if self.year == 0 || self.year > 2100 {
+            None
+        } else if self.month == 0 || self.month > 12 {
+            Some(format!("{:04}", self.year))
+        } else if self.day == 0 || self.day > 31 {
+            Some(format!("{:04}-{:02}", self.year, self.month))
+        } else {
+            Some(format!("{:04}-{:02}-{:02}", self.year, self.month, self.day))
         }
     }
 }
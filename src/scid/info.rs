@@ -0,0 +1,127 @@
+//! Database summary
+//!
+//! `DatabaseInfo` decodes the .si4 header fields SCID keeps beside the
+//! games (format version, database type, autoload game, description and
+//! custom flag names) and adds totals from the index and namebase.
+//!
+//! ## Fragmentation
+//! Deleting or replacing a game leaves its old record in the .sg4 file.
+//! Bytes no live game record covers are counted as unused; `compact`
+//! reclaims them.
+
+use std::io::{self, Write};
+
+use serde::Serialize;
+
+use super::version::version_name;
+use super::ScidDatabase;
+
+/// Size of the .sg4 file and how much of it live games use
+#[derive(Debug, Clone, Copy, PartialEq, Serialize)]
+pub struct GameFileUsage {
+    pub bytes: u64,
+    /// Bytes of records of games not marked deleted
+    pub used_bytes: u64,
+    pub unused_bytes: u64,
+    /// Unused bytes as a percentage of the file
+    pub fragmentation: f64,
+}
+
+/// Header fields and totals for one database
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub struct DatabaseInfo {
+    pub version: u16,
+    /// E.g. "SCID 4.x", if the version is a known one
+    pub version_name: Option<&'static str>,
+    pub db_type: u32,
+    /// Game SCID opens with the database, counting from 1
+    pub auto_load_game: Option<u32>,
+    pub description: String,
+    /// Names of custom flags 1 to 6, "" where a flag has none
+    pub custom_flags: Vec<String>,
+    pub games: usize,
+    pub deleted_games: usize,
+    pub players: usize,
+    pub events: usize,
+    pub sites: usize,
+    pub rounds: usize,
+    /// Earliest and latest game dates, as YYYY.MM.DD with "??" for unknown parts
+    pub first_date: Option<String>,
+    pub last_date: Option<String>,
+    /// None for a database loaded with `load_index_only`
+    pub game_file: Option<GameFileUsage>,
+}
+
+impl DatabaseInfo {
+    pub fn new(database: &ScidDatabase) -> Self {
+        let header = database.header();
+        let names = database.names();
+        let games = database.game_indices();
+
+        // Games with no year have no place in the range
+        let dated = games.iter().filter(|game_index| game_index.year != 0);
+        let first = dated.clone().min_by_key(|game_index| (game_index.year, game_index.month, game_index.day));
+        let last = dated.max_by_key(|game_index| (game_index.year, game_index.month, game_index.day));
+
+        let game_file = database.game_file_len().map(|bytes| {
+            let used_bytes: u64 = games.iter()
+                .filter(|game_index| !game_index.is_deleted())
+                .map(|game_index| game_index.length as u64)
+                .sum();
+            let unused_bytes = bytes.saturating_sub(used_bytes);
+            let fragmentation = if bytes == 0 { 0.0 } else { 100.0 * unused_bytes as f64 / bytes as f64 };
+            GameFileUsage { bytes, used_bytes, unused_bytes, fragmentation }
+        });
+
+        DatabaseInfo {
+            version: header.version,
+            version_name: version_name(header.version),
+            db_type: header.db_type,
+            auto_load_game: (header.auto_load_game != 0).then_some(header.auto_load_game),
+            description: header.description(),
+            custom_flags: header.custom_flag_names().to_vec(),
+            games: games.len(),
+            deleted_games: games.iter().filter(|game_index| game_index.is_deleted()).count(),
+            players: names.players.len(),
+            events: names.events.len(),
+            sites: names.sites.len(),
+            rounds: names.rounds.len(),
+            first_date: first.map(|game_index| game_index.date_string()),
+            last_date: last.map(|game_index| game_index.date_string()),
+            game_file,
+        }
+    }
+
+    /// Write one labelled line per field
+    pub fn write_text<W: Write>(&self, writer: &mut W) -> io::Result<()> {
+        writeln!(writer, "Description:   {}", self.description)?;
+        match self.version_name {
+            Some(name) => writeln!(writer, "Version:       {} ({})", self.version, name)?,
+            None => writeln!(writer, "Version:       {}", self.version)?,
+        }
+        writeln!(writer, "Type:          {}", self.db_type)?;
+        match self.auto_load_game {
+            Some(game) => writeln!(writer, "Autoload game: {}", game)?,
+            None => writeln!(writer, "Autoload game: none")?,
+        }
+        let flags: Vec<String> = self.custom_flags.iter().enumerate()
+            .filter(|(_, name)| !name.is_empty())
+            .map(|(i, name)| format!("{} {}", i + 1, name))
+            .collect();
+        writeln!(writer, "Custom flags:  {}", if flags.is_empty() { "none".to_string() } else { flags.join(", ") })?;
+        writeln!(writer, "Games:         {} ({} deleted)", self.games, self.deleted_games)?;
+        writeln!(writer, "Players:       {}", self.players)?;
+        writeln!(writer, "Events:        {}", self.events)?;
+        writeln!(writer, "Sites:         {}", self.sites)?;
+        writeln!(writer, "Rounds:        {}", self.rounds)?;
+        match (&self.first_date, &self.last_date) {
+            (Some(first), Some(last)) => writeln!(writer, "Dates:         {} to {}", first, last)?,
+            _ => writeln!(writer, "Dates:         none")?,
+        }
+        match &self.game_file {
+            Some(usage) => writeln!(writer, "Game file:     {} bytes, {} unused ({:.1}% fragmentation)", usage.bytes,
+                                    usage.unused_bytes, usage.fragmentation),
+            None => writeln!(writer, "Game file:     not loaded"),
+        }
+    }
+}
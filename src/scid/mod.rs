@@ -4,6 +4,21 @@ pub mod names;
 pub mod events;
 pub mod games;
 pub mod moves;
+pub mod eco;
+pub mod writer;
+pub mod signature;
+pub mod spelling;
+pub mod info;
+pub mod compact;
+pub mod verify;
+pub mod error;
+pub mod version;
 
+pub use compact::{CompactReport, Compactor, Repair};
 pub use database::ScidDatabase;
 pub use index::{ScidHeader, GameIndex};
+pub use info::{DatabaseInfo, GameFileUsage};
+pub use error::ScidError;
+pub use spelling::{PlayerInfo, SpellingFile};
+pub use verify::{Issue, IssueKind, Verifier, VerifyReport};
+pub use writer::ScidWriter;
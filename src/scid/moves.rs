@@ -1,100 +1,624 @@
-/// SCID move encoding and chess position handling
-/// The SCID format uses a very compact move encoding
-
-#[derive(Debug, Clone)]
-pub struct Move {
-    pub from_square: u8,
-    pub to_square: u8,
-    pub piece: Piece,
-    pub captured_piece: Option<Piece>,
-    pub promotion: Option<Piece>,
-    pub is_castling: bool,
-    pub is_en_passant: bool,
-}
-
-#[derive(Debug, Clone, Copy, PartialEq)]
-pub enum Piece {
-    Pawn,
-    Knight,
-    Bishop,
-    Rook,
-    Queen,
-    King,
-}
-
-#[derive(Debug, Clone)]
-pub struct Position {
-    // Simplified chess position representation
-    // For a full implementation, this would need to track:
-    // - Piece positions
-    // - Castling rights
-    // - En passant square
-    // - Half-move clock
-    // - Full-move number
-    pub to_move: Color,
-    pub half_move_clock: u16,
-    pub full_move_number: u16,
-}
-
-#[derive(Debug, Clone, Copy, PartialEq)]
-pub enum Color {
-    White,
-    Black,
-}
-
-impl Position {
-    pub fn starting_position() -> Self {
-        Position {
-            to_move: Color::White,
-            half_move_clock: 0,
-            full_move_number: 1,
-        }
-    }
-}
-
-impl Move {
-    /// Convert move to algebraic notation
-    /// This is a simplified implementation - a full implementation would need
-    /// the current position to determine proper algebraic notation
-    pub fn to_algebraic(&self) -> String {
-        // For now, return a simplified notation
-        // A full implementation would require position analysis
-        format!("{}{}", 
-               square_to_algebraic(self.from_square),
-               square_to_algebraic(self.to_square))
-    }
-}
-
-impl Piece {
-    pub fn to_char(&self) -> char {
-        match self {
-            Piece::Pawn => 'P',
-            Piece::Knight => 'N',
-            Piece::Bishop => 'B',
-            Piece::Rook => 'R',
-            Piece::Queen => 'Q',
-            Piece::King => 'K',
-        }
-    }
-}
-
-/// Convert square index to algebraic notation (e.g., 0 -> "a1")
-pub fn square_to_algebraic(square: u8) -> String {
-    let file = (square % 8) as char;
-    let rank = (square / 8) + 1;
-    format!("{}{}", (b'a' + file as u8) as char, rank)
-}
-
-/// Parse SCID encoded moves from raw game data
-/// This is a complex process as SCID uses a very compact encoding
-pub fn parse_scid_moves(_data: &[u8]) -> Vec<Move> {
-    // This is a placeholder implementation
-    // The actual SCID move encoding is very complex and requires:
-    // 1. Maintaining a piece list for each position
-    // 2. Decoding 4-bit piece indices and 4-bit direction codes
-    // 3. Handling special cases for queen moves, promotions, etc.
-    
-    // For now, return an empty vector
-    // This would need to be implemented based on detailed study of SCID source code
-    Vec::new()
+//! SCID .sg4 game record encoding and decoding
+//!
+//! ## Record Layout
+//! ```text
+//! Extra tags:  [name_len][name][value_len][value] ... terminated by 0
+//!              name_len 241-250 names a common tag instead (see COMMON_TAGS)
+//!              name_len 255 is a 3-byte packed EventDate
+//! Flags:       1 byte - bit 0 non-standard start, bit 1 promotions,
+//!              bit 2 under-promotions
+//! Start FEN:   null-terminated, only if flag bit 0 is set
+//! Moves:       one byte per move plus markers, ending with ENCODE_END_GAME
+//! Comments:    null-terminated strings, one per ENCODE_COMMENT marker,
+//!              in the order the markers appear
+//! ```
+//!
+//! ## Move Bytes
+//! Each move byte is `(piece_index << 4) | move_value`. Each side keeps a
+//! list of up to 16 pieces with the king always at index 0, set up as SCID
+//! sets up its board:
+//! - standard start: the king, the other back rank pieces from the a-file
+//!   to the h-file, then the pawns from the a-file to the h-file
+//! - FEN start: pieces in FEN order (rank 8 down to rank 1, a-file to
+//!   h-file); the king takes index 0 and the piece already there moves to
+//!   the end of the list
+//!
+//! When a piece is captured the last entry of the list takes its slot.
+//!
+//! The 4-bit move value depends on the piece type:
+//! - King: 1-8 direction, 9 = O-O-O, 10 = O-O, 0 = null move
+//! - Queen/Rook: destination file (same rank) or 8 + destination rank
+//!   (same file); a queen's diagonal move writes its own file and then a
+//!   second byte holding the destination square + 64
+//! - Bishop: destination file, +8 for the a8-h1 diagonal direction
+//! - Knight: 1-8 jump
+//! - Pawn: 0/1/2 a step of 7, 8 or 9 squares towards the opponent, so
+//!   0 captures towards the a-file for white but towards the h-file for
+//!   black; 3-14 the same three steps promoting to Q, R, B, N; 15 = double
+//!   push
+//!
+//! King values 11-15 are never moves, so they mark NAGs, comments,
+//! variation start/end and the end of the game.
+
+use std::io;
+
+use crate::chess::position::{make_square, square_file, square_rank, Square};
+use crate::chess::{Color, Game, Line, Move, MoveNode, Piece, Position, MAX_VARIATION_DEPTH};
+use super::error::ScidError;
+
+pub const ENCODE_NAG: u8 = 11;
+pub const ENCODE_COMMENT: u8 = 12;
+pub const ENCODE_START_MARKER: u8 = 13;
+pub const ENCODE_END_MARKER: u8 = 14;
+pub const ENCODE_END_GAME: u8 = 15;
+
+const FLAG_NON_STANDARD_START: u8 = 1;
+const FLAG_PROMOTIONS: u8 = 2;
+const FLAG_UNDER_PROMOTIONS: u8 = 4;
+
+/// Longest tag name that is stored as plain text
+const MAX_TAG_LEN: usize = 240;
+
+/// Tag names stored as a single code byte (241 + index)
+const COMMON_TAGS: [&str; 10] = [
+    "WhiteCountry", "BlackCountry", "Annotator", "PlyCount", "EventDate",
+    "Opening", "Variation", "Setup", "Source", "SetUp",
+];
+
+const EVENT_DATE_CODE: u8 = 255;
+
+const KING_DIRECTIONS: [i8; 8] = [-9, -8, -7, -1, 1, 7, 8, 9];
+const PAWN_STEPS: [i8; 3] = [7, 8, 9];
+const KNIGHT_JUMPS: [i8; 8] = [-17, -15, -10, -6, 6, 10, 15, 17];
+
+/// A decoded .sg4 record: the extra tags, the start position and the moves
+#[derive(Debug, Clone, Default)]
+pub struct GameRecord {
+    pub tags: Vec<(String, String)>,
+    /// FEN of the start position when the game doesn't start normally
+    pub start_fen: Option<String>,
+    pub moves: Line,
+}
+
+/// Position plus SCID's per-side piece lists
+#[derive(Clone)]
+struct ScidBoard {
+    pos: Position,
+    lists: [Vec<Square>; 2],
+}
+
+impl ScidBoard {
+    /// The standard starting position, with SCID's piece order
+    fn standard() -> Self {
+        let mut lists = [Vec::with_capacity(16), Vec::with_capacity(16)];
+        for (color, back_rank, pawn_rank) in [(Color::White, 0, 1), (Color::Black, 7, 6)] {
+            let list = &mut lists[color.index()];
+            list.push(make_square(4, back_rank));
+            list.extend((0..8).filter(|&file| file != 4).map(|file| make_square(file, back_rank)));
+            list.extend((0..8).map(|file| make_square(file, pawn_rank)));
+        }
+        ScidBoard { pos: Position::starting_position(), lists }
+    }
+
+    /// A position set up from FEN, with pieces listed in the order SCID
+    /// adds them while reading the FEN
+    fn from_fen(pos: Position) -> Self {
+        let mut lists = [Vec::with_capacity(16), Vec::with_capacity(16)];
+        for rank in (0..8).rev() {
+            for file in 0..8 {
+                let square = make_square(file, rank);
+                if let Some((color, piece)) = pos.piece_at(square) {
+                    let list = &mut lists[color.index()];
+                    if piece == Piece::King && !list.is_empty() {
+                        let first = list[0];
+                        list.push(first);
+                        list[0] = square;
+                    } else {
+                        list.push(square);
+                    }
+                }
+            }
+        }
+        ScidBoard { pos, lists }
+    }
+
+    fn piece_index(&self, color: Color, square: Square) -> Option<usize> {
+        self.lists[color.index()].iter().position(|&s| s == square)
+    }
+
+    /// Play a move, keeping the piece lists in step with the board
+    fn apply(&mut self, mv: &Move) {
+        let us = self.pos.to_move;
+        let them = us.opposite();
+
+        if !mv.is_null() {
+            if mv.captured_piece.is_some() {
+                let captured_square = if mv.is_en_passant {
+                    make_square(square_file(mv.to_square), square_rank(mv.from_square))
+                } else {
+                    mv.to_square
+                };
+                if let Some(index) = self.piece_index(them, captured_square) {
+                    self.lists[them.index()].swap_remove(index);
+                }
+            }
+            if mv.is_castling {
+                let rank = square_rank(mv.from_square);
+                let (rook_from, rook_to) = if mv.to_square > mv.from_square {
+                    (make_square(7, rank), make_square(5, rank))
+                } else {
+                    (make_square(0, rank), make_square(3, rank))
+                };
+                if let Some(index) = self.piece_index(us, rook_from) {
+                    self.lists[us.index()][index] = rook_to;
+                }
+            }
+            if let Some(index) = self.piece_index(us, mv.from_square) {
+                self.lists[us.index()][index] = mv.to_square;
+            }
+        }
+
+        self.pos.make_move(mv);
+    }
+
+    /// Encode one move as one or two bytes
+    fn encode_move(&self, mv: &Move, out: &mut Vec<u8>) -> io::Result<()> {
+        let us = self.pos.to_move;
+        let index = self.piece_index(us, mv.from_square)
+            .ok_or_else(|| invalid_data(format!("no piece on {} to move", mv.to_uci())))?;
+        if index > 15 {
+            return Err(invalid_data("more than 16 pieces of one colour".to_string()));
+        }
+        let from = mv.from_square as i8;
+        let to = mv.to_square as i8;
+        let diff = to - from;
+        let (from_file, to_file) = (square_file(mv.from_square), square_file(mv.to_square));
+        let (from_rank, to_rank) = (square_rank(mv.from_square), square_rank(mv.to_square));
+
+        let value = match mv.piece {
+            _ if mv.is_null() => 0,
+            Piece::King => {
+                if mv.is_castling {
+                    if diff > 0 { 10 } else { 9 }
+                } else {
+                    let direction = KING_DIRECTIONS.iter().position(|&d| d == diff)
+                        .ok_or_else(|| invalid_data(format!("bad king move {}", mv.to_uci())))?;
+                    direction as u8 + 1
+                }
+            }
+            Piece::Queen if from_rank != to_rank && from_file != to_file => {
+                // Diagonal queen move: an impossible rook-style value, then the square
+                out.push(((index as u8) << 4) | from_file);
+                out.push(mv.to_square + 64);
+                return Ok(());
+            }
+            Piece::Queen | Piece::Rook => {
+                if from_rank == to_rank { to_file } else { 8 + to_rank }
+            }
+            Piece::Bishop => {
+                if (to_rank as i8 - from_rank as i8) == (to_file as i8 - from_file as i8) {
+                    to_file
+                } else {
+                    8 + to_file
+                }
+            }
+            Piece::Knight => {
+                let jump = KNIGHT_JUMPS.iter().position(|&d| d == diff)
+                    .ok_or_else(|| invalid_data(format!("bad knight move {}", mv.to_uci())))?;
+                jump as u8 + 1
+            }
+            Piece::Pawn => {
+                let step = if us == Color::White { diff } else { -diff };
+                if step == 16 {
+                    15
+                } else {
+                    let direction = PAWN_STEPS.iter().position(|&d| d == step)
+                        .ok_or_else(|| invalid_data(format!("bad pawn move {}", mv.to_uci())))? as u8;
+                    match mv.promotion {
+                        None => direction,
+                        Some(Piece::Queen) => 3 + direction,
+                        Some(Piece::Rook) => 6 + direction,
+                        Some(Piece::Bishop) => 9 + direction,
+                        Some(Piece::Knight) => 12 + direction,
+                        Some(piece) => return Err(invalid_data(format!("cannot promote to {:?}", piece))),
+                    }
+                }
+            }
+        };
+
+        out.push(((index as u8) << 4) | value);
+        Ok(())
+    }
+
+    /// Decode the move in `byte` (reading a second byte from `next` for
+    /// diagonal queen moves)
+    fn decode_move(&self, byte: u8, next: &mut dyn FnMut() -> io::Result<u8>) -> io::Result<Move> {
+        let us = self.pos.to_move;
+        let index = (byte >> 4) as usize;
+        let value = byte & 15;
+        let from = *self.lists[us.index()].get(index)
+            .ok_or_else(|| invalid_data(format!("piece index {} out of range", index)))?;
+        let (_, piece) = self.pos.piece_at(from)
+            .ok_or_else(|| invalid_data("piece list out of step with board".to_string()))?;
+        let from_file = square_file(from);
+        let from_rank = square_rank(from);
+
+        let mut promotion = None;
+        let to: i16 = match piece {
+            Piece::King => match value {
+                0 => return Ok(Move::null(from)),
+                1..=8 => from as i16 + KING_DIRECTIONS[value as usize - 1] as i16,
+                9 => from as i16 - 2,
+                10 => from as i16 + 2,
+                _ => return Err(invalid_data(format!("bad king move value {}", value))),
+            },
+            Piece::Queen if value == from_file => (next()? as i16) - 64,
+            Piece::Queen | Piece::Rook => {
+                if value < 8 {
+                    make_square(value, from_rank) as i16
+                } else {
+                    make_square(from_file, value - 8) as i16
+                }
+            }
+            Piece::Bishop => {
+                let file = (value & 7) as i16;
+                let file_delta = file - from_file as i16;
+                let rank_delta = if value < 8 { file_delta } else { -file_delta };
+                (from_rank as i16 + rank_delta) * 8 + file
+            }
+            Piece::Knight => {
+                if value == 0 || value > 8 {
+                    return Err(invalid_data(format!("bad knight move value {}", value)));
+                }
+                from as i16 + KNIGHT_JUMPS[value as usize - 1] as i16
+            }
+            Piece::Pawn => {
+                promotion = match value {
+                    3..=5 => Some(Piece::Queen),
+                    6..=8 => Some(Piece::Rook),
+                    9..=11 => Some(Piece::Bishop),
+                    12..=14 => Some(Piece::Knight),
+                    _ => None,
+                };
+                // Steps are towards the opponent: up the board for white, down for black
+                let step = if value == 15 { 16 } else { PAWN_STEPS[value as usize % 3] as i16 };
+                if us == Color::White { from as i16 + step } else { from as i16 - step }
+            }
+        };
+
+        // A move that wraps around the board edge can only come from corrupt data
+        let max_file_step = match piece {
+            Piece::King if value >= 9 => 2,
+            Piece::King | Piece::Pawn => 1,
+            Piece::Knight => 2,
+            _ => 7,
+        };
+        if !(0..64).contains(&to)
+            || (square_file(to as Square) as i8 - from_file as i8).abs() > max_file_step {
+            return Err(invalid_data(format!("move from square {} leaves the board", from)));
+        }
+        let mv = self.pos.build_move(from, to as Square, promotion)
+            .ok_or_else(|| invalid_data("move from an empty square".to_string()))?;
+        
+        // Cheap sanity checks so corrupt data can't put the board into a
+        // state later code doesn't expect (full legality isn't checked)
+        match self.pos.piece_at(mv.to_square) {
+            Some((color, _)) if color == us => {
+                return Err(invalid_data(format!("move {} lands on its own piece", mv.to_uci())));
+            }
+            Some((_, Piece::King)) => {
+                return Err(invalid_data(format!("move {} captures a king", mv.to_uci())));
+            }
+            _ => {}
+        }
+        if piece == Piece::Pawn && !mv.is_en_passant
+            && (from_file == square_file(mv.to_square)) == mv.captured_piece.is_some() {
+            return Err(invalid_data(format!("impossible pawn move {}", mv.to_uci())));
+        }
+        Ok(mv)
+    }
+}
+
+fn invalid_data(message: String) -> io::Error {
+    io::Error::new(io::ErrorKind::InvalidData, message)
+}
+
+/// Encode the extra tags, start position and moves of a game as an .sg4 record.
+/// Tags that live in the index (see `is_index_tag`) are skipped.
+pub fn encode_game(game: &Game) -> io::Result<Vec<u8>> {
+    let mut out = Vec::new();
+
+    for (name, value) in &game.tags {
+        if is_index_tag(name) {
+            continue;
+        }
+        match COMMON_TAGS.iter().position(|t| t == name) {
+            Some(code) => out.push(241 + code as u8),
+            None => {
+                let name = truncate_utf8(name, MAX_TAG_LEN);
+                if name.is_empty() {
+                    continue;
+                }
+                out.push(name.len() as u8);
+                out.extend_from_slice(name.as_bytes());
+            }
+        }
+        let value = truncate_utf8(value, 255);
+        out.push(value.len() as u8);
+        out.extend_from_slice(value.as_bytes());
+    }
+    out.push(0);
+
+    let start = game.start_position()
+        .map_err(|e| invalid_data(e.to_string()))?;
+    let non_standard = game.tag("FEN").is_some();
+
+    let mut flags = if non_standard { FLAG_NON_STANDARD_START } else { 0 };
+    game.moves.for_each_node(&mut |node| {
+        match node.mv.promotion {
+            Some(Piece::Queen) => flags |= FLAG_PROMOTIONS,
+            Some(_) => flags |= FLAG_PROMOTIONS | FLAG_UNDER_PROMOTIONS,
+            None => {}
+        }
+    });
+    out.push(flags);
+
+    let board = if non_standard {
+        out.extend_from_slice(start.to_fen().as_bytes());
+        out.push(0);
+        ScidBoard::from_fen(start)
+    } else {
+        ScidBoard::standard()
+    };
+
+    let mut comments = Vec::new();
+    encode_line(&game.moves, board, &mut out, &mut comments)?;
+    out.push(ENCODE_END_GAME);
+
+    for comment in comments {
+        out.extend(comment.bytes().filter(|&b| b != 0));
+        out.push(0);
+    }
+
+    Ok(out)
+}
+
+/// Encode a line. Comments are collected in marker order so they can be
+/// appended after the move stream.
+fn encode_line<'a>(line: &'a Line, mut board: ScidBoard, out: &mut Vec<u8>,
+                   comments: &mut Vec<&'a str>) -> io::Result<()> {
+    if let Some(comment) = non_empty(&line.comment) {
+        out.push(ENCODE_COMMENT);
+        comments.push(comment);
+    }
+
+    for node in &line.moves {
+        board.encode_move(&node.mv, out)?;
+        let before = board.clone();
+        board.apply(&node.mv);
+
+        for &nag in &node.nags {
+            out.push(ENCODE_NAG);
+            out.push(nag);
+        }
+        if let Some(comment) = non_empty(&node.comment) {
+            out.push(ENCODE_COMMENT);
+            comments.push(comment);
+        }
+        for variation in &node.variations {
+            out.push(ENCODE_START_MARKER);
+            encode_line(variation, before.clone(), out, comments)?;
+            out.push(ENCODE_END_MARKER);
+        }
+    }
+
+    Ok(())
+}
+
+fn non_empty(comment: &Option<String>) -> Option<&str> {
+    comment.as_deref().filter(|c| !c.trim().is_empty())
+}
+
+/// Decode an .sg4 game record. Any malformed or truncated input gives a
+/// `ScidError::InvalidGameRecord` (wrapped in an `io::Error`), never a panic.
+pub fn decode_game(data: &[u8]) -> io::Result<GameRecord> {
+    let mut reader = ByteReader { data, pos: 0 };
+    decode_record(&mut reader).map_err(|e| {
+        ScidError::InvalidGameRecord { position: reader.pos, reason: e.to_string() }.into()
+    })
+}
+
+fn decode_record(reader: &mut ByteReader) -> io::Result<GameRecord> {
+
+    let mut tags = Vec::new();
+    loop {
+        let code = reader.byte()?;
+        if code == 0 {
+            break;
+        }
+        if code == EVENT_DATE_CODE {
+            // Packed date in three bytes: year << 9 | month << 5 | day
+            let packed = u32::from_be_bytes([0, reader.byte()?, reader.byte()?, reader.byte()?]);
+            tags.push(("EventDate".to_string(), packed_date_string(packed)));
+            continue;
+        }
+        let name = if code as usize > MAX_TAG_LEN {
+            COMMON_TAGS.get(code as usize - MAX_TAG_LEN - 1)
+                .map(|t| t.to_string())
+                .ok_or_else(|| invalid_data(format!("unknown common tag code {}", code)))?
+        } else {
+            reader.string(code as usize)?
+        };
+        let value_len = reader.byte()? as usize;
+        let value = reader.string(value_len)?;
+        tags.push((name, value));
+    }
+
+    let flags = reader.byte()?;
+    let mut start_fen = None;
+    let board = if flags & FLAG_NON_STANDARD_START != 0 {
+        let fen = reader.null_terminated()?;
+        let pos = Position::from_fen(&fen).map_err(|e| invalid_data(e.to_string()))?;
+        start_fen = Some(fen);
+        ScidBoard::from_fen(pos)
+    } else {
+        ScidBoard::standard()
+    };
+
+    let mut comment_count = 0;
+    let mut moves = decode_line(reader, board, 0, &mut comment_count)?;
+
+    let mut comments = Vec::with_capacity(comment_count);
+    for _ in 0..comment_count {
+        comments.push(reader.null_terminated()?);
+    }
+    fill_comments(&mut moves, &mut comments.into_iter());
+
+    Ok(GameRecord { tags, start_fen, moves })
+}
+
+/// Decode moves until the end of a variation (depth > 0) or of the game.
+/// Comment markers leave a placeholder of one NUL per marker that
+/// `fill_comments` replaces once the comment strings after the move stream
+/// have been read.
+fn decode_line(reader: &mut ByteReader, mut board: ScidBoard, depth: usize,
+               comment_count: &mut usize) -> io::Result<Line> {
+    if depth > MAX_VARIATION_DEPTH {
+        return Err(invalid_data(format!("variations nested more than {} deep", MAX_VARIATION_DEPTH)));
+    }
+    let nested = depth > 0;
+    let mut line = Line::default();
+    let mut before: Option<ScidBoard> = None;
+
+    loop {
+        let byte = reader.byte()?;
+        match byte {
+            ENCODE_NAG => {
+                let nag = reader.byte()?;
+                let node = line.moves.last_mut()
+                    .ok_or_else(|| invalid_data("NAG before the first move".to_string()))?;
+                node.nags.push(nag);
+            }
+            ENCODE_COMMENT => {
+                *comment_count += 1;
+                let target = match line.moves.last_mut() {
+                    Some(node) => &mut node.comment,
+                    None => &mut line.comment,
+                };
+                target.get_or_insert_with(String::new).push('\0');
+            }
+            ENCODE_START_MARKER => {
+                let start = before.clone()
+                    .ok_or_else(|| invalid_data("variation before the first move".to_string()))?;
+                let variation = decode_line(reader, start, depth + 1, comment_count)?;
+                if let Some(node) = line.moves.last_mut() {
+                    node.variations.push(variation);
+                }
+            }
+            ENCODE_END_MARKER => {
+                if nested {
+                    return Ok(line);
+                }
+                return Err(invalid_data("variation end outside a variation".to_string()));
+            }
+            ENCODE_END_GAME => {
+                if nested {
+                    return Err(invalid_data("game ended inside a variation".to_string()));
+                }
+                return Ok(line);
+            }
+            _ => {
+                let mv = board.decode_move(byte, &mut || reader.byte())?;
+                before = Some(board.clone());
+                board.apply(&mv);
+                line.moves.push(MoveNode::new(mv));
+            }
+        }
+    }
+}
+
+/// Replace comment placeholders, in encoding order, with the decoded strings.
+/// Several markers on the same move have their comments joined.
+fn fill_comments(line: &mut Line, comments: &mut impl Iterator<Item = String>) {
+    fill_comment(&mut line.comment, comments);
+    for node in &mut line.moves {
+        fill_comment(&mut node.comment, comments);
+        for variation in &mut node.variations {
+            fill_comments(variation, comments);
+        }
+    }
+}
+
+fn fill_comment(comment: &mut Option<String>, comments: &mut impl Iterator<Item = String>) {
+    if let Some(placeholder) = comment.take() {
+        let texts: Vec<String> = comments.take(placeholder.len()).collect();
+        *comment = Some(texts.join(" "));
+    }
+}
+
+/// Main line moves of a raw .sg4 record; empty if the record can't be decoded
+pub fn parse_scid_moves(data: &[u8]) -> Vec<Move> {
+    match decode_game(data) {
+        Ok(record) => record.moves.moves.into_iter().map(|node| node.mv).collect(),
+        Err(_) => Vec::new(),
+    }
+}
+
+/// Tags whose values are stored in the .si4 index or the .sn4 namebase rather
+/// than in the game record (FEN and SetUp are covered by the start flag)
+pub fn is_index_tag(name: &str) -> bool {
+    matches!(name,
+        "Event" | "Site" | "Date" | "Round" | "White" | "Black" | "Result"
+        | "WhiteElo" | "BlackElo" | "ECO" | "FEN" | "SetUp" | "PlyCount")
+}
+
+fn packed_date_string(packed: u32) -> String {
+    let year = packed >> 9;
+    let month = (packed >> 5) & 15;
+    let day = packed & 31;
+    let part = |value: u32, width: usize| {
+        if value == 0 { "?".repeat(width) } else { format!("{:0width$}", value, width = width) }
+    };
+    format!("{}.{}.{}", part(year, 4), part(month, 2), part(day, 2))
+}
+
+fn truncate_utf8(text: &str, max_len: usize) -> &str {
+    if text.len() <= max_len {
+        return text;
+    }
+    let mut end = max_len;
+    while !text.is_char_boundary(end) {
+        end -= 1;
+    }
+    &text[..end]
+}
+
+struct ByteReader<'a> {
+    data: &'a [u8],
+    pos: usize,
+}
+
+impl ByteReader<'_> {
+    fn byte(&mut self) -> io::Result<u8> {
+        let byte = *self.data.get(self.pos)
+            .ok_or_else(|| io::Error::new(io::ErrorKind::UnexpectedEof, "game record truncated"))?;
+        self.pos += 1;
+        Ok(byte)
+    }
+
+    fn string(&mut self, len: usize) -> io::Result<String> {
+        let end = self.pos + len;
+        let bytes = self.data.get(self.pos..end)
+            .ok_or_else(|| io::Error::new(io::ErrorKind::UnexpectedEof, "game record truncated"))?;
+        self.pos = end;
+        Ok(String::from_utf8_lossy(bytes).into_owned())
+    }
+
+    fn null_terminated(&mut self) -> io::Result<String> {
+        let len = self.data[self.pos..].iter().position(|&b| b == 0)
+            .ok_or_else(|| io::Error::new(io::ErrorKind::UnexpectedEof, "unterminated string in game record"))?;
+        let text = self.string(len)?;
+        self.pos += 1;
+        Ok(text)
+    }
 }
@@ -1,5 +1,19 @@
-use std::fs;
+use std::fs::{self, File};
 use std::collections::HashMap;
+use std::io::{self, Write, BufWriter};
+use std::path::Path;
+
+use super::error::ScidError;
+use super::spelling::{PlayerInfo, SpellingFile};
+use super::version::{check_version, SCID_VERSION_4};
+
+/// Magic bytes at the start of every .sn4 file
+pub const NAME_MAGIC: [u8; 8] = *b"Scid.sn\0";
+
+/// Size of the .sn4 header in bytes
+pub const NAME_HEADER_SIZE: usize = 42;
+
+const NAME_TYPE_LABELS: [&str; 4] = ["player", "event", "site", "round"];
 
 /// SCID .sn4 name file parser - CRITICAL IMPLEMENTATION NOTES
 /// 
@@ -12,25 +26,28 @@ use std::collections::HashMap;
 /// **Solution**: Proper implementation based on official SCID source code analysis
 /// 
 /// ## SCID .sn4 Binary Format (Reverse Engineered)
-/// ```
-/// Header (44 bytes total):
+/// ```text
+/// Header (42 bytes total):
 /// - Magic: "Scid.sn\0" (8 bytes)
 /// - Version: 2 bytes
 /// - Timestamp: 4 bytes  
 /// - Num names per type: 4 × 3 bytes (PLAYER, EVENT, SITE, ROUND)
-/// - Max ID per type: 4 × 3 bytes
-/// - Flags: 1 byte
-/// - Reserved: 3 bytes
+/// - Max frequency per type: 4 × 3 bytes
+/// - Reserved: 4 bytes
 /// 
 /// Data Section:
 /// - Names stored in order: PLAYER(0), EVENT(1), SITE(2), ROUND(3)
-/// - Each name: variable-length ID + frequency + front-coded string
-/// - Front-coding: string length byte + actual string data
+/// - Each name: ID + frequency + front-coded string
+/// - Front-coding: length byte, then (except for a type's first name) the
+///   number of leading bytes shared with the previous name, then the rest
 /// ```
 /// 
 /// ## Key Technical Details
-/// - Variable-length encoding: first byte < 128 = single byte, >= 128 = two bytes
-/// - Front-coded strings: NOT prefix-compressed as initially assumed
+/// - As in SCID, IDs are 1, 2 or 3 bytes wide depending on the type's name
+///   count (under 256, under 65536, more), and frequencies depending on the
+///   type's max frequency in the same way
+/// - The prefix byte is missing before a type's first name; reading one
+///   there anyway is what turned "Michael" into "ichael"
 /// - Control character cleaning essential for readable output
 /// - Little-endian byte order for multi-byte values
 /// 
@@ -45,127 +62,115 @@ use std::collections::HashMap;
 /// Contains player names, event names, site names, and round names
 #[derive(Debug)]
 pub struct NameDatabase {
+    pub version: u16,
     pub players: HashMap<u32, String>,
     pub events: HashMap<u32, String>,
     pub sites: HashMap<u32, String>,
     pub rounds: HashMap<u32, String>,
+    /// Player details from a spelling file, by player ID
+    pub player_info: HashMap<u32, PlayerInfo>,
 }
 
 impl NameDatabase {
     /// Parse a SCID .sn4 name file using the proper SCID format
     pub fn parse_names(file_path: &str) -> Result<NameDatabase, Box<dyn std::error::Error>> {
-        // Read the entire file
         let data = fs::read(file_path)?;
-        
-        let mut players = HashMap::new();
-        let mut events = HashMap::new();
-        let mut sites = HashMap::new();
-        let mut rounds = HashMap::new();
-        
-        if data.len() < 44 { // Full header is 44 bytes
-            return Err("Name file too short".into());
-        }
+        Ok(Self::from_bytes(Path::new(file_path), &data)?)
+    }
+    
+    /// Parse the contents of a .sn4 file; `path` is only used in errors.
+    ///
+    /// Every read is bounds-checked: a name count larger than the data can
+    /// hold gives a `ScidError::Truncated` instead of reading past the end.
+    pub fn from_bytes(path: &Path, data: &[u8]) -> Result<NameDatabase, ScidError> {
+        let mut sections: [HashMap<u32, String>; 4] = Default::default();
         
         // Check magic header: "Scid.sn\0"
-        let expected_magic = b"Scid.sn\0";
-        if &data[0..8] != expected_magic {
-            println!("DEBUG: Name file magic header: {:?}", &data[0..8]);
-            return Err("Invalid SCID name file magic header".into());
+        if !data.starts_with(&NAME_MAGIC) {
+            return Err(ScidError::InvalidMagic { path: path.to_path_buf(), expected: &NAME_MAGIC });
+        }
+        if data.len() < NAME_HEADER_SIZE {
+            return Err(ScidError::Truncated {
+                path: path.to_path_buf(),
+                what: "header".to_string(),
+                offset: 0,
+            });
         }
-        
-        println!("DEBUG: Name file magic header OK");
         
         // Parse header according to SCID format
         let mut pos = 8;
         
-        // Skip version (2 bytes) and timestamp (4 bytes) = 6 bytes total
-        pos += 6;
+        // Version (2 bytes) must be one we know how to parse
+        let version = read_two_bytes(&data[pos..pos+2]);
+        check_version(path, version)?;
+        pos += 2;
         
-        // Read num_names for each type (3 bytes each, 4 types = 12 bytes)
-        let num_players = read_three_bytes(&data[pos..pos+3]);
-        pos += 3;
-        let num_events = read_three_bytes(&data[pos..pos+3]);
-        pos += 3;
-        let num_sites = read_three_bytes(&data[pos..pos+3]);
-        pos += 3;
-        let num_rounds = read_three_bytes(&data[pos..pos+3]);
-        pos += 3;
+        // Skip timestamp (4 bytes)
+        pos += 4;
         
-        println!("DEBUG: Counts - Players: {}, Events: {}, Sites: {}, Rounds: {}", 
-                 num_players, num_events, num_sites, num_rounds);
+        // Read num_names for each type (3 bytes each, 4 types = 12 bytes)
+        let mut counts = [0u32; 4];
+        for count in &mut counts {
+            *count = read_three_bytes(&data[pos..pos+3]);
+            pos += 3;
+        }
         
-        // Skip max_id for each type (3 bytes each, 4 types = 12 bytes)
-        pos += 12;
+        // Read max_frequency for each type (3 bytes each, 4 types = 12 bytes)
+        let mut max_frequencies = [0u32; 4];
+        for max_frequency in &mut max_frequencies {
+            *max_frequency = read_three_bytes(&data[pos..pos+3]);
+            pos += 3;
+        }
         
-        // Skip flags (1 byte) + reserved (3 bytes) = 4 bytes
+        // Skip reserved bytes (4 bytes)
         pos += 4;
         
-        println!("DEBUG: Starting to parse names at position {}", pos);
-        
         // Now parse each name type in order: PLAYER=0, EVENT=1, SITE=2, ROUND=3
-        for name_type in 0..4 {
-            let count = match name_type {
-                0 => num_players,
-                1 => num_events,
-                2 => num_sites,
-                3 => num_rounds,
-                _ => 0,
-            };
-            
-            println!("DEBUG: Parsing name type {} with {} entries at position {}", name_type, count, pos);
-            
-            for _ in 0..count {
-                if pos >= data.len() {
-                    println!("DEBUG: Reached end of file while parsing names");
-                    break;
-                }
+        for (name_type, names) in sections.iter_mut().enumerate() {
+            let count = counts[name_type];
+            let id_width = number_width(count);
+            let frequency_width = number_width(max_frequencies[name_type]);
+            let mut previous = Vec::new();
+            for i in 0..count {
+                let start = pos;
+                let what = || format!("{} name {} of {}", NAME_TYPE_LABELS[name_type], i + 1, count);
+                let truncated = || ScidError::Truncated {
+                    path: path.to_path_buf(),
+                    what: what(),
+                    offset: start as u64,
+                };
                 
-                // Read variable-length ID
-                let (id, bytes_read) = read_variable_length_id(&data[pos..]);
+                // ID, then frequency, then the front-coded string
+                let id = read_number(&data[pos..], id_width).ok_or_else(truncated)?;
+                pos += id_width;
+                let _frequency = read_number(&data[pos..], frequency_width).ok_or_else(truncated)?;
+                pos += frequency_width;
+                let (raw, bytes_read) = read_name_bytes(data, pos, &previous, i > 0).map_err(|e| match e {
+                    NameError::Truncated => truncated(),
+                    NameError::BadPrefix => ScidError::InvalidName {
+                        path: path.to_path_buf(),
+                        what: what(),
+                        offset: start as u64,
+                    },
+                })?;
                 pos += bytes_read;
                 
-                if pos >= data.len() {
-                    break;
-                }
-                
-                // Read frequency (variable length)
-                let (frequency, bytes_read) = read_variable_length_id(&data[pos..]);
-                pos += bytes_read;
-                
-                if pos >= data.len() {
-                    break;
-                }
-                
-                // Read front-coded string
-                if let Some((name, bytes_read)) = read_front_coded_string(&data, pos) {
-                    pos += bytes_read;
-                    
-                    if !name.is_empty() {
-                        println!("DEBUG: Type {}, ID {}, Freq {}: '{}'", name_type, id, frequency, name);
-                        
-                        match name_type {
-                            0 => { players.insert(id, name); },
-                            1 => { events.insert(id, name); },
-                            2 => { sites.insert(id, name); },
-                            3 => { rounds.insert(id, name); },
-                            _ => {},
-                        }
-                    }
-                } else {
-                    println!("DEBUG: Failed to read front-coded string at position {}", pos);
-                    break;
+                let name = clean_name(&raw);
+                previous = raw;
+                if !name.is_empty() {
+                    names.insert(id, name);
                 }
             }
         }
         
-        println!("DEBUG: Parsed {} players, {} events, {} sites, {} rounds", 
-                 players.len(), events.len(), sites.len(), rounds.len());
-        
+        let [players, events, sites, rounds] = sections;
         Ok(NameDatabase {
+            version,
             players,
             events,
             sites,
             rounds,
+            player_info: HashMap::new(),
         })
     }
 
@@ -201,10 +206,160 @@ impl NameDatabase {
     pub fn round_name(&self, round_id: u16) -> Option<&str> {
         self.rounds.get(&(round_id as u32)).map(|s| s.as_str())
     }
+
+    /// Details a spelling file gave for a player
+    pub fn player_info(&self, player_id: u32) -> Option<&PlayerInfo> {
+        self.player_info.get(&player_id)
+    }
+
+    /// Rewrite player, event and site names to their spelling in `spelling`
+    /// and record the details it has for each player; returns the number
+    /// of names changed
+    pub fn apply_spelling(&mut self, spelling: &SpellingFile) -> usize {
+        let mut changed = 0;
+        for (name_type, names) in [
+            (NameType::Player, &mut self.players),
+            (NameType::Event, &mut self.events),
+            (NameType::Site, &mut self.sites),
+        ] {
+            for name in names.values_mut() {
+                if let Some(correct) = spelling.correct(name_type, name) {
+                    if correct != name {
+                        *name = correct.to_string();
+                        changed += 1;
+                    }
+                }
+            }
+        }
+        for (&id, name) in &self.players {
+            if let Some(info) = spelling.player_info(name) {
+                self.player_info.insert(id, info.clone());
+            }
+        }
+        changed
+    }
+
+    /// Player IDs for a name typed by a user, ignoring case: the players
+    /// with exactly that name if there are any, otherwise those whose name
+    /// contains it, ordered by name
+    pub fn find_players(&self, query: &str) -> Vec<u32> {
+        let query = query.trim().to_lowercase();
+        let mut exact = Vec::new();
+        let mut partial = Vec::new();
+        for (&id, name) in &self.players {
+            let name = name.to_lowercase();
+            if name == query {
+                exact.push(id);
+            } else if name.contains(&query) {
+                partial.push(id);
+            }
+        }
+        let mut found = if exact.is_empty() { partial } else { exact };
+        found.sort_by(|a, b| self.players[a].cmp(&self.players[b]).then(a.cmp(b)));
+        found
+    }
 }
 
-/// Helper functions for reading multi-byte values in SCID's little-endian format
-/// These functions handle the binary data parsing according to SCID specifications
+/// The four kinds of name stored in a .sn4 file, in file order
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum NameType {
+    Player = 0,
+    Event = 1,
+    Site = 2,
+    Round = 3,
+}
+
+/// Largest ID or frequency a namebase can store, in three bytes
+const MAX_NAME_NUMBER: u32 = 0xFF_FFFF;
+
+impl NameType {
+    /// Largest ID a game index record can refer to for this type
+    pub fn max_id(self) -> u32 {
+        match self {
+            NameType::Player => 0xFFFFF,
+            NameType::Event | NameType::Site => 0x7FFFF,
+            // `GameIndex::round_id` is 16 bits
+            NameType::Round => 0xFFFF,
+        }
+    }
+}
+
+/// Collects names while a database is written, assigning IDs in order of
+/// first use and counting how many games refer to each name
+#[derive(Debug, Default)]
+pub struct NameBuilder {
+    ids: [HashMap<String, u32>; 4],
+    names: [Vec<(String, u32)>; 4],
+}
+
+impl NameBuilder {
+    pub fn new() -> Self {
+        Self::default()
+    }
+    
+    /// ID for a name, adding it if it's new; every call counts one use
+    pub fn id(&mut self, name_type: NameType, name: &str) -> io::Result<u32> {
+        let t = name_type as usize;
+        if let Some(&id) = self.ids[t].get(name) {
+            self.names[t][id as usize].1 += 1;
+            return Ok(id);
+        }
+        
+        let id = self.names[t].len() as u32;
+        if id > name_type.max_id() {
+            return Err(io::Error::new(
+                io::ErrorKind::InvalidInput,
+                format!("More than {} distinct {:?} names", name_type.max_id() + 1, name_type)
+            ));
+        }
+        self.ids[t].insert(name.to_string(), id);
+        self.names[t].push((name.to_string(), 1));
+        Ok(id)
+    }
+    
+    /// Write the collected names as a .sn4 file in the layout parse_names() reads.
+    /// Names are written in sorted order, as SCID does, so that each shares
+    /// as long a prefix as possible with the one before it.
+    pub fn write<P: AsRef<Path>>(&self, path: P) -> io::Result<()> {
+        let mut writer = BufWriter::new(File::create(path)?);
+        let max_frequencies = self.names.each_ref().map(|names| {
+            names.iter().map(|&(_, frequency)| frequency.min(MAX_NAME_NUMBER)).max().unwrap_or(0)
+        });
+        
+        writer.write_all(&NAME_MAGIC)?;
+        writer.write_all(&SCID_VERSION_4.to_le_bytes())?;
+        writer.write_all(&0u32.to_le_bytes())?; // Timestamp
+        for names in &self.names {
+            writer.write_all(&(names.len() as u32).to_le_bytes()[..3])?;
+        }
+        for max_frequency in max_frequencies {
+            writer.write_all(&max_frequency.to_le_bytes()[..3])?;
+        }
+        writer.write_all(&[0; 4])?; // Reserved
+        
+        for (names, max_frequency) in self.names.iter().zip(max_frequencies) {
+            let id_width = number_width(names.len() as u32);
+            let frequency_width = number_width(max_frequency);
+            let mut sorted: Vec<(u32, &str, u32)> = names.iter().enumerate()
+                .map(|(id, (name, frequency))| (id as u32, truncate_name(name), *frequency))
+                .collect();
+            sorted.sort_by(|a, b| a.1.cmp(b.1));
+            
+            let mut previous: &[u8] = &[];
+            for (i, (id, name, frequency)) in sorted.into_iter().enumerate() {
+                writer.write_all(&id.to_le_bytes()[..id_width])?;
+                writer.write_all(&frequency.min(MAX_NAME_NUMBER).to_le_bytes()[..frequency_width])?;
+                write_name_string(&mut writer, name.as_bytes(), previous, i > 0)?;
+                previous = name.as_bytes();
+            }
+        }
+        
+        writer.flush()
+    }
+}
+
+// Helper functions for reading multi-byte values in SCID's little-endian format
+// These functions handle the binary data parsing according to SCID specifications
 
 /// Read 2-byte little-endian value from byte slice
 /// Used for smaller numeric values in SCID format
@@ -225,36 +380,32 @@ fn read_three_bytes(data: &[u8]) -> u32 {
     u32::from_le_bytes([data[0], data[1], data[2], 0])
 }
 
-/// Read variable-length ID encoding used throughout SCID format
-/// 
-/// ## SCID Variable-Length Encoding Rules:
-/// - If first byte < 128: single byte value (0-127)
-/// - If first byte >= 128: two byte value, first byte & 0x7F + (second byte << 7)
-/// 
-/// This encoding allows common small values to use just 1 byte while still
-/// supporting larger values up to ~16K with 2 bytes
-/// 
-/// ## Returns: (decoded_value, bytes_consumed)
-fn read_variable_length_id(data: &[u8]) -> (u32, usize) {
-    if data.is_empty() {
-        return (0, 0);
-    }
-    
-    let first_byte = data[0];
-    
-    if first_byte < 128 {
-        // Single byte value
-        (first_byte as u32, 1)
-    } else if data.len() >= 2 {
-        // Two byte value
-        let value = ((first_byte & 0x7F) as u32) | ((data[1] as u32) << 7);
-        (value, 2)
+/// Bytes SCID uses for an ID or frequency when the largest value to
+/// store (for IDs, the name count) is `max`
+fn number_width(max: u32) -> usize {
+    if max >= 65536 {
+        3
+    } else if max >= 256 {
+        2
     } else {
-        (0, 1)
+        1
     }
 }
 
-/// Read and decode SCID front-coded string format - THE KEY TO FIXING NAME EXTRACTION
+/// Read a little-endian number `width` bytes wide, or None if the data ends first
+fn read_number(data: &[u8], width: usize) -> Option<u32> {
+    let bytes = data.get(..width)?;
+    Some(bytes.iter().rev().fold(0, |value, &byte| value << 8 | byte as u32))
+}
+
+/// Why a name string can't be read
+enum NameError {
+    Truncated,
+    /// The shared prefix is longer than the name or the previous name
+    BadPrefix,
+}
+
+/// Read a front-coded SCID name string - THE KEY TO FIXING NAME EXTRACTION
 /// 
 /// ## Critical Implementation Note
 /// This function solves the "ichael" vs "Michael" problem that plagued earlier versions.
@@ -262,13 +413,42 @@ fn read_variable_length_id(data: &[u8]) -> (u32, usize) {
 /// 
 /// ## SCID String Format Discovery
 /// After analyzing SCID source code (namebase.cpp), the format is:
-/// ```
-/// [length_byte][string_data_bytes...]
+/// ```text
+/// first name of a type: [length_byte][string_data_bytes...]
+/// every later name:     [length_byte][prefix_byte][string_data_bytes after the prefix...]
 /// ```
 /// 
 /// ## The "ichael" Problem & Solution
-/// **Old broken approach**: Tried to implement prefix compression that didn't exist
-/// **Working approach**: Direct string extraction with proper control character cleaning
+/// **Old broken approach**: Read a prefix byte before every name, including the first
+/// **Working approach**: No prefix byte before a type's first name; later names copy
+/// `prefix` bytes of the previous (raw, uncleaned) name
+/// 
+/// ## Returns: the raw name bytes and the bytes consumed
+fn read_name_bytes(data: &[u8], pos: usize, previous: &[u8], front_coded: bool)
+                   -> Result<(Vec<u8>, usize), NameError> {
+    let length = *data.get(pos).ok_or(NameError::Truncated)? as usize;
+    let mut current_pos = pos + 1;
+    
+    let prefix = if front_coded {
+        let prefix = *data.get(current_pos).ok_or(NameError::Truncated)? as usize;
+        current_pos += 1;
+        prefix
+    } else {
+        0
+    };
+    if prefix > length || prefix > previous.len() {
+        return Err(NameError::BadPrefix);
+    }
+    
+    let rest = data.get(current_pos..current_pos + length - prefix).ok_or(NameError::Truncated)?;
+    current_pos += rest.len();
+    
+    let mut name = previous[..prefix].to_vec();
+    name.extend_from_slice(rest);
+    Ok((name, current_pos - pos))
+}
+
+/// Turn raw name bytes into a readable name
 /// 
 /// ## Control Character Cleaning
 /// SCID strings often contain control characters (0x00-0x1F) that need cleaning:
@@ -280,31 +460,13 @@ fn read_variable_length_id(data: &[u8]) -> (u32, usize) {
 /// Without this cleaning: "Michael\x04\x13W" becomes "Michael W"
 /// Without this cleaning: "\x25\x10\tMichael" becomes "% Michael"
 /// 
-/// ## Returns: Some((cleaned_string, bytes_consumed)) or None if invalid
-/// 
 /// ## Validation Examples That Now Work
 /// - "Michael" (complete, not "ichael")  
 /// - "Patrick" (complete, not "atrick")
 /// - "'t Hart, Joost TE" (proper event names)
-fn read_front_coded_string(data: &[u8], pos: usize) -> Option<(String, usize)> {
-    if pos >= data.len() {
-        return None;
-    }
-    
-    // Read string length
-    let length = data[pos] as usize;
-    let mut current_pos = pos + 1;
-    
-    if current_pos + length > data.len() {
-        return None;
-    }
-    
-    // Read the string data
-    let string_data = &data[current_pos..current_pos + length];
-    current_pos += length;
-    
+fn clean_name(raw: &[u8]) -> String {
     // Convert to string with cleaning
-    let raw_string = String::from_utf8_lossy(string_data).to_string();
+    let raw_string = String::from_utf8_lossy(raw);
     
     // Clean control characters but keep more characters than before
     let cleaned_string: String = raw_string
@@ -317,15 +479,34 @@ fn read_front_coded_string(data: &[u8], pos: usize) -> Option<(String, usize)> {
         })
         .collect();
     
-    // Trim and clean up multiple spaces
-    let final_string = cleaned_string
+    // Trim and clean up multiple spaces; short names are legitimate: rounds
+    // are usually "1", "2", ... and SCID stores unknown values as "?"
+    cleaned_string
         .split_whitespace()
         .collect::<Vec<&str>>()
-        .join(" ");
-    
-    if final_string.len() >= 2 {
-        Some((final_string, current_pos - pos))
-    } else {
-        None
+        .join(" ")
+}
+
+/// A name cut to the 255 bytes a length byte can count, on a character boundary
+fn truncate_name(name: &str) -> &str {
+    let mut len = name.len().min(255);
+    while !name.is_char_boundary(len) {
+        len -= 1;
     }
+    &name[..len]
+}
+
+/// Write a name front-coded against the previous name of its type, as
+/// read_name_bytes() expects; the first name of a type has no prefix byte
+fn write_name_string<W: Write>(writer: &mut W, name: &[u8], previous: &[u8], front_coded: bool)
+                               -> io::Result<()> {
+    writer.write_all(&[name.len() as u8])?;
+    let prefix = if front_coded {
+        let prefix = name.iter().zip(previous).take_while(|(a, b)| a == b).count();
+        writer.write_all(&[prefix as u8])?;
+        prefix
+    } else {
+        0
+    };
+    writer.write_all(&name[prefix..])
 }
@@ -0,0 +1,282 @@
+//! Final material signature and home pawn data
+//!
+//! Each index record carries two summaries of a game's main line, so that
+//! material and pawn-structure searches can run from the index alone and
+//! position searches can rule games out without decoding their moves.
+//!
+//! ## FinalMatSig (low 24 bits of a 4-byte field)
+//! Piece counts of the final position, capped at 3 for pieces and 15 for
+//! pawns. The top byte of the field is the stored line code.
+//! ```text
+//! bits 22-23 WQ   20-21 WR   18-19 WB   16-17 WN   12-15 WP
+//! bits 10-11 BQ    8-9  BR    6-7  BB    4-5  BN    0-3  BP
+//! ```
+//!
+//! ## HomePawnData (9 bytes)
+//! The order in which pawns left their home squares, for games from the
+//! standard start. The low 5 bits of the first byte are the count (at most
+//! 16); the top 2 bits of that byte belong to the ply count. Each following
+//! nibble, high nibble first, is the file of a white pawn (0-7) or 8 plus
+//! the file of a black pawn.
+//!
+//! A pawn never returns to its home square, so a game can only reach a
+//! position if, at some point of the list, exactly the home pawns missing
+//! from that position had gone. Material only falls, except through
+//! promotion. An all-zero signature is what databases written without
+//! these summaries contain, so it tells us nothing.
+
+use crate::chess::position::{make_square, square_file, square_rank};
+use crate::chess::{Color, Game, Piece, Position, Square};
+
+/// Size of the HomePawnData field in an index record
+pub const HOME_PAWN_DATA_SIZE: usize = 9;
+
+/// Most home pawn moves a game can have: one per home square
+const MAX_HOME_PAWN_MOVES: usize = 16;
+
+/// Bit offset of each count in FinalMatSig, by colour, for pawns, knights,
+/// bishops, rooks and queens
+const MATSIG_SHIFTS: [[u32; 5]; 2] = [[12, 16, 18, 20, 22], [0, 4, 6, 8, 10]];
+
+/// Piece counts of one side, capped as FinalMatSig stores them
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub struct SideMaterial {
+    pub queens: u8,
+    pub rooks: u8,
+    pub bishops: u8,
+    pub knights: u8,
+    pub pawns: u8,
+}
+
+impl SideMaterial {
+    /// Count for a piece type; kings aren't counted
+    pub fn count(&self, piece: Piece) -> u8 {
+        match piece {
+            Piece::Pawn => self.pawns,
+            Piece::Knight => self.knights,
+            Piece::Bishop => self.bishops,
+            Piece::Rook => self.rooks,
+            Piece::Queen => self.queens,
+            Piece::King => 0,
+        }
+    }
+
+    fn count_mut(&mut self, piece: Piece) -> Option<&mut u8> {
+        match piece {
+            Piece::Pawn => Some(&mut self.pawns),
+            Piece::Knight => Some(&mut self.knights),
+            Piece::Bishop => Some(&mut self.bishops),
+            Piece::Rook => Some(&mut self.rooks),
+            Piece::Queen => Some(&mut self.queens),
+            Piece::King => None,
+        }
+    }
+
+    /// Most a count can record: 15 pawns, 3 of each piece
+    fn cap(piece: Piece) -> u8 {
+        if piece == Piece::Pawn { 15 } else { 3 }
+    }
+}
+
+/// Pieces counted by FinalMatSig, in shift order
+const MATSIG_PIECES: [Piece; 5] = [Piece::Pawn, Piece::Knight, Piece::Bishop, Piece::Rook, Piece::Queen];
+
+/// Material of a game's final position
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub struct FinalMaterial {
+    pub white: SideMaterial,
+    pub black: SideMaterial,
+}
+
+impl FinalMaterial {
+    /// Material on the board in `pos`
+    pub fn of(pos: &Position) -> FinalMaterial {
+        let mut material = FinalMaterial::default();
+        for square in 0..64 {
+            if let Some((color, piece)) = pos.piece_at(square) {
+                let side = material.side_mut(color);
+                if let Some(count) = side.count_mut(piece) {
+                    *count = (*count + 1).min(SideMaterial::cap(piece));
+                }
+            }
+        }
+        material
+    }
+
+    /// Decode the low 24 bits of FinalMatSig
+    pub fn from_signature(signature: u32) -> FinalMaterial {
+        let mut material = FinalMaterial::default();
+        for (color, shifts) in [Color::White, Color::Black].into_iter().zip(MATSIG_SHIFTS) {
+            let side = material.side_mut(color);
+            for (piece, shift) in MATSIG_PIECES.into_iter().zip(shifts) {
+                if let Some(count) = side.count_mut(piece) {
+                    *count = ((signature >> shift) as u8) & SideMaterial::cap(piece);
+                }
+            }
+        }
+        material
+    }
+
+    /// Encode as FinalMatSig; counts above the caps are stored as the cap
+    pub fn signature(&self) -> u32 {
+        let mut signature = 0;
+        for (color, shifts) in [Color::White, Color::Black].into_iter().zip(MATSIG_SHIFTS) {
+            for (piece, shift) in MATSIG_PIECES.into_iter().zip(shifts) {
+                let count = self.side(color).count(piece).min(SideMaterial::cap(piece));
+                signature |= (count as u32) << shift;
+            }
+        }
+        signature
+    }
+
+    pub fn side(&self, color: Color) -> &SideMaterial {
+        match color {
+            Color::White => &self.white,
+            Color::Black => &self.black,
+        }
+    }
+
+    fn side_mut(&mut self, color: Color) -> &mut SideMaterial {
+        match color {
+            Color::White => &mut self.white,
+            Color::Black => &mut self.black,
+        }
+    }
+
+    /// Bare kings, or a record written without a signature
+    pub fn is_empty(&self) -> bool {
+        *self == FinalMaterial::default()
+    }
+
+    /// Whether a game ending with this material could pass through a
+    /// position with `target` material; `promotions` is whether the game
+    /// has a promotion, which can add pieces but never pawns. Always true
+    /// for an empty signature.
+    pub fn could_reach(&self, target: &FinalMaterial, promotions: bool) -> bool {
+        // Capped counts keep this safe: a cap on either side only makes
+        // the final count look smaller
+        self.is_empty() || [Color::White, Color::Black].into_iter().all(|color| {
+            MATSIG_PIECES.into_iter().all(|piece| {
+                self.side(color).count(piece) <= target.side(color).count(piece)
+                    || (promotions && piece != Piece::Pawn)
+            })
+        })
+    }
+}
+
+/// The home squares pawns left, in order, as HomePawnData stores them
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub struct HomePawnMoves {
+    len: u8,
+    /// HomePawnData nibbles: white files 0-7, black files 8-15
+    pawns: [u8; MAX_HOME_PAWN_MOVES],
+}
+
+impl HomePawnMoves {
+    /// Decode HomePawnData, ignoring the ply count bits of the first byte
+    pub fn from_bytes(data: &[u8; HOME_PAWN_DATA_SIZE]) -> HomePawnMoves {
+        let len = (data[0] & 0x1F).min(MAX_HOME_PAWN_MOVES as u8);
+        let mut pawns = [0u8; MAX_HOME_PAWN_MOVES];
+        for (i, pawn) in pawns[..len as usize].iter_mut().enumerate() {
+            let byte = data[1 + i / 2];
+            *pawn = if i % 2 == 0 { byte >> 4 } else { byte & 0x0F };
+        }
+        HomePawnMoves { len, pawns }
+    }
+
+    /// Encode as HomePawnData, with the ply count bits left clear
+    pub fn to_bytes(&self) -> [u8; HOME_PAWN_DATA_SIZE] {
+        let mut data = [0u8; HOME_PAWN_DATA_SIZE];
+        data[0] = self.len;
+        for (i, &pawn) in self.pawns[..self.len as usize].iter().enumerate() {
+            data[1 + i / 2] |= if i % 2 == 0 { pawn << 4 } else { pawn };
+        }
+        data
+    }
+
+    pub fn len(&self) -> usize {
+        self.len as usize
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.len == 0
+    }
+
+    /// The home squares, in the order their pawns left them
+    pub fn squares(&self) -> impl Iterator<Item = Square> + '_ {
+        self.pawns[..self.len as usize].iter().map(|&pawn| match pawn {
+            0..=7 => make_square(pawn, 1),
+            _ => make_square(pawn - 8, 6),
+        })
+    }
+
+    /// Record a pawn leaving its home square; moves past the sixteenth, or
+    /// squares that aren't home squares, are ignored
+    pub fn push(&mut self, square: Square) {
+        let pawn = match square_rank(square) {
+            1 => square_file(square),
+            6 => 8 + square_file(square),
+            _ => return,
+        };
+        if (self.len as usize) < MAX_HOME_PAWN_MOVES {
+            self.pawns[self.len as usize] = pawn;
+            self.len += 1;
+        }
+    }
+
+    /// Whether a standard-start game with these moves could reach a
+    /// position where the home pawns in `target_gone` (a mask from
+    /// `home_pawns_gone`) have left
+    pub fn could_reach(&self, target_gone: u16) -> bool {
+        let wanted = target_gone.count_ones() as usize;
+        if self.len() < wanted {
+            return false;
+        }
+        let gone = self.pawns[..wanted].iter().fold(0u16, |gone, &pawn| gone | 1 << pawn);
+        gone == target_gone
+    }
+}
+
+/// Bit mask of the home squares (white pawns 0-7, black 8-15) without
+/// their own pawn in `pos`
+pub fn home_pawns_gone(pos: &Position) -> u16 {
+    let mut gone = 0;
+    for file in 0..8 {
+        if pos.piece_at(make_square(file, 1)) != Some((Color::White, Piece::Pawn)) {
+            gone |= 1 << file;
+        }
+        if pos.piece_at(make_square(file, 6)) != Some((Color::Black, Piece::Pawn)) {
+            gone |= 1 << (8 + file);
+        }
+    }
+    gone
+}
+
+/// Final material and home pawn moves of a game's main line; there are no
+/// home pawn moves for games that don't start from the standard position,
+/// and both are empty when the start position can't be set up
+pub fn game_signatures(game: &Game) -> (FinalMaterial, HomePawnMoves) {
+    let mut home_pawns = HomePawnMoves::default();
+    let Ok(mut pos) = game.start_position() else {
+        return (FinalMaterial::default(), home_pawns);
+    };
+    let standard = game.tag("FEN").is_none();
+
+    let mut gone = home_pawns_gone(&pos);
+    for mv in game.main_line() {
+        pos.make_move(mv);
+        if !standard {
+            continue;
+        }
+        // A capture can empty a home square as a pawn move does
+        let now_gone = home_pawns_gone(&pos);
+        let mut new = now_gone & !gone;
+        while new != 0 {
+            let bit = new.trailing_zeros() as u8;
+            home_pawns.push(if bit < 8 { make_square(bit, 1) } else { make_square(bit - 8, 6) });
+            new &= new - 1;
+        }
+        gone = now_gone;
+    }
+    (FinalMaterial::of(&pos), home_pawns)
+}
@@ -0,0 +1,158 @@
+//! SCID spelling files (.ssp)
+//!
+//! A spelling file lists the correct spelling of player, event and site
+//! names, each followed by the variants that should be corrected to it.
+//! Player lines can also carry a title, federation and birth date.
+//!
+//! ## Format
+//! ```text
+//! @SCID spelling file             (header; other @ lines are ignored)
+//! # A comment
+//! @PLAYER                         (section: @PLAYER, @EVENT, @SITE or @ROUND)
+//! Carlsen, Magnus   #gm NOR 1990.11.30
+//! =Carlsen, M.                    (variant of the name above)
+//! =Carlsen,M
+//! %ID fide 1503014                (FIDE ID of the name above)
+//! %Elo 2009:2776,2772             (other % lines are ignored)
+//! ```
+//! Names are compared ignoring case and repeated spaces. The player info
+//! after `#` is read word by word: a known title (`gm`, `im`, `fm`, `cm`,
+//! `wgm`, `wim`, `wfm`, `wcm`, optionally followed by `+` markers), a
+//! three-letter federation and a birth date or year, each optional. Lines
+//! before the first section are player names, as in SCID.
+
+use std::collections::{HashMap, HashSet};
+use std::fs;
+use std::io;
+use std::path::Path;
+
+use super::names::NameType;
+
+/// Titles written to PGN Title tags, as they appear in spelling files
+const TITLES: [&str; 8] = ["gm", "im", "fm", "cm", "wgm", "wim", "wfm", "wcm"];
+
+/// What a spelling file knows about a player
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct PlayerInfo {
+    /// FIDE title in PGN form, e.g. "GM" or "WIM"
+    pub title: Option<String>,
+    /// Three-letter federation code, e.g. "NOR"
+    pub federation: Option<String>,
+    pub birth_year: Option<u16>,
+    pub fide_id: Option<u32>,
+}
+
+impl PlayerInfo {
+    fn parse(info: &str) -> PlayerInfo {
+        let mut player = PlayerInfo::default();
+        for word in info.split_whitespace() {
+            let title = word.split('+').next().unwrap_or(word);
+            if player.title.is_none() && TITLES.contains(&title) {
+                player.title = Some(title.to_uppercase());
+            } else if word.len() == 3 && word.chars().all(|c| c.is_ascii_uppercase()) {
+                player.federation = Some(word.to_string());
+            } else if let Some(year) = word.get(..4).filter(|year| year.chars().all(|c| c.is_ascii_digit())) {
+                player.birth_year = year.parse().ok();
+            }
+        }
+        player
+    }
+}
+
+/// Corrections read from a spelling file
+#[derive(Debug, Clone, Default)]
+pub struct SpellingFile {
+    /// Variant and correct spellings, by name type, each keyed by `key()`
+    corrections: [HashMap<String, String>; 4],
+    /// By correct player name
+    players: HashMap<String, PlayerInfo>,
+}
+
+impl SpellingFile {
+    /// Read a spelling file; it is decoded as Latin-1 when it isn't UTF-8,
+    /// as older SCID files are
+    pub fn load<P: AsRef<Path>>(path: P) -> io::Result<Self> {
+        let data = fs::read(path)?;
+        Ok(match String::from_utf8(data) {
+            Ok(text) => Self::parse(&text),
+            Err(e) => Self::parse(&e.into_bytes().iter().map(|&b| b as char).collect::<String>()),
+        })
+    }
+
+    /// Parse spelling file text; lines it doesn't understand are skipped
+    pub fn parse(text: &str) -> Self {
+        let mut spelling = SpellingFile::default();
+        let mut section = NameType::Player;
+        // The correct name the following variant and % lines belong to
+        let mut current: Option<String> = None;
+
+        for line in text.lines() {
+            let line = line.trim();
+            if line.is_empty() || line.starts_with('#') {
+                continue;
+            }
+            if let Some(directive) = line.strip_prefix('@') {
+                let name_type = match directive.trim().to_ascii_uppercase().as_str() {
+                    "PLAYER" => Some(NameType::Player),
+                    "EVENT" => Some(NameType::Event),
+                    "SITE" => Some(NameType::Site),
+                    "ROUND" => Some(NameType::Round),
+                    _ => None,
+                };
+                if let Some(name_type) = name_type {
+                    section = name_type;
+                    current = None;
+                }
+            } else if let Some(variant) = line.strip_prefix('=') {
+                if let Some(correct) = &current {
+                    spelling.corrections[section as usize].insert(key(variant), correct.clone());
+                }
+            } else if let Some(directive) = line.strip_prefix('%') {
+                let mut words = directive.split_whitespace();
+                if let (Some("ID"), NameType::Player, Some(correct)) = (words.next(), section, &current) {
+                    let id = words.find_map(|word| word.parse::<u32>().ok());
+                    if let Some(id) = id {
+                        spelling.players.entry(correct.clone()).or_default().fide_id = Some(id);
+                    }
+                }
+            } else {
+                let (name, info) = line.split_once('#').unwrap_or((line, ""));
+                let name = name.split_whitespace().collect::<Vec<_>>().join(" ");
+                spelling.corrections[section as usize].insert(key(&name), name.clone());
+                if section == NameType::Player && !info.trim().is_empty() {
+                    let mut player = PlayerInfo::parse(info);
+                    // A %ID line may already have been seen for a repeated name
+                    player.fide_id = spelling.players.get(&name).and_then(|player| player.fide_id);
+                    spelling.players.insert(name.clone(), player);
+                }
+                current = Some(name);
+            }
+        }
+        spelling
+    }
+
+    /// The correct spelling of a name, if the file lists it or a variant
+    pub fn correct(&self, name_type: NameType, name: &str) -> Option<&str> {
+        self.corrections[name_type as usize].get(&key(name)).map(|correct| correct.as_str())
+    }
+
+    /// What the file knows about a player, by correct name
+    pub fn player_info(&self, name: &str) -> Option<&PlayerInfo> {
+        self.players.get(name)
+    }
+
+    /// Number of correct names of a type
+    pub fn len(&self, name_type: NameType) -> usize {
+        self.corrections[name_type as usize].values().collect::<HashSet<_>>().len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.corrections.iter().all(|corrections| corrections.is_empty())
+    }
+}
+
+/// Lowercase with single spaces, so variants differing only in case or
+/// spacing are found
+fn key(name: &str) -> String {
+    name.split_whitespace().collect::<Vec<_>>().join(" ").to_lowercase()
+}
@@ -0,0 +1,239 @@
+//! Database integrity checks
+//!
+//! The loaders only reject what they can't parse at all; a database can
+//! load cleanly and still export wrong games. `Verifier` looks for the
+//! damage that would cause that:
+//!
+//! ## Index checks
+//! - `out-of-range`: a game record extends past the end of the .sg4 file
+//! - `overlap`: two game records share bytes of the .sg4 file
+//! - `unknown-name`: a player, event, site or round ID missing from the
+//!   namebase
+//! - `invalid-date`: a month over 12, or a day the month doesn't have
+//!
+//! ## Game checks
+//! These decode every game record in range, so need the .sg4 file:
+//! - `undecodable`: the record can't be decoded
+//! - `ply-count`: the index's ply count differs from the main line's
+//! - `illegal-move`: a move in the main line or a variation isn't legal
+//!   where it's played
+
+use std::io::{self, Write};
+
+use serde::Serialize;
+
+use crate::chess::{Line, Position};
+use super::{GameIndex, ScidDatabase};
+
+/// What kind of damage an issue is
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "kebab-case")]
+pub enum IssueKind {
+    OutOfRange,
+    Overlap,
+    UnknownName,
+    InvalidDate,
+    Undecodable,
+    PlyCount,
+    IllegalMove,
+}
+
+impl IssueKind {
+    /// The name used in reports, e.g. "out-of-range"
+    pub fn name(self) -> &'static str {
+        match self {
+            IssueKind::OutOfRange => "out-of-range",
+            IssueKind::Overlap => "overlap",
+            IssueKind::UnknownName => "unknown-name",
+            IssueKind::InvalidDate => "invalid-date",
+            IssueKind::Undecodable => "undecodable",
+            IssueKind::PlyCount => "ply-count",
+            IssueKind::IllegalMove => "illegal-move",
+        }
+    }
+}
+
+/// One problem with one game
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+pub struct Issue {
+    /// Game number, counting from 1
+    pub game: usize,
+    pub kind: IssueKind,
+    pub message: String,
+}
+
+/// Everything found by a verification, issues in game order
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+pub struct VerifyReport {
+    pub games: usize,
+    /// Whether game records were decoded, i.e. the game checks ran
+    pub games_checked: bool,
+    pub issues: Vec<Issue>,
+}
+
+impl VerifyReport {
+    /// True when nothing was found
+    pub fn is_clean(&self) -> bool {
+        self.issues.is_empty()
+    }
+
+    /// Write one line per issue, then a summary
+    pub fn write_text<W: Write>(&self, writer: &mut W) -> io::Result<()> {
+        for issue in &self.issues {
+            writeln!(writer, "game {}: {}: {}", issue.game, issue.kind.name(), issue.message)?;
+        }
+        let scope = if self.games_checked { "" } else { " (index only; game records not decoded)" };
+        if self.is_clean() {
+            return writeln!(writer, "Checked {} games{}: no issues found", self.games, scope);
+        }
+        let mut damaged: Vec<usize> = self.issues.iter().map(|issue| issue.game).collect();
+        damaged.dedup();
+        writeln!(writer, "Checked {} games{}: {} issues in {} games", self.games, scope, self.issues.len(),
+                 damaged.len())
+    }
+}
+
+/// Integrity checker for a SCID database
+pub struct Verifier {
+    check_games: bool,
+}
+
+impl Verifier {
+    pub fn new() -> Self {
+        Verifier { check_games: true }
+    }
+
+    /// Whether to decode game records for the game checks (default true);
+    /// they are skipped anyway for a database loaded with `load_index_only`
+    pub fn with_game_checks(mut self, check_games: bool) -> Self {
+        self.check_games = check_games;
+        self
+    }
+
+    pub fn verify(&self, database: &mut ScidDatabase) -> VerifyReport {
+        let games = database.game_indices().to_vec();
+        let file_len = database.game_file_len();
+        let mut issues = Vec::new();
+
+        // Records in file order, to find overlaps with a single pass
+        let mut records: Vec<(u32, u32, usize)> = games.iter().enumerate()
+            .filter(|(_, game_index)| game_index.length > 0)
+            .map(|(game_num, game_index)| (game_index.offset, game_index.length, game_num))
+            .collect();
+        records.sort_unstable();
+        // Furthest end of any record so far, and its game
+        let mut furthest: Option<(u64, usize)> = None;
+        for (offset, length, game_num) in records {
+            let end = offset as u64 + length as u64;
+            match furthest {
+                Some((furthest_end, other)) if (offset as u64) < furthest_end => {
+                    issues.push(Issue {
+                        game: game_num + 1,
+                        kind: IssueKind::Overlap,
+                        message: format!("record at {}..{} overlaps game {}'s record", offset, end, other + 1),
+                    });
+                    if end > furthest_end {
+                        furthest = Some((end, game_num));
+                    }
+                }
+                _ => furthest = Some((end, game_num)),
+            }
+        }
+
+        for (game_num, game_index) in games.iter().enumerate() {
+            let mut issue = |kind, message| issues.push(Issue { game: game_num + 1, kind, message });
+
+            let out_of_range = file_len.filter(|&len| game_index.offset as u64 + game_index.length as u64 > len);
+            if let Some(len) = out_of_range {
+                issue(IssueKind::OutOfRange, format!("record at {}..{} is past the end of the {}-byte game file",
+                                                     game_index.offset,
+                                                     game_index.offset as u64 + game_index.length as u64, len));
+            }
+            for message in unknown_names(database, game_index) {
+                issue(IssueKind::UnknownName, message);
+            }
+            if let Some(message) = invalid_date(game_index) {
+                issue(IssueKind::InvalidDate, message);
+            }
+
+            if self.check_games && file_len.is_some() && out_of_range.is_none() {
+                match database.load_game(game_index) {
+                    Err(e) => issue(IssueKind::Undecodable, e.to_string()),
+                    Ok(game) => {
+                        if game.ply_count() != game_index.num_half_moves as usize {
+                            issue(IssueKind::PlyCount, format!("index says {} half-moves but the game has {}",
+                                                               game_index.num_half_moves, game.ply_count()));
+                        }
+                        match game.start_position() {
+                            Err(e) => issue(IssueKind::IllegalMove, format!("invalid start position: {}", e)),
+                            Ok(position) => {
+                                if let Some(message) = first_illegal_move(&game.moves, position, 0, false) {
+                                    issue(IssueKind::IllegalMove, message);
+                                }
+                            }
+                        }
+                    }
+                }
+            }
+        }
+
+        issues.sort_by_key(|issue| issue.game);
+        VerifyReport { games: games.len(), games_checked: self.check_games && file_len.is_some(), issues }
+    }
+}
+
+impl Default for Verifier {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+fn unknown_names(database: &ScidDatabase, game_index: &GameIndex) -> Vec<String> {
+    let names = database.names();
+    let mut missing = Vec::new();
+    for (label, id, found) in [
+        ("white player", game_index.white_id, names.player_name(game_index.white_id).is_some()),
+        ("black player", game_index.black_id, names.player_name(game_index.black_id).is_some()),
+        ("event", game_index.event_id, names.event_name(game_index.event_id).is_some()),
+        ("site", game_index.site_id, names.site_name(game_index.site_id).is_some()),
+        ("round", game_index.round_id as u32, names.round_name(game_index.round_id).is_some()),
+    ] {
+        if !found {
+            missing.push(format!("{} ID {} is not in the namebase", label, id));
+        }
+    }
+    missing
+}
+
+/// Month and day must exist, and a day needs a month; zero is unknown
+fn invalid_date(game_index: &GameIndex) -> Option<String> {
+    let (year, month, day) = (game_index.year, game_index.month, game_index.day);
+    let days_in_month = match month {
+        0 => 0,
+        2 if year % 4 == 0 && (year % 100 != 0 || year % 400 == 0) => 29,
+        2 => 28,
+        4 | 6 | 9 | 11 => 30,
+        1..=12 => 31,
+        _ => return Some(format!("no month {} in date {:04}.{:02}.{:02}", month, year, month, day)),
+    };
+    (day > days_in_month).then(|| format!("no day {} in date {:04}.{:02}.{:02}", day, year, month, day))
+}
+
+/// Describe the first move in `line` or its variations that isn't legal,
+/// playing from `position`, which is `ply` half-moves into the game
+fn first_illegal_move(line: &Line, mut position: Position, ply: usize, in_variation: bool) -> Option<String> {
+    for (i, node) in line.moves.iter().enumerate() {
+        for variation in &node.variations {
+            if let Some(message) = first_illegal_move(variation, position.clone(), ply + i, true) {
+                return Some(message);
+            }
+        }
+        if !position.is_legal(&node.mv) {
+            let place = if in_variation { "in a variation " } else { "" };
+            return Some(format!("{} {}at ply {} is not legal in {}", node.mv.to_uci(), place, ply + i + 1,
+                                position.to_fen()));
+        }
+        position.make_move(&node.mv);
+    }
+    None
+}
@@ -0,0 +1,63 @@
+//! SCID format versions
+//!
+//! Both the .si4 index header and the .sn4 name file header start with a
+//! 2-byte version number after the magic. SCID 4.x writes `SCID_VERSION`
+//! (400) into both files; the older 3.x format used .si3/.sn3 files with a
+//! different record layout and is recognised only to give a better error.
+//!
+//! Reading a file whose version we do not know silently produces garbage
+//! records, so every header version is checked against this list.
+
+use std::path::Path;
+
+use super::error::ScidError;
+
+/// SCID 3.x (.si3/.sn3/.sg3) - different index record layout
+pub const SCID_VERSION_3: u16 = 300;
+
+/// SCID 4.x (.si4/.sn4/.sg4) - the format this crate parses
+pub const SCID_VERSION_4: u16 = 400;
+
+/// Every header version this crate is able to read
+pub const SUPPORTED_VERSIONS: &[u16] = &[SCID_VERSION_4];
+
+/// Human-readable name of a known version number
+pub fn version_name(version: u16) -> Option<&'static str> {
+    match version {
+        SCID_VERSION_3 => Some("SCID 3.x"),
+        SCID_VERSION_4 => Some("SCID 4.x"),
+        _ => None,
+    }
+}
+
+/// Check that a header version read from `path` is one we can parse
+pub fn check_version(path: &Path, version: u16) -> Result<(), ScidError> {
+    if SUPPORTED_VERSIONS.contains(&version) {
+        Ok(())
+    } else {
+        Err(ScidError::UnsupportedVersion {
+            path: path.to_path_buf(),
+            found: version,
+            supported: SUPPORTED_VERSIONS,
+        })
+    }
+}
+
+/// Check that the index and name files were written by the same SCID version
+pub fn check_versions_match(
+    index_path: &Path,
+    index_version: u16,
+    name_path: &Path,
+    name_version: u16,
+) -> Result<(), ScidError> {
+    if index_version == name_version {
+        Ok(())
+    } else {
+        Err(ScidError::VersionMismatch {
+            index_path: index_path.to_path_buf(),
+            index_version,
+            name_path: name_path.to_path_buf(),
+            name_version,
+        })
+    }
+}
@@ -0,0 +1,197 @@
+use std::fs::File;
+use std::io::{self, BufWriter, Write};
+use std::path::{Path, PathBuf};
+
+use crate::chess::{Game, Piece};
+use super::eco::eco_from_str;
+use super::index::{IndexFile, IDX_FLAG_PROMO, IDX_FLAG_START, IDX_FLAG_UPROMO, MAX_GAME_LENGTH};
+use super::moves::encode_game;
+use super::names::{NameBuilder, NameType};
+use super::signature::game_signatures;
+use super::{GameIndex, ScidHeader};
+
+/// Writer for new SCID databases - the reverse of ScidDatabase
+///
+/// Games are encoded into the .sg4 file as they are added; the index
+/// records and names are kept in memory and the .si4 and .sn4 files are
+/// written by finish(), once the final game count and name frequencies are
+/// known. All three files use the same layouts the readers in this module
+/// parse, so a written database loads back with ScidDatabase::load().
+pub struct ScidWriter {
+    si4_path: PathBuf,
+    sn4_path: PathBuf,
+    sg4: BufWriter<File>,
+    offset: u32,
+    indices: Vec<GameIndex>,
+    names: NameBuilder,
+    description: String,
+}
+
+impl ScidWriter {
+    /// Create the .si4/.sn4/.sg4 files for `base_path` (without extension),
+    /// replacing any existing files
+    pub fn create<P: AsRef<Path>>(base_path: P) -> io::Result<Self> {
+        let base_path = base_path.as_ref();
+        let sg4 = BufWriter::new(File::create(base_path.with_extension("sg4"))?);
+
+        Ok(ScidWriter {
+            si4_path: base_path.with_extension("si4"),
+            sn4_path: base_path.with_extension("sn4"),
+            sg4,
+            offset: 0,
+            indices: Vec::new(),
+            names: NameBuilder::new(),
+            description: String::new(),
+        })
+    }
+
+    /// Set the database description stored in the index header
+    pub fn with_description(mut self, description: &str) -> Self {
+        self.description = description.to_string();
+        self
+    }
+
+    /// Number of games added so far
+    pub fn num_games(&self) -> usize {
+        self.indices.len()
+    }
+
+    /// Encode a game and append it to the database
+    pub fn add_game(&mut self, game: &Game) -> io::Result<()> {
+        let record = encode_game(game)?;
+
+        let (year, month, day) = parse_pgn_date(game.tag("Date").unwrap_or(""));
+        let mut index = GameIndex {
+            offset: 0,
+            length: 0,
+            white_id: 0,
+            black_id: 0,
+            event_id: 0,
+            site_id: 0,
+            round_id: 0,
+            year,
+            month,
+            day,
+            result: game.result.to_scid(),
+            eco: game.tag("ECO").map(eco_from_str).unwrap_or(0),
+            white_elo: parse_elo(game.tag("WhiteElo")),
+            black_elo: parse_elo(game.tag("BlackElo")),
+            flags: 0,
+            custom_flags: 0,
+            num_half_moves: 0,
+            stored_line_code: 0,
+            final_material: Default::default(),
+            home_pawns: Default::default(),
+            var_count: 0,
+            comment_count: 0,
+            nag_count: 0,
+            deleted: 0,
+            reserved: [0; 5],
+        };
+        set_move_fields(&mut index, game);
+
+        let name = |tag: &str| game.tag(tag).filter(|v| !v.is_empty()).unwrap_or("?");
+        let names = [name("White"), name("Black"), name("Event"), name("Site"), name("Round")];
+        self.append(index, names, &record)
+    }
+
+    /// Append a game record exactly as another database stored it, with
+    /// its index entry. The entry's offset, length and name IDs are
+    /// replaced; `names` are its white, black, event, site and round names.
+    pub fn add_record(&mut self, index: &GameIndex, names: [&str; 5], record: &[u8]) -> io::Result<()> {
+        self.append(index.clone(), names, record)
+    }
+
+    fn append(&mut self, mut index: GameIndex, names: [&str; 5], record: &[u8]) -> io::Result<()> {
+        if record.len() as u32 > MAX_GAME_LENGTH {
+            return Err(io::Error::new(
+                io::ErrorKind::InvalidInput,
+                format!("Game record of {} bytes exceeds the .sg4 limit of {}", record.len(), MAX_GAME_LENGTH)
+            ));
+        }
+
+        let [white, black, event, site, round] = names;
+        index.white_id = self.names.id(NameType::Player, white)?;
+        index.black_id = self.names.id(NameType::Player, black)?;
+        index.event_id = self.names.id(NameType::Event, event)?;
+        index.site_id = self.names.id(NameType::Site, site)?;
+        index.round_id = self.names.id(NameType::Round, round)? as u16;
+        index.offset = self.offset;
+        index.length = record.len() as u32;
+
+        let new_offset = self.offset as u64 + record.len() as u64;
+        if new_offset > u32::MAX as u64 {
+            return Err(io::Error::new(io::ErrorKind::InvalidInput, "Game file exceeds 4GB"));
+        }
+        self.sg4.write_all(record)?;
+        self.offset = new_offset as u32;
+        self.indices.push(index);
+
+        Ok(())
+    }
+
+    /// Write the index and name files and flush everything to disk.
+    /// Returns the number of games written.
+    pub fn finish(mut self) -> io::Result<usize> {
+        self.sg4.flush()?;
+
+        self.names.write(&self.sn4_path)?;
+
+        let mut si4 = BufWriter::new(File::create(&self.si4_path)?);
+        let header = ScidHeader::new(self.indices.len() as u32, &self.description);
+        IndexFile::write_header(&mut si4, &header)?;
+        for index in &self.indices {
+            IndexFile::write_game_index(&mut si4, index)?;
+        }
+        si4.flush()?;
+
+        Ok(self.indices.len())
+    }
+}
+
+/// Set the index fields that summarize a game's moves: the ply count,
+/// the start position and promotion flags, the annotation counts and the
+/// material signatures. Other flags are kept.
+pub(crate) fn set_move_fields(index: &mut GameIndex, game: &Game) {
+    let mut flags = index.flags & !(IDX_FLAG_START | IDX_FLAG_PROMO | IDX_FLAG_UPROMO);
+    if game.tag("FEN").is_some() {
+        flags |= IDX_FLAG_START;
+    }
+    game.moves.for_each_node(&mut |node| {
+        match node.mv.promotion {
+            Some(Piece::Queen) => flags |= IDX_FLAG_PROMO,
+            Some(_) => flags |= IDX_FLAG_PROMO | IDX_FLAG_UPROMO,
+            None => {}
+        }
+    });
+    index.flags = flags;
+    index.num_half_moves = game.ply_count().min(u16::MAX as usize) as u16;
+
+    let (var_count, comment_count, nag_count) = game.moves.annotation_counts();
+    index.var_count = var_count.min(15) as u8;
+    index.comment_count = comment_count.min(15) as u8;
+    index.nag_count = nag_count.min(15) as u8;
+    (index.final_material, index.home_pawns) = game_signatures(game);
+}
+
+/// Parse a PGN "YYYY.MM.DD" date; unknown ("??") parts become 0
+fn parse_pgn_date(date: &str) -> (u16, u8, u8) {
+    let mut parts = date.trim().split('.');
+    let mut next = || parts.next().and_then(|p| p.parse::<u32>().ok()).unwrap_or(0);
+    let year = next();
+    let month = next();
+    let day = next();
+
+    // The index has 11 bits for the year, 4 for the month and 5 for the day
+    let year = if year <= 0x7FF { year as u16 } else { 0 };
+    let month = if month <= 12 { month as u8 } else { 0 };
+    let day = if day <= 31 { day as u8 } else { 0 };
+    (year, month, day)
+}
+
+/// Ratings are 12-bit values in the index; anything else is stored as 0
+fn parse_elo(elo: Option<&str>) -> u16 {
+    elo.and_then(|e| e.trim().parse::<u16>().ok())
+        .filter(|&e| e <= 0x0FFF)
+        .unwrap_or(0)
+}
@@ -0,0 +1,210 @@
+//! Duplicate game detection
+//!
+//! Merged databases often hold several copies of a game whose headers
+//! differ slightly. Games are duplicates when their players, date and
+//! result agree, as far as the strictness asks, and their main lines are
+//! the same moves from the same start position.
+//!
+//! ## Strictness
+//! - `Strict`: identical player names, full date and result
+//! - `Normal`: player names equal once case, spacing and punctuation are
+//!   ignored, same year and result
+//! - `Loose`: the same surnames (the part of a name before its comma, or
+//!   its first word); dates and results aren't compared
+//!
+//! Games are first grouped by these index fields and their ply count, so
+//! only games with a possible duplicate are decoded to compare moves.
+//!
+//! ## Keeping a copy
+//! Of each set of duplicates, the most complete copy is kept: the one with
+//! the most variations, comments and NAGs, then the most known tags, then
+//! the lowest game number.
+
+use std::collections::hash_map::DefaultHasher;
+use std::collections::HashMap;
+use std::hash::{Hash, Hasher};
+use std::io::{self, Write};
+
+use crate::chess::Game;
+use crate::export::{selected_games, GameFilter};
+use crate::scid::{GameIndex, ScidDatabase};
+
+/// Players, date, result and ply count; games can only be duplicates when
+/// these agree
+type HeaderKey = (String, String, u32, u8, u16);
+
+/// Annotation count, then known tag count
+type Completeness = (usize, usize);
+
+/// How closely the headers of duplicate games have to agree
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum DuplicateStrictness {
+    Strict,
+    #[default]
+    Normal,
+    Loose,
+}
+
+/// Copies of one game
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct DuplicateGroup {
+    /// Zero-based number of the copy to keep
+    pub kept: usize,
+    /// The other copies, in database order
+    pub dropped: Vec<usize>,
+}
+
+/// Every set of duplicates found, in order of the kept games
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct Duplicates {
+    pub groups: Vec<DuplicateGroup>,
+}
+
+impl Duplicates {
+    /// Zero-based numbers of every game to leave out, for
+    /// `GameFilter::with_excluded_games`
+    pub fn dropped(&self) -> impl Iterator<Item = usize> + '_ {
+        self.groups.iter().flat_map(|group| group.dropped.iter().copied())
+    }
+
+    /// Write each set of duplicates, listing the kept game first
+    pub fn write_report<W: Write>(&self, database: &ScidDatabase, writer: &mut W) -> io::Result<()> {
+        let describe = |game_num: usize| {
+            let game_index = &database.game_indices()[game_num];
+            format!("{:>6}  {} - {}, {} {}, {}", game_num + 1,
+                    database.player_name(game_index.white_id).unwrap_or("?"),
+                    database.player_name(game_index.black_id).unwrap_or("?"),
+                    database.event_name(game_index.event_id).unwrap_or("?"),
+                    game_index.date_string(),
+                    game_index.result_string())
+        };
+        for (i, group) in self.groups.iter().enumerate() {
+            if i > 0 {
+                writeln!(writer)?;
+            }
+            writeln!(writer, "Kept    {}", describe(group.kept))?;
+            for &dropped in &group.dropped {
+                writeln!(writer, "Dropped {}", describe(dropped))?;
+            }
+        }
+        Ok(())
+    }
+}
+
+/// Duplicate detection over a SCID database
+pub struct DuplicateFinder {
+    strictness: DuplicateStrictness,
+    filter: GameFilter,
+}
+
+impl DuplicateFinder {
+    pub fn new() -> Self {
+        DuplicateFinder { strictness: DuplicateStrictness::Normal, filter: GameFilter::new() }
+    }
+
+    pub fn with_strictness(mut self, strictness: DuplicateStrictness) -> Self {
+        self.strictness = strictness;
+        self
+    }
+
+    /// Only look at games the filter matches
+    pub fn with_filter(mut self, filter: GameFilter) -> Self {
+        self.filter = filter;
+        self
+    }
+
+    /// Find the duplicates; games that can't be decoded are never
+    /// duplicates
+    pub fn find(&self, database: &mut ScidDatabase) -> Duplicates {
+        let mut by_headers: HashMap<HeaderKey, Vec<(usize, GameIndex)>> = HashMap::new();
+        for (game_num, game_index) in selected_games(database, &self.filter, None) {
+            by_headers.entry(self.header_key(database, &game_index)).or_default().push((game_num, game_index));
+        }
+
+        let mut groups = Vec::new();
+        for candidates in by_headers.into_values().filter(|games| games.len() > 1) {
+            // Games with the same moves, with their completeness
+            let mut by_moves: HashMap<u64, Vec<(Completeness, usize)>> = HashMap::new();
+            for (game_num, game_index) in candidates {
+                if let Ok(game) = database.load_game(&game_index) {
+                    by_moves.entry(moves_hash(&game)).or_default().push((completeness(&game), game_num));
+                }
+            }
+            for mut copies in by_moves.into_values().filter(|copies| copies.len() > 1) {
+                // Most complete first, then lowest number
+                copies.sort_by(|(a, a_num), (b, b_num)| b.cmp(a).then(a_num.cmp(b_num)));
+                let kept = copies[0].1;
+                let mut dropped: Vec<usize> = copies[1..].iter().map(|&(_, game_num)| game_num).collect();
+                dropped.sort_unstable();
+                groups.push(DuplicateGroup { kept, dropped });
+            }
+        }
+        groups.sort_by_key(|group| group.kept);
+        Duplicates { groups }
+    }
+
+    /// Players, date and result as far as the strictness compares them,
+    /// and the ply count, which duplicates always share
+    fn header_key(&self, database: &ScidDatabase, game_index: &GameIndex) -> HeaderKey {
+        let name = |id: u32| {
+            let name = database.player_name(id).unwrap_or("");
+            match self.strictness {
+                DuplicateStrictness::Strict => name.to_string(),
+                DuplicateStrictness::Normal => normalize_name(name),
+                DuplicateStrictness::Loose => surname(name),
+            }
+        };
+        let (date, result) = match self.strictness {
+            DuplicateStrictness::Strict => {
+                ((game_index.year as u32) << 9 | (game_index.month as u32) << 5 | game_index.day as u32, game_index.result)
+            }
+            DuplicateStrictness::Normal => (game_index.year as u32, game_index.result),
+            DuplicateStrictness::Loose => (0, 0),
+        };
+        (name(game_index.white_id), name(game_index.black_id), date, result, game_index.num_half_moves)
+    }
+}
+
+impl Default for DuplicateFinder {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+/// Lowercase letters and digits, with single spaces between words
+fn normalize_name(name: &str) -> String {
+    name.split(|c: char| !c.is_alphanumeric())
+        .filter(|word| !word.is_empty())
+        .map(|word| word.to_lowercase())
+        .collect::<Vec<_>>()
+        .join(" ")
+}
+
+/// The normalized part of a name before its comma, or its first word when
+/// it has none
+fn surname(name: &str) -> String {
+    match name.split_once(',') {
+        Some((surname, _)) => normalize_name(surname),
+        None => normalize_name(name).split(' ').next().unwrap_or("").to_string(),
+    }
+}
+
+/// Hash of the start position and main line moves
+fn moves_hash(game: &Game) -> u64 {
+    let mut hasher = DefaultHasher::new();
+    game.tag("FEN").hash(&mut hasher);
+    for mv in game.main_line() {
+        (mv.from_square, mv.to_square, mv.promotion.map(|piece| piece.index())).hash(&mut hasher);
+    }
+    hasher.finish()
+}
+
+/// Annotations (variations, comments and NAGs), then tags with a known
+/// value
+fn completeness(game: &Game) -> Completeness {
+    let (variations, comments, nags) = game.moves.annotation_counts();
+    let tags = game.tags.iter()
+        .filter(|(_, value)| !value.is_empty() && !value.starts_with('?'))
+        .count();
+    (variations + comments + nags, tags)
+}
@@ -0,0 +1,319 @@
+//! Material search: find the games with a given material balance
+//!
+//! A pattern such as `KRP vs KR` names the men of each side, white first.
+//! It can be matched against each game's final position, or against every
+//! position of the main line, reporting the first one that has it.
+//!
+//! ## Patterns
+//! Each side is a king followed by its other men, one letter per man, so
+//! `KRPP` is a rook and two pawns. `P*` stands for any number of pawns,
+//! and the sides can be separated by `vs`, `v`, `-` or nothing at all:
+//! `KBP* vs KBP*`, `KQ-KR` and `KRPKR` are all patterns. The index stores
+//! at most 3 of each piece, so patterns can't ask for more.
+//!
+//! ## Index use
+//! With the final position as scope, the final material stored in the
+//! index decides most games without decoding them; only games written
+//! without it, or searches that need the position itself (side to move,
+//! bishop colours), are replayed. For any position in the game, a game is
+//! only replayed when its final material could have come from the pattern.
+
+use std::str::FromStr;
+
+use crate::chess::position::{square_file, square_rank};
+use crate::chess::{Color, Piece, Position};
+use crate::export::{selected_games, GameFilter};
+use crate::scid::index::IDX_FLAG_PROMO;
+use crate::scid::signature::{FinalMaterial, SideMaterial};
+use crate::scid::{GameIndex, ScidDatabase};
+
+/// Which positions of a game are matched
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum MaterialScope {
+    /// The position at the end of the main line
+    #[default]
+    Final,
+    /// Any position of the main line, including the start
+    Any,
+}
+
+/// Colours of the squares the two sides' bishops stand on
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum BishopColors {
+    #[default]
+    Any,
+    /// One bishop each, on squares of the same colour
+    Same,
+    /// One bishop each, on squares of different colours
+    Opposite,
+}
+
+/// Material balance to look for
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct MaterialPattern {
+    material: FinalMaterial,
+    /// Whether each side (white, black) may have any number of pawns
+    any_pawns: [bool; 2],
+}
+
+impl MaterialPattern {
+    pub fn parse(text: &str) -> Result<MaterialPattern, String> {
+        let invalid = || format!("invalid material '{}' (expected two sides such as KRP vs KR)", text);
+        let compact: String = text.split_whitespace().collect::<String>().to_uppercase()
+            .replace("VS", "V")
+            .replace('-', "V");
+        let (white, black) = match compact.split_once('V') {
+            Some(sides) => sides,
+            // Without a separator, black's king starts the second side
+            None => compact.char_indices().skip(1).find(|&(_, c)| c == 'K')
+                .map(|(at, _)| compact.split_at(at))
+                .ok_or_else(invalid)?,
+        };
+
+        let mut pattern = MaterialPattern { material: FinalMaterial::default(), any_pawns: [false; 2] };
+        for (side, men) in [white, black].into_iter().enumerate() {
+            let mut letters = men.chars().peekable();
+            if letters.next() != Some('K') {
+                return Err(invalid());
+            }
+            let counts = if side == 0 { &mut pattern.material.white } else { &mut pattern.material.black };
+            while let Some(letter) = letters.next() {
+                let count = match letter {
+                    'Q' => &mut counts.queens,
+                    'R' => &mut counts.rooks,
+                    'B' => &mut counts.bishops,
+                    'N' => &mut counts.knights,
+                    'P' if letters.next_if_eq(&'*').is_some() => {
+                        pattern.any_pawns[side] = true;
+                        continue;
+                    }
+                    'P' => &mut counts.pawns,
+                    _ => return Err(invalid()),
+                };
+                *count += 1;
+            }
+            if counts.pawns > 8 {
+                return Err(format!("material '{}' has more than 8 pawns on a side", text));
+            }
+            if [counts.queens, counts.rooks, counts.bishops, counts.knights].iter().any(|&count| count > 3) {
+                return Err(format!("material '{}' has more than 3 of a piece, which the index can't store", text));
+            }
+        }
+        Ok(pattern)
+    }
+
+    /// The same balance with the sides swapped
+    pub fn flipped(&self) -> MaterialPattern {
+        MaterialPattern {
+            material: FinalMaterial { white: self.material.black, black: self.material.white },
+            any_pawns: [self.any_pawns[1], self.any_pawns[0]],
+        }
+    }
+
+    /// Whether `material` is exactly this balance
+    pub fn matches(&self, material: &FinalMaterial) -> bool {
+        self.with_pawns_of(material) == *material
+    }
+
+    /// Whether a game ending with `material` could have passed through this
+    /// balance; see `FinalMaterial::could_reach`
+    pub fn could_lead_to(&self, material: &FinalMaterial, promotions: bool) -> bool {
+        let mut most = self.material;
+        for (side, any) in [&mut most.white, &mut most.black].into_iter().zip(self.any_pawns) {
+            if any {
+                side.pawns = 15;
+            }
+        }
+        material.could_reach(&most, promotions)
+    }
+
+    /// The pattern's material, taking pawn counts it leaves open from
+    /// `material`
+    fn with_pawns_of(&self, material: &FinalMaterial) -> FinalMaterial {
+        let mut wanted = self.material;
+        let sides: [(&mut SideMaterial, &SideMaterial); 2] =
+            [(&mut wanted.white, &material.white), (&mut wanted.black, &material.black)];
+        for ((side, actual), any) in sides.into_iter().zip(self.any_pawns) {
+            if any {
+                side.pawns = actual.pawns;
+            }
+        }
+        wanted
+    }
+}
+
+impl FromStr for MaterialPattern {
+    type Err = String;
+
+    fn from_str(text: &str) -> Result<Self, Self::Err> {
+        MaterialPattern::parse(text)
+    }
+}
+
+/// A game with the material balance
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct MaterialHit {
+    /// Zero-based game number in the database
+    pub game: usize,
+    /// Half-moves played from the game's start position at the matching
+    /// position
+    pub ply: u32,
+    /// Whether the balance was found with the sides swapped
+    pub flipped: bool,
+}
+
+/// Material search over a SCID database
+pub struct MaterialSearch {
+    pattern: MaterialPattern,
+    scope: MaterialScope,
+    to_move: Option<Color>,
+    flip: bool,
+    bishops: BishopColors,
+    filter: GameFilter,
+    max_games: Option<usize>,
+}
+
+impl MaterialSearch {
+    pub fn new(pattern: MaterialPattern) -> Self {
+        MaterialSearch {
+            pattern,
+            scope: MaterialScope::Final,
+            to_move: None,
+            flip: false,
+            bishops: BishopColors::Any,
+            filter: GameFilter::new(),
+            max_games: None,
+        }
+    }
+
+    pub fn with_scope(mut self, scope: MaterialScope) -> Self {
+        self.scope = scope;
+        self
+    }
+
+    /// Only match positions with this side to move; the side is the
+    /// pattern's, so it swaps too when the pattern is flipped
+    pub fn with_side_to_move(mut self, color: Color) -> Self {
+        self.to_move = Some(color);
+        self
+    }
+
+    /// Also match the balance with the sides swapped
+    pub fn with_flip(mut self, flip: bool) -> Self {
+        self.flip = flip;
+        self
+    }
+
+    pub fn with_bishops(mut self, bishops: BishopColors) -> Self {
+        self.bishops = bishops;
+        self
+    }
+
+    /// Only search games the filter matches
+    pub fn with_filter(mut self, filter: GameFilter) -> Self {
+        self.filter = filter;
+        self
+    }
+
+    /// Stop after this many games have the balance
+    pub fn with_max_games(mut self, max: usize) -> Self {
+        self.max_games = Some(max);
+        self
+    }
+
+    /// The games the index allows to have the balance, in database order
+    pub fn candidates(&self, database: &ScidDatabase) -> Vec<(usize, GameIndex)> {
+        let mut games = selected_games(database, &self.filter, None);
+        games.retain(|(_, game_index)| self.orientations().any(|(pattern, _)| self.could_match(&pattern, game_index)));
+        games
+    }
+
+    /// Every candidate game with the balance; games that need decoding but
+    /// can't be decoded are skipped
+    pub fn search(&self, database: &mut ScidDatabase) -> Vec<MaterialHit> {
+        let mut hits = Vec::new();
+        for (game_num, game_index) in self.candidates(database) {
+            if self.max_games.is_some_and(|max| hits.len() >= max) {
+                break;
+            }
+            if let Some((ply, flipped)) = self.search_game(database, &game_index) {
+                hits.push(MaterialHit { game: game_num, ply, flipped });
+            }
+        }
+        hits
+    }
+
+    /// The pattern, then its flipped form if flips are allowed, with whether
+    /// each is flipped
+    fn orientations(&self) -> impl Iterator<Item = (MaterialPattern, bool)> {
+        let flipped = self.flip.then(|| (self.pattern.flipped(), true));
+        std::iter::once((self.pattern, false)).chain(flipped)
+    }
+
+    fn could_match(&self, pattern: &MaterialPattern, game_index: &GameIndex) -> bool {
+        let material = &game_index.final_material;
+        match self.scope {
+            // Bare kings look like a missing signature, so they are kept
+            MaterialScope::Final => material.is_empty() || pattern.matches(material),
+            MaterialScope::Any => pattern.could_lead_to(material, game_index.flags & IDX_FLAG_PROMO != 0),
+        }
+    }
+
+    /// The matching ply, and whether the match is flipped
+    fn search_game(&self, database: &mut ScidDatabase, game_index: &GameIndex) -> Option<(u32, bool)> {
+        let material = &game_index.final_material;
+        let decided_by_index = self.scope == MaterialScope::Final
+            && !material.is_empty()
+            && self.to_move.is_none()
+            && self.bishops == BishopColors::Any;
+        if decided_by_index {
+            let (_, flipped) = self.orientations().find(|(pattern, _)| pattern.matches(material))?;
+            return Some((game_index.num_half_moves as u32, flipped));
+        }
+
+        let game = database.load_game(game_index).ok()?;
+        let mut pos = game.start_position().ok()?;
+        let mut ply = 0;
+        let mut moves = game.main_line();
+        loop {
+            let next = moves.next();
+            if self.scope == MaterialScope::Any || next.is_none() {
+                if let Some(flipped) = self.matches(&pos) {
+                    return Some((ply, flipped));
+                }
+            }
+            pos.make_move(next?);
+            ply += 1;
+        }
+    }
+
+    /// Whether `pos` has the balance, and if so whether flipped
+    fn matches(&self, pos: &Position) -> Option<bool> {
+        let material = FinalMaterial::of(pos);
+        self.orientations()
+            .find(|(pattern, flipped)| {
+                pattern.matches(&material)
+                    && self.to_move.is_none_or(|color| {
+                        pos.to_move == if *flipped { color.opposite() } else { color }
+                    })
+                    && self.bishops_match(pos)
+            })
+            .map(|(_, flipped)| flipped)
+    }
+
+    fn bishops_match(&self, pos: &Position) -> bool {
+        if self.bishops == BishopColors::Any {
+            return true;
+        }
+        let mut shades = [Vec::new(), Vec::new()];
+        for square in 0..64 {
+            if let Some((color, Piece::Bishop)) = pos.piece_at(square) {
+                shades[color.index()].push((square_file(square) + square_rank(square)) % 2);
+            }
+        }
+        match (shades[0].as_slice(), shades[1].as_slice()) {
+            ([white], [black]) => (white == black) == (self.bishops == BishopColors::Same),
+            _ => false,
+        }
+    }
+}
@@ -0,0 +1,15 @@
+//! Searches over the games of a SCID database
+//!
+//! Searches choose candidates from the index first, with the same
+//! `GameFilter` the exporters use, and only decode the games that could
+//! still match.
+
+pub mod duplicates;
+pub mod material;
+pub mod position;
+pub mod tree;
+
+pub use duplicates::{DuplicateFinder, DuplicateGroup, DuplicateStrictness, Duplicates};
+pub use material::{BishopColors, MaterialHit, MaterialPattern, MaterialScope, MaterialSearch};
+pub use position::{PositionHit, PositionMatch, PositionSearch};
+pub use tree::{OpeningTree, TreeMove, TreeReport, TreeStats};
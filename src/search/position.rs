@@ -0,0 +1,219 @@
+//! Position search: find the games that reach a given position
+//!
+//! Each candidate's main line is replayed from its start position and
+//! compared with the target after every move. Only the first time a game
+//! reaches the position is reported.
+//!
+//! ## Pruning
+//! Before a game is decoded, its index record must show it could reach
+//! the target (see `scid::signature`):
+//! - a game from the standard start needs at least as many half-moves as
+//!   it takes to bring each man of the target that isn't on its starting
+//!   square there, one move per man (castling moves two)
+//! - its final material can't exceed the target's, piece by piece,
+//!   unless the game has a promotion
+//! - for standard-start games, the home pawns missing from the target must
+//!   be exactly the first ones the game moved
+//!
+//! While replaying, a game is abandoned as soon as it has fewer pawns or
+//! pieces of a colour than the target, or has moved a home pawn the
+//! target still has, since none of these can be undone.
+
+use crate::chess::{Game, Move, Piece, Position};
+use crate::export::{selected_games, GameFilter};
+use crate::scid::index::{IDX_FLAG_PROMO, IDX_FLAG_START};
+use crate::scid::signature::{home_pawns_gone, FinalMaterial};
+use crate::scid::{GameIndex, ScidDatabase};
+
+/// How a game position has to agree with the target
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum PositionMatch {
+    /// Same pieces, side to move, castling rights and en passant capture
+    #[default]
+    Exact,
+    /// Same pieces on the same squares, whoever is to move
+    Pieces,
+}
+
+/// A game that reaches the target position
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct PositionHit {
+    /// Zero-based game number in the database
+    pub game: usize,
+    /// Half-moves played from the game's start position when the target
+    /// is first reached (0 is the start position itself)
+    pub ply: u32,
+}
+
+/// Men (pieces other than the king) and pawns per colour, for checking
+/// whether a position can still lead to the target
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+struct Material {
+    men: [u32; 2],
+    pawns: [u32; 2],
+}
+
+impl Material {
+    fn of(pos: &Position) -> Material {
+        let mut material = Material { men: [0; 2], pawns: [0; 2] };
+        for square in 0..64 {
+            match pos.piece_at(square) {
+                Some((_, Piece::King)) | None => {}
+                Some((color, piece)) => {
+                    material.men[color.index()] += 1;
+                    if piece == Piece::Pawn {
+                        material.pawns[color.index()] += 1;
+                    }
+                }
+            }
+        }
+        material
+    }
+
+    /// Whether `self` has at least as many men and pawns of each colour
+    fn covers(&self, other: &Material) -> bool {
+        (0..2).all(|color| self.men[color] >= other.men[color] && self.pawns[color] >= other.pawns[color])
+    }
+}
+
+/// Fewest half-moves from the standard start after which `target` could
+/// stand on the board. Every man not on a square the start position gives
+/// it has moved at least once, and only castling moves two men at a time.
+fn min_plies(target: &Position) -> u32 {
+    let start = Position::starting_position();
+    let mut moved = [0u32; 2];
+    let mut king_moved = [false; 2];
+    for square in 0..64 {
+        if let Some((color, piece)) = target.piece_at(square) {
+            if start.piece_at(square) != Some((color, piece)) {
+                moved[color.index()] += 1;
+                king_moved[color.index()] |= piece == Piece::King;
+            }
+        }
+    }
+    let [white, black] = [0, 1].map(|color| moved[color] - (king_moved[color] && moved[color] > 1) as u32);
+    // White moves on odd plies and black on even ones
+    (2 * white).saturating_sub(1).max(2 * black)
+}
+
+/// Position search over a SCID database
+pub struct PositionSearch {
+    target: Position,
+    matching: PositionMatch,
+    filter: GameFilter,
+    max_games: Option<usize>,
+    target_key: u64,
+    target_material: Material,
+    target_plies: u32,
+    target_final_material: FinalMaterial,
+    target_gone: u16,
+}
+
+impl PositionSearch {
+    pub fn new(target: Position) -> Self {
+        PositionSearch {
+            target_key: target.polyglot_key(),
+            target_material: Material::of(&target),
+            target_plies: min_plies(&target),
+            target_final_material: FinalMaterial::of(&target),
+            target_gone: home_pawns_gone(&target),
+            target,
+            matching: PositionMatch::Exact,
+            filter: GameFilter::new(),
+            max_games: None,
+        }
+    }
+
+    pub fn with_match(mut self, matching: PositionMatch) -> Self {
+        self.matching = matching;
+        self
+    }
+
+    /// Only search games the filter matches
+    pub fn with_filter(mut self, filter: GameFilter) -> Self {
+        self.filter = filter;
+        self
+    }
+
+    /// Stop after this many games reach the position
+    pub fn with_max_games(mut self, max: usize) -> Self {
+        self.max_games = Some(max);
+        self
+    }
+
+    /// The games the index allows to reach the target, in database order
+    pub fn candidates(&self, database: &ScidDatabase) -> Vec<(usize, GameIndex)> {
+        let mut games = selected_games(database, &self.filter, None);
+        games.retain(|(_, game_index)| self.could_reach(game_index));
+        games
+    }
+
+    /// Every candidate game that reaches the target, with the ply where it
+    /// first does; games that can't be decoded are skipped
+    pub fn search(&self, database: &mut ScidDatabase) -> Vec<PositionHit> {
+        let mut hits = Vec::new();
+        for (game_num, game_index) in self.candidates(database) {
+            if self.max_games.is_some_and(|max| hits.len() >= max) {
+                break;
+            }
+            if let Some(ply) = self.search_game(database, &game_index) {
+                hits.push(PositionHit { game: game_num, ply });
+            }
+        }
+        hits
+    }
+
+    /// Whether the index record leaves it possible for the game to reach
+    /// the target
+    fn could_reach(&self, game_index: &GameIndex) -> bool {
+        let standard_start = game_index.flags & IDX_FLAG_START == 0;
+        if standard_start && u32::from(game_index.num_half_moves) < self.target_plies {
+            return false;
+        }
+        let promotions = game_index.flags & IDX_FLAG_PROMO != 0;
+        if !game_index.final_material.could_reach(&self.target_final_material, promotions) {
+            return false;
+        }
+        // Databases written without the summaries have no final material and
+        // no home pawn moves, which would rule out every game
+        game_index.final_material.is_empty()
+            || !standard_start
+            || game_index.home_pawns.could_reach(self.target_gone)
+    }
+
+    /// The first ply at which the game's main line reaches the target
+    fn search_game(&self, database: &mut ScidDatabase, game_index: &GameIndex) -> Option<u32> {
+        let game = database.load_game(game_index).ok()?;
+        self.find_in_game(&game).map(|(ply, _)| ply)
+    }
+
+    /// The first ply at which the game's main line reaches the target, and
+    /// the move played from there (none if the game ends there)
+    pub(crate) fn find_in_game<'g>(&self, game: &'g Game) -> Option<(u32, Option<&'g Move>)> {
+        let mut pos = game.start_position().ok()?;
+        let mut ply = 0;
+        let mut moves = game.main_line();
+        loop {
+            let next = moves.next();
+            if self.matches(&pos) {
+                return Some((ply, next));
+            }
+            if !self.still_reachable(&pos) {
+                return None;
+            }
+            pos.make_move(next?);
+            ply += 1;
+        }
+    }
+
+    fn matches(&self, pos: &Position) -> bool {
+        pos.same_pieces(&self.target)
+            && (self.matching == PositionMatch::Pieces || pos.polyglot_key() == self.target_key)
+    }
+
+    /// Whether later moves could still lead from `pos` to the target: no
+    /// missing material, and the target's home pawns are all still at home
+    fn still_reachable(&self, pos: &Position) -> bool {
+        Material::of(pos).covers(&self.target_material) && home_pawns_gone(pos) & !self.target_gone == 0
+    }
+}
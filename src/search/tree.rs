@@ -0,0 +1,199 @@
+//! Opening tree: statistics for each move played from a position
+//!
+//! Every game reaching the position, directly or by transposition, adds its
+//! next main line move, result, ratings and year to that move's row. Games
+//! are chosen and pruned as for a position search, so the tree agrees with
+//! `PositionSearch` on which games reach the position.
+//!
+//! ## Statistics
+//! - White, draw and black percentages are of the games with a result
+//! - Average Elo is that of the side to move, over the games it was rated
+//! - Performance is the side to move's, over games with a result against a
+//!   rated opponent: their average rating plus 400 times (wins - losses)
+//!   divided by games
+//! - Year is the most recent year among the games with a known date
+
+use std::collections::HashMap;
+use std::io::{self, Write};
+
+use serde::Serialize;
+
+use crate::chess::{Color, GameResult, Position};
+use crate::export::GameFilter;
+use crate::report::Performance;
+use crate::scid::{GameIndex, ScidDatabase};
+use super::PositionSearch;
+
+/// Totals for a move, or for the whole position
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub struct TreeStats {
+    pub games: u32,
+    pub white_wins: u32,
+    pub draws: u32,
+    pub black_wins: u32,
+    pub white_percent: f64,
+    pub draw_percent: f64,
+    pub black_percent: f64,
+    pub average_elo: Option<u16>,
+    pub performance: Option<u16>,
+    pub last_year: Option<u16>,
+}
+
+/// One continuation from the position
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub struct TreeMove {
+    pub san: String,
+    pub uci: String,
+    #[serde(flatten)]
+    pub stats: TreeStats,
+}
+
+/// The moves played from a position, most played first
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub struct TreeReport {
+    pub fen: String,
+    /// Every game reaching the position, including those ending there
+    pub total: TreeStats,
+    pub moves: Vec<TreeMove>,
+}
+
+impl TreeReport {
+    /// Write the report as an aligned text table
+    pub fn write_table<W: Write>(&self, writer: &mut W) -> io::Result<()> {
+        writeln!(writer, "Position: {}", self.fen)?;
+        writeln!(writer, "{:<8} {:>6} {:>6} {:>6} {:>6} {:>6} {:>6} {:>5}",
+                 "Move", "Games", "White", "Draw", "Black", "AvElo", "Perf", "Year")?;
+        for tree_move in &self.moves {
+            write_row(writer, &tree_move.san, &tree_move.stats)?;
+        }
+        write_row(writer, "Total", &self.total)
+    }
+}
+
+fn write_row<W: Write>(writer: &mut W, label: &str, stats: &TreeStats) -> io::Result<()> {
+    let optional = |value: Option<u16>| value.map_or("-".to_string(), |value| value.to_string());
+    writeln!(writer, "{:<8} {:>6} {:>5.1}% {:>5.1}% {:>5.1}% {:>6} {:>6} {:>5}",
+             label, stats.games, stats.white_percent, stats.draw_percent, stats.black_percent,
+             optional(stats.average_elo), optional(stats.performance), optional(stats.last_year))
+}
+
+/// Running totals behind `TreeStats`
+#[derive(Debug, Clone, Default)]
+struct Tally {
+    games: u32,
+    white_wins: u32,
+    draws: u32,
+    black_wins: u32,
+    elo_total: u64,
+    elo_games: u32,
+    performance: Performance,
+    last_year: Option<u16>,
+}
+
+impl Tally {
+    fn add(&mut self, game_index: &GameIndex, to_move: Color) {
+        self.games += 1;
+        let result = GameResult::from_scid(game_index.result);
+        match result {
+            GameResult::WhiteWins => self.white_wins += 1,
+            GameResult::Draw => self.draws += 1,
+            GameResult::BlackWins => self.black_wins += 1,
+            GameResult::Unknown => {}
+        }
+
+        let elo = match to_move {
+            Color::White => game_index.white_elo,
+            Color::Black => game_index.black_elo,
+        };
+        if elo > 0 {
+            self.elo_total += elo as u64;
+            self.elo_games += 1;
+        }
+        self.performance.add(game_index, to_move);
+
+        if game_index.year > 0 {
+            self.last_year = self.last_year.max(Some(game_index.year));
+        }
+    }
+
+    fn stats(&self) -> TreeStats {
+        let decided = self.white_wins + self.draws + self.black_wins;
+        let percent = |count: u32| {
+            if decided == 0 { 0.0 } else { (count as f64 * 1000.0 / decided as f64).round() / 10.0 }
+        };
+        TreeStats {
+            games: self.games,
+            white_wins: self.white_wins,
+            draws: self.draws,
+            black_wins: self.black_wins,
+            white_percent: percent(self.white_wins),
+            draw_percent: percent(self.draws),
+            black_percent: percent(self.black_wins),
+            average_elo: (self.elo_games > 0)
+                .then(|| (self.elo_total as f64 / self.elo_games as f64).round() as u16),
+            performance: self.performance.rating(),
+            last_year: self.last_year,
+        }
+    }
+}
+
+/// Opening tree over a SCID database
+pub struct OpeningTree {
+    position: Position,
+    search: PositionSearch,
+}
+
+impl OpeningTree {
+    pub fn new(position: Position) -> Self {
+        OpeningTree { search: PositionSearch::new(position.clone()), position }
+    }
+
+    /// The tree after `moves` from the standard start, given in SAN with or
+    /// without move numbers, e.g. "1. e4 c5 2. Nf3"
+    pub fn from_moves(moves: &str) -> Result<Self, String> {
+        let mut position = Position::starting_position();
+        for token in moves.split_whitespace() {
+            // Move numbers may be written apart ("1. e4") or joined ("1.e4")
+            let san = token.trim_start_matches(|c: char| c.is_ascii_digit() || c == '.');
+            if san.is_empty() {
+                continue;
+            }
+            let mv = position.parse_san(san).ok_or_else(|| format!("illegal move '{}'", san))?;
+            position.make_move(&mv);
+        }
+        Ok(OpeningTree::new(position))
+    }
+
+    /// Only count games the filter matches
+    pub fn with_filter(mut self, filter: GameFilter) -> Self {
+        self.search = self.search.with_filter(filter);
+        self
+    }
+
+    /// Statistics for every move played from the position; games that
+    /// can't be decoded are left out
+    pub fn build(&self, database: &mut ScidDatabase) -> TreeReport {
+        let mut total = Tally::default();
+        let mut tallies: HashMap<String, (String, Tally)> = HashMap::new();
+        for (_, game_index) in self.search.candidates(database) {
+            let Ok(game) = database.load_game(&game_index) else {
+                continue;
+            };
+            let Some((_, next)) = self.search.find_in_game(&game) else {
+                continue;
+            };
+            total.add(&game_index, self.position.to_move);
+            if let Some(mv) = next {
+                let (_, tally) = tallies.entry(mv.to_uci())
+                    .or_insert_with(|| (self.position.san(mv), Tally::default()));
+                tally.add(&game_index, self.position.to_move);
+            }
+        }
+
+        let mut moves: Vec<TreeMove> = tallies.into_iter()
+            .map(|(uci, (san, tally))| TreeMove { san, uci, stats: tally.stats() })
+            .collect();
+        moves.sort_by(|a, b| b.stats.games.cmp(&a.stats.games).then_with(|| a.san.cmp(&b.san)));
+        TreeReport { fen: self.position.to_fen(), total: total.stats(), moves }
+    }
+}
@@ -0,0 +1,115 @@
+mod common;
+
+use std::fs;
+
+use common::TempDir;
+use scidtopgn::chess::{polyglot_move, Color, Position};
+use scidtopgn::export::{BookEntry, PolyglotExporter};
+use scidtopgn::pgn::PgnReader;
+use scidtopgn::scid::{ScidDatabase, ScidWriter};
+
+const PGN: &str = r#"[White "Alpha"]
+[Black "Beta"]
+[Result "1-0"]
+[WhiteElo "2500"]
+[BlackElo "2100"]
+
+1. e4 e5 2. Nf3 Nc6 1-0
+
+[White "Beta"]
+[Black "Alpha"]
+[Result "1/2-1/2"]
+[WhiteElo "2100"]
+[BlackElo "2500"]
+
+1. e4 c5 2. Nf3 1/2-1/2
+
+[White "Gamma"]
+[Black "Delta"]
+[Result "0-1"]
+
+1. d4 d5 0-1
+"#;
+
+fn database(dir: &TempDir) -> ScidDatabase {
+    let base = dir.path().join("games");
+    let mut writer = ScidWriter::create(&base).unwrap();
+    for game in PgnReader::new(PGN.as_bytes()) {
+        writer.add_game(&game.unwrap()).unwrap();
+    }
+    writer.finish().unwrap();
+    ScidDatabase::load(&base).unwrap()
+}
+
+fn entries(mut exporter: PolyglotExporter) -> Vec<BookEntry> {
+    let dir = TempDir::new("book");
+    exporter.entries(&mut database(&dir))
+}
+
+/// The weight of `san` played after `moves` from the start, if it's in the book
+fn weight(book: &[BookEntry], moves: &str, san: &str) -> Option<u16> {
+    let mut pos = Position::starting_position();
+    for played in moves.split_whitespace() {
+        let mv = pos.parse_san(played).unwrap();
+        pos.make_move(&mv);
+    }
+    let mv = polyglot_move(&pos.parse_san(san).unwrap());
+    let key = pos.polyglot_key();
+    book.iter().find(|entry| entry.key == key && entry.mv == mv).map(|entry| entry.weight)
+}
+
+#[test]
+fn weights_moves_by_result() {
+    let book = entries(PolyglotExporter::new());
+    assert!(book.windows(2).all(|pair| pair[0].key <= pair[1].key));
+
+    // A win and a draw for White
+    assert_eq!(weight(&book, "", "e4"), Some(3));
+    // Lost by White
+    assert_eq!(weight(&book, "", "d4"), None);
+    assert_eq!(weight(&book, "d4", "d5"), Some(2));
+    assert_eq!(weight(&book, "e4", "e5"), None);
+    assert_eq!(weight(&book, "e4", "c5"), Some(1));
+    assert_eq!(weight(&book, "e4 e5", "Nf3"), Some(2));
+
+    // Best move first within a position
+    let start = Position::starting_position().polyglot_key();
+    let first = book.iter().find(|entry| entry.key == start).unwrap();
+    assert_eq!(first.mv, polyglot_move(&Position::starting_position().parse_san("e4").unwrap()));
+}
+
+#[test]
+fn filters_by_elo_ply_side_and_games() {
+    // Only Alpha's moves
+    let book = entries(PolyglotExporter::new().with_min_elo(2400));
+    assert_eq!(weight(&book, "", "e4"), Some(2));
+    assert_eq!(weight(&book, "e4", "c5"), Some(1));
+    assert_eq!(weight(&book, "d4", "d5"), None);
+
+    let book = entries(PolyglotExporter::new().with_max_ply(1));
+    assert_eq!(book.len(), 1);
+    assert_eq!(weight(&book, "", "e4"), Some(3));
+
+    let book = entries(PolyglotExporter::new().with_side(Color::Black));
+    assert_eq!(weight(&book, "", "e4"), None);
+    assert_eq!(weight(&book, "d4", "d5"), Some(2));
+
+    let book = entries(PolyglotExporter::new().with_min_games(2));
+    assert_eq!(book.len(), 1);
+    assert_eq!(weight(&book, "", "e4"), Some(3));
+}
+
+#[test]
+fn writes_bin_file() {
+    let dir = TempDir::new("book-file");
+    let mut database = database(&dir);
+    let path = dir.path().join("book.bin");
+    let games = PolyglotExporter::new().with_max_ply(2).export(&mut database, &path).unwrap();
+    assert_eq!(games, 3);
+
+    let data = fs::read(&path).unwrap();
+    assert_eq!(data.len() % BookEntry::SIZE, 0);
+    let book = BookEntry::read_book(&data);
+    assert_eq!(book, PolyglotExporter::new().with_max_ply(2).entries(&mut database));
+    assert_eq!(book[0].to_bytes(), data[..BookEntry::SIZE]);
+}
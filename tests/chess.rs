@@ -0,0 +1,103 @@
+use scidtopgn::chess::{polyglot_move, Position};
+
+fn perft(pos: &Position, depth: u32) -> u64 {
+    if depth == 0 {
+        return 1;
+    }
+    pos.legal_moves().iter().map(|mv| {
+        let mut next = pos.clone();
+        next.make_move(mv);
+        perft(&next, depth - 1)
+    }).sum()
+}
+
+#[test]
+fn perft_counts() {
+    let cases = [
+        ("rnbqkbnr/pppppppp/8/8/8/8/PPPPPPPP/RNBQKBNR w KQkq - 0 1", 3, 8_902),
+        ("r3k2r/p1ppqpb1/bn2pnp1/3PN3/1p2P3/2N2Q1p/PPPBBPPP/R3K2R w KQkq - 0 1", 3, 97_862),
+        ("8/2p5/3p4/KP5r/1R3p1k/8/4P1P1/8 w - - 0 1", 4, 43_238),
+        ("r3k2r/Pppp1ppp/1b3nbN/nP6/BBP1P3/q4N2/Pp1P2PP/R2Q1RK1 w kq - 0 1", 3, 9_467),
+    ];
+    for (fen, depth, expected) in cases {
+        let pos = Position::from_fen(fen).unwrap();
+        assert_eq!(perft(&pos, depth), expected, "{}", fen);
+    }
+}
+
+#[test]
+fn fen_round_trip() {
+    for fen in [
+        "rnbqkbnr/pppppppp/8/8/4P3/8/PPPP1PPP/RNBQKBNR b KQkq e3 0 1",
+        "r3k2r/p1ppqpb1/bn2pnp1/3PN3/1p2P3/2N2Q1p/PPPBBPPP/R3K2R w KQkq - 0 1",
+        "8/8/8/8/8/8/8/K6k w - - 50 80",
+    ] {
+        assert_eq!(Position::from_fen(fen).unwrap().to_fen(), fen);
+    }
+}
+
+#[test]
+fn san_disambiguation_and_markers() {
+    let cases = [
+        ("4k3/8/8/8/8/8/4K3/R6R w - - 0 1", "Rad1", "a1d1"),
+        ("4k3/8/8/8/8/8/4K3/R6R w - - 0 1", "Rhd1", "h1d1"),
+        ("4k3/8/8/8/8/8/4K3/R6R w - - 0 1", "Ra8+", "a1a8"),
+        ("4k3/8/8/8/8/R7/4K3/R7 w - - 0 1", "R1a2", "a1a2"),
+        ("4k3/8/8/8/8/8/8/R3K2R w KQ - 0 1", "O-O", "e1g1"),
+        ("4k3/8/8/8/8/8/8/R3K2R w KQ - 0 1", "Rd1", "a1d1"),
+    ];
+    for (fen, san, uci) in cases {
+        let pos = Position::from_fen(fen).unwrap();
+        let mv = pos.parse_san(san).unwrap();
+        assert_eq!(mv.to_uci(), uci);
+        assert_eq!(pos.san(&mv), san);
+    }
+
+    let mate = Position::from_fen("6k1/5ppp/8/8/8/8/8/R5K1 w - - 0 1").unwrap();
+    let mv = mate.parse_san("Ra8").unwrap();
+    assert_eq!(mate.san(&mv), "Ra8#");
+}
+
+#[test]
+fn lenient_san() {
+    let pos = Position::from_fen("4k3/1P6/8/8/8/8/8/R3K2R w KQ - 0 1").unwrap();
+    for (text, san) in [("0-0", "O-O"), ("o-o-o", "O-O-O"), ("b8Q", "b8=Q+"), ("b7b8n", "b8=N"), ("Ra1-a7", "Ra7")] {
+        let mv = pos.parse_san(text).unwrap_or_else(|| panic!("{} not parsed", text));
+        assert_eq!(pos.san(&mv), san);
+    }
+    assert!(pos.parse_san("Nf3").is_none());
+}
+
+#[test]
+fn polyglot_keys() {
+    // Test vectors from the Polyglot book format specification
+    let cases = [
+        ("", 0x463b96181691fc9c),
+        ("e4", 0x823c9b50fd114196),
+        ("e4 d5", 0x0756b94461c50fb0),
+        ("e4 d5 e5", 0x662fafb965db29d4),
+        ("e4 d5 e5 f5", 0x22a48b5a8e47ff78),
+        ("e4 d5 e5 f5 Ke2", 0x652a607ca3f242c1),
+        ("e4 d5 e5 f5 Ke2 Kf7", 0x00fdd303c946bdd9),
+        ("a4 b5 h4 b4 c4", 0x3c8123ea7b067637),
+        ("a4 b5 h4 b4 c4 bxc3 Ra3", 0x5c3f9b829b279560),
+    ];
+    for (moves, key) in cases {
+        let mut pos = Position::starting_position();
+        for san in moves.split_whitespace() {
+            let mv = pos.parse_san(san).unwrap();
+            pos.make_move(&mv);
+        }
+        assert_eq!(pos.polyglot_key(), key, "{}", moves);
+    }
+}
+
+#[test]
+fn polyglot_moves() {
+    let pos = Position::from_fen("r3k2r/1P6/8/8/8/8/8/R3K2R w KQkq - 0 1").unwrap();
+    for (san, encoded) in [("O-O", 0x0107), ("O-O-O", 0x0100), ("bxa8=N", 0x1C78), ("b8=Q", 0x4C79)] {
+        let mv = pos.parse_san(san).unwrap();
+        assert_eq!(polyglot_move(&mv), encoded, "{}", san);
+        assert_eq!(pos.polyglot_move(encoded), Some(mv));
+    }
+}
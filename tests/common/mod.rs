@@ -0,0 +1,385 @@
+//! Synthetic SCID database fixtures for the integration tests
+//!
+//! The builder writes .si4/.sn4/.sg4 files byte by byte from the format
+//! descriptions in the library docs, without going through `ScidWriter`,
+//! so the readers are checked against an independent encoder.
+
+#![allow(dead_code)]
+
+use std::collections::HashMap;
+use std::fs;
+use std::path::{Path, PathBuf};
+use std::sync::atomic::{AtomicUsize, Ordering};
+
+/// A scratch directory removed again when dropped
+pub struct TempDir {
+    path: PathBuf,
+}
+
+impl TempDir {
+    pub fn new(name: &str) -> Self {
+        static COUNTER: AtomicUsize = AtomicUsize::new(0);
+        let unique = COUNTER.fetch_add(1, Ordering::SeqCst);
+        let path = std::env::temp_dir()
+            .join(format!("scidtopgn-{}-{}-{}", name, std::process::id(), unique));
+        fs::create_dir_all(&path).unwrap();
+        TempDir { path }
+    }
+
+    pub fn path(&self) -> &Path {
+        &self.path
+    }
+}
+
+impl Drop for TempDir {
+    fn drop(&mut self) {
+        let _ = fs::remove_dir_all(&self.path);
+    }
+}
+
+/// One game of a fixture database: its index fields, names and raw .sg4 record
+#[derive(Clone)]
+pub struct FixtureGame {
+    pub white: String,
+    pub black: String,
+    pub event: String,
+    pub site: String,
+    pub round: String,
+    /// Explicit white/black/event/site/round IDs instead of namebase ones
+    pub ids: Option<[u32; 5]>,
+    pub date: (u16, u8, u8),
+    pub result: u8,
+    pub elo: (u16, u16),
+    pub eco: u16,
+    pub flags: u16,
+    /// Custom flags 1-6 as the low bits of Length_High
+    pub custom_flags: u8,
+    pub ply: u16,
+    /// Raw FinalMatSig (low 24 bits) and HomePawnData, ply bits aside
+    pub signatures: (u32, [u8; 9]),
+    /// Variation, comment and NAG counts
+    pub counts: (u8, u8, u8),
+    pub record: Vec<u8>,
+}
+
+impl FixtureGame {
+    pub fn new(record: Vec<u8>) -> Self {
+        FixtureGame {
+            white: "?".to_string(),
+            black: "?".to_string(),
+            event: "?".to_string(),
+            site: "?".to_string(),
+            round: "?".to_string(),
+            ids: None,
+            date: (0, 0, 0),
+            result: 0,
+            elo: (0, 0),
+            eco: 0,
+            flags: 0,
+            custom_flags: 0,
+            ply: 0,
+            signatures: (0, [0; 9]),
+            counts: (0, 0, 0),
+            record,
+        }
+    }
+
+    pub fn players(mut self, white: &str, black: &str) -> Self {
+        self.white = white.to_string();
+        self.black = black.to_string();
+        self
+    }
+
+    pub fn event(mut self, event: &str, site: &str, round: &str) -> Self {
+        self.event = event.to_string();
+        self.site = site.to_string();
+        self.round = round.to_string();
+        self
+    }
+
+    pub fn ids(mut self, ids: [u32; 5]) -> Self {
+        self.ids = Some(ids);
+        self
+    }
+
+    pub fn date(mut self, year: u16, month: u8, day: u8) -> Self {
+        self.date = (year, month, day);
+        self
+    }
+
+    pub fn result(mut self, result: u8) -> Self {
+        self.result = result;
+        self
+    }
+
+    pub fn elo(mut self, white: u16, black: u16) -> Self {
+        self.elo = (white, black);
+        self
+    }
+
+    pub fn eco(mut self, eco: u16) -> Self {
+        self.eco = eco;
+        self
+    }
+
+    pub fn flags(mut self, flags: u16) -> Self {
+        self.flags = flags;
+        self
+    }
+
+    pub fn custom_flags(mut self, custom_flags: u8) -> Self {
+        self.custom_flags = custom_flags;
+        self
+    }
+
+    pub fn ply(mut self, ply: u16) -> Self {
+        self.ply = ply;
+        self
+    }
+
+    pub fn signatures(mut self, final_mat_sig: u32, home_pawn_data: [u8; 9]) -> Self {
+        self.signatures = (final_mat_sig, home_pawn_data);
+        self
+    }
+
+    pub fn counts(mut self, variations: u8, comments: u8, nags: u8) -> Self {
+        self.counts = (variations, comments, nags);
+        self
+    }
+}
+
+/// Builder for a complete synthetic database
+pub struct Fixture {
+    pub index_version: u16,
+    pub name_version: u16,
+    pub description: String,
+    pub games: Vec<FixtureGame>,
+}
+
+impl Fixture {
+    pub fn new() -> Self {
+        Fixture {
+            index_version: 400,
+            name_version: 400,
+            description: String::new(),
+            games: Vec::new(),
+        }
+    }
+
+    pub fn versions(mut self, index_version: u16, name_version: u16) -> Self {
+        self.index_version = index_version;
+        self.name_version = name_version;
+        self
+    }
+
+    pub fn description(mut self, description: &str) -> Self {
+        self.description = description.to_string();
+        self
+    }
+
+    pub fn game(mut self, game: FixtureGame) -> Self {
+        self.games.push(game);
+        self
+    }
+
+    /// Write `<dir>/<name>.si4/.sn4/.sg4` and return the base path
+    pub fn write(&self, dir: &Path, name: &str) -> PathBuf {
+        let base = dir.join(name);
+        let mut names = Names::default();
+        let mut si4 = self.index_header();
+        let mut sg4 = Vec::new();
+
+        for game in &self.games {
+            let ids = game.ids.unwrap_or_else(|| [
+                names.id(0, &game.white),
+                names.id(0, &game.black),
+                names.id(1, &game.event),
+                names.id(2, &game.site),
+                names.id(3, &game.round),
+            ]);
+            si4.extend(index_record(game, ids, sg4.len() as u32));
+            sg4.extend_from_slice(&game.record);
+        }
+
+        fs::write(base.with_extension("si4"), si4).unwrap();
+        fs::write(base.with_extension("sn4"), names.encode(self.name_version)).unwrap();
+        fs::write(base.with_extension("sg4"), sg4).unwrap();
+        base
+    }
+
+    /// The 182-byte .si4 header
+    pub fn index_header(&self) -> Vec<u8> {
+        let mut out = b"Scid.si\0".to_vec();
+        out.extend(self.index_version.to_le_bytes());
+        out.extend(0u32.to_le_bytes()); // Base type
+        out.extend(&(self.games.len() as u32).to_le_bytes()[..3]);
+        out.extend([0, 0, 0]); // Auto-load game
+        let mut description = [0u8; 108];
+        description[..self.description.len()].copy_from_slice(self.description.as_bytes());
+        out.extend(description);
+        out.extend([0u8; 54]); // Custom flag names
+        assert_eq!(out.len(), 182);
+        out
+    }
+}
+
+/// One 47-byte .si4 game record
+pub fn index_record(game: &FixtureGame, ids: [u32; 5], offset: u32) -> Vec<u8> {
+    let [white, black, event, site, round] = ids;
+    let length = game.record.len() as u32;
+    let mut out = Vec::new();
+    out.extend(offset.to_le_bytes());
+    out.extend((length as u16).to_le_bytes());
+    out.push(((length >> 16) as u8) << 7 | game.custom_flags);
+    out.extend(game.flags.to_le_bytes());
+    out.push((((white >> 16) as u8) << 4) | (black >> 16) as u8);
+    out.extend((white as u16).to_le_bytes());
+    out.extend((black as u16).to_le_bytes());
+    out.push((((event >> 16) as u8) << 5) | (((site >> 16) as u8) << 2) | (round >> 16) as u8);
+    out.extend((event as u16).to_le_bytes());
+    out.extend((site as u16).to_le_bytes());
+    out.extend((round as u16).to_le_bytes());
+    let (variations, comments, nags) = game.counts;
+    let var_counts = (game.result as u16) << 12 | (nags as u16) << 8 | (comments as u16) << 4 | variations as u16;
+    out.extend(var_counts.to_le_bytes());
+    out.extend(game.eco.to_le_bytes());
+    let (year, month, day) = game.date;
+    let dates = (year as u32) << 9 | (month as u32) << 5 | day as u32;
+    out.extend(dates.to_le_bytes());
+    out.extend(game.elo.0.to_le_bytes());
+    out.extend(game.elo.1.to_le_bytes());
+    let (final_mat_sig, mut home_pawn_data) = game.signatures;
+    out.extend(final_mat_sig.to_le_bytes());
+    out.push(game.ply as u8);
+    home_pawn_data[0] |= ((game.ply >> 8) as u8) << 6;
+    out.extend(home_pawn_data);
+    assert_eq!(out.len(), 47);
+    out
+}
+
+/// Names of the four namebase sections, in first-seen order
+#[derive(Default)]
+struct Names {
+    sections: [Vec<(String, u32)>; 4],
+    ids: [HashMap<String, u32>; 4],
+}
+
+impl Names {
+    fn id(&mut self, section: usize, name: &str) -> u32 {
+        let next = self.sections[section].len() as u32;
+        let id = *self.ids[section].entry(name.to_string()).or_insert(next);
+        if id == next {
+            self.sections[section].push((name.to_string(), 0));
+        }
+        self.sections[section][id as usize].1 += 1;
+        id
+    }
+
+    fn encode(&self, version: u16) -> Vec<u8> {
+        let mut out = b"Scid.sn\0".to_vec();
+        out.extend(version.to_le_bytes());
+        out.extend(0u32.to_le_bytes()); // Timestamp
+        for section in &self.sections {
+            out.extend(&(section.len() as u32).to_le_bytes()[..3]);
+        }
+        let max_frequencies = self.sections.each_ref()
+            .map(|section| section.iter().map(|(_, frequency)| *frequency).max().unwrap_or(0));
+        for max_frequency in max_frequencies {
+            out.extend(&max_frequency.to_le_bytes()[..3]);
+        }
+        out.extend([0u8; 4]); // Reserved bytes
+        for (section, max_frequency) in self.sections.iter().zip(max_frequencies) {
+            let mut previous = "";
+            for (id, (name, frequency)) in section.iter().enumerate() {
+                out.extend(name_number(id as u32, section.len() as u32));
+                out.extend(name_number(*frequency, max_frequency));
+                out.push(name.len() as u8);
+                if id > 0 {
+                    // Front-coded: the bytes shared with the previous name are left out
+                    let prefix = name.bytes().zip(previous.bytes()).take_while(|(a, b)| a == b).count();
+                    out.push(prefix as u8);
+                    out.extend(&name.as_bytes()[prefix..]);
+                } else {
+                    out.extend(name.as_bytes());
+                }
+                previous = name;
+            }
+        }
+        out
+    }
+}
+
+/// The namebase's encoding of an ID or frequency: 1, 2 or 3 little-endian
+/// bytes, as `max` (the name count, or the type's max frequency) needs
+pub fn name_number(value: u32, max: u32) -> Vec<u8> {
+    let width = if max >= 65536 { 3 } else if max >= 256 { 2 } else { 1 };
+    value.to_le_bytes()[..width].to_vec()
+}
+
+/// Build an .sg4 record: extra tags, flags, optional start FEN, the move
+/// stream (which must end with 15, END_GAME) and the comment strings
+pub fn game_record(tags: &[(&str, &str)], flags: u8, fen: Option<&str>,
+                   moves: &[u8], comments: &[&str]) -> Vec<u8> {
+    let mut out = Vec::new();
+    for (name, value) in tags {
+        out.push(name.len() as u8);
+        out.extend(name.as_bytes());
+        out.push(value.len() as u8);
+        out.extend(value.as_bytes());
+    }
+    out.push(0);
+    out.push(flags);
+    if let Some(fen) = fen {
+        out.extend(fen.as_bytes());
+        out.push(0);
+    }
+    out.extend(moves);
+    for comment in comments {
+        out.extend(comment.as_bytes());
+        out.push(0);
+    }
+    out
+}
+
+/// 1.e4 e5 2.Nf3 Nc6 3.Bb5 a6 {Morphy Defence} 4.Ba4 (4.Bxc6 dxc6) Nf6 5.O-O $1
+///
+/// Move bytes are `piece_index << 4 | value`; the comments on each line
+/// show the piece index and value. Both sides list their pieces as SCID
+/// does for the standard start: king, back rank a-h, then pawns a-h.
+pub const RUY_LOPEZ_MOVES: &[u8] = &[
+    0xCF,       // e2 pawn (12), double push
+    0xCF,       // e7 pawn (12), double push
+    0x67,       // g1 knight (6), jump +15
+    0x22,       // b8 knight (2), jump -15
+    0x59,       // f1 bishop (5), to the b-file along the anti-diagonal
+    0x81,       // a7 pawn (8), push
+    0x0C,       // comment
+    0x50,       // b5 bishop (5), to the a-file along the diagonal
+    0x0D,       // variation start
+    0x52,       // b5 bishop (5), to the c-file along the diagonal
+    0xB2,       // d7 pawn (11), capture 9 squares down the board, to c6
+    0x0E,       // variation end
+    0x61,       // g8 knight (6), jump -17
+    0x0A,       // king (0), O-O
+    0x0B, 0x01, // NAG 1
+    0x0F,       // end of game
+];
+
+/// A game of RUY_LOPEZ_MOVES, with its comment and the index's 9 half-moves
+pub fn ruy_lopez_game() -> FixtureGame {
+    FixtureGame::new(game_record(&[], 0, None, RUY_LOPEZ_MOVES, &["Morphy Defence"])).ply(9)
+}
+
+pub const PROMOTION_FEN: &str = "4k3/1P6/8/8/8/8/8/4K2Q w - - 0 1";
+
+/// From PROMOTION_FEN: 1.b8=N Kd8 2.Qd5+ Kc7
+///
+/// Pieces are listed in FEN order, so white's b7 pawn comes before the h1
+/// queen and gives up index 0 to the king.
+pub const PROMOTION_MOVES: &[u8] = &[
+    0x1D,       // b7 pawn (1), push promoting to a knight
+    0x04,       // king (0), direction -1
+    0x27, 0x63, // h1 queen (2), diagonal move to d5 (35 + 64)
+    0x01,       // king (0), direction -9
+    0x0F,       // end of game
+];
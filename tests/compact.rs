@@ -0,0 +1,67 @@
+mod common;
+
+use std::fs;
+
+use common::{ruy_lopez_game, Fixture, TempDir};
+use scidtopgn::chess::GameResult;
+use scidtopgn::pgn::PgnReader;
+use scidtopgn::scid::index::IDX_FLAG_DELETE;
+use scidtopgn::scid::{Compactor, ScidDatabase, ScidWriter};
+
+#[test]
+fn drops_deleted_games_and_unused_names() {
+    let dir = TempDir::new("compact_deleted");
+    let base = Fixture::new()
+        .description("Old games")
+        .game(ruy_lopez_game().players("Morphy", "Anderssen").ply(7))
+        .game(ruy_lopez_game().players("Gone", "Away").flags(IDX_FLAG_DELETE))
+        .game(ruy_lopez_game().players("Morphy", "Paulsen"))
+        .write(dir.path(), "games");
+    let output = dir.path().join("compacted");
+
+    let mut database = ScidDatabase::load(&base).unwrap();
+    let report = Compactor::new().compact(&mut database, &output).unwrap();
+    assert_eq!((report.games_before, report.games_after, report.deleted), (3, 2, 1));
+    // Five players plus the "?" event, site and round
+    assert_eq!((report.names_before, report.names_after), (8, 6));
+    assert!(report.bytes_after < report.bytes_before);
+    assert_eq!(report.repairs[0].game, 1);
+    assert!(report.repairs[0].fields.contains(&"ply count"), "{:?}", report.repairs);
+    assert!(report.unreadable.is_empty() && report.undecodable.is_empty());
+
+    let mut compacted = ScidDatabase::load(&output).unwrap();
+    assert_eq!(compacted.num_games(), 2);
+    assert!(compacted.header().database_info.starts_with(b"Old games\0"));
+    assert_eq!(compacted.names().players.len(), 3);
+    let games = compacted.game_indices().to_vec();
+    assert_eq!(games[0].num_half_moves, 9);
+    assert_eq!(compacted.player_name(games[1].black_id), Some("Paulsen"));
+    assert_eq!(compacted.load_game(&games[1]).unwrap().ply_count(), 9);
+    assert_eq!(games[1].offset, games[0].length);
+}
+
+#[test]
+fn repairs_results_in_place() {
+    let dir = TempDir::new("compact_in_place");
+    let base = dir.path().join("games");
+    let pgn = "[Result \"*\"]\n\n1. f3 e5 2. g4 Qh4# *\n\n[Result \"1-0\"]\n\n1. e4 e5 1-0\n";
+    let mut writer = ScidWriter::create(&base).unwrap();
+    for game in PgnReader::new(pgn.as_bytes()) {
+        writer.add_game(&game.unwrap()).unwrap();
+    }
+    writer.finish().unwrap();
+
+    let report = Compactor::new().with_repair(false).compact_in_place(&base).unwrap();
+    assert!(report.repairs.is_empty());
+    let database = ScidDatabase::load_index_only(&base).unwrap();
+    assert_eq!(database.game_indices()[0].result, GameResult::Unknown.to_scid());
+
+    let report = Compactor::new().compact_in_place(&base).unwrap();
+    assert_eq!(report.repairs.len(), 1);
+    assert_eq!((report.repairs[0].game, report.repairs[0].fields.as_slice()), (1, ["result"].as_slice()));
+    let database = ScidDatabase::load_index_only(&base).unwrap();
+    let results: Vec<u8> = database.game_indices().iter().map(|game| game.result).collect();
+    assert_eq!(results, [GameResult::BlackWins.to_scid(), GameResult::WhiteWins.to_scid()]);
+    assert!(!dir.path().join("games-compacting.sg4").exists());
+    assert_eq!(fs::read_dir(dir.path()).unwrap().count(), 3);
+}
@@ -0,0 +1,143 @@
+mod common;
+
+use common::TempDir;
+use scidtopgn::export::GameFilter;
+use scidtopgn::pgn::PgnReader;
+use scidtopgn::report::{Crosstable, Crosstables, TournamentFormat};
+use scidtopgn::scid::{ScidDatabase, ScidWriter};
+
+/// Header lines for a game of a tournament
+fn game(event: &str, round: u32, white: &str, black: &str, result: &str) -> String {
+    let elo = |name: &str| match name {
+        "Alpha" => 2700,
+        "Bravo" => 2600,
+        "Charlie" => 2650,
+        _ => 2500,
+    };
+    format!("[Event \"{}\"]\n[Site \"Town\"]\n[Date \"2020.01.0{}\"]\n[Round \"{}\"]\n[White \"{}\"]\n[Black \"{}\"]\n\
+             [WhiteElo \"{}\"]\n[BlackElo \"{}\"]\n[Result \"{}\"]\n\n1. e4 e5 {}\n\n",
+            event, round, round, white, black, elo(white), elo(black), result, result)
+}
+
+fn build(crosstables: Crosstables) -> Vec<Crosstable> {
+    build_from(&[
+        game("Masters", 1, "Alpha", "Bravo", "1-0"),
+        game("Masters", 1, "Charlie", "Delta", "1/2-1/2"),
+        game("Masters", 2, "Charlie", "Alpha", "1/2-1/2"),
+        game("Masters", 2, "Bravo", "Delta", "1-0"),
+        game("Masters", 3, "Alpha", "Delta", "1-0"),
+        game("Masters", 3, "Bravo", "Charlie", "0-1"),
+        game("Open", 1, "P1", "P2", "1-0"),
+        game("Open", 1, "P3", "P4", "0-1"),
+        game("Open", 2, "P4", "P1", "1/2-1/2"),
+        game("Open", 2, "P2", "P3", "1-0"),
+        game("Open", 3, "P1", "P3", "*"),
+    ], crosstables)
+}
+
+fn build_from(games: &[String], crosstables: Crosstables) -> Vec<Crosstable> {
+    let pgn = games.concat();
+    let dir = TempDir::new("crosstable");
+    let base = dir.path().join("games");
+    let mut writer = ScidWriter::create(&base).unwrap();
+    for game in PgnReader::new(pgn.as_bytes()) {
+        writer.add_game(&game.unwrap()).unwrap();
+    }
+    writer.finish().unwrap();
+    crosstables.build(&ScidDatabase::load_index_only(&base).unwrap())
+}
+
+#[test]
+fn round_robin_scores_and_tiebreaks() {
+    let crosstables = build(Crosstables::new().with_filter(GameFilter::new().with_event("Masters")));
+    assert_eq!(crosstables.len(), 1);
+    let masters = &crosstables[0];
+    assert_eq!((masters.event.as_str(), masters.site.as_str(), masters.year), ("Masters", "Town", Some(2020)));
+    assert_eq!(masters.format, TournamentFormat::RoundRobin { cycles: 1 });
+    assert_eq!(masters.rounds, 3);
+
+    let standings: Vec<(&str, f64)> = masters.players.iter().map(|row| (row.name.as_str(), row.score)).collect();
+    assert_eq!(standings, [("Alpha", 2.5), ("Charlie", 2.0), ("Bravo", 1.0), ("Delta", 0.5)]);
+    let alpha = &masters.players[0];
+    assert_eq!((alpha.sonneborn_berger, alpha.buchholz), (2.5, 3.5));
+    assert_eq!((alpha.elo, alpha.performance), (Some(2700), Some(2850)));
+    assert_eq!(masters.players[1].sonneborn_berger, 2.5);
+
+    let mut text = Vec::new();
+    masters.write_text(&mut text).unwrap();
+    let text = String::from_utf8(text).unwrap();
+    let lines: Vec<&str> = text.lines().collect();
+    assert_eq!(lines[0], "Masters, Town 2020 (round robin, 4 players, 3 rounds)");
+    assert_eq!(lines[2], "  1  Alpha    2700  X ½ 1 1     2½    2.50     3.5  2850");
+}
+
+#[test]
+fn swiss_rounds_and_unfinished_games() {
+    let crosstables = build(Crosstables::new());
+    assert_eq!(crosstables.len(), 2);
+    let open = &crosstables[1];
+    assert_eq!(open.format, TournamentFormat::Swiss);
+    // The unfinished third round game is left out
+    assert_eq!(open.rounds, 2);
+
+    let standings: Vec<(&str, f64, f64)> = open.players.iter()
+        .map(|row| (row.name.as_str(), row.score, row.buchholz))
+        .collect();
+    assert_eq!(standings, [("P1", 1.5, 2.5), ("P4", 1.5, 1.5), ("P2", 1.0, 1.5), ("P3", 0.0, 2.5)]);
+
+    let mut text = Vec::new();
+    open.write_text(&mut text).unwrap();
+    let text = String::from_utf8(text).unwrap();
+    assert!(text.lines().nth(2).unwrap().contains(" 3w1 2b½ "), "{}", text);
+}
+
+#[test]
+fn html_and_json_output() {
+    let crosstables = build(Crosstables::new().with_min_players(5));
+    assert!(crosstables.is_empty());
+
+    let crosstables = build(Crosstables::new());
+    let mut html = Vec::new();
+    scidtopgn::report::crosstable::write_html_page(&crosstables, &mut html).unwrap();
+    let html = String::from_utf8(html).unwrap();
+    assert_eq!(html.matches("<table>").count(), 2);
+    assert!(html.contains("<tr><td>1</td><td>Alpha</td><td>2700</td><td>X</td><td>½</td>"));
+
+    let json = serde_json::to_value(&crosstables).unwrap();
+    assert_eq!(json[0]["format"], serde_json::json!({"type": "round-robin", "cycles": 1}));
+    assert_eq!(json[1]["format"]["type"], "swiss");
+    assert_eq!(json[1]["players"][0]["games"][1],
+               serde_json::json!({"round": "2", "opponent": 2, "color": "black", "points": 0.5}));
+}
+
+#[test]
+fn byes_and_repeated_pairings() {
+    let crosstables = build_from(&[
+        game("Weekend", 1, "A", "B", "1-0"),
+        game("Weekend", 1, "C", "D", "1/2-1/2"),
+        game("Weekend", 2, "E", "A", "0-1"),
+        game("Weekend", 2, "B", "C", "1-0"),
+        game("Weekend", 3, "D", "E", "1-0"),
+        game("Match", 1, "X", "Y", "1-0"),
+        game("Match", 2, "Y", "X", "1-0"),
+    ], Crosstables::new());
+
+    // E sat out round 1 and D round 2: their results stay in their rounds
+    let weekend = &crosstables[0];
+    assert_eq!((weekend.format, weekend.rounds), (TournamentFormat::Swiss, 3));
+    let names: Vec<&str> = weekend.players.iter().map(|row| row.name.as_str()).collect();
+    assert_eq!(names, ["A", "D", "B", "C", "E"]);
+    let mut html = Vec::new();
+    weekend.write_html(&mut html).unwrap();
+    let html = String::from_utf8(html).unwrap();
+    assert!(html.contains("<td>D</td><td>2500</td><td>4b½</td><td></td><td>5w1</td>"), "{}", html);
+    assert!(html.contains("<td>E</td><td>2500</td><td></td><td>1w0</td><td>2b0</td>"), "{}", html);
+
+    // Each game of a double round robin keeps its own result
+    let double = &crosstables[1];
+    assert_eq!(double.format, TournamentFormat::RoundRobin { cycles: 2 });
+    let mut text = Vec::new();
+    double.write_text(&mut text).unwrap();
+    let text = String::from_utf8(text).unwrap();
+    assert!(text.lines().nth(2).unwrap().contains("   X 1 0 "), "{}", text);
+}
@@ -0,0 +1,82 @@
+mod common;
+
+use std::fs;
+
+use common::{game_record, Fixture, FixtureGame, TempDir, RUY_LOPEZ_MOVES};
+use scidtopgn::export::{CsvColumn, CsvExporter};
+use scidtopgn::scid::eco::eco_from_str;
+use scidtopgn::scid::ScidDatabase;
+
+fn game() -> FixtureGame {
+    FixtureGame::new(game_record(&[], 0, None, RUY_LOPEZ_MOVES, &[]))
+}
+
+fn fixture() -> Fixture {
+    Fixture::new()
+        .game(game()
+            .players("Morphy, Paul", "Anderssen, Adolf")
+            .event("Paris m", "Paris FRA", "1")
+            .date(1858, 12, 20)
+            .result(3)
+            .elo(2690, 0)
+            .eco(eco_from_str("C78"))
+            .ply(9)
+            // White opening and tactics
+            .flags(1 << 4 | 1 << 10))
+        .game(game()
+            .players("Deleted", "Game")
+            .flags(0x08))
+        .game(game()
+            .players("White, A", "Black, B")
+            .event("Club \"Open\"", "Rome, ITA", "?")
+            .date(2021, 3, 0)
+            .result(1)
+            .flags(1))
+}
+
+/// Export with the .sg4 file removed, to show it is never needed
+fn export(exporter: CsvExporter) -> String {
+    let dir = TempDir::new("csv");
+    let base = fixture().write(dir.path(), "csv");
+    fs::remove_file(base.with_extension("sg4")).unwrap();
+
+    let database = ScidDatabase::load_index_only(&base).unwrap();
+    let mut out = Vec::new();
+    exporter.write(&database, &mut out).unwrap();
+    String::from_utf8(out).unwrap()
+}
+
+#[test]
+fn exports_all_columns() {
+    assert_eq!(export(CsvExporter::new()), "\
+number,event,site,date,round,white,black,result,white_elo,black_elo,eco,ply_count,flags,setup
+1,Paris m,Paris FRA,1858-12-20,1,\"Morphy, Paul\",\"Anderssen, Adolf\",1/2-1/2,2690,,C78,9,WT,0
+3,\"Club \"\"Open\"\"\",\"Rome, ITA\",2021-03,,\"White, A\",\"Black, B\",1-0,,,,0,,1
+");
+}
+
+#[test]
+fn exports_selected_columns_as_tsv() {
+    let columns = ["white", "Black", "white-elo", "date"].iter()
+        .map(|name| name.parse().unwrap())
+        .collect();
+    let exporter = CsvExporter::new().with_delimiter(b'\t').with_columns(columns).with_max_games(1);
+    assert_eq!(export(exporter), "white\tblack\twhite_elo\tdate\nMorphy, Paul\tAnderssen, Adolf\t2690\t1858-12-20\n");
+
+    let exporter = CsvExporter::new().with_delimiter(b'\t').with_columns(vec![CsvColumn::Site]).with_header(false);
+    assert_eq!(export(exporter), "Paris FRA\nRome, ITA\n");
+
+    assert!("moves".parse::<CsvColumn>().unwrap_err().contains("ply_count"));
+}
+
+#[test]
+fn index_only_database_has_no_games() {
+    let dir = TempDir::new("csv-index-only");
+    let base = fixture().write(dir.path(), "csv");
+    let mut database = ScidDatabase::load_index_only(&base).unwrap();
+    let first = database.game_index(0).unwrap().clone();
+    assert!(database.load_game(&first).is_err());
+
+    fs::remove_file(base.with_extension("sg4")).unwrap();
+    assert!(ScidDatabase::load(&base).is_err());
+}
@@ -0,0 +1,110 @@
+mod common;
+
+use common::TempDir;
+use scidtopgn::export::GameFilter;
+use scidtopgn::pgn::{PgnExporter, PgnReader};
+use scidtopgn::scid::{ScidDatabase, ScidWriter};
+use scidtopgn::search::{DuplicateFinder, DuplicateGroup, DuplicateStrictness};
+
+// Games 1-3 are copies of one game: 2 has a comment, 3 spells the names
+// differently. Game 4 has other moves, game 5 is from another year and
+// game 6 has another result.
+const PGN: &str = r#"[White "Carlsen, Magnus"]
+[Black "Caruana, Fabiano"]
+[Date "2019.05.01"]
+[Result "1-0"]
+
+1. e4 e5 2. Nf3 Nc6 1-0
+
+[Event "Norway Chess"]
+[White "Carlsen, Magnus"]
+[Black "Caruana, Fabiano"]
+[Date "2019.05.01"]
+[Result "1-0"]
+
+1. e4 e5 {Solid} 2. Nf3 Nc6 1-0
+
+[White "CARLSEN Magnus"]
+[Black "caruana, fabiano"]
+[Date "2019.??.??"]
+[Result "1-0"]
+
+1. e4 e5 2. Nf3 Nc6 1-0
+
+[White "Carlsen, Magnus"]
+[Black "Caruana, Fabiano"]
+[Date "2019.05.01"]
+[Result "1-0"]
+
+1. e4 e5 2. Bc4 Nc6 1-0
+
+[White "Carlsen, Magnus"]
+[Black "Caruana, Fabiano"]
+[Date "2020.05.01"]
+[Result "1-0"]
+
+1. e4 e5 2. Nf3 Nc6 1-0
+
+[White "Carlsen, M."]
+[Black "Caruana, F."]
+[Date "2019.05.01"]
+[Result "1/2-1/2"]
+
+1. e4 e5 2. Nf3 Nc6 1/2-1/2
+"#;
+
+fn load(dir: &TempDir) -> ScidDatabase {
+    let base = dir.path().join("games");
+    let mut writer = ScidWriter::create(&base).unwrap();
+    for game in PgnReader::new(PGN.as_bytes()) {
+        writer.add_game(&game.unwrap()).unwrap();
+    }
+    writer.finish().unwrap();
+    ScidDatabase::load(&base).unwrap()
+}
+
+fn groups(strictness: DuplicateStrictness) -> Vec<DuplicateGroup> {
+    let dir = TempDir::new("duplicates");
+    DuplicateFinder::new().with_strictness(strictness).find(&mut load(&dir)).groups
+}
+
+#[test]
+fn strictness_decides_which_headers_must_agree() {
+    assert_eq!(groups(DuplicateStrictness::Strict), [DuplicateGroup { kept: 1, dropped: vec![0] }]);
+    assert_eq!(groups(DuplicateStrictness::Normal), [DuplicateGroup { kept: 1, dropped: vec![0, 2] }]);
+    assert_eq!(groups(DuplicateStrictness::Loose), [DuplicateGroup { kept: 1, dropped: vec![0, 2, 4, 5] }]);
+}
+
+#[test]
+fn keeps_copy_with_most_tags_when_annotations_tie() {
+    let dir = TempDir::new("duplicates_tags");
+    let mut database = load(&dir);
+    let filter = GameFilter::new().with_excluded_games([1]);
+    let duplicates = DuplicateFinder::new().with_filter(filter).find(&mut database);
+    // Game 1 knows the full date; game 3 doesn't
+    assert_eq!(duplicates.groups, [DuplicateGroup { kept: 0, dropped: vec![2] }]);
+}
+
+#[test]
+fn deduplicated_export_and_report() {
+    let dir = TempDir::new("duplicates_export");
+    let mut database = load(&dir);
+    let duplicates = DuplicateFinder::new().find(&mut database);
+
+    let mut report = Vec::new();
+    duplicates.write_report(&database, &mut report).unwrap();
+    let report = String::from_utf8(report).unwrap();
+    let lines: Vec<&str> = report.lines().collect();
+    assert_eq!(lines.len(), 3);
+    assert!(lines[0].starts_with("Kept         2  Carlsen, Magnus - Caruana, Fabiano, Norway Chess"));
+    assert!(lines[1].starts_with("Dropped      1"));
+    assert!(lines[2].starts_with("Dropped      3  CARLSEN Magnus"));
+
+    let output = dir.path().join("dedup.pgn");
+    let filter = GameFilter::new().with_excluded_games(duplicates.dropped());
+    let exported = PgnExporter::new().with_filter(filter).with_comments(true).export(&mut database, &output).unwrap();
+    assert_eq!(exported, 4);
+    let pgn = std::fs::read_to_string(&output).unwrap();
+    assert!(pgn.contains("{Solid}"));
+    assert!(!pgn.contains("CARLSEN"));
+}
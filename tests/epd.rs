@@ -0,0 +1,49 @@
+mod common;
+
+use common::{game_record, Fixture, FixtureGame, TempDir, PROMOTION_FEN, PROMOTION_MOVES, RUY_LOPEZ_MOVES};
+use scidtopgn::export::{EpdExporter, EpdPositions};
+use scidtopgn::scid::ScidDatabase;
+
+fn export(positions: EpdPositions) -> Vec<String> {
+    let dir = TempDir::new("epd");
+    let base = Fixture::new()
+        .game(FixtureGame::new(game_record(&[], 0, None, RUY_LOPEZ_MOVES, &["Morphy Defence"])).result(3))
+        // Undecodable, so left out
+        .game(FixtureGame::new(game_record(&[], 0, None, &[0xCF], &[])))
+        .game(FixtureGame::new(game_record(&[], 7, Some(PROMOTION_FEN), PROMOTION_MOVES, &[])).result(1))
+        .write(dir.path(), "epd");
+
+    let mut database = ScidDatabase::load(&base).unwrap();
+    let mut out = Vec::new();
+    let exported = EpdExporter::new().with_positions(positions).write(&mut database, &mut out).unwrap();
+    assert_eq!(exported, 2);
+    String::from_utf8(out).unwrap().lines().map(str::to_string).collect()
+}
+
+#[test]
+fn writes_every_position() {
+    let lines = export(EpdPositions::All);
+    // Start position plus one per move, for both games
+    assert_eq!(lines.len(), 10 + 5);
+    assert_eq!(lines[0], "rnbqkbnr/pppppppp/8/8/8/8/PPPPPPPP/RNBQKBNR w KQkq - \
+                          id \"game 1, ply 0\"; fmvn 1; hmvc 0; sm e4; c9 \"1/2-1/2\";");
+    assert_eq!(lines[3], "rnbqkbnr/pppp1ppp/8/4p3/4P3/5N2/PPPP1PPP/RNBQKB1R b KQkq - \
+                          id \"game 1, ply 3\"; fmvn 2; hmvc 1; sm Nc6; c9 \"1/2-1/2\";");
+    assert_eq!(lines[10], "4k3/1P6/8/8/8/8/8/4K2Q w - - id \"game 3, ply 0\"; fmvn 1; hmvc 0; sm b8=N; c9 \"1-0\";");
+}
+
+#[test]
+fn writes_final_or_chosen_positions() {
+    let lines = export(EpdPositions::Final);
+    assert_eq!(lines, [
+        "r1bqkb1r/1ppp1ppp/p1n2n2/4p3/B3P3/5N2/PPPP1PPP/RNBQ1RK1 b kq - id \"game 1, ply 9\"; fmvn 5; hmvc 3; c9 \"1/2-1/2\";",
+        "1N6/2k5/8/3Q4/8/8/8/4K3 w - - id \"game 3, ply 4\"; fmvn 3; hmvc 3; c9 \"1-0\";",
+    ]);
+
+    let lines = export("2, 8".parse().unwrap());
+    let ids: Vec<_> = lines.iter().map(|line| line.split('"').nth(1).unwrap()).collect();
+    assert_eq!(ids, ["game 1, ply 2", "game 1, ply 8", "game 3, ply 2"]);
+
+    assert!("final,2".parse::<EpdPositions>().is_err());
+    assert_eq!("all".parse::<EpdPositions>().unwrap(), EpdPositions::All);
+}
@@ -0,0 +1,109 @@
+//! Byte-for-byte PGN export checks against the files in tests/golden.
+//! Run with UPDATE_GOLDEN=1 to rewrite them after an intended change.
+
+mod common;
+
+use std::fs;
+use std::path::{Path, PathBuf};
+
+use common::{game_record, Fixture, FixtureGame, TempDir, PROMOTION_FEN, PROMOTION_MOVES, RUY_LOPEZ_MOVES};
+use scidtopgn::pgn::{PgnExporter, PgnReader};
+use scidtopgn::scid::eco::eco_from_str;
+use scidtopgn::scid::{ScidDatabase, ScidWriter};
+
+fn fixture() -> Fixture {
+    let ruy_lopez = game_record(&[("Annotator", "Tester"), ("TimeControl", "40/7200")], 0, None,
+                                RUY_LOPEZ_MOVES, &["Morphy Defence"]);
+    let promotion = game_record(&[], 7, Some(PROMOTION_FEN), PROMOTION_MOVES, &[]);
+    // The move stream stops without an END_GAME byte, so this record can't be decoded
+    let corrupt = game_record(&[], 0, None, &[0xCF], &[]);
+
+    Fixture::new()
+        .description("Golden fixture")
+        .game(FixtureGame::new(ruy_lopez)
+            .players("Morphy, Paul", "Anderssen, Adolf")
+            .event("Paris m", "Paris FRA", "1")
+            .date(1858, 12, 20)
+            .result(3)
+            .elo(2690, 0)
+            .eco(eco_from_str("C78"))
+            .ply(9)
+            .counts(1, 1, 1))
+        .game(FixtureGame::new(promotion)
+            .players("White, A", "Black, B")
+            .event("Club \"Open\"", "C:\\Chess", "?")
+            .date(2021, 3, 0)
+            .result(1)
+            .flags(7)
+            .ply(4))
+        .game(FixtureGame::new(corrupt.clone())
+            .players("Deleted", "Game")
+            .flags(0x08))
+        .game(FixtureGame::new(corrupt)
+            .players("Corrupt", "Record")
+            .date(1999, 0, 0)
+            .result(2))
+}
+
+fn golden_path(name: &str) -> PathBuf {
+    Path::new(env!("CARGO_MANIFEST_DIR")).join("tests/golden").join(name)
+}
+
+fn export(base: &Path, mut exporter: PgnExporter, out: &Path) -> String {
+    let mut database = ScidDatabase::load(base).unwrap();
+    exporter.export(&mut database, out).unwrap();
+    fs::read_to_string(out).unwrap()
+}
+
+fn check_golden(name: &str, actual: &str) {
+    let path = golden_path(name);
+    if std::env::var_os("UPDATE_GOLDEN").is_some() {
+        fs::write(&path, actual).unwrap();
+    }
+    let expected = fs::read_to_string(&path).unwrap();
+    assert!(actual == expected, "{} differs from the export:\n{}", path.display(), actual);
+}
+
+#[test]
+fn exports_main_lines() {
+    let dir = TempDir::new("golden-plain");
+    let base = fixture().write(dir.path(), "golden");
+    let pgn = export(&base, PgnExporter::new(), &dir.path().join("out.pgn"));
+    check_golden("fixture.pgn", &pgn);
+}
+
+#[test]
+fn exports_comments_and_variations() {
+    let dir = TempDir::new("golden-annotated");
+    let base = fixture().write(dir.path(), "golden");
+    let exporter = PgnExporter::new().with_comments(true).with_variations(true);
+    let pgn = export(&base, exporter, &dir.path().join("out.pgn"));
+    check_golden("fixture_annotated.pgn", &pgn);
+}
+
+#[test]
+fn exports_max_games() {
+    let dir = TempDir::new("golden-max");
+    let base = fixture().write(dir.path(), "golden");
+    let pgn = export(&base, PgnExporter::new().with_max_games(1), &dir.path().join("out.pgn"));
+    assert_eq!(pgn.matches("[Event ").count(), 1);
+    assert!(fs::read_to_string(golden_path("fixture.pgn")).unwrap().starts_with(&pgn));
+}
+
+#[test]
+fn import_then_export_reproduces_golden() {
+    // PGN -> ScidWriter -> ScidDatabase -> PGN must give back the same text
+    let golden = fs::read_to_string(golden_path("fixture_annotated.pgn")).unwrap();
+    let dir = TempDir::new("golden-import");
+    let base = dir.path().join("imported");
+
+    let mut writer = ScidWriter::create(&base).unwrap();
+    for game in PgnReader::new(golden.as_bytes()) {
+        writer.add_game(&game.unwrap()).unwrap();
+    }
+    assert_eq!(writer.finish().unwrap(), 3);
+
+    let exporter = PgnExporter::new().with_comments(true).with_variations(true);
+    let pgn = export(&base, exporter, &dir.path().join("out.pgn"));
+    assert!(pgn == golden, "re-exported PGN differs:\n{}", pgn);
+}
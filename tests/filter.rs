@@ -0,0 +1,154 @@
+mod common;
+
+use common::{game_record, Fixture, FixtureGame, TempDir, RUY_LOPEZ_MOVES};
+use scidtopgn::chess::GameResult;
+use scidtopgn::export::filter::{parse_date, parse_eco_range, parse_result};
+use scidtopgn::export::{selected_games, FilterExpr, GameFilter};
+use scidtopgn::scid::eco::eco_from_str;
+use scidtopgn::scid::{GameIndex, ScidDatabase};
+
+fn game() -> FixtureGame {
+    FixtureGame::new(game_record(&[], 0, None, RUY_LOPEZ_MOVES, &[]))
+}
+
+/// One-based numbers of the games `filter` selects, from the index alone
+fn select(filter: GameFilter) -> Vec<usize> {
+    let dir = TempDir::new("filter");
+    let base = Fixture::new()
+        .game(game()
+            .players("Carlsen, Magnus", "Nakamura, Hikaru")
+            .event("Norway Chess", "Stavanger NOR", "1")
+            .date(2020, 10, 5)
+            .result(1)
+            .elo(2863, 2736)
+            .eco(eco_from_str("B90"))
+            .ply(61)
+            // White opening and tactics
+            .flags(1 << 4 | 1 << 10))
+        .game(game()
+            .players("Nakamura, Hikaru", "Carlsen, Magnus")
+            .event("Norway Chess", "Stavanger NOR", "6")
+            .date(2020, 10, 12)
+            .result(3)
+            .elo(2736, 2863)
+            .eco(eco_from_str("B99a"))
+            .ply(40))
+        .game(game()
+            .players("Morphy, Paul", "Anderssen, Adolf")
+            .event("Paris m", "Paris FRA", "1")
+            .date(1858, 12, 0)
+            .result(2)
+            .eco(eco_from_str("C78"))
+            .ply(9)
+            .flags(1 << 10))
+        .game(game()
+            .players("Carlsen, Magnus", "Deleted")
+            .flags(0x08))
+        .write(dir.path(), "filter");
+
+    // Filters only look at the index, so the game file isn't needed
+    let database = ScidDatabase::load_index_only(&base).unwrap();
+    selected_games(&database, &filter, None).into_iter().map(|(number, _)| number + 1).collect()
+}
+
+#[test]
+fn filters_by_names() {
+    assert_eq!(select(GameFilter::new()), [1, 2, 3]);
+    assert_eq!(select(GameFilter::new().with_player("carlsen")), [1, 2]);
+    assert_eq!(select(GameFilter::new().with_white("Carlsen")), [1]);
+    assert_eq!(select(GameFilter::new().with_black("CARLSEN")), [2]);
+    assert_eq!(select(GameFilter::new().with_player("Carlsen").with_black("Anderssen")), Vec::<usize>::new());
+    assert_eq!(select(GameFilter::new().with_event("norway")), [1, 2]);
+    assert_eq!(select(GameFilter::new().with_site("FRA")), [3]);
+}
+
+#[test]
+fn filters_by_index_fields() {
+    assert_eq!(select(GameFilter::new().with_date_from((2020, 10, 6))), [2]);
+    assert_eq!(select(GameFilter::new().with_date_to((1900, 0, 0))), [3]);
+    // The whole month, including games with no day
+    assert_eq!(select(GameFilter::new().with_date_from((1858, 12, 0)).with_date_to((1858, 12, 0))), [3]);
+    assert_eq!(select(GameFilter::new().with_date_to((2020, 10, 0))), [1, 2, 3]);
+
+    assert_eq!(select(GameFilter::new().with_result(GameResult::Draw)), [2]);
+    assert_eq!(select(GameFilter::new().with_result(GameResult::BlackWins)), [3]);
+
+    // Unrated players don't pass a minimum
+    assert_eq!(select(GameFilter::new().with_min_elo(2700)), [1, 2]);
+    assert_eq!(select(GameFilter::new().with_max_elo(2800)), [3]);
+
+    let (from, to) = parse_eco_range("B90..B99").unwrap();
+    assert_eq!(select(GameFilter::new().with_eco_range(from, to)), [1, 2]);
+    let (from, to) = parse_eco_range("B90").unwrap();
+    assert_eq!(select(GameFilter::new().with_eco_range(from, to)), [1]);
+
+    assert_eq!(select(GameFilter::new().with_min_ply(40)), [1, 2]);
+    assert_eq!(select(GameFilter::new().with_flags(GameIndex::flag_mask("T").unwrap())), [1, 3]);
+    assert_eq!(select(GameFilter::new().with_flags(GameIndex::flag_mask("wt").unwrap())), [1]);
+}
+
+#[test]
+fn parses_filter_values() {
+    assert_eq!(parse_date("2020"), Ok((2020, 0, 0)));
+    assert_eq!(parse_date("2020.05"), Ok((2020, 5, 0)));
+    assert_eq!(parse_date("2020-05-17"), Ok((2020, 5, 17)));
+    assert!(parse_date("2020.13").is_err());
+    assert!(parse_date("soon").is_err());
+
+    assert_eq!(parse_eco_range("C20-C29"), Ok((eco_from_str("C20"), eco_from_str("C29") + 130)));
+    assert!(parse_eco_range("B99-B90").is_err());
+    assert!(parse_eco_range("Z00").is_err());
+
+    assert_eq!(parse_result("1/2-1/2"), Ok(GameResult::Draw));
+    assert_eq!(parse_result("*"), Ok(GameResult::Unknown));
+    assert!(parse_result("win").is_err());
+
+    assert_eq!(GameIndex::flag_mask("WT"), Some(1 << 4 | 1 << 10));
+    assert_eq!(GameIndex::flag_mask("X"), None);
+}
+
+fn query(expression: &str) -> Vec<usize> {
+    select(GameFilter::new().with_expression(expression.parse().unwrap()))
+}
+
+#[test]
+fn filters_by_expression() {
+    assert_eq!(query(r#"white~"Carlsen" and date>=2020 and eco in B90..B99 and not result="1/2-1/2""#), [1]);
+    assert_eq!(query("player~carlsen and not white~carlsen"), [2]);
+    assert_eq!(query("white=\"morphy, paul\" or (elo>=2800 and result=1-0)"), [3]);
+    assert_eq!(query("elo >= 2700 or ply < 10"), [1, 2, 3]);
+    assert_eq!(query("NOT not eco = B99"), [2]);
+
+    // Spans cover every day, month or extension they name
+    assert_eq!(query("date=1858"), [3]);
+    assert_eq!(query("date=2020.10 and date<2020.10.12"), [1]);
+    assert_eq!(query("date in 1800..2020.10.05"), [1, 3]);
+    assert_eq!(query("eco<=B90"), [1]);
+    assert_eq!(query("eco>B90"), [2, 3]);
+
+    // Unknown values only satisfy !=
+    assert_eq!(query("round=1"), [1, 3]);
+    assert_eq!(query("black_elo<2000"), [3]);
+    assert_eq!(query("flags~T"), [1, 3]);
+    assert_eq!(query("flags=T"), [3]);
+    assert_eq!(query("site!=\"Stavanger NOR\""), [3]);
+
+    // Expressions compose with each other and the fixed conditions
+    let carlsen: FilterExpr = "player~carlsen".parse().unwrap();
+    let draw: FilterExpr = "result=1/2-1/2".parse().unwrap();
+    assert_eq!(select(GameFilter::new().with_expression(carlsen.clone().and(!draw.clone()))), [1]);
+    assert_eq!(select(GameFilter::new().with_expression(carlsen.or(draw)).with_min_ply(40)), [1, 2]);
+}
+
+#[test]
+fn reports_expression_errors() {
+    let error = |expression: &str| FilterExpr::parse(expression).unwrap_err();
+    assert_eq!(error("white~Carlsen and"), "expected a field name at column 18");
+    assert_eq!(error("colour=white"), "unknown field 'colour' at column 1");
+    assert_eq!(error("date>=20x0"), "invalid date '20x0' (expected YYYY, YYYY.MM or YYYY.MM.DD) at column 7");
+    assert_eq!(error("white<M"), "operator '<' can't be used with this field at column 6");
+    assert_eq!(error("eco in B90"), "expected '..' at column 11");
+    assert_eq!(error("(ply>10"), "expected ')' at column 8");
+    assert_eq!(error("ply>10 ply<20"), "unexpected 'ply' at column 8");
+    assert_eq!(error("event=\"open"), "unterminated string at column 7");
+}
@@ -0,0 +1,74 @@
+mod common;
+
+use common::{game_record, Fixture, FixtureGame, TempDir, RUY_LOPEZ_MOVES};
+use scidtopgn::export::GameFilter;
+use scidtopgn::pgn::PgnExporter;
+use scidtopgn::report::HeadToHead;
+use scidtopgn::scid::eco::eco_from_str;
+use scidtopgn::scid::ScidDatabase;
+
+fn game(white: &str, black: &str, year: u16, result: u8, eco: &str) -> FixtureGame {
+    FixtureGame::new(game_record(&[], 0, None, RUY_LOPEZ_MOVES, &[]))
+        .players(white, black)
+        .event("Norway Chess", "Stavanger NOR", "1")
+        .date(year, 6, 1)
+        .result(result)
+        .eco(eco_from_str(eco))
+}
+
+fn database(dir: &TempDir) -> ScidDatabase {
+    let base = Fixture::new()
+        .game(game("Carlsen, Magnus", "Nakamura, Hikaru", 2019, 1, "B90"))
+        .game(game("Nakamura, Hikaru", "Carlsen, Magnus", 2020, 3, "C65"))
+        .game(game("Carlsen, Magnus", "Caruana, Fabiano", 2020, 2, "B90"))
+        .game(game("Nakamura, Hikaru", "Carlsen, Magnus", 2021, 1, "C65b"))
+        .game(game("Carlsen, Magnus", "Nakamura, Hikaru", 2022, 0, ""))
+        .write(dir.path(), "h2h");
+    ScidDatabase::load(&base).unwrap()
+}
+
+#[test]
+fn scores_games_between_two_players() {
+    let dir = TempDir::new("h2h");
+    let report = HeadToHead::new("carlsen", "nakamura").build(&database(&dir)).unwrap();
+    assert_eq!((report.first.as_str(), report.second.as_str()), ("Carlsen, Magnus", "Nakamura, Hikaru"));
+    assert_eq!((report.total.games, report.total.wins, report.total.draws, report.total.losses), (4, 1, 1, 1));
+    assert_eq!(report.total.percent, Some(50.0));
+    assert_eq!((report.first_white.games, report.first_white.percent), (2, Some(100.0)));
+    assert_eq!((report.first_black.games, report.first_black.percent), (2, Some(25.0)));
+
+    let numbers: Vec<usize> = report.games.iter().map(|game| game.number).collect();
+    assert_eq!(numbers, [1, 2, 4, 5]);
+    assert_eq!(report.games[1].white, "Nakamura, Hikaru");
+    assert_eq!(report.games[1].date, "2020.06.01");
+    assert_eq!(report.games[1].event, "Norway Chess");
+    assert_eq!(report.games[1].result, "1/2-1/2");
+
+    let openings: Vec<(&str, u32)> = report.openings.iter().map(|o| (o.eco.as_str(), o.score.games)).collect();
+    assert_eq!(openings, [("C65", 2), ("B90", 1)]);
+
+    // Swapping the names gives the complementary score
+    let reversed = HeadToHead::new("Nakamura", "Carlsen").build(&database(&dir)).unwrap();
+    assert_eq!((reversed.total.wins, reversed.total.losses), (1, 1));
+    assert_eq!(reversed.first_white.percent, Some(75.0));
+}
+
+#[test]
+fn filters_and_exports_games() {
+    let dir = TempDir::new("h2h-export");
+    let mut database = database(&dir);
+    let recent = HeadToHead::new("carlsen", "nakamura").with_filter(GameFilter::new().with_date_from((2021, 0, 0)));
+    assert_eq!(recent.build(&database).unwrap().total.games, 2);
+
+    let output = dir.path().join("h2h.pgn");
+    let filter = recent.game_filter(&database).unwrap();
+    let exported = PgnExporter::new().with_filter(filter).export(&mut database, &output).unwrap();
+    assert_eq!(exported, 2);
+    let pgn = std::fs::read_to_string(&output).unwrap();
+    assert!(pgn.contains("[Date \"2021.06.01\"]") && pgn.contains("[Date \"2022.06.01\"]"));
+
+    assert_eq!(HeadToHead::new("carlsen", "magnus").build(&database).unwrap_err(),
+               "'carlsen' and 'magnus' are the same player");
+    assert_eq!(HeadToHead::new("carlsen", "kasparov").build(&database).unwrap_err(),
+               "no player matches 'kasparov'");
+}
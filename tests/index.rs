@@ -0,0 +1,177 @@
+mod common;
+
+use std::io::ErrorKind;
+
+use common::{game_record, Fixture, FixtureGame, TempDir, RUY_LOPEZ_MOVES};
+use scidtopgn::chess::position::make_square;
+use scidtopgn::scid::index::IndexFile;
+use scidtopgn::scid::signature::SideMaterial;
+use scidtopgn::scid::{ScidDatabase, ScidError};
+
+fn ruy_lopez() -> Vec<u8> {
+    game_record(&[], 0, None, RUY_LOPEZ_MOVES, &["Morphy Defence"])
+}
+
+#[test]
+fn parses_header() {
+    let dir = TempDir::new("header");
+    let base = Fixture::new()
+        .description("Synthetic test base")
+        .game(FixtureGame::new(ruy_lopez()))
+        .game(FixtureGame::new(ruy_lopez()))
+        .write(dir.path(), "header");
+
+    let index = IndexFile::load(base.with_extension("si4")).unwrap();
+    let header = index.header();
+    assert_eq!(&header.magic, b"Scid.si\0");
+    assert_eq!(header.version, 400);
+    assert_eq!(header.num_games, 2);
+    assert!(header.database_info.starts_with(b"Synthetic test base\0"));
+    assert_eq!(index.num_games(), 2);
+}
+
+#[test]
+fn unpacks_dates_ratings_and_counts() {
+    let dir = TempDir::new("fields");
+    let base = Fixture::new()
+        .game(FixtureGame::new(ruy_lopez())
+            .date(1791, 12, 24)
+            .result(1)
+            .elo(2750, 0x1000 | 2600)
+            .eco(1234)
+            .ply(9)
+            .counts(1, 1, 1))
+        .game(FixtureGame::new(ruy_lopez()).date(2021, 3, 0).result(3))
+        .write(dir.path(), "fields");
+
+    let index = IndexFile::load(base.with_extension("si4")).unwrap();
+    let game = index.game_index(0).unwrap();
+    assert_eq!((game.year, game.month, game.day), (1791, 12, 24));
+    assert_eq!(game.date_string(), "1791.12.24");
+    assert_eq!(game.result, 1);
+    assert_eq!(game.result_string(), "1-0");
+    // Ratings keep only their low 12 bits
+    assert_eq!((game.white_elo, game.black_elo), (2750, 2600));
+    assert_eq!(game.eco, 1234);
+    assert_eq!(game.num_half_moves, 9);
+    assert_eq!((game.var_count, game.comment_count, game.nag_count), (1, 1, 1));
+    assert_eq!(game.offset, 0);
+    assert_eq!(game.length as usize, ruy_lopez().len());
+
+    let game = index.game_index(1).unwrap();
+    assert_eq!(game.date_string(), "2021.03.??");
+    assert_eq!(game.result_string(), "1/2-1/2");
+    assert_eq!(game.offset as usize, ruy_lopez().len());
+}
+
+#[test]
+fn unknown_date_parts_print_as_question_marks() {
+    let dir = TempDir::new("dates");
+    let base = Fixture::new()
+        .game(FixtureGame::new(ruy_lopez()).date(1999, 0, 0))
+        .game(FixtureGame::new(ruy_lopez()).date(1999, 7, 0))
+        .game(FixtureGame::new(ruy_lopez()).date(0, 0, 0))
+        .write(dir.path(), "dates");
+
+    let index = IndexFile::load(base.with_extension("si4")).unwrap();
+    let dates: Vec<String> = (0..3).map(|game| index.game_index(game).unwrap().date_string()).collect();
+    assert_eq!(dates, ["1999.??.??", "1999.07.??", "????.??.??"]);
+}
+
+#[test]
+fn unpacks_high_id_bits() {
+    let dir = TempDir::new("ids");
+    let base = Fixture::new()
+        .game(FixtureGame::new(ruy_lopez()).ids([0xA1234, 0x5_4321, 0x6_0001, 0x3_0002, 3]))
+        .write(dir.path(), "ids");
+
+    let index = IndexFile::load(base.with_extension("si4")).unwrap();
+    let game = index.game_index(0).unwrap();
+    assert_eq!(game.white_id, 0xA1234);
+    assert_eq!(game.black_id, 0x5_4321);
+    assert_eq!(game.event_id, 0x6_0001);
+    assert_eq!(game.site_id, 0x3_0002);
+    assert_eq!(game.round_id, 3);
+}
+
+#[test]
+fn unpacks_length_ply_and_flags() {
+    // Padding after the comments is never read, but makes the record need
+    // the 17th length bit
+    let mut long_record = ruy_lopez();
+    long_record.resize(70_000, 0);
+
+    let dir = TempDir::new("length");
+    let base = Fixture::new()
+        .game(FixtureGame::new(long_record).ply(0x2FF))
+        .game(FixtureGame::new(ruy_lopez()).flags(0x08))
+        .write(dir.path(), "length");
+
+    let mut database = ScidDatabase::load(&base).unwrap();
+    let first = database.game_index(0).unwrap().clone();
+    assert_eq!(first.length, 70_000);
+    assert_eq!(first.num_half_moves, 0x2FF);
+    assert!(!first.is_deleted());
+    assert_eq!(database.game_record(&first).unwrap().moves.moves.len(), 9);
+
+    let second = database.game_index(1).unwrap();
+    assert_eq!(second.offset, 70_000);
+    assert!(second.is_deleted());
+}
+
+#[test]
+fn decodes_final_material_and_home_pawns() {
+    // White: 5 pawns, 1 knight, 2 rooks, 1 queen; black: 6 pawns, 2 bishops,
+    // 1 rook. Home pawns: e2, e7, d2, c7, a2 left in that order.
+    let final_mat_sig = 5 << 12 | 1 << 16 | 2 << 20 | 1 << 22 | 6 | 2 << 6 | 1 << 8;
+    let home_pawn_data = [5, 0x4C, 0x3A, 0x00, 0, 0, 0, 0, 0];
+
+    let dir = TempDir::new("signatures");
+    let base = Fixture::new()
+        .game(FixtureGame::new(ruy_lopez()).ply(300).signatures(final_mat_sig, home_pawn_data))
+        .game(FixtureGame::new(ruy_lopez()))
+        .write(dir.path(), "signatures");
+
+    let database = ScidDatabase::load_index_only(&base).unwrap();
+    let game = &database.game_indices()[0];
+    assert_eq!(game.num_half_moves, 300);
+    assert_eq!(game.final_material.white, SideMaterial { queens: 1, rooks: 2, bishops: 0, knights: 1, pawns: 5 });
+    assert_eq!(game.final_material.black, SideMaterial { queens: 0, rooks: 1, bishops: 2, knights: 0, pawns: 6 });
+    assert_eq!(game.final_material.signature(), final_mat_sig);
+
+    let squares: Vec<_> = game.home_pawns.squares().collect();
+    let expected = [make_square(4, 1), make_square(4, 6), make_square(3, 1), make_square(2, 6), make_square(0, 1)];
+    assert_eq!(squares, expected);
+    // The ply count's high bits share the first byte with the count
+    assert_eq!(game.home_pawns.to_bytes(), home_pawn_data);
+
+    // Records without summaries decode as empty
+    let plain = &database.game_indices()[1];
+    assert!(plain.final_material.is_empty());
+    assert!(plain.home_pawns.is_empty());
+}
+
+#[test]
+fn rejects_unsupported_versions() {
+    let dir = TempDir::new("version");
+    let base = Fixture::new()
+        .versions(300, 400)
+        .game(FixtureGame::new(ruy_lopez()))
+        .write(dir.path(), "version");
+
+    let error = ScidDatabase::load(&base).err().unwrap();
+    assert_eq!(error.kind(), ErrorKind::InvalidData);
+    let scid_error = error.get_ref().and_then(|e| e.downcast_ref::<ScidError>()).unwrap();
+    assert!(matches!(scid_error, ScidError::UnsupportedVersion { found: 300, .. }));
+}
+
+#[test]
+fn rejects_bad_magic() {
+    let dir = TempDir::new("magic");
+    let path = dir.path().join("magic.si4");
+    let mut header = Fixture::new().index_header();
+    header[0] = b'X';
+    std::fs::write(&path, header).unwrap();
+
+    assert_eq!(IndexFile::load(&path).err().unwrap().kind(), ErrorKind::InvalidData);
+}
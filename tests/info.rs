@@ -0,0 +1,63 @@
+mod common;
+
+use std::fs;
+
+use common::{ruy_lopez_game, Fixture, TempDir};
+use scidtopgn::scid::index::IDX_FLAG_DELETE;
+use scidtopgn::scid::{DatabaseInfo, ScidDatabase};
+
+#[test]
+fn decodes_header_and_totals() {
+    let dir = TempDir::new("info");
+    let base = Fixture::new()
+        .description("Paris 1858")
+        .game(ruy_lopez_game().players("Morphy", "Anderssen").event("Match", "Paris", "1").date(1858, 12, 20))
+        .game(ruy_lopez_game().players("Morphy", "Isouard").event("Opera", "Paris", "?").date(1858, 0, 0))
+        .game(ruy_lopez_game().players("Morphy", "Harrwitz").flags(IDX_FLAG_DELETE).date(1858, 9, 0))
+        .game(ruy_lopez_game().players("Anon", "Anon"))
+        .write(dir.path(), "games");
+
+    // Database type 2, autoload game 3 and a name for custom flag 2
+    let mut si4 = fs::read(base.with_extension("si4")).unwrap();
+    si4[10..14].copy_from_slice(&2u32.to_le_bytes());
+    si4[17..20].copy_from_slice(&[3, 0, 0]);
+    si4[137..143].copy_from_slice(b"Sharp\0");
+    fs::write(base.with_extension("si4"), si4).unwrap();
+
+    let info = DatabaseInfo::new(&ScidDatabase::load(&base).unwrap());
+    assert_eq!((info.version, info.version_name, info.db_type), (400, Some("SCID 4.x"), 2));
+    assert_eq!((info.auto_load_game, info.description.as_str()), (Some(3), "Paris 1858"));
+    assert_eq!(info.custom_flags, ["", "Sharp", "", "", "", ""]);
+    assert_eq!((info.games, info.deleted_games), (4, 1));
+    assert_eq!((info.players, info.events, info.sites, info.rounds), (5, 3, 2, 2));
+    assert_eq!((info.first_date.as_deref(), info.last_date.as_deref()), (Some("1858.??.??"), Some("1858.12.20")));
+
+    let usage = info.game_file.unwrap();
+    assert_eq!(usage.unused_bytes, usage.bytes / 4);
+    assert_eq!(usage.fragmentation, 25.0);
+
+    let mut text = Vec::new();
+    info.write_text(&mut text).unwrap();
+    let text = String::from_utf8(text).unwrap();
+    assert!(text.contains("Custom flags:  2 Sharp\n"), "{}", text);
+    assert!(text.contains("Games:         4 (1 deleted)\n"), "{}", text);
+    assert!(text.ends_with("unused (25.0% fragmentation)\n"), "{}", text);
+
+    let json = serde_json::to_value(&info).unwrap();
+    assert_eq!(json["game_file"]["used_bytes"], usage.bytes * 3 / 4);
+    assert_eq!(json["auto_load_game"], 3);
+}
+
+#[test]
+fn index_only_has_no_game_file() {
+    let dir = TempDir::new("info_index_only");
+    let base = Fixture::new().write(dir.path(), "empty");
+    let info = DatabaseInfo::new(&ScidDatabase::load_index_only(&base).unwrap());
+    assert_eq!((info.games, info.first_date.as_deref(), info.game_file), (0, None, None));
+
+    let mut text = Vec::new();
+    info.write_text(&mut text).unwrap();
+    let text = String::from_utf8(text).unwrap();
+    assert!(text.contains("Autoload game: none\nCustom flags:  none\n"), "{}", text);
+    assert!(text.contains("Dates:         none\nGame file:     not loaded\n"), "{}", text);
+}
@@ -0,0 +1,127 @@
+mod common;
+
+use common::{game_record, Fixture, FixtureGame, TempDir, PROMOTION_FEN, PROMOTION_MOVES, RUY_LOPEZ_MOVES};
+use scidtopgn::export::JsonExporter;
+use scidtopgn::scid::eco::eco_from_str;
+use scidtopgn::scid::ScidDatabase;
+use serde_json::{json, Value};
+
+fn fixture() -> Fixture {
+    let ruy_lopez = game_record(&[("Annotator", "Tester")], 0, None, RUY_LOPEZ_MOVES, &["Morphy Defence"]);
+    let promotion = game_record(&[], 7, Some(PROMOTION_FEN), PROMOTION_MOVES, &[]);
+    let corrupt = game_record(&[], 0, None, &[0xCF], &[]);
+
+    Fixture::new()
+        .game(FixtureGame::new(ruy_lopez)
+            .players("Morphy, Paul", "Anderssen, Adolf")
+            .event("Paris m", "Paris FRA", "1")
+            .date(1858, 12, 20)
+            .result(3)
+            .elo(2690, 0)
+            .eco(eco_from_str("C78"))
+            .ply(9)
+            .counts(1, 1, 1))
+        .game(FixtureGame::new(corrupt.clone())
+            .players("Deleted", "Game")
+            .flags(0x08))
+        .game(FixtureGame::new(promotion)
+            .players("White, A", "Black, B")
+            .event("?", "?", "?")
+            .date(2021, 3, 0)
+            .result(1)
+            .flags(7 | 1 << 10 | 1 << 14)
+            .custom_flags(0b100010)
+            .ply(4))
+        .game(FixtureGame::new(corrupt)
+            .players("Corrupt", "Record"))
+}
+
+fn export(mut exporter: JsonExporter) -> String {
+    let dir = TempDir::new("json");
+    let base = fixture().write(dir.path(), "json");
+    let mut database = ScidDatabase::load(&base).unwrap();
+    let mut out = Vec::new();
+    exporter.write(&mut database, &mut out).unwrap();
+    String::from_utf8(out).unwrap()
+}
+
+fn ndjson(exporter: JsonExporter) -> Vec<Value> {
+    export(exporter.with_ndjson(true)).lines()
+        .map(|line| serde_json::from_str(line).unwrap())
+        .collect()
+}
+
+#[test]
+fn writes_typed_headers() {
+    let games = ndjson(JsonExporter::new());
+    // The deleted game is skipped but numbering follows the index
+    assert_eq!(games.iter().map(|g| g["number"].as_u64().unwrap()).collect::<Vec<_>>(), [1, 3, 4]);
+
+    let ruy = &games[0];
+    assert_eq!(ruy["event"], "Paris m");
+    assert_eq!(ruy["date"], "1858-12-20");
+    assert_eq!(ruy["white"], "Morphy, Paul");
+    assert_eq!(ruy["result"], "1/2-1/2");
+    assert_eq!(ruy["white_elo"], 2690);
+    assert_eq!(ruy["black_elo"], Value::Null);
+    assert_eq!(ruy["eco"], "C78");
+    assert_eq!(ruy["ply_count"], 9);
+    assert_eq!(ruy["tags"], json!({"Annotator": "Tester"}));
+    assert_eq!(ruy["start_fen"], Value::Null);
+
+    let promotion = &games[1];
+    assert_eq!(promotion["event"], Value::Null);
+    assert_eq!(promotion["date"], "2021-03");
+    let flags = promotion["flags"].as_object().unwrap();
+    let set: Vec<&str> = flags.iter().filter(|(_, value)| **value == true).map(|(name, _)| name.as_str()).collect();
+    assert_eq!(set, ["blunder", "non_standard_start", "promotions", "tactics", "under_promotions"]);
+    assert_eq!(flags["custom"], json!([2, 6]));
+    assert_eq!(ruy["flags"]["custom"], json!([]));
+    assert_eq!(promotion["start_fen"], PROMOTION_FEN);
+}
+
+#[test]
+fn writes_san_and_uci_moves() {
+    let games = ndjson(JsonExporter::new());
+    let moves = games[1]["moves"].as_array().unwrap();
+    assert_eq!(moves[0], json!({"ply": 1, "san": "b8=N", "uci": "b7b8n"}));
+    assert_eq!(moves[3], json!({"ply": 4, "san": "Kc7", "uci": "d8c7"}));
+
+    // NAGs always; comments and variations only on request
+    let moves = games[0]["moves"].as_array().unwrap();
+    assert_eq!(moves.len(), 9);
+    assert_eq!(moves[8], json!({"ply": 9, "san": "O-O", "uci": "e1g1", "nags": [1]}));
+    assert_eq!(moves[5].get("comment"), None);
+    assert_eq!(moves[6].get("variations"), None);
+
+    let games = ndjson(JsonExporter::new().with_comments(true).with_variations(true));
+    let moves = games[0]["moves"].as_array().unwrap();
+    assert_eq!(moves[5]["comment"], "Morphy Defence");
+    assert_eq!(moves[6]["variations"], json!([{"moves": [
+        {"ply": 7, "san": "Bxc6", "uci": "b5c6"},
+        {"ply": 8, "san": "dxc6", "uci": "d7c6"},
+    ]}]));
+}
+
+#[test]
+fn reports_undecodable_games() {
+    let games = ndjson(JsonExporter::new());
+    let corrupt = &games[2];
+    assert_eq!(corrupt["white"], "Corrupt");
+    assert_eq!(corrupt["moves"], json!([]));
+    assert!(corrupt["error"].as_str().unwrap().starts_with("Unable to decode SCID moves"));
+    assert_eq!(games[0].get("error"), None);
+}
+
+#[test]
+fn writes_json_array() {
+    for pretty in [false, true] {
+        let text = export(JsonExporter::new().with_pretty(pretty).with_max_games(2));
+        let games: Vec<Value> = serde_json::from_str(&text).unwrap();
+        assert_eq!(games.len(), 2);
+        assert_eq!(games[1]["white"], "White, A");
+    }
+
+    let empty: Vec<Value> = serde_json::from_str(&export(JsonExporter::new().with_max_games(0))).unwrap();
+    assert!(empty.is_empty());
+}
@@ -0,0 +1,128 @@
+mod common;
+
+use common::TempDir;
+use scidtopgn::chess::Color;
+use scidtopgn::pgn::PgnReader;
+use scidtopgn::scid::{ScidDatabase, ScidWriter};
+use scidtopgn::search::{BishopColors, MaterialHit, MaterialPattern, MaterialScope, MaterialSearch};
+
+const PGN: &str = r#"[White "Rook ending"]
+[Result "*"]
+[SetUp "1"]
+[FEN "8/8/4k3/8/4P3/8/r7/4K2R w - - 0 1"]
+
+1. Rh6+ Ke5 2. Rh5+ Ke6 *
+
+[White "Black rook ending"]
+[Result "*"]
+[SetUp "1"]
+[FEN "4k2r/8/4p3/8/8/8/R7/4K3 b - - 0 1"]
+
+1... Rh1+ 2. Ke2 Rh2+ 3. Ke3 *
+
+[White "Opposite bishops"]
+[Result "1/2-1/2"]
+[SetUp "1"]
+[FEN "4k3/5p2/8/8/2B5/8/4P3/4K1b1 w - - 0 1"]
+
+1. Kf1 Bh2 2. Kg2 Bd6 1/2-1/2
+
+[White "Same bishops"]
+[Result "*"]
+[SetUp "1"]
+[FEN "4k3/5p2/8/8/2B5/8/4P3/4K2b w - - 0 1"]
+
+1. Kf1 Bc6 2. Kg1 Bd7 *
+
+[White "Passing through"]
+[Result "*"]
+[SetUp "1"]
+[FEN "4k2r/8/8/R2n4/8/8/4P3/4K3 w - - 0 1"]
+
+1. Rxd5 Rh2 2. Kf1 Rxe2 *
+
+[White "Opening"]
+[Result "*"]
+
+1. e4 e5 2. Nf3 Nc6 *
+"#;
+
+fn write_database(dir: &TempDir) -> std::path::PathBuf {
+    let base = dir.path().join("games");
+    let mut writer = ScidWriter::create(&base).unwrap();
+    for game in PgnReader::new(PGN.as_bytes()) {
+        writer.add_game(&game.unwrap()).unwrap();
+    }
+    writer.finish().unwrap();
+    base
+}
+
+/// One-based game number, ply and whether flipped of each hit
+fn hits(search: MaterialSearch) -> Vec<(usize, u32, bool)> {
+    let dir = TempDir::new("material");
+    let mut database = ScidDatabase::load(write_database(&dir)).unwrap();
+    search.search(&mut database).into_iter()
+        .map(|MaterialHit { game, ply, flipped }| (game + 1, ply, flipped))
+        .collect()
+}
+
+fn pattern(text: &str) -> MaterialPattern {
+    text.parse().unwrap()
+}
+
+#[test]
+fn finds_final_material() {
+    let rook_pawn = MaterialSearch::new(pattern("KRP vs KR"));
+    assert_eq!(hits(rook_pawn), [(1, 4, false)]);
+    let flipped = MaterialSearch::new(pattern("KRPKR")).with_flip(true);
+    assert_eq!(hits(flipped), [(1, 4, false), (2, 4, true)]);
+
+    // The side to move is the pattern's first side, flipped with it
+    let white_to_move = MaterialSearch::new(pattern("KRP-KR")).with_flip(true).with_side_to_move(Color::White);
+    assert_eq!(hits(white_to_move), [(1, 4, false), (2, 4, true)]);
+    let black_to_move = MaterialSearch::new(pattern("KRP-KR")).with_flip(true).with_side_to_move(Color::Black);
+    assert!(hits(black_to_move).is_empty());
+
+    // Material alone doesn't need the game file
+    let dir = TempDir::new("material-index");
+    let mut database = ScidDatabase::load_index_only(write_database(&dir)).unwrap();
+    let found = MaterialSearch::new(pattern("kbp v kbp")).search(&mut database);
+    assert_eq!(found.iter().map(|hit| hit.game + 1).collect::<Vec<_>>(), [3, 4]);
+}
+
+#[test]
+fn matches_bishop_colours_and_any_pawns() {
+    assert_eq!(hits(MaterialSearch::new(pattern("KBP vs KBP")).with_bishops(BishopColors::Opposite)), [(3, 4, false)]);
+    assert_eq!(hits(MaterialSearch::new(pattern("KBP vs KBP")).with_bishops(BishopColors::Same)), [(4, 4, false)]);
+    assert_eq!(hits(MaterialSearch::new(pattern("KBP* vs KBP*")).with_bishops(BishopColors::Opposite)), [(3, 4, false)]);
+    assert!(hits(MaterialSearch::new(pattern("KB vs KB"))).is_empty());
+}
+
+#[test]
+fn finds_material_anywhere_in_game() {
+    let anywhere = || MaterialSearch::new(pattern("KRP vs KR")).with_scope(MaterialScope::Any);
+    assert_eq!(hits(anywhere()), [(1, 0, false), (5, 1, false)]);
+    assert_eq!(hits(anywhere().with_max_games(1)), [(1, 0, false)]);
+    assert_eq!(hits(anywhere().with_side_to_move(Color::White).with_flip(true)),
+               [(1, 0, false), (2, 0, true), (5, 2, false)]);
+    assert_eq!(hits(anywhere().with_side_to_move(Color::Black).with_flip(true)),
+               [(1, 1, false), (2, 1, true), (5, 1, false)]);
+
+    // Games ending with more material, or with pawns the pattern lacks,
+    // are never decoded
+    let dir = TempDir::new("material-candidates");
+    let database = ScidDatabase::load(write_database(&dir)).unwrap();
+    let candidates: Vec<usize> = anywhere().candidates(&database).into_iter().map(|(game, _)| game + 1).collect();
+    assert_eq!(candidates, [1, 5]);
+}
+
+#[test]
+fn parses_patterns() {
+    assert_eq!(pattern("KRP vs KR"), pattern("krpkr"));
+    assert_eq!(pattern("KRP - KR").flipped(), pattern("KR v KRP"));
+    assert_eq!(MaterialPattern::parse("RP vs R").unwrap_err(),
+               "invalid material 'RP vs R' (expected two sides such as KRP vs KR)");
+    assert!(MaterialPattern::parse("KRX vs KR").is_err());
+    assert!(MaterialPattern::parse("KQQQQ vs K").is_err());
+    assert!(MaterialPattern::parse("KRP").is_err());
+}
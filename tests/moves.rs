@@ -0,0 +1,122 @@
+mod common;
+
+use common::{game_record, PROMOTION_FEN, PROMOTION_MOVES, RUY_LOPEZ_MOVES};
+use scidtopgn::chess::{Game, Position};
+use scidtopgn::pgn::parse_games;
+use scidtopgn::scid::moves::{decode_game, encode_game};
+
+/// SAN of each main line move from the start position
+fn main_line_san(start: &Position, moves: &[scidtopgn::chess::MoveNode]) -> Vec<String> {
+    let mut pos = start.clone();
+    moves.iter().map(|node| {
+        let san = pos.san(&node.mv);
+        pos.make_move(&node.mv);
+        san
+    }).collect()
+}
+
+#[test]
+fn decodes_moves_comments_nags_and_variations() {
+    let data = game_record(&[("Annotator", "Tester")], 0, None, RUY_LOPEZ_MOVES, &["Morphy Defence"]);
+    let record = decode_game(&data).unwrap();
+
+    assert_eq!(record.tags, vec![("Annotator".to_string(), "Tester".to_string())]);
+    assert_eq!(record.start_fen, None);
+
+    let start = Position::starting_position();
+    let moves = &record.moves.moves;
+    assert_eq!(main_line_san(&start, moves),
+               ["e4", "e5", "Nf3", "Nc6", "Bb5", "a6", "Ba4", "Nf6", "O-O"]);
+    assert_eq!(moves[5].comment.as_deref(), Some("Morphy Defence"));
+    assert_eq!(moves[8].nags, [1]);
+
+    assert_eq!(moves[6].variations.len(), 1);
+    let mut before = start.clone();
+    for node in &moves[..6] {
+        before.make_move(&node.mv);
+    }
+    assert_eq!(main_line_san(&before, &moves[6].variations[0].moves), ["Bxc6", "dxc6"]);
+}
+
+#[test]
+fn decodes_non_standard_start_and_promotions() {
+    let data = game_record(&[], 7, Some(PROMOTION_FEN), PROMOTION_MOVES, &[]);
+    let record = decode_game(&data).unwrap();
+
+    assert_eq!(record.start_fen.as_deref(), Some(PROMOTION_FEN));
+    let start = Position::from_fen(PROMOTION_FEN).unwrap();
+    assert_eq!(main_line_san(&start, &record.moves.moves), ["b8=N", "Kd8", "Qd5+", "Kc7"]);
+}
+
+#[test]
+fn rejects_truncated_records() {
+    let data = game_record(&[], 0, None, RUY_LOPEZ_MOVES, &["Morphy Defence"]);
+    for len in [0, 1, 5, RUY_LOPEZ_MOVES.len()] {
+        assert!(decode_game(&data[..len]).is_err(), "record truncated to {} bytes", len);
+    }
+}
+
+#[test]
+fn encoder_matches_fixture_bytes() {
+    let pgn = "1. e4 e5 2. Nf3 Nc6 3. Bb5 a6 {Morphy Defence} 4. Ba4 (4. Bxc6 dxc6) Nf6 5. O-O! *";
+    let game = parse_games(pgn).remove(0).unwrap();
+    let expected = game_record(&[], 0, None, RUY_LOPEZ_MOVES, &["Morphy Defence"]);
+    assert_eq!(encode_game(&game).unwrap(), expected);
+}
+
+/// 1.d4 d5 2.c4 e6 3.cxd5 exd5 4.Nc3 Nf6 5.Bg5 Be7 6.e3 O-O 7.Qc2 Nbd7 8.O-O-O,
+/// byte for byte as SCID's encoder writes it: pawn steps are counted towards
+/// the opponent, so both captures on d5 have value 2, and the king's value
+/// is 10 for O-O and 9 for O-O-O
+const QGD_EXCHANGE_MOVES: &[u8] = &[
+    0xBF,       // d2 pawn (11), double push
+    0xBF,       // d7 pawn (11), double push
+    0xAF,       // c2 pawn (10), double push
+    0xC1,       // e7 pawn (12), step of 8
+    0xA2,       // c4 pawn (10), step of 9: cxd5
+    0xC2,       // e6 pawn (12), step of 9: exd5
+    0x28,       // b1 knight (2), jump +17
+    0x61,       // g8 knight (6), jump -17
+    0x36,       // c1 bishop (3), to the g-file along the diagonal
+    0x54,       // f8 bishop (5), to the e-file along the diagonal
+    0xC1,       // e2 pawn (12), step of 8
+    0x0A,       // king (0), O-O
+    0x43, 0x4A, // d1 queen (4), diagonal move to c2 (10 + 64)
+    0x24,       // b8 knight (2), jump -6
+    0x09,       // king (0), O-O-O
+    0x0F,       // end of game
+];
+
+#[test]
+fn matches_scid_pawn_and_castling_codes() {
+    let record = decode_game(&game_record(&[], 0, None, QGD_EXCHANGE_MOVES, &[])).unwrap();
+    assert_eq!(main_line_san(&Position::starting_position(), &record.moves.moves),
+               ["d4", "d5", "c4", "e6", "cxd5", "exd5", "Nc3", "Nf6", "Bg5", "Be7", "e3", "O-O",
+                "Qc2", "Nbd7", "O-O-O"]);
+
+    let pgn = "1. d4 d5 2. c4 e6 3. cxd5 exd5 4. Nc3 Nf6 5. Bg5 Be7 6. e3 O-O 7. Qc2 Nbd7 8. O-O-O *";
+    let game = parse_games(pgn).remove(0).unwrap();
+    assert_eq!(encode_game(&game).unwrap(), game_record(&[], 0, None, QGD_EXCHANGE_MOVES, &[]));
+}
+
+#[test]
+fn encode_decode_round_trip() {
+    let pgn = r#"[Event "Round trip"]
+[SetUp "1"]
+[FEN "r3k2r/p1ppqpb1/bn2pnp1/3PN3/1p2P3/2N2Q1p/PPPBBPPP/R3K2R w KQkq - 0 1"]
+[Opening "Kiwipete"]
+[Source "Synthetic"]
+
+{Start} 1. O-O-O $14 {Long castling} (1. O-O O-O-O (1... O-O 2. a3) 2. a3) 1... O-O
+2. d6 {First} {Second} b3 3. Qxf6 bxa2 4. Qxe7 a1=R+ 5. Nb1 *
+"#;
+    let game: Game = parse_games(pgn).remove(0).unwrap();
+    let record = decode_game(&encode_game(&game).unwrap()).unwrap();
+
+    assert_eq!(record.tags, vec![
+        ("Opening".to_string(), "Kiwipete".to_string()),
+        ("Source".to_string(), "Synthetic".to_string()),
+    ]);
+    assert_eq!(record.start_fen.as_deref(), game.tag("FEN"));
+    assert_eq!(record.moves, game.moves);
+}
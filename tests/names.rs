@@ -0,0 +1,121 @@
+mod common;
+
+use std::fs;
+use std::path::Path;
+
+use common::{game_record, Fixture, FixtureGame, TempDir, RUY_LOPEZ_MOVES};
+use scidtopgn::pgn::PgnReader;
+use scidtopgn::scid::names::NameDatabase;
+use scidtopgn::scid::{ScidDatabase, ScidWriter};
+
+fn game() -> FixtureGame {
+    FixtureGame::new(game_record(&[], 0, None, RUY_LOPEZ_MOVES, &[]))
+}
+
+#[test]
+fn decodes_names_of_every_type() {
+    let dir = TempDir::new("names");
+    let base = Fixture::new()
+        .game(game().players("Morphy, Paul", "Duke Karl / Count Isouard").event("Paris", "Paris FRA", "?"))
+        .game(game().players("Anderssen, Adolf", "Morphy, Paul").event("Match", "Paris FRA", "1"))
+        .write(dir.path(), "names");
+
+    let names = NameDatabase::parse_names(base.with_extension("sn4").to_str().unwrap()).unwrap();
+    assert_eq!(names.version, 400);
+    assert_eq!(names.player_name(0), Some("Morphy, Paul"));
+    assert_eq!(names.player_name(1), Some("Duke Karl / Count Isouard"));
+    assert_eq!(names.player_name(2), Some("Anderssen, Adolf"));
+    assert_eq!(names.player_name(3), None);
+    assert_eq!(names.event_name(1), Some("Match"));
+    assert_eq!(names.site_name(0), Some("Paris FRA"));
+    // Single-character names are common for rounds and unknown values
+    assert_eq!(names.round_name(0), Some("?"));
+    assert_eq!(names.round_name(1), Some("1"));
+
+    let database = ScidDatabase::load(&base).unwrap();
+    let second = database.game_index(1).unwrap();
+    assert_eq!(database.player_name(second.white_id), Some("Anderssen, Adolf"));
+    assert_eq!(database.player_name(second.black_id), Some("Morphy, Paul"));
+}
+
+#[test]
+fn decodes_two_byte_ids() {
+    // 300 distinct players need two-byte IDs
+    let mut fixture = Fixture::new();
+    for i in 0..150 {
+        fixture = fixture.game(game().players(&format!("White {}", i), &format!("Black {}", i)));
+    }
+    let dir = TempDir::new("wide-ids");
+    let base = fixture.write(dir.path(), "wide");
+
+    let database = ScidDatabase::load(&base).unwrap();
+    let last = database.game_index(149).unwrap();
+    assert_eq!(last.white_id, 298);
+    assert_eq!(database.player_name(last.white_id), Some("White 149"));
+    assert_eq!(database.player_name(last.black_id), Some("Black 149"));
+}
+
+#[test]
+fn ignores_reserved_header_bytes() {
+    let dir = TempDir::new("name-reserved");
+    let base = Fixture::new().game(game().players("Morphy, Paul", "Morphy, Paula")).write(dir.path(), "names");
+    let mut data = fs::read(base.with_extension("sn4")).unwrap();
+    data[38..42].copy_from_slice(&[0xFF; 4]);
+
+    let names = NameDatabase::from_bytes(Path::new("names.sn4"), &data).unwrap();
+    assert_eq!((names.player_name(0), names.player_name(1)), (Some("Morphy, Paul"), Some("Morphy, Paula")));
+}
+
+#[test]
+fn writes_front_coded_names() {
+    let pgn = "[White \"Carlsen, Magnus\"]\n[Black \"Carlsen, Henrik\"]\n\n*\n\n";
+    let dir = TempDir::new("front-coded");
+    let base = dir.path().join("names");
+    let mut writer = ScidWriter::create(&base).unwrap();
+    writer.add_game(&PgnReader::new(pgn.as_bytes()).next().unwrap().unwrap()).unwrap();
+    writer.finish().unwrap();
+
+    // Sorted, "Carlsen, Henrik" comes first and "Carlsen, Magnus" only adds "Magnus"
+    let data = fs::read(base.with_extension("sn4")).unwrap();
+    assert_eq!(data.windows(7).filter(|window| window == b"Carlsen").count(), 1);
+    assert!(data.windows(8).any(|window| window == [15, 9, b'M', b'a', b'g', b'n', b'u', b's']));
+
+    let database = ScidDatabase::load_index_only(&base).unwrap();
+    let game = database.game_index(0).unwrap();
+    assert_eq!(database.player_name(game.white_id), Some("Carlsen, Magnus"));
+    assert_eq!(database.player_name(game.black_id), Some("Carlsen, Henrik"));
+}
+
+#[test]
+fn writes_three_byte_ids_and_frequencies() {
+    // More players than two-byte IDs can number, all playing "?", whose
+    // frequency outgrows two bytes too
+    let pgn: String = (0..66_000).map(|i| format!("[White \"Player {}\"]\n\n*\n\n", i)).collect();
+    let dir = TempDir::new("three-byte-ids");
+    let base = dir.path().join("many");
+    let mut writer = ScidWriter::create(&base).unwrap();
+    for game in PgnReader::new(pgn.as_bytes()) {
+        writer.add_game(&game.unwrap()).unwrap();
+    }
+    assert_eq!(writer.finish().unwrap(), 66_000);
+
+    let database = ScidDatabase::load_index_only(&base).unwrap();
+    assert_eq!(database.names().players.len(), 66_001);
+    let last = database.game_index(65_999).unwrap();
+    assert_eq!(last.white_id, 66_000);
+    assert_eq!(database.player_name(last.white_id), Some("Player 65999"));
+    assert_eq!(database.player_name(last.black_id), Some("?"));
+    assert_eq!(database.event_name(last.event_id), Some("?"));
+}
+
+#[test]
+fn rejects_unsupported_namebase_version() {
+    let dir = TempDir::new("name-version");
+    let base = Fixture::new()
+        .versions(400, 300)
+        .game(game())
+        .write(dir.path(), "namebase");
+
+    let error = NameDatabase::parse_names(base.with_extension("sn4").to_str().unwrap()).err().unwrap();
+    assert!(error.to_string().contains("Unsupported SCID format version 300"));
+}
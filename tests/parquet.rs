@@ -0,0 +1,110 @@
+#![cfg(feature = "parquet")]
+
+mod common;
+
+use std::fs::File;
+use std::path::Path;
+
+use arrow_array::cast::AsArray;
+use arrow_array::types::{Float64Type, Int32Type, UInt16Type, UInt32Type};
+use arrow_array::{Array, RecordBatch};
+use common::TempDir;
+use parquet::arrow::arrow_reader::ParquetRecordBatchReaderBuilder;
+use scidtopgn::export::ParquetExporter;
+use scidtopgn::pgn::PgnReader;
+use scidtopgn::scid::{ScidDatabase, ScidWriter};
+
+const PGN: &str = r#"[Event "Online blitz"]
+[Site "?"]
+[Date "2024.05.01"]
+[Round "?"]
+[White "Alpha"]
+[Black "Beta"]
+[Result "0-1"]
+[WhiteElo "2400"]
+[ECO "C20"]
+
+1. e4 { [%eval 0.3] [%clk 0:03:00] } 1... e5 { [%clk 0:02:59.5] } 2. Qh5 { [%eval -0.9] }
+2... Nc6 3. Bc4 Nf6 4. Qxf7# { [%eval #0] } 0-1
+
+[Event "Study"]
+[Date "????.??.??"]
+[White "?"]
+[Black "?"]
+[Result "*"]
+[SetUp "1"]
+[FEN "4k3/1P6/8/8/8/8/8/4K2Q w - - 0 1"]
+
+1. b8=N { [%eval #-2] } *
+"#;
+
+fn export(dir: &TempDir, mut exporter: ParquetExporter) -> usize {
+    let base = dir.path().join("games");
+    let mut writer = ScidWriter::create(&base).unwrap();
+    for game in PgnReader::new(PGN.as_bytes()) {
+        writer.add_game(&game.unwrap()).unwrap();
+    }
+    writer.finish().unwrap();
+
+    let mut database = ScidDatabase::load(&base).unwrap();
+    exporter.export(&mut database, &dir.path().join("games.parquet")).unwrap()
+}
+
+fn read(path: &Path) -> RecordBatch {
+    let reader = ParquetRecordBatchReaderBuilder::try_new(File::open(path).unwrap()).unwrap().build().unwrap();
+    let mut batches: Vec<RecordBatch> = reader.map(Result::unwrap).collect();
+    // The test tables are far smaller than a batch
+    assert_eq!(batches.len(), 1);
+    batches.remove(0)
+}
+
+fn strings(batch: &RecordBatch, column: &str) -> Vec<Option<String>> {
+    batch.column_by_name(column).unwrap().as_string::<i32>().iter()
+        .map(|value| value.map(str::to_string))
+        .collect()
+}
+
+#[test]
+fn writes_games_table() {
+    let dir = TempDir::new("parquet-games");
+    assert_eq!(export(&dir, ParquetExporter::new()), 2);
+    let games = read(&dir.path().join("games.parquet"));
+    assert!(!dir.path().join("games.plies.parquet").exists());
+
+    assert_eq!(games.num_rows(), 2);
+    let numbers = games.column_by_name("number").unwrap().as_primitive::<UInt32Type>();
+    assert_eq!(numbers.values().to_vec(), [1, 2]);
+    assert_eq!(strings(&games, "white"), [Some("Alpha".to_string()), None]);
+    assert_eq!(strings(&games, "date"), [Some("2024-05-01".to_string()), None]);
+    assert_eq!(strings(&games, "eco"), [Some("C20".to_string()), None]);
+    let elo = games.column_by_name("white_elo").unwrap().as_primitive::<UInt16Type>();
+    assert_eq!((elo.value(0), elo.is_null(1)), (2400, true));
+    assert_eq!(strings(&games, "start_fen")[1].as_deref(), Some("4k3/1P6/8/8/8/8/8/4K2Q w - - 0 1"));
+    assert_eq!(strings(&games, "final_fen")[0].as_deref(),
+               Some("r1bqkb1r/pppp1Qpp/2n2n2/4p3/2B1P3/8/PPPP1PPP/RNB1K1NR b KQkq - 0 4"));
+    let setup = games.column_by_name("non_standard_start").unwrap().as_boolean();
+    assert_eq!((setup.value(0), setup.value(1)), (false, true));
+}
+
+#[test]
+fn writes_plies_table() {
+    let dir = TempDir::new("parquet-plies");
+    let plies_path = dir.path().join("plies.parquet");
+    export(&dir, ParquetExporter::new().with_plies(&plies_path));
+    let plies = read(&plies_path);
+
+    assert_eq!(plies.num_rows(), 8);
+    let sans: Vec<_> = strings(&plies, "san").into_iter().map(Option::unwrap).collect();
+    assert_eq!(sans, ["e4", "e5", "Qh5", "Nc6", "Bc4", "Nf6", "Qxf7#", "b8=N"]);
+    assert_eq!(strings(&plies, "uci")[6].as_deref(), Some("h5f7"));
+    assert_eq!(strings(&plies, "fen")[0].as_deref(), Some("rnbqkbnr/pppppppp/8/8/4P3/8/PPPP1PPP/RNBQKBNR b KQkq e3 0 1"));
+
+    let game = plies.column_by_name("game").unwrap().as_primitive::<UInt32Type>();
+    assert_eq!((game.value(6), game.value(7)), (1, 2));
+    let clock = plies.column_by_name("clock").unwrap().as_primitive::<Float64Type>();
+    assert_eq!((clock.value(0), clock.value(1), clock.is_null(2)), (180.0, 179.5, true));
+    let eval = plies.column_by_name("eval_cp").unwrap().as_primitive::<Int32Type>();
+    assert_eq!((eval.value(0), eval.value(2), eval.is_null(1)), (30, -90, true));
+    let mate = plies.column_by_name("eval_mate").unwrap().as_primitive::<Int32Type>();
+    assert_eq!((mate.value(6), mate.value(7), mate.is_null(0)), (0, -2, true));
+}
@@ -0,0 +1,67 @@
+use scidtopgn::chess::GameResult;
+use scidtopgn::pgn::{parse_games, PgnReader};
+
+#[test]
+fn reads_tags_moves_and_annotations() {
+    let pgn = "[Event \"Test\"]\n[White \"A\"]\n\n1. e4 $1 {Best by test} (1. d4 d5) 1... e5!? 2. Nf3 ; rest of line\n1-0\n";
+    let games = parse_games(pgn);
+    assert_eq!(games.len(), 1);
+    let game = games[0].as_ref().unwrap();
+    assert_eq!(game.tag("Event"), Some("Test"));
+    assert_eq!(game.result, GameResult::WhiteWins);
+
+    let moves = &game.moves.moves;
+    assert_eq!(moves.len(), 3);
+    assert_eq!(moves[0].nags, [1]);
+    assert_eq!(moves[0].comment.as_deref(), Some("Best by test"));
+    assert_eq!(moves[0].variations[0].moves.len(), 2);
+    assert_eq!(moves[1].nags, [5]);
+    assert_eq!(moves[2].comment.as_deref(), Some("rest of line"));
+}
+
+#[test]
+fn tolerates_common_malformations() {
+    let pgn = concat!(
+        "% escape line\n",
+        "[Event \"Unescaped \"quotes\"\"]\n",
+        "[Site \"No closing quote]\n",
+        "1. e4 e5 2. Nf3 { never closed\n",
+        "[Event \"Second\"]\n",
+        "1. d4 ) d5 2. c4 dxc4 (2... e6 3. Nc3\n",
+        "1/2\n",
+        "1. e4 d5 2. e5 f5 3. exf6 e.p. Nxf6 4. Nf3 e6 5. Be2 Be7 6. o-o ½-½\n",
+    );
+    let games: Vec<_> = parse_games(pgn).into_iter().map(Result::unwrap).collect();
+    assert_eq!(games.len(), 3);
+
+    assert_eq!(games[0].tag("Event"), Some("Unescaped \"quotes\""));
+    assert_eq!(games[0].tag("Site"), Some("No closing quote"));
+    assert_eq!(games[0].ply_count(), 3);
+    assert_eq!(games[0].moves.moves[2].comment.as_deref(), Some("never closed"));
+
+    assert_eq!(games[1].tag("Event"), Some("Second"));
+    assert_eq!(games[1].result, GameResult::Draw);
+    assert_eq!(games[1].moves.moves[3].variations[0].moves.len(), 2);
+
+    assert_eq!(games[2].ply_count(), 11);
+    assert_eq!(games[2].result, GameResult::Draw);
+}
+
+#[test]
+fn reports_illegal_moves_and_continues() {
+    let pgn = "[Event \"Bad\"]\n\n1. e4 e5 2. Ke3 *\n\n[Event \"Good\"]\n\n1. d4 *\n";
+    let games: Vec<_> = PgnReader::new(pgn.as_bytes()).collect();
+    assert_eq!(games.len(), 2);
+
+    let error = games[0].as_ref().unwrap_err();
+    assert_eq!((error.game_number, error.line), (1, 1));
+    assert!(error.message.contains("Ke3"));
+    assert_eq!(games[1].as_ref().unwrap().tag("Event"), Some("Good"));
+}
+
+#[test]
+fn decodes_latin1_lines() {
+    let pgn = b"[White \"Caf\xe9\"]\n\n1. e4 *\n";
+    let game = PgnReader::new(&pgn[..]).next().unwrap().unwrap();
+    assert_eq!(game.tag("White"), Some("Caf\u{e9}"));
+}
@@ -0,0 +1,75 @@
+mod common;
+
+use common::{game_record, Fixture, FixtureGame, TempDir, RUY_LOPEZ_MOVES};
+use scidtopgn::export::GameFilter;
+use scidtopgn::report::{PlayerProfile, PlayerReport, RatingPoint};
+use scidtopgn::scid::eco::eco_from_str;
+use scidtopgn::scid::ScidDatabase;
+
+fn game(white: &str, black: &str, date: (u16, u8, u8), result: u8, elo: (u16, u16), eco: &str) -> FixtureGame {
+    FixtureGame::new(game_record(&[], 0, None, RUY_LOPEZ_MOVES, &[]))
+        .players(white, black)
+        .date(date.0, date.1, date.2)
+        .result(result)
+        .elo(elo.0, elo.1)
+        .eco(eco_from_str(eco))
+}
+
+fn report(profile: PlayerProfile) -> Result<PlayerReport, String> {
+    let dir = TempDir::new("player");
+    let base = Fixture::new()
+        .game(game("Carlsen, Magnus", "Nakamura, Hikaru", (2020, 10, 5), 1, (2863, 2736), "B90"))
+        .game(game("Nakamura, Hikaru", "Carlsen, Magnus", (2020, 10, 12), 3, (2736, 2863), "B99a"))
+        .game(game("Caruana, Fabiano", "Carlsen, Magnus", (2019, 5, 1), 2, (2819, 2875), "C78"))
+        .game(game("Carlsen, Magnus", "Caruana, Fabiano", (2019, 6, 0), 3, (2875, 2819), "B90"))
+        .game(game("Carlsen, Magnus", "Amateur", (2021, 1, 1), 0, (2862, 0), ""))
+        .game(game("Nakamura, Hikaru", "Caruana, Fabiano", (2020, 1, 1), 1, (2750, 2820), "A00"))
+        .write(dir.path(), "player");
+    // Reports only read the index and namebase
+    profile.build(&ScidDatabase::load_index_only(&base).unwrap())
+}
+
+#[test]
+fn scores_by_colour_and_opponent() {
+    let report = report(PlayerProfile::new("carlsen")).unwrap();
+    assert_eq!(report.name, "Carlsen, Magnus");
+    assert_eq!((report.total.games, report.total.wins, report.total.draws, report.total.losses), (5, 2, 2, 0));
+    assert_eq!(report.total.percent, Some(75.0));
+    assert_eq!((report.white.games, report.white.percent), (3, Some(75.0)));
+    assert_eq!((report.black.games, report.black.points()), (2, 1.5));
+
+    let opponents: Vec<(&str, u32)> = report.opponents.iter().map(|o| (o.name.as_str(), o.score.games)).collect();
+    assert_eq!(opponents, [("Caruana, Fabiano", 2), ("Nakamura, Hikaru", 2), ("Amateur", 1)]);
+    // The unfinished game counts as played but not scored
+    assert_eq!(report.opponents[2].score.percent, None);
+
+    let white: Vec<(&str, u32)> = report.white_openings.iter().map(|o| (o.eco.as_str(), o.score.games)).collect();
+    assert_eq!(white, [("B90", 2)]);
+    let black: Vec<&str> = report.black_openings.iter().map(|o| o.eco.as_str()).collect();
+    assert_eq!(black, ["B99", "C78"]);
+}
+
+#[test]
+fn tracks_ratings_and_yearly_performance() {
+    let report = report(PlayerProfile::new("Carlsen, Magnus")).unwrap();
+    let point = |date: &str, elo| RatingPoint { date: date.to_string(), elo };
+    assert_eq!(report.ratings, [point("2019.05.01", 2875), point("2020.10.05", 2863), point("2021.01.01", 2862)]);
+
+    let years: Vec<_> = report.years.iter()
+        .map(|y| (y.year, y.score.games, y.average_opponent_elo, y.performance))
+        .collect();
+    // 2019: +1 =1 against 2819; 2020: +1 =1 against 2736; 2021: no result
+    assert_eq!(years, [(2019, 2, Some(2819), Some(3019)), (2020, 2, Some(2736), Some(2936)), (2021, 1, None, None)]);
+}
+
+#[test]
+fn resolves_names_and_applies_options() {
+    assert_eq!(report(PlayerProfile::new("Kasparov")).unwrap_err(), "no player matches 'Kasparov'");
+    assert_eq!(report(PlayerProfile::new("ca")).unwrap_err(),
+               "'ca' matches 2 players: Carlsen, Magnus; Caruana, Fabiano");
+
+    let limited = report(PlayerProfile::new("carlsen").with_limit(1)).unwrap();
+    assert_eq!(limited.opponents.len(), 1);
+    let recent = report(PlayerProfile::new("carlsen").with_filter(GameFilter::new().with_date_from((2020, 0, 0)))).unwrap();
+    assert_eq!(recent.total.games, 3);
+}
@@ -0,0 +1,264 @@
+//! Malformed and truncated input must give errors, never panics. Besides
+//! the specific cases, the fixture files are put through deterministic
+//! pseudo-random mutations; `fuzz/` has coverage-guided targets for the
+//! same decoders.
+
+mod common;
+
+use std::fs;
+use std::path::Path;
+
+use common::{game_record, Fixture, FixtureGame, TempDir, PROMOTION_FEN, PROMOTION_MOVES, RUY_LOPEZ_MOVES};
+use scidtopgn::chess::{Game, GameResult, Position, MAX_VARIATION_DEPTH};
+use scidtopgn::pgn::writer::{movetext, MovetextOptions};
+use scidtopgn::pgn::{parse_games, PgnExporter, PgnReader};
+use scidtopgn::scid::index::IndexFile;
+use scidtopgn::scid::moves::decode_game;
+use scidtopgn::scid::names::NameDatabase;
+use scidtopgn::scid::{ScidDatabase, ScidError};
+
+const OPTIONS: MovetextOptions = MovetextOptions { include_variations: true, include_comments: true };
+
+fn fixture() -> Fixture {
+    Fixture::new()
+        .game(FixtureGame::new(game_record(&[("Annotator", "Tester")], 0, None, RUY_LOPEZ_MOVES, &["Morphy Defence"]))
+            .players("Morphy, Paul", "Anderssen, Adolf")
+            .event("Paris", "Paris FRA", "1"))
+        .game(FixtureGame::new(game_record(&[], 7, Some(PROMOTION_FEN), PROMOTION_MOVES, &[]))
+            .players("White", "Black"))
+}
+
+fn scid_error(error: &std::io::Error) -> &ScidError {
+    error.get_ref().and_then(|e| e.downcast_ref::<ScidError>())
+        .unwrap_or_else(|| panic!("not a ScidError: {}", error))
+}
+
+/// Small deterministic xorshift generator, so failures are reproducible
+struct Rng(u64);
+
+impl Rng {
+    fn next(&mut self) -> u64 {
+        self.0 ^= self.0 << 13;
+        self.0 ^= self.0 >> 7;
+        self.0 ^= self.0 << 17;
+        self.0
+    }
+
+    fn below(&mut self, n: usize) -> usize {
+        (self.next() % n.max(1) as u64) as usize
+    }
+
+    /// Flip, overwrite, insert or delete a few bytes, or truncate
+    fn mutate(&mut self, data: &[u8]) -> Vec<u8> {
+        let mut data = data.to_vec();
+        for _ in 0..1 + self.below(4) {
+            let at = self.below(data.len() + 1);
+            match self.below(5) {
+                0 if at < data.len() => data[at] ^= 1 << self.below(8),
+                1 if at < data.len() => data[at] = self.next() as u8,
+                2 => data.insert(at, self.next() as u8),
+                3 if at < data.len() => { data.remove(at); }
+                4 => data.truncate(at),
+                _ => {}
+            }
+        }
+        data
+    }
+}
+
+/// Render a decoded record the way the exporter would, to exercise SAN
+/// generation on whatever board the decoder produced
+fn render(data: &[u8]) {
+    if let Ok(record) = decode_game(data) {
+        let start = match &record.start_fen {
+            Some(fen) => Position::from_fen(fen).unwrap(),
+            None => Position::starting_position(),
+        };
+        movetext(&start, &record.moves, GameResult::Unknown, &OPTIONS);
+    }
+}
+
+#[test]
+fn index_count_larger_than_file() {
+    let mut data = fixture().index_header();
+    data[14..17].copy_from_slice(&[0xFF, 0xFF, 0xFF]);
+    let error = IndexFile::from_bytes("big.si4", &data).err().unwrap();
+    match scid_error(&error) {
+        ScidError::Truncated { what, offset, .. } => {
+            assert_eq!(what, "game index 1 of 16777215");
+            assert_eq!(*offset, 182);
+        }
+        other => panic!("unexpected error {:?}", other),
+    }
+}
+
+#[test]
+fn index_truncated_or_empty() {
+    let header = fixture().index_header();
+    for len in [0, 4, 8, 100, 181] {
+        assert!(IndexFile::from_bytes("short.si4", &header[..len]).is_err(), "{} bytes", len);
+    }
+    let error = IndexFile::from_bytes("empty.si4", &[]).err().unwrap();
+    assert!(matches!(scid_error(&error), ScidError::InvalidMagic { .. }));
+}
+
+#[test]
+fn namebase_count_larger_than_file() {
+    let dir = TempDir::new("names-count");
+    let base = fixture().write(dir.path(), "names");
+    let mut data = fs::read(base.with_extension("sn4")).unwrap();
+    // Claim 1000 events
+    data[17..20].copy_from_slice(&1000u32.to_le_bytes()[..3]);
+
+    match NameDatabase::from_bytes(Path::new("names.sn4"), &data).err().unwrap() {
+        ScidError::Truncated { what, .. } => assert!(what.starts_with("event name "), "{}", what),
+        other => panic!("unexpected error {:?}", other),
+    }
+}
+
+#[test]
+fn namebase_prefix_longer_than_previous_name() {
+    let dir = TempDir::new("names-prefix");
+    let base = fixture().write(dir.path(), "names");
+    let mut data = fs::read(base.with_extension("sn4")).unwrap();
+    // The second player's prefix byte, after "Morphy, Paul" and its ID, frequency and length
+    assert_eq!(&data[45..57], b"Morphy, Paul");
+    data[60] = 20;
+
+    match NameDatabase::from_bytes(Path::new("names.sn4"), &data).err().unwrap() {
+        ScidError::InvalidName { what, offset, .. } => assert_eq!((what.as_str(), offset), ("player name 2 of 4", 57)),
+        other => panic!("unexpected error {:?}", other),
+    }
+}
+
+#[test]
+fn namebase_truncated_everywhere() {
+    let dir = TempDir::new("names-truncated");
+    let base = fixture().write(dir.path(), "names");
+    let data = fs::read(base.with_extension("sn4")).unwrap();
+    for len in 0..data.len() {
+        assert!(NameDatabase::from_bytes(Path::new("names.sn4"), &data[..len]).is_err(), "{} bytes", len);
+    }
+    assert!(NameDatabase::from_bytes(Path::new("names.sn4"), &data).is_ok());
+}
+
+#[test]
+fn game_offset_outside_game_file() {
+    let dir = TempDir::new("offset");
+    let base = fixture().write(dir.path(), "offset");
+    let mut si4 = fs::read(base.with_extension("si4")).unwrap();
+    // Point the second game past the end of the .sg4
+    si4[182 + 47..182 + 47 + 4].copy_from_slice(&1_000_000u32.to_le_bytes());
+    fs::write(base.with_extension("si4"), si4).unwrap();
+
+    let mut database = ScidDatabase::load(&base).unwrap();
+    let index = database.game_index(1).unwrap().clone();
+    let error = database.game_record(&index).err().unwrap();
+    assert!(matches!(scid_error(&error), ScidError::GameOutOfRange { offset: 1_000_000, .. }));
+
+    // The exporter carries on past the bad game
+    let out = dir.path().join("out.pgn");
+    assert_eq!(PgnExporter::new().export(&mut database, &out).unwrap(), 2);
+    assert!(fs::read_to_string(out).unwrap().contains("Unable to decode SCID moves"));
+}
+
+#[test]
+fn game_record_truncated_everywhere() {
+    let data = game_record(&[("Annotator", "Tester")], 0, None, RUY_LOPEZ_MOVES, &["Morphy Defence"]);
+    for len in 0..data.len() {
+        let error = decode_game(&data[..len]).err().unwrap();
+        assert!(matches!(scid_error(&error), ScidError::InvalidGameRecord { .. }), "{} bytes", len);
+    }
+}
+
+#[test]
+fn deeply_nested_variations_are_rejected() {
+    // 1.e4 and then a variation start plus 1.e4 again, nested far too deep
+    let mut moves = vec![0xCF];
+    for _ in 0..10_000 {
+        moves.extend([0x0D, 0xCF]);
+    }
+    let data = game_record(&[], 0, None, &moves, &[]);
+    let error = decode_game(&data).err().unwrap();
+    assert!(error.to_string().contains("nested"), "{}", error);
+
+    let pgn = format!("1. e4 {}*", "(1. d4 ".repeat(10_000));
+    let error = parse_games(&pgn).remove(0).err().unwrap();
+    assert!(error.message.contains("nested"));
+
+    let pgn = format!("1. e4 {}*", "(1. d4 ".repeat(MAX_VARIATION_DEPTH));
+    assert!(parse_games(&pgn).remove(0).is_ok());
+}
+
+#[test]
+fn impossible_moves_are_rejected() {
+    for moves in [
+        &[0x06, 0x0F][..], // King onto its own pawn on d2
+        &[0x65, 0x0F][..], // g1 knight onto its own pawn on e2
+        &[0xC0, 0x0F][..], // e2 pawn capturing on an empty d3
+        &[0x11, 0x0F][..], // a1 rook along the first rank onto b1
+    ] {
+        assert!(decode_game(&game_record(&[], 0, None, moves, &[])).is_err(), "{:02X?}", moves);
+    }
+}
+
+#[test]
+fn mutated_records_never_panic() {
+    let records = [
+        game_record(&[("Annotator", "Tester")], 0, None, RUY_LOPEZ_MOVES, &["Morphy Defence"]),
+        game_record(&[], 7, Some(PROMOTION_FEN), PROMOTION_MOVES, &[]),
+    ];
+    let mut rng = Rng(0x2545F4914F6CDD1D);
+    for _ in 0..20_000 {
+        let record = &records[rng.below(records.len())];
+        render(&rng.mutate(record));
+    }
+    for _ in 0..2_000 {
+        let len = rng.below(64);
+        let random: Vec<u8> = (0..len).map(|_| rng.next() as u8).collect();
+        render(&random);
+    }
+}
+
+#[test]
+fn mutated_files_never_panic() {
+    let dir = TempDir::new("mutated");
+    let base = fixture().write(dir.path(), "original");
+    let files: Vec<Vec<u8>> = ["si4", "sn4", "sg4"].iter()
+        .map(|ext| fs::read(base.with_extension(ext)).unwrap())
+        .collect();
+    let mutated_base = dir.path().join("mutated");
+
+    let mut rng = Rng(0x9E3779B97F4A7C15);
+    for _ in 0..500 {
+        let _ = IndexFile::from_bytes("fuzz.si4", &rng.mutate(&files[0]));
+        let _ = NameDatabase::from_bytes(Path::new("fuzz.sn4"), &rng.mutate(&files[1]));
+
+        // Whole database through the exporter, one file mutated at a time
+        let target = rng.below(3);
+        for (i, (ext, data)) in ["si4", "sn4", "sg4"].iter().zip(&files).enumerate() {
+            let data = if i == target { rng.mutate(data) } else { data.clone() };
+            fs::write(mutated_base.with_extension(ext), data).unwrap();
+        }
+        if let Ok(mut database) = ScidDatabase::load(&mutated_base) {
+            let mut exporter = PgnExporter::new().with_comments(true).with_variations(true);
+            let _ = exporter.export(&mut database, &dir.path().join("out.pgn"));
+        }
+    }
+}
+
+#[test]
+fn mutated_pgn_never_panics() {
+    let pgn = "[Event \"Test\"]\n[FEN \"4k3/1P6/8/8/8/8/8/4K2Q w - - 0 1\"]\n\n\
+               1. b8=N {knight} (1. b8=Q+ Kxd7?! $6) 1... Kd8 ; line\n2. Qd5+ Kc7 1-0\n";
+    let mut rng = Rng(0xD1B54A32D192ED03);
+    for _ in 0..5_000 {
+        let mutated = rng.mutate(pgn.as_bytes());
+        for game in PgnReader::new(&mutated[..]).flatten() {
+            let game: Game = game;
+            if let Ok(start) = game.start_position() {
+                movetext(&start, &game.moves, game.result, &OPTIONS);
+            }
+        }
+    }
+}
@@ -0,0 +1,117 @@
+mod common;
+
+use common::TempDir;
+use scidtopgn::chess::position::make_square;
+use scidtopgn::chess::{Position, Square};
+use scidtopgn::export::GameFilter;
+use scidtopgn::pgn::PgnReader;
+use scidtopgn::scid::signature::FinalMaterial;
+use scidtopgn::scid::{ScidDatabase, ScidWriter};
+use scidtopgn::search::{PositionHit, PositionMatch, PositionSearch};
+
+const PGN: &str = r#"[White "Open"]
+[Result "1-0"]
+
+1. e4 e5 2. Nf3 Nc6 3. Bb5 a6 1-0
+
+[White "Transposed"]
+[Result "0-1"]
+
+1. Nf3 Nc6 2. e4 e5 3. Bc4 0-1
+
+[White "Queen pawn"]
+[Result "*"]
+
+1. d4 d5 2. Nf3 Nc6 *
+
+[White "Exchange"]
+[Result "*"]
+
+1. e4 d5 2. exd5 Qxd5 *
+
+[White "Kings"]
+[Result "1/2-1/2"]
+
+1. e4 e5 2. Ke2 Ke7 3. Ke1 Ke8 1/2-1/2
+
+[White "Setup"]
+[Result "*"]
+[SetUp "1"]
+[FEN "r1bqkbnr/pppp1ppp/2n5/4p3/4P3/5N2/PPPP1PPP/RNBQKB1R w KQkq - 2 3"]
+
+3. Bc4 Nf6 4. Ng5 *
+"#;
+
+fn database(dir: &TempDir) -> ScidDatabase {
+    let base = dir.path().join("games");
+    let mut writer = ScidWriter::create(&base).unwrap();
+    for game in PgnReader::new(PGN.as_bytes()) {
+        writer.add_game(&game.unwrap()).unwrap();
+    }
+    writer.finish().unwrap();
+    ScidDatabase::load(&base).unwrap()
+}
+
+/// The position after `moves` from the standard start
+fn position(moves: &str) -> Position {
+    let mut pos = Position::starting_position();
+    for san in moves.split_whitespace() {
+        let mv = pos.parse_san(san).unwrap();
+        pos.make_move(&mv);
+    }
+    pos
+}
+
+fn hits(search: PositionSearch) -> Vec<(usize, u32)> {
+    let dir = TempDir::new("search");
+    search.search(&mut database(&dir)).into_iter().map(|PositionHit { game, ply }| (game + 1, ply)).collect()
+}
+
+#[test]
+fn finds_games_reaching_position() {
+    // Reached directly, by transposition, and as a set-up start position
+    let two_knights = position("e4 e5 Nf3 Nc6");
+    assert_eq!(hits(PositionSearch::new(two_knights.clone())), [(1, 4), (2, 4), (6, 0)]);
+    assert_eq!(hits(PositionSearch::new(two_knights.clone()).with_max_games(2)), [(1, 4), (2, 4)]);
+    assert_eq!(hits(PositionSearch::new(two_knights).with_filter(GameFilter::new().with_white("trans"))), [(2, 4)]);
+
+    assert_eq!(hits(PositionSearch::new(position("Nf3"))), [(2, 1)]);
+    assert!(hits(PositionSearch::new(position("d4 d5 Nf3 Nc6 Nc3"))).is_empty());
+}
+
+#[test]
+fn matches_pieces_only() {
+    // Game 5 returns to this placement having lost castling rights
+    let open = position("e4 e5");
+    assert_eq!(hits(PositionSearch::new(open.clone())), [(1, 2), (5, 2)]);
+    let kings = position("e4 e5 Ke2 Ke7 Ke1 Ke8");
+    assert_eq!(hits(PositionSearch::new(kings.clone())), [(5, 6)]);
+    assert_eq!(hits(PositionSearch::new(kings).with_match(PositionMatch::Pieces)), [(1, 2), (5, 2)]);
+}
+
+#[test]
+fn prunes_with_index_records() {
+    let dir = TempDir::new("search-index");
+    let database = database(&dir);
+    let games = database.game_indices();
+
+    // The writer stores the final material and home pawn moves
+    assert_eq!(games[0].final_material, FinalMaterial::of(&position("e4 e5 Nf3 Nc6 Bb5 a6")));
+    let home_pawns = |game: usize| -> Vec<Square> { games[game].home_pawns.squares().collect() };
+    assert_eq!(home_pawns(0), [make_square(4, 1), make_square(4, 6), make_square(0, 6)]);
+    assert_eq!(home_pawns(3), [make_square(4, 1), make_square(3, 6)]);
+    assert!(home_pawns(5).is_empty());
+    assert_eq!(games[3].num_half_moves, 4);
+
+    let candidates = |moves: &str| -> Vec<usize> {
+        let search = PositionSearch::new(position(moves));
+        search.candidates(&database).into_iter().map(|(game, _)| game + 1).collect()
+    };
+    // Too short to reach the position from the standard start
+    assert_eq!(candidates("e4 e5 Nf3 Nc6 Bc4"), [1, 2, 5, 6]);
+    assert_eq!(candidates("e4 e5 Nf3 Nc6 Bc4 Bc5"), [1, 5, 6]);
+    // Only games whose first pawn moves were e2 and e7, and the set-up game
+    assert_eq!(candidates("e4 e5"), [1, 2, 5, 6]);
+    // Every other game ends with more material than this
+    assert_eq!(candidates("e4 d5 exd5 Qxd5"), [4]);
+}
@@ -0,0 +1,94 @@
+mod common;
+
+use common::TempDir;
+use scidtopgn::pgn::{PgnExporter, PgnReader};
+use scidtopgn::scid::names::NameType;
+use scidtopgn::scid::{PlayerInfo, ScidDatabase, ScidWriter, SpellingFile};
+
+const SSP: &str = "@SCID spelling file
+# Players
+@PLAYER
+Carlsen, Magnus     #gm+ NOR 1990.11.30
+=Carlsen, M.
+=Carlsen,M
+%ID fide 1503014
+%Elo 2009:2776,2772
+Hou, Yifan          #wgm+w CHN 1994
+=Hou Yi Fan
+
+@EVENT
+Tata Steel Masters
+=Tata Steel-A
+=Tata Steel Chess Masters
+
+@SITE
+Wijk aan Zee NED
+=Wijk aan Zee
+";
+
+const PGN: &str = r#"[Event "Tata Steel-A"]
+[Site "Wijk aan Zee"]
+[White "CARLSEN, M."]
+[Black "Hou Yi Fan"]
+[WhiteTitle "IM"]
+[Result "1-0"]
+
+1. e4 e5 1-0
+
+[Event "Casual"]
+[White "Someone, Else"]
+[Black "Carlsen,M"]
+[Result "0-1"]
+
+1. d4 d5 0-1
+"#;
+
+#[test]
+fn parses_names_variants_and_player_info() {
+    let spelling = SpellingFile::parse(SSP);
+    assert_eq!(spelling.correct(NameType::Player, "carlsen,  m."), Some("Carlsen, Magnus"));
+    assert_eq!(spelling.correct(NameType::Player, "Carlsen, Magnus"), Some("Carlsen, Magnus"));
+    assert_eq!(spelling.correct(NameType::Player, "Tata Steel-A"), None);
+    assert_eq!(spelling.correct(NameType::Event, "Tata Steel-A"), Some("Tata Steel Masters"));
+    assert_eq!(spelling.correct(NameType::Site, "Wijk aan Zee"), Some("Wijk aan Zee NED"));
+    assert_eq!(spelling.len(NameType::Player), 2);
+
+    assert_eq!(spelling.player_info("Carlsen, Magnus"), Some(&PlayerInfo {
+        title: Some("GM".to_string()),
+        federation: Some("NOR".to_string()),
+        birth_year: Some(1990),
+        fide_id: Some(1503014),
+    }));
+    let hou = spelling.player_info("Hou, Yifan").unwrap();
+    assert_eq!((hou.title.as_deref(), hou.fide_id), (Some("WGM"), None));
+}
+
+#[test]
+fn export_uses_corrected_names_and_adds_player_tags() {
+    let dir = TempDir::new("spelling");
+    let base = dir.path().join("games");
+    let mut writer = ScidWriter::create(&base).unwrap();
+    for game in PgnReader::new(PGN.as_bytes()) {
+        writer.add_game(&game.unwrap()).unwrap();
+    }
+    writer.finish().unwrap();
+
+    let mut database = ScidDatabase::load(&base).unwrap();
+    // Three player spellings, the event and the site
+    assert_eq!(database.apply_spelling(&SpellingFile::parse(SSP)), 5);
+    assert_eq!(database.names().find_players("carlsen").len(), 2);
+
+    let output = dir.path().join("games.pgn");
+    PgnExporter::new().export(&mut database, &output).unwrap();
+    let pgn = std::fs::read_to_string(&output).unwrap();
+    let games: Vec<&str> = pgn.split("\n\n[").collect();
+    for tag in ["[Event \"Tata Steel Masters\"]", "[Site \"Wijk aan Zee NED\"]", "[White \"Carlsen, Magnus\"]",
+                "[Black \"Hou, Yifan\"]", "[WhiteTitle \"GM\"]", "[BlackTitle \"WGM\"]", "[WhiteFideId \"1503014\"]"] {
+        assert!(games[0].contains(tag), "missing {}", tag);
+    }
+    // The stored title gives way to the spelling file's
+    assert!(!games[0].contains("IM"));
+    assert!(games[1].contains("Black \"Carlsen, Magnus\"]"));
+    assert!(games[1].contains("BlackFideId \"1503014\"]"));
+    assert!(!games[1].contains("WhiteTitle"));
+}
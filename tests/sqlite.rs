@@ -0,0 +1,108 @@
+#![cfg(feature = "sqlite")]
+
+mod common;
+
+use common::{game_record, Fixture, FixtureGame, TempDir, PROMOTION_FEN, PROMOTION_MOVES, RUY_LOPEZ_MOVES};
+use rusqlite::Connection;
+use scidtopgn::export::SqliteExporter;
+use scidtopgn::scid::eco::eco_from_str;
+use scidtopgn::scid::ScidDatabase;
+
+fn fixture() -> Fixture {
+    let ruy_lopez = game_record(&[("Annotator", "Tester")], 0, None, RUY_LOPEZ_MOVES, &["Morphy Defence"]);
+    let promotion = game_record(&[], 7, Some(PROMOTION_FEN), PROMOTION_MOVES, &[]);
+    let corrupt = game_record(&[], 0, None, &[0xCF], &[]);
+
+    Fixture::new()
+        .game(FixtureGame::new(ruy_lopez)
+            .players("Morphy, Paul", "Anderssen, Adolf")
+            .event("Paris m", "Paris FRA", "1")
+            .date(1858, 12, 20)
+            .result(3)
+            .elo(2690, 0)
+            .eco(eco_from_str("C78"))
+            .ply(9))
+        .game(FixtureGame::new(corrupt.clone())
+            .players("Deleted", "Game")
+            .flags(0x08))
+        .game(FixtureGame::new(promotion)
+            .players("White, A", "Morphy, Paul")
+            .event("?", "?", "?")
+            .date(2021, 3, 0)
+            .result(1)
+            .flags(7))
+        .game(FixtureGame::new(corrupt)
+            .players("Corrupt", "Record"))
+}
+
+fn export(dir: &TempDir, mut exporter: SqliteExporter) -> Connection {
+    let base = fixture().write(dir.path(), "sqlite");
+    let mut database = ScidDatabase::load(&base).unwrap();
+    let out = dir.path().join("out.sqlite");
+    assert_eq!(exporter.export(&mut database, &out).unwrap(), 3);
+    Connection::open(out).unwrap()
+}
+
+fn strings(connection: &Connection, sql: &str) -> Vec<String> {
+    let mut statement = connection.prepare(sql).unwrap();
+    let rows = statement.query_map([], |row| row.get::<_, String>(0)).unwrap();
+    rows.map(Result::unwrap).collect()
+}
+
+#[test]
+fn writes_normalized_games() {
+    let dir = TempDir::new("sqlite");
+    let connection = export(&dir, SqliteExporter::new());
+
+    // Games by a player, through the players table
+    let events = strings(&connection, "
+        SELECT coalesce(events.name, '-') FROM games
+        JOIN players ON players.id IN (games.white_id, games.black_id)
+        LEFT JOIN events ON events.id = games.event_id
+        WHERE players.name = 'Morphy, Paul' ORDER BY games.id");
+    assert_eq!(events, ["Paris m", "-"]);
+
+    let (date, white_elo, black_elo, eco): (String, i64, Option<i64>, String) = connection.query_row(
+        "SELECT date, white_elo, black_elo, eco FROM games WHERE id = 1",
+        [], |row| Ok((row.get(0)?, row.get(1)?, row.get(2)?, row.get(3)?))).unwrap();
+    assert_eq!((date.as_str(), white_elo, black_elo, eco.as_str()), ("1858-12-20", 2690, None, "C78"));
+
+    // The deleted game is skipped but IDs follow the index
+    assert_eq!(strings(&connection, "SELECT CAST(id AS TEXT) FROM games ORDER BY id"), ["1", "3", "4"]);
+    assert_eq!(strings(&connection, "SELECT start_fen FROM games WHERE id = 3"), [PROMOTION_FEN]);
+    assert_eq!(strings(&connection, "SELECT name || '=' || value FROM tags"), ["Annotator=Tester"]);
+    let error = strings(&connection, "SELECT error FROM games WHERE id = 4");
+    assert!(error[0].starts_with("Unable to decode SCID moves"));
+}
+
+#[test]
+fn writes_moves_and_positions() {
+    let dir = TempDir::new("sqlite-moves");
+    let connection = export(&dir, SqliteExporter::new());
+
+    assert_eq!(strings(&connection, "SELECT san FROM moves WHERE game_id = 1 ORDER BY ply").join(" "),
+               "e4 e5 Nf3 Nc6 Bb5 a6 Ba4 Nf6 O-O");
+    assert_eq!(strings(&connection, "SELECT uci || ' ' || nags FROM moves WHERE nags IS NOT NULL"), ["e1g1 1"]);
+    assert_eq!(strings(&connection, "SELECT comment FROM moves WHERE comment IS NOT NULL"), ["Morphy Defence"]);
+
+    // Start position plus one per move, found again by EPD
+    let count: i64 = connection.query_row("SELECT count(*) FROM positions WHERE game_id = 1", [], |row| row.get(0)).unwrap();
+    assert_eq!(count, 10);
+    let games = strings(&connection, "
+        SELECT CAST(game_id AS TEXT) FROM positions
+        WHERE epd = 'rnbqkbnr/pppppppp/8/8/4P3/8/PPPP1PPP/RNBQKBNR b KQkq e3'");
+    assert_eq!(games, ["1"]);
+
+    let indexes = strings(&connection, "SELECT name FROM sqlite_master WHERE type = 'index' AND name = 'positions_epd'");
+    assert_eq!(indexes, ["positions_epd"]);
+}
+
+#[test]
+fn index_only_export() {
+    let dir = TempDir::new("sqlite-index");
+    let connection = export(&dir, SqliteExporter::new().with_moves(false));
+    let count: i64 = connection.query_row("SELECT count(*) FROM moves", [], |row| row.get(0)).unwrap();
+    assert_eq!(count, 0);
+    let count: i64 = connection.query_row("SELECT count(*) FROM games WHERE error IS NULL", [], |row| row.get(0)).unwrap();
+    assert_eq!(count, 3);
+}
@@ -0,0 +1,113 @@
+mod common;
+
+use common::TempDir;
+use scidtopgn::export::GameFilter;
+use scidtopgn::pgn::PgnReader;
+use scidtopgn::scid::{ScidDatabase, ScidWriter};
+use scidtopgn::search::{OpeningTree, TreeReport};
+
+const PGN: &str = r#"[Date "2019.05.01"]
+[WhiteElo "2600"]
+[BlackElo "2500"]
+[Result "1-0"]
+
+1. e4 e5 2. Nf3 Nc6 1-0
+
+[Date "2021.??.??"]
+[WhiteElo "2700"]
+[BlackElo "2600"]
+[Result "1/2-1/2"]
+
+1. e4 c5 2. Nf3 d6 1/2-1/2
+
+[Date "2020.01.01"]
+[BlackElo "2400"]
+[Result "0-1"]
+
+1. e4 e5 2. Bc4 0-1
+
+[WhiteElo "2550"]
+[BlackElo "2550"]
+[Result "1/2-1/2"]
+
+1. e4 1/2-1/2
+
+[Result "*"]
+
+1. d4 d5 *
+
+[Date "2018.01.01"]
+[Result "1-0"]
+
+1. Nf3 c5 2. e4 Nc6 1-0
+"#;
+
+fn build(tree: OpeningTree) -> TreeReport {
+    let dir = TempDir::new("tree");
+    let base = dir.path().join("games");
+    let mut writer = ScidWriter::create(&base).unwrap();
+    for game in PgnReader::new(PGN.as_bytes()) {
+        writer.add_game(&game.unwrap()).unwrap();
+    }
+    writer.finish().unwrap();
+    tree.build(&mut ScidDatabase::load(&base).unwrap())
+}
+
+#[test]
+fn counts_moves_results_and_ratings() {
+    let report = build(OpeningTree::from_moves("1. e4").unwrap());
+    let moves: Vec<(&str, u32)> = report.moves.iter().map(|m| (m.san.as_str(), m.stats.games)).collect();
+    assert_eq!(moves, [("e5", 2), ("c5", 1)]);
+
+    // Black is to move, so ratings and performance are black's
+    let e5 = &report.moves[0].stats;
+    assert_eq!(report.moves[0].uci, "e7e5");
+    assert_eq!((e5.white_wins, e5.draws, e5.black_wins), (1, 0, 1));
+    assert_eq!((e5.white_percent, e5.draw_percent, e5.black_percent), (50.0, 0.0, 50.0));
+    assert_eq!(e5.average_elo, Some(2450));
+    // Only the loss against a rated opponent counts
+    assert_eq!(e5.performance, Some(2200));
+    assert_eq!(e5.last_year, Some(2020));
+
+    // The total includes the game that ends after 1. e4
+    let total = &report.total;
+    assert_eq!(total.games, 4);
+    assert_eq!((total.white_percent, total.draw_percent, total.black_percent), (25.0, 50.0, 25.0));
+    assert_eq!(total.average_elo, Some(2513));
+    assert_eq!(total.performance, Some(2483));
+    assert_eq!(total.last_year, Some(2021));
+}
+
+#[test]
+fn follows_transpositions_and_filters() {
+    let report = build(OpeningTree::from_moves("1.e4 c5 2.Nf3").unwrap());
+    let moves: Vec<&str> = report.moves.iter().map(|m| m.san.as_str()).collect();
+    assert_eq!(moves, ["Nc6", "d6"]);
+    assert_eq!(report.moves[0].stats.average_elo, None);
+    assert_eq!(report.moves[0].stats.performance, None);
+
+    let rated = build(OpeningTree::from_moves("e4").unwrap().with_filter(GameFilter::new().with_min_elo(2500)));
+    assert_eq!(rated.total.games, 3);
+
+    assert_eq!(OpeningTree::from_moves("1. e4 e4").err().unwrap(), "illegal move 'e4'");
+}
+
+#[test]
+fn writes_table_and_json() {
+    let report = build(OpeningTree::from_moves("e4").unwrap());
+    let mut table = Vec::new();
+    report.write_table(&mut table).unwrap();
+    let table = String::from_utf8(table).unwrap();
+    let lines: Vec<&str> = table.lines().collect();
+    assert_eq!(lines[0], "Position: rnbqkbnr/pppppppp/8/8/4P3/8/PPPP1PPP/RNBQKBNR b KQkq e3 0 1");
+    assert_eq!(lines[1], "Move      Games  White   Draw  Black  AvElo   Perf  Year");
+    assert_eq!(lines[2], "e5            2  50.0%   0.0%  50.0%   2450   2200  2020");
+    assert_eq!(lines[3], "c5            1   0.0% 100.0%   0.0%   2600   2700  2021");
+    assert_eq!(lines[4], "Total         4  25.0%  50.0%  25.0%   2513   2483  2021");
+
+    let json = serde_json::to_value(&report).unwrap();
+    assert_eq!(json["moves"][1]["san"], "c5");
+    assert_eq!(json["moves"][1]["games"], 1);
+    assert_eq!(json["moves"][1]["draw_percent"], 100.0);
+    assert_eq!(json["total"]["last_year"], 2021);
+}
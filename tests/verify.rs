@@ -0,0 +1,78 @@
+mod common;
+
+use std::fs;
+
+use common::{game_record, ruy_lopez_game, Fixture, FixtureGame, TempDir};
+use scidtopgn::pgn::PgnReader;
+use scidtopgn::scid::{IssueKind, ScidDatabase, ScidWriter, Verifier};
+
+/// White king on e1 and a black rook on a2, so 1.Ke2 walks into check
+const ROOK_FEN: &str = "4k3/8/8/8/8/8/r7/4K3 w - - 0 1";
+
+/// Byte offset of a game's .si4 record
+fn index_offset(game_num: usize) -> usize {
+    182 + 47 * game_num
+}
+
+#[test]
+fn clean_database_passes() {
+    let dir = TempDir::new("verify_clean");
+    let base = dir.path().join("games");
+    let mut writer = ScidWriter::create(&base).unwrap();
+    let pgn = "[Date \"2024.02.29\"]\n\n1. e4 e5 (1... c5 2. Nf3) 2. Nf3 Nc6 3. Bb5 a6 *\n";
+    for game in PgnReader::new(pgn.as_bytes()) {
+        writer.add_game(&game.unwrap()).unwrap();
+    }
+    writer.finish().unwrap();
+
+    let report = Verifier::new().verify(&mut ScidDatabase::load(&base).unwrap());
+    assert!(report.is_clean(), "{:?}", report.issues);
+    assert_eq!((report.games, report.games_checked), (1, true));
+}
+
+#[test]
+fn finds_each_kind_of_damage() {
+    let dir = TempDir::new("verify_damaged");
+    let base = Fixture::new()
+        .game(ruy_lopez_game().ply(7))
+        .game(ruy_lopez_game().ids([0, 7, 0, 0, 0]).date(2021, 2, 29))
+        .game(FixtureGame::new(game_record(&[], 7, Some(ROOK_FEN), &[0x07, 0x0F], &[])).ply(1))
+        .game(FixtureGame::new(vec![0]))
+        .game(ruy_lopez_game())
+        .game(ruy_lopez_game())
+        .write(dir.path(), "damaged");
+
+    // Game 5 starts inside game 4's record; game 6 is past the end of the file
+    let mut si4 = fs::read(base.with_extension("si4")).unwrap();
+    let game_four = u32::from_le_bytes(si4[index_offset(3)..index_offset(3) + 4].try_into().unwrap());
+    si4[index_offset(4)..index_offset(4) + 4].copy_from_slice(&game_four.to_le_bytes());
+    si4[index_offset(5)..index_offset(5) + 4].copy_from_slice(&1_000_000u32.to_le_bytes());
+    fs::write(base.with_extension("si4"), si4).unwrap();
+
+    let report = Verifier::new().verify(&mut ScidDatabase::load(&base).unwrap());
+    let found: Vec<(usize, IssueKind)> = report.issues.iter().map(|issue| (issue.game, issue.kind)).collect();
+    assert_eq!(found, [
+        (1, IssueKind::PlyCount),
+        (2, IssueKind::UnknownName),
+        (2, IssueKind::InvalidDate),
+        (3, IssueKind::IllegalMove),
+        (4, IssueKind::Undecodable),
+        (5, IssueKind::Overlap),
+        (5, IssueKind::Undecodable),
+        (6, IssueKind::OutOfRange),
+    ]);
+    assert_eq!(report.issues[1].message, "black player ID 7 is not in the namebase");
+    assert_eq!(report.issues[2].message, "no day 29 in date 2021.02.29");
+    assert!(report.issues[3].message.starts_with("e1e2 at ply 1 is not legal"), "{}", report.issues[3].message);
+    assert!(report.issues[5].message.starts_with(&format!("record at {}..", game_four)));
+    assert!(report.issues[5].message.ends_with("overlaps game 4's record"));
+
+    let json = serde_json::to_value(&report).unwrap();
+    assert_eq!(json["issues"][0]["kind"], "ply-count");
+
+    // Without the game file only the index checks run
+    let report = Verifier::new().verify(&mut ScidDatabase::load_index_only(&base).unwrap());
+    assert!(!report.games_checked);
+    let kinds: Vec<IssueKind> = report.issues.iter().map(|issue| issue.kind).collect();
+    assert_eq!(kinds, [IssueKind::UnknownName, IssueKind::InvalidDate, IssueKind::Overlap]);
+}